@@ -0,0 +1,4 @@
+DROP TABLE task_comments;
+
+ALTER TABLE project_permissions
+DROP COLUMN role;
@@ -0,0 +1,17 @@
+-- Existing collaborators could do everything, so keep them as owners.
+ALTER TABLE project_permissions
+ADD COLUMN role varchar(16) NOT NULL DEFAULT 'owner';
+
+ALTER TABLE project_permissions
+ALTER COLUMN role DROP DEFAULT;
+
+-- Comments on tasks, which commenters and more privileged roles may add.
+CREATE TABLE task_comments (
+    comment_id varchar(22) PRIMARY KEY,
+    project_id varchar(36) NOT NULL,
+    task_id varchar NOT NULL,
+    email varchar(320) NOT NULL,
+    body varchar NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+CREATE INDEX task_comments_task ON task_comments (project_id, task_id, created_at);
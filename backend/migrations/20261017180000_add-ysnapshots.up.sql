@@ -0,0 +1,10 @@
+CREATE TABLE ysnapshots (
+    project_id varchar(36) NOT NULL,
+    snapshot_id SERIAL NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    actor jsonb NOT NULL,
+    update_v2 bytea NOT NULL,
+    PRIMARY KEY (project_id, snapshot_id)
+);
+
+CREATE INDEX ysnapshots_project_id_created_at ON ysnapshots (project_id, created_at);
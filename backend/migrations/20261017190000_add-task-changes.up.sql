@@ -0,0 +1,13 @@
+-- Audit log of per-field task changes.
+CREATE TABLE task_changes (
+    change_id BIGSERIAL PRIMARY KEY,
+    project_id varchar(36) NOT NULL,
+    task_id varchar NOT NULL,
+    field varchar NOT NULL,
+    old_value jsonb,
+    new_value jsonb,
+    actor jsonb NOT NULL,
+    changed_at timestamptz NOT NULL DEFAULT NOW()
+);
+CREATE INDEX task_changes_project_id_task_id ON task_changes (project_id, task_id, change_id);
+CREATE INDEX task_changes_project_id ON task_changes (project_id, change_id);
@@ -0,0 +1,2 @@
+DROP TABLE task_index_projects;
+DROP TABLE task_index;
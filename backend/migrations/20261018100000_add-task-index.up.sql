@@ -0,0 +1,25 @@
+-- Searchable copy of every task, kept current by the collab doc observers.
+CREATE TABLE task_index (
+    project_id varchar(36) NOT NULL,
+    task_id varchar NOT NULL,
+    name varchar NOT NULL,
+    description varchar,
+    assignee varchar,
+    status varchar,
+    kind varchar,
+    deadline bigint,
+    archived boolean NOT NULL,
+    task jsonb NOT NULL,
+    search tsvector GENERATED ALWAYS AS (
+        to_tsvector('simple', name || ' ' || coalesce(description, ''))
+    ) STORED,
+    PRIMARY KEY (project_id, task_id)
+);
+CREATE INDEX task_index_search ON task_index USING GIN (search);
+CREATE INDEX task_index_assignee ON task_index (assignee);
+
+-- Projects whose existing tasks have been backfilled into task_index.
+CREATE TABLE task_index_projects (
+    project_id varchar(36) PRIMARY KEY,
+    indexed_at timestamptz NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+-- Verification emails requested, used to rate limit them per user and per address.
+CREATE TABLE email_verifications (
+    email varchar(320) NOT NULL,
+    address varchar(320) NOT NULL,
+    requested_at timestamptz NOT NULL DEFAULT NOW()
+);
+CREATE INDEX email_verifications_email ON email_verifications (email, requested_at);
+CREATE INDEX email_verifications_address ON email_verifications (address, requested_at);
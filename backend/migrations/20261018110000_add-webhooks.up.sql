@@ -0,0 +1,31 @@
+-- Outgoing webhooks subscribed to the events of a project.
+CREATE TABLE project_webhooks (
+    webhook_id varchar(22) PRIMARY KEY,
+    project_id varchar(36) NOT NULL,
+    url varchar NOT NULL,
+    secret varchar NOT NULL,
+    -- The subscribed event types, or NULL for all events.
+    events varchar[],
+    created_by varchar(320) NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+CREATE INDEX project_webhooks_project_id ON project_webhooks (project_id);
+
+-- Log of every webhook delivery, to a project webhook or a user's webhook notifier.
+CREATE TABLE webhook_deliveries (
+    delivery_id varchar(22) PRIMARY KEY,
+    webhook_id varchar(22),
+    email varchar(320),
+    url varchar NOT NULL,
+    event varchar NOT NULL,
+    payload jsonb NOT NULL,
+    attempts integer NOT NULL,
+    status_code integer,
+    error varchar,
+    succeeded boolean NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+CREATE INDEX webhook_deliveries_webhook_id ON webhook_deliveries (webhook_id, created_at);
+CREATE INDEX webhook_deliveries_email ON webhook_deliveries (email, created_at);
+-- Deliveries are pruned by age.
+CREATE INDEX webhook_deliveries_created_at ON webhook_deliveries (created_at);
@@ -0,0 +1,3 @@
+DROP TABLE queued_notifications;
+DROP TABLE user_notification_preferences;
+ALTER TABLE user_notification_configs DROP COLUMN delivery;
@@ -0,0 +1,23 @@
+-- How each notifier delivers notifications: immediately or batched into a digest.
+ALTER TABLE user_notification_configs ADD COLUMN delivery varchar NOT NULL DEFAULT 'immediate';
+
+CREATE TABLE user_notification_preferences (
+    email varchar(320) PRIMARY KEY,
+    time_zone varchar NOT NULL DEFAULT 'UTC',
+    quiet_hours_start time,
+    quiet_hours_end time
+);
+
+-- Notifications held for a digest or until the end of quiet hours.
+CREATE TABLE queued_notifications (
+    notification_id BIGSERIAL PRIMARY KEY,
+    email varchar(320) NOT NULL,
+    notifier varchar(64) NOT NULL,
+    message varchar NOT NULL,
+    queued_at timestamptz NOT NULL DEFAULT NOW(),
+    -- Failed attempts to send the notification.
+    attempts int NOT NULL DEFAULT 0,
+    -- When set, the notification is being sent or waiting to be retried until then.
+    retry_at timestamptz
+);
+CREATE INDEX queued_notifications_email_notifier ON queued_notifications (email, notifier);
@@ -0,0 +1,3 @@
+DROP TABLE deadline_notifications;
+DROP TABLE task_mentions;
+ALTER TABLE user_notification_configs DROP COLUMN disabled_notifications;
@@ -0,0 +1,20 @@
+-- Kinds of notifications, like "mentioned", the user turned off for a notifier.
+ALTER TABLE user_notification_configs ADD COLUMN disabled_notifications varchar[] NOT NULL DEFAULT '{}';
+
+-- Users mentioned in each task's description, so that only new mentions are notified.
+CREATE TABLE task_mentions (
+    project_id varchar(36) NOT NULL,
+    task_id varchar NOT NULL,
+    email varchar(320) NOT NULL,
+    PRIMARY KEY (project_id, task_id, email)
+);
+
+-- Deadline notifications already sent. A changed deadline is notified again.
+CREATE TABLE deadline_notifications (
+    project_id varchar(36) NOT NULL,
+    task_id varchar NOT NULL,
+    kind varchar NOT NULL,
+    deadline bigint NOT NULL,
+    notified_at timestamptz NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (project_id, task_id, kind)
+);
@@ -0,0 +1,15 @@
+-- User managed tokens for calling the API from scripts and CI jobs.
+-- Only a SHA-256 hash of each token is stored.
+CREATE TABLE personal_access_tokens (
+    token_id varchar(22) PRIMARY KEY,
+    email varchar(320) NOT NULL,
+    name varchar NOT NULL,
+    token_hash varchar(64) NOT NULL UNIQUE,
+    -- The projects the token may access, or NULL for all of the user's projects.
+    project_ids varchar[],
+    read_only boolean NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    expires_at timestamptz,
+    last_used_at timestamptz
+);
+CREATE INDEX personal_access_tokens_email ON personal_access_tokens (email);
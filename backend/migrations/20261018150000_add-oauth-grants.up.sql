@@ -0,0 +1,17 @@
+-- Grants issued to MCP clients when a user approves an authorization request.
+-- Access and refresh tokens carry the ID of their grant and are rejected once
+-- the grant is revoked or expires.
+-- Revoked grants are kept rather than deleted. Tokens issued before grants were
+-- introduced have no grant, and are backfilled one on first use, so a missing
+-- grant must never be mistaken for a revoked one.
+CREATE TABLE oauth_grants (
+    grant_id varchar(64) PRIMARY KEY,
+    client_id varchar(64) NOT NULL,
+    email varchar(320) NOT NULL,
+    scope varchar NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    last_used_at timestamptz,
+    expires_at timestamptz NOT NULL,
+    revoked_at timestamptz
+);
+CREATE INDEX oauth_grants_email ON oauth_grants (email);
@@ -5,29 +5,41 @@ use axum::{
     middleware,
     response::IntoResponse,
 };
-use axum_anyhow::{ApiError, ApiResult, OptionExt, bad_request};
+use axum_anyhow::{ApiError, ApiResult, OptionExt, bad_request, forbidden};
 use axum_extra::headers;
 use google::User;
-use model::{ProjectId, ProjectPermission};
+use model::{ProjectId, ProjectPermission, ProjectRole};
 use rmcp::model::ErrorCode;
 use sqlx::postgres::PgPool;
 use std::backtrace::{Backtrace, BacktraceStatus};
 
 use crate::notifiers;
 
+pub(crate) mod activity;
 pub(crate) mod anthropic;
 pub(crate) mod auth;
 pub(crate) mod billing;
 pub(crate) mod collab;
+pub(crate) mod comments;
+pub(crate) mod dependencies;
 pub(crate) mod dev;
 pub(crate) mod dupes;
+pub(crate) mod export;
 pub(crate) mod gemini;
 pub(crate) mod google;
+pub(crate) mod import;
+pub(crate) mod integrity;
+pub(crate) mod jira;
 pub(crate) mod model;
 pub(crate) mod profile;
 pub(crate) mod projects;
+pub(crate) mod search;
 pub(crate) mod simulate;
+pub(crate) mod snapshots;
+pub(crate) mod tokens;
 pub(crate) mod users;
+pub(crate) mod webhooks;
+pub(crate) mod work;
 pub(crate) mod ws;
 pub(crate) mod yproxy;
 
@@ -40,6 +52,9 @@ pub(crate) fn router() -> Result<Router> {
         .nest("/auth", auth::router())
         .nest("/ws", ws::router())
         .nest("/users", users::router())
+        .nest("/search", search::router())
+        .nest("/work", work::router())
+        .nest("/tokens", tokens::router())
         .nest("/dev", dev::router())
         .nest("/anthropic", anthropic::router()?)
         .nest("/gemini", gemini::router()?)
@@ -70,22 +85,32 @@ pub(crate) async fn verify_premium(pool: &PgPool, user: &User) -> ApiResult<()>
     }
 }
 
-/// Verify that the user has access to the given project.
+/// Verify that the user holds at least the `required` role in the given project.
+/// Returns the role actually held by the user.
 pub(crate) async fn verify_project_access(
     pool: &PgPool,
     user: &User,
     project_id: &ProjectId,
-) -> ApiResult<()> {
+    required: ProjectRole,
+) -> ApiResult<ProjectRole> {
     if project_id.is_empty() {
         return Err(bad_request(
             "EMPTY_PROJECT_ID",
             "Project ID must not be empty",
         ));
     }
+    if let Some(scope) = &user.scope
+        && !scope.allows(project_id)
+    {
+        return Err(forbidden(
+            "UNAUTHORIZED",
+            &format!("Token is not authorized to access {project_id}"),
+        ));
+    }
 
-    sqlx::query_as::<_, ProjectPermission>(
+    let permission = sqlx::query_as::<_, ProjectPermission>(
         "
-        SELECT project_id, email
+        SELECT project_id, email, role
         FROM project_permissions
         WHERE project_id = $1
           AND email = $2;",
@@ -102,8 +127,22 @@ pub(crate) async fn verify_project_access(
             user.email, project_id
         ),
     )?;
+    let role = match &user.scope {
+        Some(scope) => scope.limit_role(permission.role),
+        None => permission.role,
+    };
+
+    if role < required {
+        return Err(forbidden(
+            "INSUFFICIENT_ROLE",
+            &format!(
+                "User {} is a {:?} of {} but {:?} is required",
+                user.email, role, project_id, required
+            ),
+        ));
+    }
 
-    Ok(())
+    Ok(role)
 }
 
 pub(crate) async fn handler_404() -> impl IntoResponse {
@@ -220,6 +259,7 @@ impl ErrorRender<'_> {
     }
 }
 
+#[derive(Debug)]
 pub(crate) struct RmcpErrorData(rmcp::ErrorData);
 
 impl<E> From<E> for RmcpErrorData
@@ -0,0 +1,156 @@
+use crate::api::{
+    collab::txn_origin::Actor,
+    google::User,
+    model::{ProjectId, ProjectRole},
+    verify_project_access,
+};
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+};
+use axum_anyhow::{ApiResult, bad_request};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{
+    postgres::PgPool,
+    types::chrono::{DateTime, Utc},
+};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// A single change to a field of a task, as recorded in the audit log.
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskChange {
+    pub(crate) change_id: i64,
+    pub(crate) project_id: ProjectId,
+    pub(crate) task_id: String,
+    /// The name of the changed field, or "task" when the task itself was created or deleted.
+    pub(crate) field: String,
+    pub(crate) old_value: Option<Value>,
+    pub(crate) new_value: Option<Value>,
+    #[sqlx(json)]
+    pub(crate) actor: Actor,
+    pub(crate) changed_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub(crate) struct NewTaskChange {
+    pub(crate) task_id: String,
+    pub(crate) field: String,
+    pub(crate) old_value: Option<Value>,
+    pub(crate) new_value: Option<Value>,
+}
+
+/// Pagination parameters. Changes are returned newest first,
+/// starting before the `before` change ID, if given.
+#[derive(Deserialize, Debug)]
+pub(crate) struct ActivityQuery {
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl ActivityQuery {
+    fn limit(&self) -> ApiResult<i64> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(bad_request(
+                "INVALID_LIMIT",
+                &format!("Limit must be between 1 and {MAX_LIMIT}"),
+            ));
+        }
+        Ok(limit)
+    }
+}
+
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn task_history_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path((project_id, task_id)): Path<(ProjectId, String)>,
+    Query(query): Query<ActivityQuery>,
+) -> ApiResult<Json<Vec<TaskChange>>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let changes = list_task_changes(
+        &project_id,
+        Some(&task_id),
+        query.before,
+        query.limit()?,
+        pool,
+    )
+    .await?;
+    Ok(Json(changes))
+}
+
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn project_activity_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(project_id): Path<ProjectId>,
+    Query(query): Query<ActivityQuery>,
+) -> ApiResult<Json<Vec<TaskChange>>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let changes = list_task_changes(&project_id, None, query.before, query.limit()?, pool).await?;
+    Ok(Json(changes))
+}
+
+pub(crate) async fn record_task_changes(
+    project_id: &ProjectId,
+    actor: &Actor,
+    changes: &[NewTaskChange],
+    pool: &PgPool,
+) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut txn = pool.begin().await?;
+    for change in changes {
+        sqlx::query(
+            "
+            INSERT INTO task_changes (project_id, task_id, field, old_value, new_value, actor)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(project_id)
+        .bind(&change.task_id)
+        .bind(&change.field)
+        .bind(&change.old_value)
+        .bind(&change.new_value)
+        .bind(sqlx::types::Json(actor))
+        .execute(&mut *txn)
+        .await
+        .context("Failed to record task change")?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+async fn list_task_changes(
+    project_id: &ProjectId,
+    task_id: Option<&str>,
+    before: Option<i64>,
+    limit: i64,
+    pool: &PgPool,
+) -> Result<Vec<TaskChange>> {
+    sqlx::query_as(
+        "
+        SELECT change_id, project_id, task_id, field, old_value, new_value, actor, changed_at
+        FROM task_changes
+        WHERE project_id = $1
+        AND ($2::varchar IS NULL OR task_id = $2)
+        AND ($3::bigint IS NULL OR change_id < $3)
+        ORDER BY change_id DESC
+        LIMIT $4",
+    )
+    .bind(project_id)
+    .bind(task_id)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list task changes")
+}
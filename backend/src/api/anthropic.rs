@@ -1,6 +1,7 @@
 use crate::{
     api::{
-        collab::Collab, google::User, simulate::simulate, verify_premium, verify_project_access,
+        collab::Collab, google::User, model::ProjectRole, simulate::simulate, verify_premium,
+        verify_project_access, yproxy::YDocProxy,
     },
     secrets::{Secret, read_secret},
 };
@@ -10,6 +11,7 @@ use axum_anyhow::ApiResult;
 use serde_json::to_string;
 use sqlx::postgres::PgPool;
 use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use yrs::ReadTxn;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 struct Task {
@@ -23,6 +25,107 @@ struct Task {
     estimate: Option<i64>,
 }
 
+pub(crate) const SUMMARIZE_PROMPT: &str = "Render a one or two sentence summary in Markdown for each of the following sections: Goal, Completed Work, Remaining Work, Key Risks, Next Step";
+pub(crate) const ITERATION_PROMPT: &str = "Attached is a JSON document that represents an iteration in a project plan. The plan is represented as a graph of tasks where relationships between tasks are expressed using the children field.";
+pub(crate) const BREAKDOWN_PROMPT: &str =
+    "Break down the task into its first order tasks, one per line, without any preamble.";
+
+/// Returns the IDs of the task and all of its descendants.
+pub(crate) fn subtree_ids<T: ReadTxn>(
+    ydoc: &YDocProxy,
+    txn: &T,
+    task_id: &str,
+) -> Result<BTreeSet<String>> {
+    let mut task_ids = BTreeSet::<String>::new();
+    let mut stack = vec![task_id.to_string()];
+
+    while let Some(curr) = stack.pop() {
+        let ytask = ydoc.get(txn, &curr)?;
+        task_ids.insert(curr);
+        for child_id in ytask.get_children(txn)? {
+            stack.push(child_id);
+        }
+    }
+
+    Ok(task_ids)
+}
+
+/// Serializes the given tasks to the JSON sent to models.
+pub(crate) fn serialize_tasks<'a, T: ReadTxn>(
+    ydoc: &YDocProxy,
+    txn: &T,
+    task_ids: impl IntoIterator<Item = &'a String>,
+) -> Result<String> {
+    let tasks = task_ids
+        .into_iter()
+        .map(|id| {
+            let task = ydoc.get(txn, id)?;
+            let children = task.get_children(txn)?;
+            let kind = match task.get_kind(txn)? {
+                Some(kind) => kind,
+                None => {
+                    if children.is_empty() {
+                        "Task".into()
+                    } else {
+                        "Rollup".into()
+                    }
+                }
+            };
+            Ok(Task {
+                id: task.get_id(txn)?,
+                num: task.get_num(txn)?,
+                name: task.get_name(txn)?,
+                children,
+                assignee: task.get_assignee(txn)?,
+                status: task.get_status(txn)?,
+                kind,
+                estimate: task.get_estimate(txn)?,
+            })
+        })
+        .collect::<Result<Vec<Task>>>()?;
+    Ok(to_string(&tasks)?)
+}
+
+/// Returns the name and description of the task and, as context, of all its ancestors.
+pub(crate) fn breakdown_context<T: ReadTxn>(
+    ydoc: &YDocProxy,
+    txn: &T,
+    task_id: &str,
+) -> Result<Vec<String>> {
+    let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+    for task in ydoc.tasks(txn)? {
+        let parent_id = task.get_id(txn)?;
+        for child_id in task.get_children(txn)? {
+            parents.entry(child_id).or_default().push(parent_id.clone());
+        }
+    }
+
+    let mut content = VecDeque::<String>::new();
+    let mut processed: HashSet<String> = HashSet::new();
+    let mut remaining: VecDeque<String> = VecDeque::from([task_id.to_string()]);
+    while let Some(curr) = remaining.pop_front() {
+        if let Some(parents) = parents.get(&curr) {
+            for parent in parents {
+                remaining.push_back(parent.into());
+            }
+        }
+        if !processed.contains(&curr) {
+            let ytask = ydoc.get(txn, &curr)?;
+            if let Some(task_desc) = ytask.get_desc(txn)? {
+                content.push_front(task_desc);
+            }
+            content.push_front(ytask.get_name(txn)?);
+            if task_id == curr {
+                content.push_front("Task:".to_string());
+            }
+            processed.insert(curr);
+        }
+    }
+    content.push_front("Context:".to_string());
+
+    Ok(content.into())
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 struct AnthropicContent {
     r#type: String,
@@ -112,7 +215,7 @@ async fn summarize_handler(
     Extension(client): Extension<AnthropicClient>,
     req: Query<SummarizeTaskRequest>,
 ) -> ApiResult<Response> {
-    verify_project_access(pool, &user, &req.project_id).await?;
+    verify_project_access(pool, &user, &req.project_id, ProjectRole::Viewer).await?;
     verify_premium(pool, &user).await?;
 
     if req.simulate.unwrap_or(false) {
@@ -122,63 +225,20 @@ async fn summarize_handler(
     let ydoc = collab.get_doc(&req.project_id).await?;
     let txn = ydoc.transact();
 
-    let tasks = {
-        let mut task_ids = BTreeSet::<String>::new();
-        let mut stack = vec![req.task_id.clone()];
-
-        while let Some(curr) = stack.pop() {
-            let ytask = ydoc.get(&txn, &curr)?;
-            task_ids.insert(curr);
-            for child_id in ytask.get_children(&txn)? {
-                stack.push(child_id);
-            }
-        }
-
-        task_ids
-    }
-    .iter()
-    .map(|id| {
-        let task = ydoc.get(&txn, id)?;
-        let children = task.get_children(&txn)?;
-        let kind = match task.get_kind(&txn)? {
-            Some(kind) => kind,
-            None => {
-                if children.is_empty() {
-                    "Task".into()
-                } else {
-                    "Rollup".into()
-                }
-            }
-        };
-        Ok(Task {
-            id: task.get_id(&txn)?,
-            num: task.get_num(&txn)?,
-            name: task.get_name(&txn)?,
-            children: task.get_children(&txn)?,
-            assignee: task.get_assignee(&txn)?,
-            status: task.get_status(&txn)?,
-            kind,
-            estimate: task.get_estimate(&txn)?,
-        })
-    })
-    .collect::<Result<Vec<Task>>>()?;
+    let tasks = serialize_tasks(&ydoc, &txn, &subtree_ids(&ydoc, &txn, &req.task_id)?)?;
 
     let response = client
         .message(&AnthropicMessageRequest {
             model: req.model.clone(),
             max_tokens: 8192,
             stream: Some(true),
-            system: vec![
-                text("Render a one or two sentence summary in Markdown for each of the following sections: Goal, Completed Work, Remaining Work, Key Risks, Next Step")
-            ],
+            system: vec![text(SUMMARIZE_PROMPT)],
             messages: vec![AnthropicMessage {
                 role: "user".into(),
-                content: vec![
-                    text("Attached is a JSON document that represents an iteration in a project plan. The plan is represented as a graph of tasks where relationships between tasks are expressed using the children field."),
-                    text(&to_string(&tasks)?),
-                ],
+                content: vec![text(ITERATION_PROMPT), text(&tasks)],
             }],
-        }).await?;
+        })
+        .await?;
 
     Ok(Response::builder()
         .status(200)
@@ -202,7 +262,7 @@ async fn breakdown_handler(
     Extension(client): Extension<AnthropicClient>,
     req: Query<BreakdownTaskRequest>,
 ) -> ApiResult<Response> {
-    verify_project_access(pool, &user, &req.project_id).await?;
+    verify_project_access(pool, &user, &req.project_id, ProjectRole::Viewer).await?;
     verify_premium(pool, &user).await?;
 
     if req.simulate.unwrap_or(false) {
@@ -212,52 +272,19 @@ async fn breakdown_handler(
     let ydoc = collab.get_doc(&req.project_id).await?;
     let txn = ydoc.transact();
 
-    let mut parents: HashMap<String, Vec<String>> = HashMap::new();
-    for task in ydoc.tasks(&txn)? {
-        let parent_id = task.get_id(&txn)?;
-        for child_id in task.get_children(&txn)? {
-            parents.entry(child_id).or_default().push(parent_id.clone());
-        }
-    }
-
-    let content = {
-        let mut content = VecDeque::<AnthropicContent>::new();
-
-        let mut processed: HashSet<String> = HashSet::new();
-        let mut remaining: VecDeque<String> = VecDeque::from([req.task_id.to_string()]);
-        while let Some(task_id) = remaining.pop_front() {
-            if let Some(parents) = parents.get(&task_id) {
-                for parent in parents {
-                    remaining.push_back(parent.into());
-                }
-            }
-            if !processed.contains(&task_id) {
-                let ytask = ydoc.get(&txn, &task_id)?;
-                if let Some(task_desc) = ytask.get_desc(&txn)? {
-                    content.push_front(text(&task_desc));
-                }
-                content.push_front(text(&ytask.get_name(&txn)?));
-                if req.task_id == task_id {
-                    content.push_front(text("Task:"));
-                }
-                processed.insert(task_id);
-            }
-        }
-        content.push_front(text("Context:"));
-
-        content
-    };
+    let content = breakdown_context(&ydoc, &txn, &req.task_id)?
+        .iter()
+        .map(|t| text(t))
+        .collect();
 
     let message = AnthropicMessageRequest {
         model: req.model.clone(),
         max_tokens: 8192,
         stream: Some(true),
-        system: vec![text(
-            "Break down the task into its first order tasks, one per line, without any preamble.",
-        )],
+        system: vec![text(BREAKDOWN_PROMPT)],
         messages: vec![AnthropicMessage {
             role: "user".into(),
-            content: content.into(),
+            content,
         }],
     };
 
@@ -1,13 +1,16 @@
 use crate::api::billing::update_user_subscription_end_time;
 use anyhow::Context as _;
-use axum::{Extension, Router, routing::post};
+use axum::{Extension, Router, middleware, routing::post};
 use axum_anyhow::ApiResult;
 use sqlx::PgPool;
 
 use crate::api::google::User;
+use crate::api::tokens;
 
 pub(super) fn router() -> Router {
-    Router::new().route("/login", post(login_handler))
+    Router::new()
+        .route("/login", post(login_handler))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
 }
 #[tracing::instrument(skip(user, pool))]
 async fn login_handler(
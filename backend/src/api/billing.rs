@@ -10,6 +10,7 @@ use crate::{
             webhook::WebhookSecret,
         },
         google::{self, User},
+        tokens,
     },
     secrets::{self},
     settings::settings,
@@ -41,6 +42,7 @@ pub(super) fn router() -> Result<Router> {
             post(handle_create_portal_session),
         )
         .route("/subscriptions", put(handle_update_subscription))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
         .layer(middleware::from_fn(google::authenticate))
         // The webhook endpoint is invoked by Stripe and not users. Don't authenticate using Google.
         .route("/stripe/webhook", post(webhook::handle_webhook))
@@ -814,6 +816,7 @@ mod tests {
             name: "IntegTesting DoNotDelete".to_string(),
             picture: "".to_string(),
             exp: 5,
+            scope: None,
         };
         let client = StripeClient {
             client: reqwest::Client::new(),
@@ -858,6 +861,7 @@ mod tests {
             name: "IntegTesting DoNotDelete".to_string(),
             picture: "".to_string(),
             exp: 5,
+            scope: None,
         };
         let client = StripeClient {
             client: reqwest::Client::new(),
@@ -907,6 +911,7 @@ mod tests {
             name: "IntegTesting DoNotDelete".to_string(),
             picture: "".to_string(),
             exp: 5,
+            scope: None,
         };
 
         // Remove user-1@test.koso.app
@@ -996,6 +1001,7 @@ mod tests {
             name: "IntegTesting DoNotDelete".to_string(),
             picture: "".to_string(),
             exp: 5,
+            scope: None,
         };
         let webhook_secret = WebhookSecret(Secret {
             data: "something".as_bytes().to_vec(),
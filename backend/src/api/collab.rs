@@ -10,32 +10,42 @@ use crate::api::{
     collab::{
         client::{CLOSE_UNAUTHORIZED, from_socket},
         client_messages::{ClientMessage, ClientMessageProcessor},
+        cluster::ClusterListener,
         doc_updates::{DocUpdate, DocUpdateProcessor},
-        projects_state::ProjectsState,
+        projects_state::{DocBox, ProjectsState},
+        txn_origin::YOrigin,
     },
     google::User,
-    model::{Graph, ProjectId},
+    model::{Graph, ProjectId, ProjectRole, ProjectSnapshot, Snapshot},
     yproxy::YDocProxy,
 };
 use anyhow::Result;
 use anyhow::{Error, Ok};
 use axum::extract::ws::WebSocket;
-use notifications::{EventProcessor, KosoEvent};
 use projects_state::ProjectState;
 use sqlx::PgPool;
-use std::{sync::Arc, time::Duration};
-use tokio::sync::mpsc::{self};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use task_changes::TaskChanges;
 use tokio::time::sleep;
-use tokio_util::task::TaskTracker;
+use tokio::{
+    sync::mpsc::{self},
+    task::JoinHandle,
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use uuid::Uuid;
+use yrs::{ReadTxn as _, StateVector};
 
 pub(crate) mod awareness;
 pub(crate) mod client;
 pub(crate) mod client_messages;
+pub(crate) mod cluster;
 pub(crate) mod doc_updates;
 pub(crate) mod msg_sync;
 pub(crate) mod notifications;
 pub(crate) mod projects_state;
+pub(crate) mod search_index;
 pub(crate) mod storage;
+pub(crate) mod task_changes;
 pub(crate) mod txn_origin;
 
 #[derive(Clone)]
@@ -47,42 +57,67 @@ struct Inner {
     state: ProjectsState,
     pool: &'static PgPool,
     tracker: tokio_util::task::TaskTracker,
+    /// Cancelled when collab is stopped.
+    stop_token: CancellationToken,
+    /// The task processing cluster messages. Joined on stop since it
+    /// reconnects through the pool, which must outlive it.
+    cluster_listener: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
 
 impl Collab {
-    pub(crate) fn new(pool: &'static PgPool) -> Result<Collab> {
+    pub(crate) async fn new(pool: &'static PgPool) -> Result<Collab> {
         let (process_msg_tx, process_msg_rx) = mpsc::channel::<ClientMessage>(1);
         let (doc_update_tx, doc_update_rx) = mpsc::channel::<DocUpdate>(50);
-        let (event_tx, event_rx) = mpsc::channel::<KosoEvent>(50);
         let tracker = tokio_util::task::TaskTracker::new();
+        let (event_tx, event_processor) = notifications::channel(pool, tracker.clone())?;
+        let (index_tx, index_processor) = search_index::channel(pool);
+        // Identifies this instance among all nodes sharing the database.
+        let node_id = Uuid::new_v4().to_string();
         let collab = Collab {
             inner: Arc::new(Inner {
                 state: ProjectsState::new(
+                    node_id.clone(),
                     process_msg_tx,
                     doc_update_tx,
                     event_tx,
+                    index_tx,
                     pool,
                     tracker.clone(),
                 ),
                 pool,
                 tracker,
+                stop_token: CancellationToken::new(),
+                cluster_listener: std::sync::Mutex::new(None),
             }),
         };
 
-        collab
-            .inner
-            .tracker
-            .spawn(DocUpdateProcessor::new(pool, doc_update_rx).process_doc_updates());
+        collab.inner.tracker.spawn(
+            DocUpdateProcessor::new(node_id.clone(), pool, doc_update_rx).process_doc_updates(),
+        );
+
+        let cluster_listener = ClusterListener::new(
+            node_id,
+            pool,
+            Arc::downgrade(&collab.inner),
+            collab.inner.stop_token.clone(),
+        )
+        .listen()
+        .await?;
+        *collab.inner.cluster_listener.lock().unwrap() =
+            Some(collab.inner.tracker.spawn(cluster_listener));
 
         collab
             .inner
             .tracker
             .spawn(ClientMessageProcessor::new(process_msg_rx).process_messages());
 
+        collab.inner.tracker.spawn(event_processor.process_events());
+
         collab
             .inner
             .tracker
-            .spawn(EventProcessor::new(pool, event_rx)?.process_events());
+            .spawn(index_processor.process_index_updates(Arc::downgrade(&collab.inner)));
+        collab.backfill_search_index();
 
         Ok(collab)
     }
@@ -97,12 +132,18 @@ impl Collab {
     ) -> Result<()> {
         tracing::debug!("Registering client");
 
-        let (mut sender, receiver) = from_socket(socket, &who, &user, &project_id);
+        let (mut sender, mut receiver) = from_socket(socket, &who, &user, &project_id);
 
         // Before doing anything else, make sure the user has access to the project.
-        if let Err(e) = api::verify_project_access(self.inner.pool, &user, &project_id).await {
-            sender.close(CLOSE_UNAUTHORIZED, "Unauthorized.").await;
-            return Err(e.into_error());
+        // Viewers may connect and receive updates, but their own updates are rejected.
+        match api::verify_project_access(self.inner.pool, &user, &project_id, ProjectRole::Viewer)
+            .await
+        {
+            Result::Ok(role) => receiver.role = role,
+            Err(e) => {
+                sender.close(CLOSE_UNAUTHORIZED, "Unauthorized.").await;
+                return Err(e.into_error());
+            }
         }
 
         self.inner
@@ -126,10 +167,28 @@ impl Collab {
         Ok(LocalClient { project })
     }
 
+    /// Index, in the background, the tasks of projects that haven't been indexed yet.
+    pub(crate) fn backfill_search_index(&self) {
+        self.inner.state.request_index_backfill();
+    }
+
+    /// Subscribe to the tasks changed in projects loaded on this node.
+    /// Register a local client to keep a project loaded while subscribed.
+    pub(crate) fn subscribe_task_changes(&self) -> tokio::sync::broadcast::Receiver<TaskChanges> {
+        self.inner.state.subscribe_task_changes()
+    }
+
     #[tracing::instrument(skip(self))]
     pub(crate) async fn stop(self) {
         tracing::debug!("Closing all clients...");
+        self.inner.stop_token.cancel();
         self.inner.state.stop().await;
+        let cluster_listener = self.inner.cluster_listener.lock().unwrap().take();
+        if let Some(cluster_listener) = cluster_listener
+            && let Err(e) = cluster_listener.await
+        {
+            tracing::warn!("Cluster listener failed: {e:?}");
+        }
 
         let tracker = self.inner.tracker.clone();
         // Drop the Collab instance to release inner.state which
@@ -170,6 +229,79 @@ impl Collab {
         let txn = ydoc.transact();
         ydoc.to_graph(&txn)
     }
+
+    /// Returns the graphs of several projects, loading their docs together.
+    pub(super) async fn get_graphs(
+        &self,
+        project_ids: &[ProjectId],
+    ) -> Result<HashMap<ProjectId, Graph>> {
+        storage::load_docs(project_ids, self.inner.pool)
+            .await?
+            .into_iter()
+            .map(|(project_id, ydoc)| {
+                let graph = ydoc.to_graph(&ydoc.transact())?;
+                Ok((project_id, graph))
+            })
+            .collect()
+    }
+
+    pub(super) async fn get_snapshot(
+        &self,
+        project_id: &ProjectId,
+        snapshot_id: i32,
+    ) -> Result<Option<ProjectSnapshot>> {
+        let Some((snapshot, ydoc)) =
+            storage::load_snapshot(project_id, snapshot_id, self.inner.pool).await?
+        else {
+            return Ok(None);
+        };
+        let txn = ydoc.transact();
+        let graph = ydoc.to_graph(&txn)?;
+        Ok(Some(ProjectSnapshot { snapshot, graph }))
+    }
+
+    /// Restore the project to the state captured by the given snapshot.
+    ///
+    /// The current state is snapshotted first, so a restore can itself be undone.
+    /// Tasks are rewritten through a regular transaction, meaning connected clients
+    /// receive the restore as an ordinary update.
+    /// Returns the snapshot of the pre-restore state, or None if the snapshot doesn't exist.
+    pub(super) async fn restore_snapshot(
+        &self,
+        project_id: &ProjectId,
+        snapshot_id: i32,
+        origin: YOrigin,
+    ) -> Result<Option<Snapshot>> {
+        let Some(target) = self.get_snapshot(project_id, snapshot_id).await? else {
+            return Ok(None);
+        };
+
+        // Hold the doc lock from the backup through the restore so no update
+        // lands in between, missing from the backup yet undone by the restore.
+        let client = self.register_local_client(project_id).await?;
+        let doc_box = client.project.doc_box.lock().await;
+        let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+        let data = doc
+            .transact()
+            .encode_state_as_update_v2(&StateVector::default());
+        let actor = client.project.snapshots.lock().await.actor();
+        let backup = storage::persist_snapshot(project_id, &actor, &data, self.inner.pool).await?;
+
+        let mut txn = doc.transact_mut_with(origin.as_origin()?);
+        let current = doc.to_graph(&txn)?;
+        for id in current.keys() {
+            if !target.graph.contains_key(id) {
+                doc.remove(&mut txn, id);
+            }
+        }
+        for task in target.graph.values() {
+            if current.get(&task.id) != Some(task) {
+                doc.set(&mut txn, task);
+            }
+        }
+
+        Ok(Some(backup))
+    }
 }
 
 pub struct LocalClient {
@@ -25,7 +25,7 @@ impl AwarenessUpdate {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct AwarenessState {
     client_id: i64,
@@ -34,7 +34,7 @@ pub(crate) struct AwarenessState {
     user: AwarenessUser,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct AwarenessUser {
     pub(crate) email: String,
@@ -1,4 +1,7 @@
-use crate::api::{google::User, model::ProjectId};
+use crate::api::{
+    google::User,
+    model::{ProjectId, ProjectRole},
+};
 use axum::extract::ws::{CloseCode, CloseFrame, Message, WebSocket};
 use futures::SinkExt as _;
 use std::fmt;
@@ -22,6 +25,8 @@ pub(super) fn from_socket(
             ws_receiver,
             who: who.to_owned(),
             user: user.clone(),
+            // Start with the least privileged role until access is verified.
+            role: ProjectRole::Viewer,
             project_id: project_id.clone(),
         },
     )
@@ -95,6 +100,7 @@ pub(super) struct ClientReceiver {
     ws_receiver: futures::stream::SplitStream<WebSocket>,
     pub(super) who: String,
     pub(super) user: User,
+    pub(super) role: ProjectRole,
     pub(super) project_id: ProjectId,
 }
 
@@ -109,6 +115,7 @@ impl fmt::Debug for ClientReceiver {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ClientReceiver")
             .field("who", &self.who)
+            .field("role", &self.role)
             .field("project_id", &self.project_id)
             .finish()
     }
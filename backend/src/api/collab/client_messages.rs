@@ -10,6 +10,7 @@ use crate::api::{
         txn_origin::{Actor, YOrigin},
     },
     google::User,
+    model::ProjectRole,
 };
 use anyhow::{Result, anyhow};
 use axum::extract::ws::Message;
@@ -111,6 +112,7 @@ impl ClientMessageReceiver {
                     .send(ClientMessage {
                         who: self.receiver.who.clone(),
                         user: self.receiver.user.clone(),
+                        role: self.receiver.role,
                         project: Arc::clone(&self.project),
                         id: Uuid::new_v4().to_string(),
                         data: data.into(),
@@ -228,6 +230,18 @@ impl ClientMessageProcessor {
                 MSG_SYNC_RESPONSE | MSG_SYNC_UPDATE => {
                     tracing::debug!("Handling sync_update|sync_response message");
                     let update = Update::decode_v2(decoder.read_buf()?)?;
+                    if msg.role < ProjectRole::Editor {
+                        // Clients reply to the initial sync_request even when they have
+                        // nothing new, so only complain about non-empty updates.
+                        if update == Update::default() {
+                            return Ok(());
+                        }
+                        return Err(anyhow!(
+                            "Rejecting update from {} with role {:?}",
+                            msg.user.email,
+                            msg.role
+                        ));
+                    }
                     msg.project
                         .apply_doc_update(
                             YOrigin {
@@ -261,6 +275,7 @@ impl ClientMessageProcessor {
 pub(super) struct ClientMessage {
     pub(super) who: String,
     pub(super) user: User,
+    pub(super) role: ProjectRole,
     pub(super) project: Arc<ProjectState>,
     /// Unique ID associated with this update.
     pub(super) id: String,
@@ -273,6 +288,7 @@ impl fmt::Debug for ClientMessage {
         f.debug_struct("YrsMessage")
             .field("project_id", &self.project.project_id)
             .field("who", &self.who)
+            .field("role", &self.role)
             .field("id", &self.id)
             .field("data.len()", &self.data.len())
             .finish()
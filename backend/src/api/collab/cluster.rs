@@ -0,0 +1,270 @@
+//! Fans collab activity out across backend nodes sharing a database.
+//!
+//! Every node persists the updates applied by its own clients to `yupdates`
+//! and then publishes a small notification, via Postgres NOTIFY, referencing the
+//! persisted row. Other nodes that have the project loaded LISTEN for these,
+//! fetch the row and apply it to their doc, which broadcasts it to their clients.
+//! Awareness changes are forwarded inline since they're not persisted.
+//!
+//! Notifications published while a node's listener is disconnected are lost.
+//! After reconnecting, the node catches up each loaded project by applying
+//! the updates persisted after the seq it last loaded or caught up to.
+
+use super::{Inner, awareness::AwarenessState, projects_state::ProjectState, txn_origin::YOrigin};
+use crate::api::model::ProjectId;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, postgres::PgListener};
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+use yrs::{Update, updates::decoder::Decode as _};
+
+const CHANNEL: &str = "koso_collab";
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub(super) enum ClusterMessage {
+    /// An update was persisted to `yupdates` with the given `seq`.
+    Update { project_id: ProjectId, seq: i32 },
+    /// The awareness of a client changed. A state of None means the client left.
+    Awareness {
+        project_id: ProjectId,
+        who: String,
+        state: Option<AwarenessState>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    node_id: String,
+    message: ClusterMessage,
+}
+
+pub(super) async fn publish(node_id: &str, message: ClusterMessage, pool: &PgPool) -> Result<()> {
+    let payload = serde_json::to_string(&Envelope {
+        node_id: node_id.to_string(),
+        message,
+    })?;
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(CHANNEL)
+        .bind(payload)
+        .execute(pool)
+        .await
+        .context("Failed to publish cluster message")?;
+    Ok(())
+}
+
+/// ClusterListener receives messages published by other nodes
+/// and applies them to projects loaded on this node.
+pub(super) struct ClusterListener {
+    node_id: String,
+    pool: &'static PgPool,
+    inner: Weak<Inner>,
+    stop: CancellationToken,
+}
+
+impl ClusterListener {
+    pub(super) fn new(
+        node_id: String,
+        pool: &'static PgPool,
+        inner: Weak<Inner>,
+        stop: CancellationToken,
+    ) -> Self {
+        ClusterListener {
+            node_id,
+            pool,
+            inner,
+            stop,
+        }
+    }
+
+    /// Start listening. Completes once the listener is subscribed, returning
+    /// the future that processes messages until stopped.
+    pub(super) async fn listen(self) -> Result<impl Future<Output = ()>> {
+        let listener = self.connect().await?;
+        Ok(self.process_messages(listener))
+    }
+
+    async fn connect(&self) -> Result<PgListener> {
+        let mut listener = PgListener::connect_with(self.pool)
+            .await
+            .context("Failed to connect cluster listener")?;
+        listener
+            .listen(CHANNEL)
+            .await
+            .context("Failed to listen to cluster channel")?;
+        Ok(listener)
+    }
+
+    #[tracing::instrument(skip(self, listener), fields(node_id=self.node_id))]
+    async fn process_messages(self, mut listener: PgListener) {
+        loop {
+            // Unlike recv, try_recv surfaces a lost connection rather than
+            // silently reconnecting, letting us catch up on missed messages.
+            let notification = tokio::select! {
+                _ = self.stop.cancelled() => break,
+                notification = listener.try_recv() => notification,
+            };
+            match notification {
+                Ok(Some(notification)) => {
+                    if let Err(e) = self.process_message(notification.payload()).await {
+                        tracing::warn!("Failed to process cluster message: {e:?}");
+                    }
+                }
+                Ok(None) => {
+                    tracing::warn!("Lost cluster listener connection");
+                    let Some(l) = self.reconnect().await else {
+                        break;
+                    };
+                    listener = l;
+                }
+                // The pool is only closed on shutdown and can't be reconnected through.
+                Err(sqlx::Error::PoolClosed) => {
+                    tracing::info!("Cluster listener pool closed");
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to receive cluster message: {e:?}");
+                    let Some(l) = self.reconnect().await else {
+                        break;
+                    };
+                    listener = l;
+                }
+            }
+        }
+        tracing::info!("Stopped processing cluster messages");
+    }
+
+    /// Reconnect the listener and then catch up every loaded project on updates
+    /// whose notifications may have been missed while disconnected.
+    /// Returns None if stopped, or the pool closed, before reconnecting.
+    async fn reconnect(&self) -> Option<PgListener> {
+        let mut backoff = Duration::from_millis(100);
+        let listener = loop {
+            match self.connect().await {
+                Ok(listener) => break listener,
+                Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::PoolClosed)) => {
+                    tracing::info!("Cluster listener pool closed");
+                    return None;
+                }
+                Err(e) => tracing::warn!("Failed to reconnect cluster listener: {e:?}"),
+            }
+            tokio::select! {
+                _ = self.stop.cancelled() => return None,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+        };
+        tracing::info!("Reconnected cluster listener");
+
+        // Catch up only once subscribed again, so updates persisted from here
+        // on are either caught up on or notified.
+        if let Some(inner) = self.inner.upgrade() {
+            for project in inner.state.loaded().await {
+                if let Err(e) = project.catch_up().await {
+                    tracing::warn!("Failed to catch up {}: {e:?}", project.project_id);
+                }
+            }
+        }
+        Some(listener)
+    }
+
+    async fn process_message(&self, payload: &str) -> Result<()> {
+        let envelope: Envelope =
+            serde_json::from_str(payload).context("Failed to parse cluster message")?;
+        if envelope.node_id == self.node_id {
+            return Ok(());
+        }
+        tracing::trace!("Processing cluster message: {envelope:?}");
+
+        let Some(inner) = self.inner.upgrade() else {
+            return Ok(());
+        };
+        match envelope.message {
+            ClusterMessage::Update { project_id, seq } => {
+                let Some(project) = inner.state.get(&project_id).await else {
+                    return Ok(());
+                };
+                self.apply_update(&project, &envelope.node_id, seq).await
+            }
+            ClusterMessage::Awareness {
+                project_id,
+                who,
+                state,
+            } => {
+                let Some(project) = inner.state.get(&project_id).await else {
+                    return Ok(());
+                };
+                project.set_remote_awareness(&who, state).await
+            }
+        }
+    }
+
+    async fn apply_update(
+        &self,
+        project: &Arc<ProjectState>,
+        node_id: &str,
+        seq: i32,
+    ) -> Result<()> {
+        // Compaction may have merged the update into a later one.
+        // Applying an update more than once is harmless.
+        let update: Option<(Vec<u8>,)> = sqlx::query_as(
+            "
+            SELECT update_v2
+            FROM yupdates
+            WHERE project_id = $1
+            AND seq >= $2
+            ORDER BY seq ASC
+            LIMIT 1",
+        )
+        .bind(&project.project_id)
+        .bind(seq)
+        .fetch_optional(self.pool)
+        .await
+        .context("Failed to load remote update")?;
+        let Some((update,)) = update else {
+            tracing::warn!("Remote update {seq} is missing");
+            return Ok(());
+        };
+
+        project
+            .apply_doc_update(YOrigin::remote(node_id, seq), Update::decode_v2(&update)?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::db::UnsafePoolWrapper;
+
+    #[test_log::test(sqlx::test)]
+    async fn listener_stops_once_pool_closed(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let listener = ClusterListener::new(
+            "node".to_string(),
+            pool,
+            Weak::new(),
+            CancellationToken::new(),
+        )
+        .listen()
+        .await
+        .unwrap();
+        let listener = tokio::spawn(listener);
+
+        // Closing waits for the listener to return its connection,
+        // so the listener must stop rather than try to reconnect.
+        let close = tokio::spawn(pool.close());
+        tokio::time::timeout(Duration::from_secs(5), listener)
+            .await
+            .unwrap()
+            .unwrap();
+        close.await.unwrap();
+    }
+}
@@ -1,8 +1,12 @@
-use crate::api::collab::{msg_sync::sync_update, storage};
+use crate::api::collab::{
+    cluster::{self, ClusterMessage},
+    msg_sync::sync_update,
+    storage,
+};
 use crate::api::collab::{projects_state::ProjectState, txn_origin::from_origin};
 use crate::api::yproxy::YTaskProxy;
 use anyhow::{Context, Result};
-use sqlx::PgPool;
+use sqlx::{PgPool, types::chrono::Utc};
 use std::{fmt, sync::Arc};
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
@@ -12,7 +16,7 @@ use yrs::Map as _;
 use yrs::types::map::MapEvent;
 
 use super::projects_state::{DocBox, DocBoxProvider};
-use super::txn_origin::YOrigin;
+use super::txn_origin::{Actor, YOrigin};
 
 // Handles updates applied to a project doc and forward them to the doc_update_tx
 // for handling by the `DocUpdateProcessor`.
@@ -46,9 +50,11 @@ impl DocObserver {
             }
         };
         let update = DocUpdate {
+            remote: origin.is_remote(),
             who: origin.who,
             project,
             id: origin.id,
+            actor: origin.actor,
             data: event.update.clone(),
         };
 
@@ -65,16 +71,23 @@ impl DocObserver {
 }
 
 /// DocUpdateProcessor receives doc updates from a channel
-/// and 1) persists them to the DB, and 2) broadcasts them
-/// to other clients connected for the given project.
+/// and 1) persists them to the DB, 2) publishes them to other
+/// nodes, and 3) broadcasts them to other clients connected for the given project.
+/// Updates received from other nodes are only broadcast.
 pub(super) struct DocUpdateProcessor {
+    node_id: String,
     pool: &'static PgPool,
     doc_update_rx: Receiver<DocUpdate>,
 }
 
 impl DocUpdateProcessor {
-    pub(super) fn new(pool: &'static PgPool, doc_update_rx: Receiver<DocUpdate>) -> Self {
+    pub(super) fn new(
+        node_id: String,
+        pool: &'static PgPool,
+        doc_update_rx: Receiver<DocUpdate>,
+    ) -> Self {
         DocUpdateProcessor {
+            node_id,
             pool,
             doc_update_rx,
         }
@@ -99,13 +112,61 @@ impl DocUpdateProcessor {
     }
 
     async fn process_doc_update_internal(&self, update: DocUpdate) -> Result<()> {
-        storage::persist_update(&update.project.project_id, &update.data, self.pool)
+        if update.remote {
+            update
+                .project
+                .broadcast_msg(sync_update(&update.data), None)
+                .await;
+            return Ok(());
+        }
+
+        let seq = storage::persist_update(&update.project.project_id, &update.data, self.pool)
             .await
             .context("Failed to persist update")?;
+        cluster::publish(
+            &self.node_id,
+            ClusterMessage::Update {
+                project_id: update.project.project_id.clone(),
+                seq,
+            },
+            self.pool,
+        )
+        .await?;
         update
             .project
             .broadcast_msg(sync_update(&update.data), Some(&update.who))
             .await;
+        self.maybe_snapshot(&update, seq)
+            .await
+            .context("Failed to snapshot project")?;
+        Ok(())
+    }
+
+    /// Snapshot the project as it was before the update persisted at `seq`,
+    /// if the last snapshot is older than `storage::SNAPSHOT_INTERVAL`.
+    /// The snapshot is attributed to the actor of the last update it includes.
+    async fn maybe_snapshot(&self, update: &DocUpdate, seq: i32) -> Result<()> {
+        let project_id = &update.project.project_id;
+        let now = Utc::now();
+        let mut snapshots = update.project.snapshots.lock().await;
+        let actor = snapshots.actor();
+        snapshots.last_actor = Some(update.actor.clone());
+        if snapshots
+            .last_snapshot_at
+            .is_some_and(|at| now - at < storage::SNAPSHOT_INTERVAL)
+        {
+            return Ok(());
+        }
+        // Other nodes snapshot the project too, so confirm with the DB before taking one.
+        if let Some(at) = storage::latest_snapshot_at(project_id, self.pool).await?
+            && now - at < storage::SNAPSHOT_INTERVAL
+        {
+            snapshots.last_snapshot_at = Some(at);
+            return Ok(());
+        }
+        let data = storage::encode_updates_before(project_id, seq, self.pool).await?;
+        let snapshot = storage::persist_snapshot(project_id, &actor, &data, self.pool).await?;
+        snapshots.last_snapshot_at = Some(snapshot.created_at);
         Ok(())
     }
 }
@@ -117,6 +178,10 @@ pub(super) struct DocUpdate {
     /// Unique ID associated with this update.
     /// Piped through from the triggering YrsMessage::id.
     pub(super) id: String,
+    /// The actor responsible for this update.
+    pub(super) actor: Actor,
+    /// True if the update was applied on behalf of another node.
+    pub(super) remote: bool,
     /// A yrs Update in the v2 encoding.
     /// Can be decoded via Update::decode_v2.
     pub(super) data: Vec<u8>,
@@ -180,8 +245,13 @@ impl GraphObserver {
                     continue;
                 }
 
+                let origin = from_origin(txn.origin())?;
+                if origin.is_remote() {
+                    // The originating node rewrites the num.
+                    return Ok(());
+                }
+                let origin = origin.delegated("rw");
                 let project = project.clone();
-                let origin = from_origin(txn.origin())?.delegated("rw");
                 let mod_id = mod_id.clone();
                 self.tracker.spawn(
                     async move {
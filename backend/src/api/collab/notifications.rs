@@ -4,17 +4,33 @@ use super::{
 };
 use crate::{
     api::{
+        activity::{self, NewTaskChange},
         collab::txn_origin::Actor,
         google::User,
-        model::Task,
+        model::{ProjectId, Task},
         yproxy::{YDocProxy, YTaskProxy},
     },
-    notifiers::Notifier,
+    notifiers::{
+        NotificationKind, Notifier, task_link,
+        webhook::{WebhookEvent, WebhookEventKind},
+    },
 };
 use anyhow::{Context, Result, anyhow};
+use regex::Regex;
+use serde_json::Value;
 use sqlx::PgPool;
-use std::{collections::HashMap, fmt, sync::Arc, time::SystemTime};
-use tokio::sync::mpsc::Receiver;
+use std::{
+    cell::LazyCell,
+    collections::{HashMap, VecDeque, hash_map::Entry},
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::mpsc::{self, Receiver, error::TrySendError},
+    time::Instant,
+};
+use tokio_util::task::TaskTracker;
 use yrs::{
     ReadTxn, TransactionMut,
     types::{EntryChange, Event, Events, PathSegment},
@@ -30,8 +46,21 @@ pub(super) struct KosoEvent {
 
 #[derive(Debug)]
 pub(super) enum KosoEventChanges {
+    /// The task was added to the graph.
+    Created(),
+    /// The task was removed from the graph.
+    /// Only the ID of the event's task is known.
+    Deleted(),
     Task(HashMap<String, KosoEntryChange>),
-    Children(),
+    /// The text of the task's description was edited.
+    Description(),
+    Children {
+        removed: bool,
+    },
+    /// The tasks blocking the task changed.
+    Dependencies {
+        removed: bool,
+    },
 }
 
 pub(super) struct KosoEntryChange(EntryChange);
@@ -52,18 +81,32 @@ pub(super) fn handle_deep_graph_update_events(
     events: &Events,
     project: Arc<ProjectState>,
 ) {
+    // The originating node handles events for updates applied on behalf of other nodes.
+    if let Ok(origin) = from_origin(txn.origin())
+        && origin.is_remote()
+    {
+        return;
+    }
+    let mut batch = Vec::new();
     for event in events.iter() {
-        handle_deep_graph_update_event(txn, event, &project)
+        handle_deep_graph_update_event(txn, event, &project, &mut batch)
+    }
+    if batch.is_empty() {
+        return;
+    }
+    if let Err(e) = project.event_tx.send(batch) {
+        tracing::error!("Failed to send events to deep graph observer: {e:?}");
     }
 }
 
-#[tracing::instrument(skip(txn, event, project), fields(?path=event.path(), ?target=event.target()))]
+#[tracing::instrument(skip(txn, event, project, batch), fields(?path=event.path(), ?target=event.target()))]
 fn handle_deep_graph_update_event(
     txn: &TransactionMut,
     event: &Event,
     project: &Arc<ProjectState>,
+    batch: &mut Vec<KosoEvent>,
 ) {
-    if let Err(e) = handle_deep_graph_update_event_internal(txn, event, project) {
+    if let Err(e) = handle_deep_graph_update_event_internal(txn, event, project, batch) {
         tracing::error!("Failed to handle deep_graph_update event: {e:?}");
     }
 }
@@ -72,10 +115,42 @@ fn handle_deep_graph_update_event_internal(
     txn: &TransactionMut,
     event: &Event,
     project: &Arc<ProjectState>,
+    batch: &mut Vec<KosoEvent>,
 ) -> Result<()> {
     tracing::trace!("Handling deep_graph_update event");
 
     match event {
+        yrs::types::Event::Map(map_event) if map_event.path().is_empty() => {
+            for (task_id, change) in map_event.keys(txn).iter() {
+                let (changes, task) = match change {
+                    EntryChange::Inserted(yrs::Out::YMap(task)) => (
+                        KosoEventChanges::Created(),
+                        YTaskProxy::new(task.clone())
+                            .to_task(txn)
+                            .with_context(|| {
+                                format!("Failed to convert inserted task {task_id}")
+                            })?,
+                    ),
+                    // The fields of a removed task are no longer readable.
+                    EntryChange::Removed(_) => (
+                        KosoEventChanges::Deleted(),
+                        Task {
+                            id: task_id.to_string(),
+                            ..Task::default()
+                        },
+                    ),
+                    _ => continue,
+                };
+                let origin = from_origin(txn.origin())?;
+                let event = KosoEvent {
+                    project: project.clone(),
+                    changes,
+                    task,
+                    origin,
+                };
+                batch.push(event);
+            }
+        }
         yrs::types::Event::Map(map_event) => {
             if map_event.path().len() != 1 {
                 return Ok(());
@@ -95,18 +170,12 @@ fn handle_deep_graph_update_event_internal(
                 task,
                 origin,
             };
-            return project
-                .event_tx
-                .try_send(event)
-                .context("Failed to send event to deep graph observer");
+            batch.push(event);
         }
         yrs::types::Event::Array(array_event) => {
             if array_event.path().len() != 2 {
                 return Ok(());
             }
-            if array_event.removes(txn).is_empty() {
-                return Ok(());
-            }
 
             let path = array_event.path();
             let PathSegment::Key(task_id) = path.front().context("missing task path segment")?
@@ -116,7 +185,36 @@ fn handle_deep_graph_update_event_internal(
             let PathSegment::Key(field) = path.get(1).context("missing task field segment")? else {
                 return Err(anyhow!("Expected field path, got: {path:?}"));
             };
-            if field.as_ref() != "children" {
+            let removed = !array_event.removes(txn).is_empty();
+            let changes = match field.as_ref() {
+                "children" => KosoEventChanges::Children { removed },
+                "blockedBy" => KosoEventChanges::Dependencies { removed },
+                _ => return Ok(()),
+            };
+
+            let origin = from_origin(txn.origin())?;
+
+            let doc = YDocProxy::new_from_existing_doc(txn.doc().clone(), txn)?;
+            let task = doc
+                .get(txn, task_id)?
+                .to_task(txn)
+                .context("Failed to convert ArrayEvent to Koso Task")?;
+            let event = KosoEvent {
+                project: project.clone(),
+                changes,
+                task,
+                origin,
+            };
+            batch.push(event);
+        }
+        yrs::types::Event::Text(text_event) => {
+            let path = text_event.path();
+            let (Some(PathSegment::Key(task_id)), Some(PathSegment::Key(field)), 2) =
+                (path.front(), path.get(1), path.len())
+            else {
+                return Ok(());
+            };
+            if field.as_ref() != "desc" {
                 return Ok(());
             }
 
@@ -126,83 +224,261 @@ fn handle_deep_graph_update_event_internal(
             let task = doc
                 .get(txn, task_id)?
                 .to_task(txn)
-                .context("Failed to convert ArrayEvent to Koso Task")?;
+                .context("Failed to convert TextEvent to Koso Task")?;
             let event = KosoEvent {
                 project: project.clone(),
-                changes: KosoEventChanges::Children(),
+                changes: KosoEventChanges::Description(),
                 task,
                 origin,
             };
-            return project
-                .event_tx
-                .try_send(event)
-                .context("Failed to send array event to deep graph observer");
+            batch.push(event);
         }
         _ => (),
     }
     Ok(())
 }
 
+/// Sends the events of each transaction to the `EventProcessor` as one batch.
+///
+/// Observers run synchronously and can't wait for room in the channel. While it's full,
+/// batches are queued in order and forwarded as the processor catches up.
+#[derive(Clone)]
+pub(super) struct EventSender {
+    tx: mpsc::Sender<Vec<KosoEvent>>,
+    overflow: Arc<std::sync::Mutex<Overflow>>,
+    tracker: TaskTracker,
+}
+
+#[derive(Default)]
+struct Overflow {
+    batches: VecDeque<Vec<KosoEvent>>,
+    /// Whether a task is forwarding the queued batches.
+    forwarding: bool,
+}
+
+impl EventSender {
+    fn send(&self, batch: Vec<KosoEvent>) -> Result<()> {
+        let mut overflow = self
+            .overflow
+            .lock()
+            .map_err(|e| anyhow!("Failed to lock event overflow: {e}"))?;
+        // Queue behind batches already waiting, lest events be processed out of order.
+        if overflow.forwarding {
+            overflow.batches.push_back(batch);
+            return Ok(());
+        }
+        match self.tx.try_send(batch) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(batch)) => {
+                tracing::warn!("Event channel is full. Queueing events until it drains");
+                overflow.batches.push_back(batch);
+                overflow.forwarding = true;
+                self.tracker.spawn(self.clone().forward_overflow());
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(anyhow!("Event channel is closed")),
+        }
+    }
+
+    async fn forward_overflow(self) {
+        loop {
+            let batch = {
+                let Ok(mut overflow) = self.overflow.lock() else {
+                    tracing::error!("Failed to lock event overflow");
+                    return;
+                };
+                let Some(batch) = overflow.batches.pop_front() else {
+                    overflow.forwarding = false;
+                    return;
+                };
+                batch
+            };
+            if self.tx.send(batch).await.is_err() {
+                tracing::error!("Event channel closed while forwarding queued events");
+                return;
+            }
+        }
+    }
+}
+
+pub(super) fn channel(
+    pool: &'static PgPool,
+    tracker: TaskTracker,
+) -> Result<(EventSender, EventProcessor)> {
+    let (tx, rx) = mpsc::channel::<Vec<KosoEvent>>(50);
+    Ok((
+        EventSender {
+            tx,
+            overflow: Arc::new(std::sync::Mutex::new(Overflow::default())),
+            tracker,
+        },
+        EventProcessor::new(pool, rx)?,
+    ))
+}
+
+/// Descriptions are edited a keystroke at a time. Rather than on every keystroke,
+/// their mentions are checked once edits to the description pause for this long.
+pub(crate) const MENTION_SETTLE_DELAY: Duration = Duration::from_secs(2);
+/// How long mention checks may be deferred while a description is continuously edited.
+const MENTION_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A deferred check of the mentions in a task's description.
+struct DeferredMentions {
+    /// The latest edit of the description.
+    event: KosoEvent,
+    /// When the first deferred edit was made.
+    deferred_at: Instant,
+    due: Instant,
+}
+
 pub(super) struct EventProcessor {
-    event_rx: Receiver<KosoEvent>,
+    pool: &'static PgPool,
+    event_rx: Receiver<Vec<KosoEvent>>,
     notifier: Notifier,
+    /// Keyed by project and task ID.
+    deferred_mentions: HashMap<(ProjectId, String), DeferredMentions>,
 }
 
 impl EventProcessor {
-    pub(super) fn new(pool: &'static PgPool, event_rx: Receiver<KosoEvent>) -> Result<Self> {
+    fn new(pool: &'static PgPool, event_rx: Receiver<Vec<KosoEvent>>) -> Result<Self> {
         Ok(EventProcessor {
+            pool,
             event_rx,
             notifier: Notifier::new(pool)?,
+            deferred_mentions: HashMap::new(),
         })
     }
 
     #[tracing::instrument(skip(self))]
     pub(super) async fn process_events(mut self) {
         loop {
-            let Some(event) = self.event_rx.recv().await else {
-                break;
-            };
-            self.process_event(event).await;
+            let next_due = self.deferred_mentions.values().map(|d| d.due).min();
+            tokio::select! {
+                batch = self.event_rx.recv() => {
+                    let Some(batch) = batch else {
+                        break;
+                    };
+                    for event in batch {
+                        self.process_event(event).await;
+                    }
+                }
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                    self.notify_deferred_mentions(Instant::now()).await;
+                }
+            }
         }
+        // Check what's left rather than dropping it.
+        self.notify_deferred_mentions(Instant::now() + MENTION_MAX_DELAY)
+            .await;
+        self.notifier.close().await;
         tracing::info!("Stopped processing events");
     }
 
     #[tracing::instrument(skip(self))]
-    async fn process_event(&self, event: KosoEvent) {
+    async fn process_event(&mut self, event: KosoEvent) {
         tracing::trace!("Processing event");
         if let Err(e) = self.process_event_internal(event).await {
             tracing::warn!("Failed to process event: {e:?}");
         }
     }
 
-    async fn process_event_internal(&self, event: KosoEvent) -> Result<()> {
+    /// Defer checking the mentions of an edited description until the edits settle.
+    fn defer_mentions(&mut self, event: KosoEvent) {
+        let now = Instant::now();
+        let key = (event.project.project_id.clone(), event.task.id.clone());
+        match self.deferred_mentions.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let deferred = entry.get_mut();
+                deferred.due =
+                    (now + MENTION_SETTLE_DELAY).min(deferred.deferred_at + MENTION_MAX_DELAY);
+                deferred.event = event;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(DeferredMentions {
+                    event,
+                    deferred_at: now,
+                    due: now + MENTION_SETTLE_DELAY,
+                });
+            }
+        }
+    }
+
+    /// Check the mentions deferred until no later than `now`.
+    async fn notify_deferred_mentions(&mut self, now: Instant) {
+        let due: Vec<(ProjectId, String)> = self
+            .deferred_mentions
+            .iter()
+            .filter(|(_, deferred)| deferred.due <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in due {
+            let Some(deferred) = self.deferred_mentions.remove(&key) else {
+                continue;
+            };
+            if let Err(e) = self.notify_mentions(&deferred.event).await {
+                tracing::warn!("Failed to notify mentions: {e:?}");
+            }
+        }
+    }
+
+    /// Forget any deferred check of the task's mentions, when checking them right away.
+    fn cancel_deferred_mentions(&mut self, event: &KosoEvent) {
+        self.deferred_mentions
+            .remove(&(event.project.project_id.clone(), event.task.id.clone()));
+    }
+
+    async fn process_event_internal(&mut self, event: KosoEvent) -> Result<()> {
+        self.record_changes(&event)
+            .await
+            .context("Failed to record task changes")?;
+
         match &event.changes {
+            KosoEventChanges::Created() => {
+                self.publish(&event, WebhookEventKind::TaskCreated, None)
+                    .await?;
+                if event.task.desc.is_some() {
+                    self.cancel_deferred_mentions(&event);
+                    self.notify_mentions(&event).await?;
+                }
+            }
+            KosoEventChanges::Deleted() => {
+                self.publish(&event, WebhookEventKind::TaskDeleted, None)
+                    .await?;
+                // The task has no description, forgetting all of its mentions.
+                self.cancel_deferred_mentions(&event);
+                self.notify_mentions(&event).await?;
+                // Deleted tasks no longer block the tasks depending on them.
+                self.unblock_and_notify_actionable_tasks(&event).await?;
+            }
+            KosoEventChanges::Description() => {
+                self.defer_mentions(event);
+                return Ok(());
+            }
             KosoEventChanges::Task(changes) => {
-                for (field, change) in changes {
-                    match (field.as_str(), change) {
-                        (
-                            "assignee",
-                            KosoEntryChange(EntryChange::Updated(
-                                _,
-                                yrs::Out::Any(yrs::Any::String(assignee)),
-                            )),
-                        )
-                        | (
-                            "assignee",
-                            KosoEntryChange(EntryChange::Inserted(yrs::Out::Any(
-                                yrs::Any::String(assignee),
-                            ))),
-                        ) => {
-                            self.notify_assignee(&event, assignee).await?;
+                if changes.contains_key("desc") {
+                    self.cancel_deferred_mentions(&event);
+                    self.notify_mentions(&event).await?;
+                }
+                for (field, KosoEntryChange(change)) in changes {
+                    let (previous, current) = match change {
+                        EntryChange::Inserted(new) => (None, out_to_string(new)),
+                        EntryChange::Updated(old, new) => (out_to_string(old), out_to_string(new)),
+                        EntryChange::Removed(_) => continue,
+                    };
+                    match (field.as_str(), current) {
+                        ("assignee", Some(assignee)) => {
+                            self.notify_assignee(&event, &assignee, previous).await?;
                         }
-                        (
-                            "status",
-                            KosoEntryChange(EntryChange::Updated(
-                                _,
-                                yrs::Out::Any(yrs::Any::String(status)),
-                            )),
-                        ) => {
-                            if status.as_ref() == "Done" {
+                        ("status", Some(status)) => {
+                            let updated = matches!(change, EntryChange::Updated(..));
+                            self.publish(
+                                &event,
+                                WebhookEventKind::TaskStatusChanged,
+                                previous.clone(),
+                            )
+                            .await?;
+                            self.notify_reporter(&event, &status, previous).await?;
+                            if updated && status == "Done" {
                                 self.unblock_and_notify_actionable_tasks(&event).await?;
                             }
                         }
@@ -210,14 +486,103 @@ impl EventProcessor {
                     }
                 }
             }
-            KosoEventChanges::Children() => {
-                self.unblock_and_notify_actionable_tasks(&event).await?;
+            KosoEventChanges::Children { removed } | KosoEventChanges::Dependencies { removed } => {
+                if *removed {
+                    self.unblock_and_notify_actionable_tasks(&event).await?;
+                }
             }
         }
         Ok(())
     }
 
-    async fn notify_assignee(&self, event: &KosoEvent, assignee: &str) -> Result<()> {
+    /// Append the event's changes to the task audit log.
+    /// Edits to the text of a task's description are not recorded.
+    async fn record_changes(&self, event: &KosoEvent) -> Result<()> {
+        let changes: Vec<NewTaskChange> = match &event.changes {
+            KosoEventChanges::Created() => vec![NewTaskChange {
+                task_id: event.task.id.clone(),
+                field: "task".to_string(),
+                old_value: None,
+                new_value: Some(serde_json::to_value(&event.task)?),
+            }],
+            KosoEventChanges::Task(changes) => changes
+                .iter()
+                .map(|(field, KosoEntryChange(change))| {
+                    let (old_value, new_value) = match change {
+                        EntryChange::Inserted(new) => (None, out_to_json(new)),
+                        EntryChange::Updated(old, new) => (out_to_json(old), out_to_json(new)),
+                        EntryChange::Removed(old) => (out_to_json(old), None),
+                    };
+                    NewTaskChange {
+                        task_id: event.task.id.clone(),
+                        field: field.clone(),
+                        old_value,
+                        new_value,
+                    }
+                })
+                .collect(),
+            KosoEventChanges::Deleted() => vec![NewTaskChange {
+                task_id: event.task.id.clone(),
+                field: "task".to_string(),
+                old_value: None,
+                new_value: None,
+            }],
+            KosoEventChanges::Description() => vec![],
+            // YRS doesn't expose the removed elements of a YArray,
+            // so only the resulting children are known.
+            KosoEventChanges::Children { .. } => vec![NewTaskChange {
+                task_id: event.task.id.clone(),
+                field: "children".to_string(),
+                old_value: None,
+                new_value: Some(serde_json::to_value(&event.task.children)?),
+            }],
+            KosoEventChanges::Dependencies { .. } => vec![NewTaskChange {
+                task_id: event.task.id.clone(),
+                field: "blockedBy".to_string(),
+                old_value: None,
+                new_value: Some(serde_json::to_value(&event.task.blocked_by)?),
+            }],
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+        activity::record_task_changes(
+            &event.project.project_id,
+            &event.origin.actor,
+            &changes,
+            self.pool,
+        )
+        .await
+    }
+
+    /// Deliver the event to the project's webhooks.
+    async fn publish(
+        &self,
+        event: &KosoEvent,
+        kind: WebhookEventKind,
+        previous: Option<String>,
+    ) -> Result<()> {
+        let webhook_event = WebhookEvent::task(
+            kind,
+            &event.project.project_id,
+            event.task.clone(),
+            &event.origin.actor,
+        )?
+        .with_previous(previous);
+        self.notifier
+            .publish(&event.project.project_id, &webhook_event)
+            .await
+    }
+
+    async fn notify_assignee(
+        &self,
+        event: &KosoEvent,
+        assignee: &str,
+        previous: Option<String>,
+    ) -> Result<()> {
+        self.publish(event, WebhookEventKind::TaskAssigned, previous.clone())
+            .await?;
+
         // Don't notify a user if they assigned the task to themself.
         if let Actor::User(user) = &event.origin.actor
             && user.email == assignee
@@ -226,35 +591,156 @@ impl EventProcessor {
         };
 
         let msg = format!(
-            "🎁 *{}* assigned to you:\n[{}](https://koso.app/projects/{}?taskId={})",
+            "🎁 *{}* assigned to you:\n{}",
             Sender::from_actor(&event.origin.actor).format(),
-            task_display_name(&event.task),
-            event.project.project_id,
-            event.task.id,
+            task_link(&event.project.project_id, &event.task),
         );
-        self.notifier.notify(assignee, &msg, None).await
+        let webhook_event = WebhookEvent::task(
+            WebhookEventKind::TaskAssigned,
+            &event.project.project_id,
+            event.task.clone(),
+            &event.origin.actor,
+        )?
+        .with_previous(previous);
+        self.notifier
+            .notify_with_event(
+                assignee,
+                NotificationKind::Assigned,
+                &msg,
+                Some(&webhook_event),
+            )
+            .await
+    }
+
+    /// Notify the task's reporter when someone else changes the status of the task.
+    async fn notify_reporter(
+        &self,
+        event: &KosoEvent,
+        status: &str,
+        previous: Option<String>,
+    ) -> Result<()> {
+        let Some(reporter) = &event.task.reporter else {
+            return Ok(());
+        };
+        if let Actor::User(user) = &event.origin.actor
+            && user.email == *reporter
+        {
+            return Ok(());
+        };
+
+        let msg = format!(
+            "🔄 *{}* moved a task you reported to *{status}*:\n{}",
+            Sender::from_actor(&event.origin.actor).format(),
+            task_link(&event.project.project_id, &event.task),
+        );
+        let webhook_event = WebhookEvent::task(
+            WebhookEventKind::TaskStatusChanged,
+            &event.project.project_id,
+            event.task.clone(),
+            &event.origin.actor,
+        )?
+        .with_previous(previous);
+        self.notifier
+            .notify_with_event(
+                reporter,
+                NotificationKind::ReportedStatusChanged,
+                &msg,
+                Some(&webhook_event),
+            )
+            .await
+    }
+
+    /// Notify project members newly @mentioned in the task's description.
+    /// Mentions are remembered so that later edits of the description don't notify them again.
+    async fn notify_mentions(&self, event: &KosoEvent) -> Result<()> {
+        let project_id = &event.project.project_id;
+        let mentions = parse_mentions(event.task.desc.as_deref().unwrap_or_default());
+
+        let mut txn = self.pool.begin().await?;
+        sqlx::query(
+            "
+            DELETE FROM task_mentions
+            WHERE project_id = $1 AND task_id = $2 AND NOT (email = ANY($3))",
+        )
+        .bind(project_id)
+        .bind(&event.task.id)
+        .bind(&mentions)
+        .execute(&mut *txn)
+        .await
+        .context("Failed to delete task mentions")?;
+        let mentioned: Vec<(String,)> = sqlx::query_as(
+            "
+            INSERT INTO task_mentions (project_id, task_id, email)
+            SELECT project_id, $2, email
+            FROM project_permissions
+            WHERE project_id = $1 AND email = ANY($3)
+            ON CONFLICT DO NOTHING
+            RETURNING email",
+        )
+        .bind(project_id)
+        .bind(&event.task.id)
+        .bind(&mentions)
+        .fetch_all(&mut *txn)
+        .await
+        .context("Failed to insert task mentions")?;
+        txn.commit().await?;
+
+        if mentioned.is_empty() {
+            return Ok(());
+        }
+        let msg = format!(
+            "💬 *{}* mentioned you in:\n{}",
+            Sender::from_actor(&event.origin.actor).format(),
+            task_link(project_id, &event.task),
+        );
+        let webhook_event = WebhookEvent::task(
+            WebhookEventKind::TaskMentioned,
+            project_id,
+            event.task.clone(),
+            &event.origin.actor,
+        )?;
+        for (email,) in mentioned {
+            // Don't notify a user if they mentioned themself.
+            if let Actor::User(user) = &event.origin.actor
+                && user.email == email
+            {
+                continue;
+            }
+            self.notifier
+                .notify_with_event(
+                    &email,
+                    NotificationKind::Mentioned,
+                    &msg,
+                    Some(&webhook_event),
+                )
+                .await?;
+        }
+        Ok(())
     }
 
     async fn unblock_and_notify_actionable_tasks(&self, event: &KosoEvent) -> Result<()> {
-        let actionable = Self::find_actionable_tasks(&event.task.id, &event.project).await?;
+        let mut actionable = Self::find_actionable_tasks(&event.task.id, &event.project).await?;
         if actionable.is_empty() {
             return Ok(());
         }
 
+        let status_time = now()?;
         {
             let doc = event.project.doc_box.lock().await;
             let doc = &doc.as_ref().context("No doc initialized.")?.ydoc;
             let mut txn = doc.transact_mut_with(event.origin.delegated("unblock").as_origin()?);
             // TODO: Handle partial failures.
-            for (task_id, _, _) in actionable.iter() {
-                tracing::debug!("Unblocking task {task_id}");
-                match doc.get(&txn, task_id) {
-                    Ok(task) => {
-                        task.set_status(&mut txn, Some("Not Started"));
-                        task.set_status_time(&mut txn, Some(now()?));
+            for task in actionable.iter_mut() {
+                tracing::debug!("Unblocking task {}", task.id);
+                match doc.get(&txn, &task.id) {
+                    Ok(ytask) => {
+                        ytask.set_status(&mut txn, Some("Not Started"));
+                        ytask.set_status_time(&mut txn, Some(status_time));
+                        task.status = Some("Not Started".to_string());
+                        task.status_time = Some(status_time);
                     }
                     Err(e) => {
-                        tracing::warn!("Failed to get task {task_id}: {e:?}");
+                        tracing::warn!("Failed to get task {}: {e:?}", task.id);
                         continue;
                     }
                 }
@@ -262,19 +748,41 @@ impl EventProcessor {
         }
 
         // TODO: We could parallelize this.
-        for (task_id, assignee, name) in actionable {
+        for task in actionable {
+            let webhook_event = WebhookEvent::task(
+                WebhookEventKind::TaskUnblocked,
+                &event.project.project_id,
+                task,
+                &event.origin.actor,
+            )?
+            .with_previous(Some("Blocked".to_string()));
+            self.notifier
+                .publish(&event.project.project_id, &webhook_event)
+                .await?;
+
+            let task = webhook_event.task.as_ref().context("Missing task")?;
+            let Some(assignee) = &task.assignee else {
+                continue;
+            };
             // Don't notify a user if they unblocked the task themself.
             if let Actor::User(user) = &event.origin.actor
-                && user.email == assignee
+                && user.email == *assignee
             {
                 continue;
             }
 
             let msg = format!(
-                "🎁 *Koso* assigned to you:\n[{}](https://koso.app/projects/{}?taskId={})",
-                name, event.project.project_id, task_id
+                "🎁 *Koso* assigned to you:\n{}",
+                task_link(&event.project.project_id, task),
             );
-            self.notifier.notify(&assignee, &msg, None).await?;
+            self.notifier
+                .notify_with_event(
+                    assignee,
+                    NotificationKind::Unblocked,
+                    &msg,
+                    Some(&webhook_event),
+                )
+                .await?;
         }
         Ok(())
     }
@@ -282,75 +790,107 @@ impl EventProcessor {
     async fn find_actionable_tasks(
         event_task_id: &String,
         project: &ProjectState,
-    ) -> Result<Vec<(String, String, String)>> {
+    ) -> Result<Vec<Task>> {
         let doc = project.doc_box.lock().await;
         let doc = &doc.as_ref().context("No doc initialized.")?.ydoc;
         let txn = doc.transact();
+        actionable_tasks(doc, &txn, event_task_id)
+    }
+}
 
-        // Perform a DFS starting from all Blocked tasks.
-        let mut actionable: Vec<(String, String, String)> = vec![];
-        for task in doc.tasks(&txn)? {
-            if task.get_kind(&txn)?.unwrap_or_default() == "Task"
-                && task.get_status(&txn)?.unwrap_or_default() == "Blocked"
-            {
-                // In the case of removing a child of the task, the
-                // event_task_id will be the id of the task and not
-                // the removed child since YRS doesn't allow us to discover
-                // which element was removed from a YArray.
-                let mut found = *event_task_id == task.get_id(&txn)?;
-                let mut complete = true;
-                let mut stack = vec![];
-                stack.extend(task.get_children(&txn)?);
-                loop {
-                    let Some(descendent_id) = stack.pop() else {
-                        break;
-                    };
+/// Finds the assigned, Blocked tasks that the event's task was holding up,
+/// and which are no longer waiting on anything.
+///
+/// A task waits on the tasks blocking it and, if it's of kind Task, on its children.
+/// Waiting on a rollup means waiting on all of its descendants.
+/// Tasks that have been deleted don't block anything.
+fn actionable_tasks<T: ReadTxn>(
+    doc: &YDocProxy,
+    txn: &T,
+    event_task_id: &String,
+) -> Result<Vec<Task>> {
+    // Perform a DFS starting from all Blocked tasks.
+    let mut actionable: Vec<Task> = vec![];
+    for task in doc.tasks(txn)? {
+        if task.get_status(txn)?.unwrap_or_default() != "Blocked" {
+            continue;
+        }
+        // In the case of removing a child or blocker of the task, the
+        // event_task_id will be the id of the task and not
+        // the removed child since YRS doesn't allow us to discover
+        // which element was removed from a YArray.
+        let mut found = *event_task_id == task.get_id(txn)?;
+        let blocked_by = task.get_blocked_by(txn)?;
+        let is_task = task.get_kind(txn)?.unwrap_or_default() == "Task";
+        if !is_task && blocked_by.is_empty() && (!found || task.is_rollup(txn)?) {
+            continue;
+        }
 
-                    // First, mark if the event's task was found.
-                    if descendent_id == *event_task_id {
-                        found = true;
-                    }
+        let mut complete = true;
+        let mut stack = blocked_by;
+        if is_task {
+            stack.extend(task.get_children(txn)?);
+        }
+        while let Some(descendent_id) = stack.pop() {
+            // First, mark if the event's task was found.
+            if descendent_id == *event_task_id {
+                found = true;
+            }
+            if !doc.contains(txn, &descendent_id) {
+                continue;
+            }
 
-                    // Next, check if this task or all of its descendants are complete.
-                    let descendent = doc.get(&txn, &descendent_id)?;
-                    if !descendent.is_rollup(&txn)? {
-                        if descendent.get_status(&txn)?.unwrap_or_default() != "Done" {
-                            complete = false;
-                            break;
-                        }
-                    } else {
-                        stack.extend(descendent.get_children(&txn)?);
-                    }
-                }
-                if found
-                    && complete
-                    && let Some(assignee) = task.get_assignee(&txn)?
-                {
-                    actionable.push((
-                        task.get_id(&txn)?,
-                        assignee,
-                        ytask_display_name(&task, &txn)?,
-                    ));
+            // Next, check if this task or all of its descendants are complete.
+            let descendent = doc.get(txn, &descendent_id)?;
+            if !descendent.is_rollup(txn)? {
+                if descendent.get_status(txn)?.unwrap_or_default() != "Done" {
+                    complete = false;
+                    break;
                 }
+            } else {
+                stack.extend(descendent.get_children(txn)?);
             }
         }
-        Ok(actionable)
+        if found && complete && task.get_assignee(txn)?.is_some() {
+            actionable.push(task.to_task(txn)?);
+        }
     }
+    Ok(actionable)
+}
+
+thread_local! {
+    static MENTION_RE: LazyCell<Regex> = LazyCell::new(|| {
+        Regex::new(r"(?:^|[^\w.+-])@([\w.+-]+@[\w-]+(?:\.[\w-]+)+)").unwrap()
+    });
+}
+
+/// Returns the lowercased, deduplicated emails @mentioned in the text, like "@alice@example.com".
+fn parse_mentions(text: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = MENTION_RE.with(|re| {
+        re.captures_iter(text)
+            .map(|c| c[1].trim_end_matches('.').to_lowercase())
+            .collect()
+    });
+    mentions.sort();
+    mentions.dedup();
+    mentions
 }
 
-fn ytask_display_name<T: ReadTxn>(task: &YTaskProxy, txn: &T) -> Result<String> {
-    let name = task.get_name(txn)?;
-    if !name.is_empty() {
-        return Ok(name);
+/// Converts a primitive field value to JSON.
+/// Shared types, like the YText of a description, have no JSON representation here.
+fn out_to_json(out: &yrs::Out) -> Option<Value> {
+    match out {
+        yrs::Out::Any(yrs::Any::Null | yrs::Any::Undefined) => None,
+        yrs::Out::Any(any) => serde_json::to_value(any).ok(),
+        _ => None,
     }
-    Ok(format!("Task #{}", task.get_num(txn)?))
 }
 
-fn task_display_name(task: &Task) -> String {
-    if !task.name.is_empty() {
-        return task.name.clone();
+fn out_to_string(out: &yrs::Out) -> Option<String> {
+    match out {
+        yrs::Out::Any(yrs::Any::String(s)) => Some(s.to_string()),
+        _ => None,
     }
-    format!("Task #{}", task.num)
 }
 
 fn now() -> Result<i64> {
@@ -380,3 +920,138 @@ impl Sender<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mentions_test() {
+        assert_eq!(
+            parse_mentions("cc @Alice@Example.com and @bob.smith+koso@mail.example.org."),
+            vec!["alice@example.com", "bob.smith+koso@mail.example.org"]
+        );
+        assert_eq!(
+            parse_mentions("@alice@example.com, @alice@example.com"),
+            vec!["alice@example.com"]
+        );
+        assert!(parse_mentions("mail alice@example.com or @alice").is_empty());
+        assert!(parse_mentions("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn event_sender_queues_batches_when_full_test() {
+        let (tx, mut rx) = mpsc::channel::<Vec<KosoEvent>>(1);
+        let tracker = TaskTracker::new();
+        let sender = EventSender {
+            tx,
+            overflow: Arc::new(std::sync::Mutex::new(Overflow::default())),
+            tracker: tracker.clone(),
+        };
+
+        for _ in 0..5 {
+            sender.send(Vec::new()).unwrap();
+        }
+        assert!(sender.overflow.lock().unwrap().forwarding);
+
+        for _ in 0..5 {
+            rx.recv().await.unwrap();
+        }
+        tracker.close();
+        tracker.wait().await;
+        let overflow = sender.overflow.lock().unwrap();
+        assert!(!overflow.forwarding);
+        assert!(overflow.batches.is_empty());
+        assert!(rx.try_recv().is_err());
+    }
+
+    fn origin() -> yrs::Origin {
+        YOrigin {
+            who: "notifications_test".to_string(),
+            id: "test".to_string(),
+            actor: Actor::Server,
+        }
+        .as_origin()
+        .unwrap()
+    }
+
+    fn doc_with(tasks: Vec<Task>) -> YDocProxy {
+        let doc = YDocProxy::new();
+        let mut txn = doc.transact_mut_with(origin());
+        for task in &tasks {
+            doc.set(&mut txn, task);
+        }
+        drop(txn);
+        doc
+    }
+
+    fn task(id: &str, status: &str, children: &[&str], blocked_by: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            num: id.to_string(),
+            name: id.to_string(),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            blocked_by: blocked_by.iter().map(|b| b.to_string()).collect(),
+            assignee: Some("a@koso.app".to_string()),
+            status: Some(status.to_string()),
+            kind: Some("Task".to_string()),
+            ..Task::default()
+        }
+    }
+
+    fn actionable(doc: &YDocProxy, event_task_id: &str) -> Vec<String> {
+        actionable_tasks(doc, &doc.transact(), &event_task_id.to_string())
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect()
+    }
+
+    #[test]
+    fn actionable_tasks_honors_dependencies() {
+        let mut rollup = task("r", "", &["r1"], &[]);
+        rollup.kind = None;
+        rollup.status = None;
+        let doc = doc_with(vec![
+            task("1", "Blocked", &[], &["2", "r"]),
+            task("2", "Done", &[], &[]),
+            rollup,
+            task("r1", "In Progress", &[], &[]),
+        ]);
+        // The rollup's descendant isn't done yet.
+        assert!(actionable(&doc, "2").is_empty());
+
+        {
+            let mut txn = doc.transact_mut_with(origin());
+            doc.get(&txn, "r1")
+                .unwrap()
+                .set_status(&mut txn, Some("Done"));
+        }
+        assert_eq!(actionable(&doc, "r1"), vec!["1"]);
+        // Unrelated tasks don't unblock it.
+        assert!(actionable(&doc, "other").is_empty());
+    }
+
+    #[test]
+    fn actionable_tasks_skips_deleted_blockers() {
+        let mut unassigned = task("3", "Blocked", &[], &["gone"]);
+        unassigned.assignee = None;
+        let doc = doc_with(vec![
+            task("1", "Blocked", &["2"], &["gone"]),
+            task("2", "Done", &[], &[]),
+            unassigned,
+        ]);
+        assert_eq!(actionable(&doc, "gone"), vec!["1"]);
+        assert_eq!(actionable(&doc, "1"), vec!["1"]);
+    }
+
+    #[test]
+    fn actionable_tasks_unblocks_task_without_blockers() {
+        let mut leaf = task("1", "Blocked", &[], &[]);
+        leaf.kind = None;
+        let doc = doc_with(vec![leaf]);
+        // The task's last blocker was removed.
+        assert_eq!(actionable(&doc, "1"), vec!["1"]);
+        assert!(actionable(&doc, "other").is_empty());
+    }
+}
@@ -1,8 +1,11 @@
 use super::{
     YDocProxy,
     awareness::{AwarenessState, AwarenessUpdate},
+    cluster::{self, ClusterMessage},
     msg_sync::koso_awareness_state,
     notifications,
+    search_index::{self, IndexSender},
+    task_changes::{self, TaskChanges},
 };
 use crate::api::{
     collab::{
@@ -12,16 +15,20 @@ use crate::api::{
         client_messages::{ClientMessage, ClientMessageReceiver},
         doc_updates::{DocObserver, DocUpdate, GraphObserver},
         msg_sync::sync_request,
-        notifications::KosoEvent,
+        notifications::EventSender,
         storage::{self, compact},
-        txn_origin::YOrigin,
+        txn_origin::{Actor, YOrigin},
     },
     google::User,
+    integrity,
     model::ProjectId,
 };
 use anyhow::{Context as _, Result, anyhow};
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::{
+    PgPool,
+    types::chrono::{DateTime, Utc},
+};
 use std::{
     collections::{HashMap, hash_map::Entry},
     fmt,
@@ -30,10 +37,13 @@ use std::{
         atomic::{self, Ordering::Relaxed},
     },
 };
-use tokio::sync::{Mutex, MutexGuard, mpsc::Sender};
+use tokio::sync::{Mutex, MutexGuard, broadcast, mpsc::Sender};
 use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
-use yrs::{ReadTxn as _, StateVector, Subscription, Update};
+use yrs::{ReadTxn as _, StateVector, Subscription, Update, updates::decoder::Decode as _};
+
+/// The node recorded in the origin of updates applied when catching up.
+const CATCH_UP_NODE: &str = "catch-up";
 
 #[derive(Debug)]
 enum ProjectInsertionError {
@@ -47,23 +57,29 @@ struct ProjectsMap {
 }
 
 pub(super) struct ProjectsState {
+    node_id: String,
     projects: Mutex<ProjectsMap>,
     process_msg_tx: Sender<ClientMessage>,
     doc_update_tx: Sender<DocUpdate>,
-    event_tx: Sender<KosoEvent>,
+    event_tx: EventSender,
+    index_tx: IndexSender,
+    changes_tx: broadcast::Sender<TaskChanges>,
     pool: &'static PgPool,
     tracker: tokio_util::task::TaskTracker,
 }
 
 impl ProjectsState {
     pub(super) fn new(
+        node_id: String,
         process_msg_tx: Sender<ClientMessage>,
         doc_update_tx: Sender<DocUpdate>,
-        event_tx: Sender<KosoEvent>,
+        event_tx: EventSender,
+        index_tx: IndexSender,
         pool: &'static PgPool,
         tracker: tokio_util::task::TaskTracker,
     ) -> Self {
         ProjectsState {
+            node_id,
             projects: Mutex::new(ProjectsMap {
                 map: HashMap::new(),
                 stopped: false,
@@ -71,11 +87,21 @@ impl ProjectsState {
             process_msg_tx,
             doc_update_tx,
             event_tx,
+            index_tx,
+            changes_tx: task_changes::channel(),
             pool,
             tracker,
         }
     }
 
+    pub(super) fn request_index_backfill(&self) {
+        self.index_tx.request_backfill();
+    }
+
+    pub(super) fn subscribe_task_changes(&self) -> broadcast::Receiver<TaskChanges> {
+        self.changes_tx.subscribe()
+    }
+
     pub(super) async fn add_and_init_local_client(
         &self,
         project_id: &ProjectId,
@@ -161,6 +187,24 @@ impl ProjectsState {
         Ok(())
     }
 
+    /// Returns the project, if it's loaded on this node.
+    pub(super) async fn get(&self, project_id: &ProjectId) -> Option<Arc<ProjectState>> {
+        let projects = self.projects.lock().await;
+        if projects.stopped {
+            return None;
+        }
+        projects.map.get(project_id).and_then(Weak::upgrade)
+    }
+
+    /// Returns all projects loaded on this node.
+    pub(super) async fn loaded(&self) -> Vec<Arc<ProjectState>> {
+        let projects = self.projects.lock().await;
+        if projects.stopped {
+            return Vec::new();
+        }
+        projects.map.values().filter_map(Weak::upgrade).collect()
+    }
+
     async fn get_or_init(
         &self,
         project_id: &ProjectId,
@@ -198,6 +242,7 @@ impl ProjectsState {
     fn new_project(&self, project_id: &String) -> Arc<ProjectState> {
         Arc::new(ProjectState {
             project_id: project_id.to_string(),
+            node_id: self.node_id.clone(),
             clients: Mutex::new(ClientsMap {
                 map: HashMap::new(),
                 stopped: false,
@@ -206,7 +251,11 @@ impl ProjectsState {
             doc_box: Mutex::new(None),
             doc_update_tx: self.doc_update_tx.clone(),
             event_tx: self.event_tx.clone(),
+            index_tx: self.index_tx.clone(),
+            changes_tx: self.changes_tx.clone(),
             updates: atomic::AtomicUsize::new(0),
+            synced_seq: atomic::AtomicI32::new(0),
+            snapshots: Mutex::new(SnapshotState::default()),
             pool: self.pool,
             tracker: self.tracker.clone(),
             stopped_token: CancellationToken::new(),
@@ -242,17 +291,43 @@ pub(crate) struct ClientsMap {
 
 pub(crate) struct ProjectState {
     pub(crate) project_id: ProjectId,
+    node_id: String,
     clients: Mutex<ClientsMap>,
     awarenesses: Mutex<HashMap<String, AwarenessState>>,
     pub(crate) doc_box: Mutex<Option<DocBox>>,
     updates: atomic::AtomicUsize,
+    /// Every update persisted, by any node, up to this seq has been applied to the doc.
+    /// Only advanced when loading or catching up, never from cluster notifications,
+    /// since those may arrive out of seq order.
+    synced_seq: atomic::AtomicI32,
+    pub(super) snapshots: Mutex<SnapshotState>,
     doc_update_tx: Sender<DocUpdate>,
-    pub(super) event_tx: Sender<KosoEvent>,
+    pub(super) event_tx: EventSender,
+    pub(super) index_tx: IndexSender,
+    pub(super) changes_tx: broadcast::Sender<TaskChanges>,
     pool: &'static PgPool,
     tracker: tokio_util::task::TaskTracker,
     pub(super) stopped_token: CancellationToken,
 }
 
+/// Tracks when to snapshot the project and whom to attribute the snapshot to.
+#[derive(Default)]
+pub(super) struct SnapshotState {
+    /// When the last periodic snapshot of the project was taken, if known.
+    /// Caches `storage::latest_snapshot_at` to avoid querying it on every update.
+    pub(super) last_snapshot_at: Option<DateTime<Utc>>,
+    /// The actor of the last update this node persisted, the latest a snapshot
+    /// taken now would include. None if this node hasn't persisted any yet.
+    pub(super) last_actor: Option<Actor>,
+}
+
+impl SnapshotState {
+    /// The actor to attribute a snapshot of the persisted updates to.
+    pub(super) fn actor(&self) -> Actor {
+        self.last_actor.clone().unwrap_or(Actor::Server)
+    }
+}
+
 impl ProjectState {
     async fn insert_client(
         &self,
@@ -296,15 +371,31 @@ impl ProjectState {
 
         // Load the doc if it wasn't already loaded by another client.
         tracing::debug!("Initializing new YDoc");
+        // Read the seq first. Updates persisted after it are either loaded too,
+        // or are yet to be committed and will be notified to the cluster listener.
+        let synced_seq = storage::latest_seq(&project.project_id, project.pool).await?;
         let (ydoc, update_count) = storage::load_doc(&project.project_id, project.pool).await?;
         tracing::debug!("Initialized new YDoc with {update_count} updates");
+        match integrity::check(&ydoc, &ydoc.transact()) {
+            Ok(report) if !report.violations.is_empty() => tracing::warn!(
+                "Project {} has {} integrity violations: {:?}",
+                project.project_id,
+                report.violations.len(),
+                report.violations
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to check integrity of {}: {e:?}", project.project_id),
+        }
         project.updates.store(update_count, Relaxed);
+        project.synced_seq.store(synced_seq, Relaxed);
 
         // Attach observers to the doc.
         let subs = vec![
             Self::create_doc_observer(project, &ydoc)?,
             Self::create_graph_observer(project, &ydoc),
             Self::create_deep_graph_observer(project, &ydoc),
+            Self::create_search_index_observer(project, &ydoc),
+            Self::create_task_changes_observer(project, &ydoc),
         ];
 
         let db = DocBox { ydoc, subs };
@@ -366,6 +457,38 @@ impl ProjectState {
         })
     }
 
+    fn create_search_index_observer(project: &Arc<ProjectState>, doc: &YDocProxy) -> Subscription {
+        let project = Arc::downgrade(project);
+        doc.observe_deep_graph(move |txn, events| {
+            let Some(project) = project.upgrade() else {
+                // This will never happen because the observer is invoked syncronously in
+                // ProjectState.apply_update while holding a strong reference to the project.
+                tracing::error!(
+                    "handle_deep_graph_update_events but weak project reference was destroyed"
+                );
+                return;
+            };
+
+            search_index::handle_deep_graph_update_events(txn, events, project);
+        })
+    }
+
+    fn create_task_changes_observer(project: &Arc<ProjectState>, doc: &YDocProxy) -> Subscription {
+        let project = Arc::downgrade(project);
+        doc.observe_deep_graph(move |txn, events| {
+            let Some(project) = project.upgrade() else {
+                // This will never happen because the observer is invoked syncronously in
+                // ProjectState.apply_update while holding a strong reference to the project.
+                tracing::error!(
+                    "handle_deep_graph_update_events but weak project reference was destroyed"
+                );
+                return;
+            };
+
+            task_changes::handle_deep_graph_update_events(txn, events, project);
+        })
+    }
+
     pub(super) async fn encode_state_as_update(&self, sv: &StateVector) -> Result<Vec<u8>> {
         let doc_box = self.doc_box.lock().await;
         let update = DocBox::doc_or_error(doc_box.as_ref())?
@@ -383,6 +506,38 @@ impl ProjectState {
             .context("Failed to apply doc update")
     }
 
+    /// Apply updates persisted since the doc was loaded or last caught up.
+    /// Used to recover updates whose cluster notifications were missed.
+    /// Some of them may already be applied, which is harmless.
+    pub(super) async fn catch_up(&self) -> Result<()> {
+        let doc_box = self.doc_box.lock().await;
+        let Some(doc_box) = doc_box.as_ref() else {
+            return Ok(());
+        };
+        let since = self.synced_seq.load(Relaxed);
+        let updates = storage::load_updates_after(&self.project_id, since, self.pool).await?;
+        let Some((latest, _)) = updates.last() else {
+            return Ok(());
+        };
+        let latest = *latest;
+        tracing::debug!(
+            "Catching up on {} updates of {} after seq {since}",
+            updates.len(),
+            self.project_id
+        );
+        {
+            let mut txn = doc_box
+                .ydoc
+                .transact_mut_with(YOrigin::remote(CATCH_UP_NODE, latest).as_origin()?);
+            for (_, update) in updates {
+                txn.apply_update(Update::decode_v2(&update)?)
+                    .context("Failed to apply caught up update")?;
+            }
+        }
+        self.synced_seq.fetch_max(latest, Relaxed);
+        Ok(())
+    }
+
     pub(super) async fn broadcast_msg(&self, data: Vec<u8>, exclude_who: Option<&String>) {
         let mut clients = self.clients.lock().await;
         if clients.stopped {
@@ -428,6 +583,9 @@ impl ProjectState {
         if let Err(e) = self.broadcast_awarenesses().await {
             tracing::warn!("Failed to broadcast awareness: {e:?}");
         }
+        if let Err(e) = self.publish_awareness(who, None).await {
+            tracing::warn!("Failed to publish awareness: {e:?}");
+        }
 
         match client {
             Some(mut client) => {
@@ -471,11 +629,44 @@ impl ProjectState {
         update: AwarenessUpdate,
     ) -> Result<()> {
         let state = update.into_state(user);
-        self.awarenesses.lock().await.insert(who.into(), state);
+        self.awarenesses
+            .lock()
+            .await
+            .insert(who.into(), state.clone());
         self.broadcast_awarenesses().await?;
+        self.publish_awareness(who, Some(state)).await?;
         Ok(())
     }
 
+    /// Apply an awareness change of a client connected to another node.
+    pub(super) async fn set_remote_awareness(
+        &self,
+        who: &str,
+        state: Option<AwarenessState>,
+    ) -> Result<()> {
+        {
+            let mut awarenesses = self.awarenesses.lock().await;
+            match state {
+                Some(state) => awarenesses.insert(who.into(), state),
+                None => awarenesses.remove(who),
+            };
+        }
+        self.broadcast_awarenesses().await
+    }
+
+    async fn publish_awareness(&self, who: &str, state: Option<AwarenessState>) -> Result<()> {
+        cluster::publish(
+            &self.node_id,
+            ClusterMessage::Awareness {
+                project_id: self.project_id.clone(),
+                who: who.to_string(),
+                state,
+            },
+            self.pool,
+        )
+        .await
+    }
+
     async fn broadcast_awarenesses(&self) -> Result<()> {
         let state = {
             let awarenesses = self.awarenesses.lock().await;
@@ -0,0 +1,418 @@
+use super::{
+    Inner,
+    projects_state::{DocBox, ProjectState},
+    storage,
+    txn_origin::from_origin,
+};
+use crate::api::{
+    model::{Graph, ProjectId, Task},
+    search,
+    yproxy::YTaskProxy,
+};
+use anyhow::{Context, Result, anyhow};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicBool, Ordering::Relaxed},
+    },
+};
+use tokio::sync::{
+    Notify,
+    mpsc::{Receiver, Sender, error::TrySendError},
+};
+use yrs::{Map as _, ReadTxn as _, types::EntryChange, types::Events, types::PathSegment};
+
+/// The tasks of a project touched by a single transaction.
+#[derive(Debug)]
+pub(super) struct IndexUpdate {
+    project_id: ProjectId,
+    tasks: Vec<Task>,
+    removed: Vec<String>,
+}
+
+/// Work for the `IndexProcessor` beyond the updates in its channel.
+#[derive(Default)]
+struct Reindex {
+    /// Projects with dropped index updates, to be reindexed from their doc.
+    dirty: std::sync::Mutex<HashMap<ProjectId, Weak<ProjectState>>>,
+    /// Whether to index projects that haven't been indexed yet.
+    backfill: AtomicBool,
+    notify: Notify,
+}
+
+/// Sends index updates to the `IndexProcessor`.
+///
+/// Updates are sent from synchronous doc observers and dropped when the channel
+/// is full. The project is then reindexed instead, so the index doesn't go stale.
+#[derive(Clone)]
+pub(super) struct IndexSender {
+    tx: Sender<IndexUpdate>,
+    reindex: Arc<Reindex>,
+}
+
+impl IndexSender {
+    fn send(&self, project: &Arc<ProjectState>, update: IndexUpdate) -> Result<()> {
+        match self.tx.try_send(update) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let newly_dirty = self
+                    .reindex
+                    .dirty
+                    .lock()
+                    .map_err(|e| anyhow!("Failed to lock dirty projects: {e}"))?
+                    .insert(project.project_id.clone(), Arc::downgrade(project))
+                    .is_none();
+                if newly_dirty {
+                    tracing::warn!(
+                        "Index update channel is full. Reindexing project {}",
+                        project.project_id
+                    );
+                }
+                self.reindex.notify.notify_one();
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(anyhow!("Index update channel is closed")),
+        }
+    }
+
+    /// Request that projects which haven't been indexed yet be indexed.
+    pub(super) fn request_backfill(&self) {
+        self.reindex.backfill.store(true, Relaxed);
+        self.reindex.notify.notify_one();
+    }
+}
+
+pub(super) fn channel(pool: &'static PgPool) -> (IndexSender, IndexProcessor) {
+    let (tx, rx) = tokio::sync::mpsc::channel::<IndexUpdate>(50);
+    let reindex = Arc::new(Reindex::default());
+    (
+        IndexSender {
+            tx,
+            reindex: Arc::clone(&reindex),
+        },
+        IndexProcessor {
+            pool,
+            index_rx: rx,
+            reindex,
+        },
+    )
+}
+
+/// Callback invoked on deep graph events. Collects the tasks touched by the
+/// transaction and forwards their current state to the `IndexProcessor`.
+pub(super) fn handle_deep_graph_update_events(
+    txn: &yrs::TransactionMut,
+    events: &Events,
+    project: Arc<ProjectState>,
+) {
+    if let Err(e) = handle_deep_graph_update_events_internal(txn, events, &project) {
+        tracing::warn!("Failed to handle search index update: {e:?}");
+    }
+}
+
+fn handle_deep_graph_update_events_internal(
+    txn: &yrs::TransactionMut,
+    events: &Events,
+    project: &Arc<ProjectState>,
+) -> Result<()> {
+    // The originating node indexes updates applied on behalf of other nodes.
+    if from_origin(txn.origin())?.is_remote() {
+        return Ok(());
+    }
+
+    let mut touched = HashSet::new();
+    let mut removed = Vec::new();
+    for event in events.iter() {
+        match event {
+            yrs::types::Event::Map(map_event) if map_event.path().is_empty() => {
+                for (task_id, change) in map_event.keys(txn).iter() {
+                    match change {
+                        EntryChange::Removed(_) => removed.push(task_id.to_string()),
+                        _ => {
+                            touched.insert(task_id.to_string());
+                        }
+                    }
+                }
+            }
+            event => {
+                if let Some(PathSegment::Key(task_id)) = event.path().front() {
+                    touched.insert(task_id.to_string());
+                }
+            }
+        }
+    }
+
+    let graph = txn.get_map("graph").context("graph map missing")?;
+    let mut tasks = Vec::with_capacity(touched.len());
+    for task_id in touched {
+        let Some(yrs::Out::YMap(task)) = graph.get(txn, &task_id) else {
+            continue;
+        };
+        let task = YTaskProxy::new(task)
+            .to_task(txn)
+            .with_context(|| format!("Failed to convert task {task_id}"))?;
+        if search::is_indexed(&task) {
+            tasks.push(task);
+        }
+    }
+    if tasks.is_empty() && removed.is_empty() {
+        return Ok(());
+    }
+
+    project
+        .index_tx
+        .send(
+            project,
+            IndexUpdate {
+                project_id: project.project_id.clone(),
+                tasks,
+                removed,
+            },
+        )
+        .context("Failed to send index update")
+}
+
+/// IndexProcessor receives index updates from a channel and applies them,
+/// in order, to the search index. In between, it reindexes projects whose
+/// updates were dropped and backfills projects that aren't indexed yet.
+pub(super) struct IndexProcessor {
+    pool: &'static PgPool,
+    index_rx: Receiver<IndexUpdate>,
+    reindex: Arc<Reindex>,
+}
+
+impl IndexProcessor {
+    /// Process updates until the channel closes. Projects loaded in `collab`
+    /// are reindexed from their loaded doc.
+    #[tracing::instrument(skip(self, collab))]
+    pub(super) async fn process_index_updates(mut self, collab: Weak<Inner>) {
+        loop {
+            tokio::select! {
+                biased;
+                update = self.index_rx.recv() => {
+                    let Some(update) = update else {
+                        break;
+                    };
+                    if let Err(e) = search::index_tasks(
+                        &update.project_id,
+                        &update.tasks,
+                        &update.removed,
+                        self.pool,
+                    )
+                    .await
+                    {
+                        tracing::warn!("Failed to process index update: {e:?}");
+                    }
+                }
+                _ = self.reindex.notify.notified() => {}
+            }
+            if let Err(e) = self.reindex_dirty_projects().await {
+                tracing::warn!("Failed to reindex dirty projects: {e:?}");
+            }
+            if self.reindex.backfill.swap(false, Relaxed)
+                && let Err(e) = self.backfill_projects(&collab).await
+            {
+                tracing::warn!("Failed to backfill search index: {e:?}");
+            }
+        }
+        tracing::info!("Stopped processing index updates");
+    }
+
+    async fn reindex_dirty_projects(&self) -> Result<()> {
+        let dirty = std::mem::take(
+            &mut *self
+                .reindex
+                .dirty
+                .lock()
+                .map_err(|e| anyhow!("Failed to lock dirty projects: {e}"))?,
+        );
+        for (project_id, project) in dirty {
+            if let Err(e) = self.reindex_project(&project_id, project.upgrade()).await {
+                tracing::warn!("Failed to reindex project {project_id}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Index the tasks of all projects that haven't been indexed yet.
+    async fn backfill_projects(&self, collab: &Weak<Inner>) -> Result<()> {
+        for project_id in search::list_unindexed_projects(self.pool).await? {
+            let project = match collab.upgrade() {
+                Some(collab) => collab.state.get(&project_id).await,
+                None => None,
+            };
+            if let Err(e) = self.reindex_project(&project_id, project).await {
+                tracing::warn!("Failed to backfill project {project_id}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn reindex_project(
+        &self,
+        project_id: &ProjectId,
+        project: Option<Arc<ProjectState>>,
+    ) -> Result<()> {
+        let graph = load_graph(project_id, project, self.pool).await?;
+        search::reindex_project(project_id, &graph, self.pool).await
+    }
+}
+
+/// The project's graph, preferring the loaded doc, which may be ahead of the persisted updates.
+async fn load_graph(
+    project_id: &ProjectId,
+    project: Option<Arc<ProjectState>>,
+    pool: &PgPool,
+) -> Result<Graph> {
+    match project {
+        Some(project) => {
+            let doc_box = project.doc_box.lock().await;
+            let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+            doc.to_graph(&doc.transact())
+        }
+        None => {
+            let (doc, _) = storage::load_doc(project_id, pool).await?;
+            doc.to_graph(&doc.transact())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{
+        collab::{
+            Collab,
+            projects_state::DocBox,
+            txn_origin::{Actor, YOrigin},
+        },
+        model::Task,
+    };
+    use crate::tests::db::UnsafePoolWrapper;
+    use sqlx::PgPool;
+    use std::time::Duration;
+
+    #[test_log::test(sqlx::test)]
+    async fn dropped_index_updates_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let collab = Collab::new(pool).await.unwrap();
+        let project_id = "dropped".to_string();
+        let client = collab.register_local_client(&project_id).await.unwrap();
+        let origin = YOrigin {
+            who: "dropped_index_updates_test".into(),
+            id: "test".into(),
+            actor: Actor::None,
+        }
+        .as_origin()
+        .unwrap();
+
+        // Without yielding, each transaction's update fills the channel until they're dropped.
+        {
+            let doc_box = client.project.doc_box.lock().await;
+            let doc = &DocBox::doc_or_error(doc_box.as_ref()).unwrap().ydoc;
+            for i in 0..120 {
+                let mut txn = doc.transact_mut_with(origin.clone());
+                doc.set(
+                    &mut txn,
+                    &Task {
+                        id: format!("id{i}"),
+                        num: format!("{i}"),
+                        name: format!("Task {i}"),
+                        ..Task::default()
+                    },
+                );
+            }
+        }
+
+        // Dropping them reindexes the project instead.
+        let mut count: i64 = 0;
+        for _ in 0..50 {
+            (count,) = sqlx::query_as("SELECT COUNT(*) FROM task_index WHERE project_id = $1")
+                .bind(&project_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            if count == 120 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(count, 120);
+
+        drop(client);
+        collab.stop().await;
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn backfill_uses_loaded_doc_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let project_id = "loaded".to_string();
+        sqlx::query("INSERT INTO projects (project_id, name) VALUES ($1, 'Loaded')")
+            .bind(&project_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        let collab = Collab::new(pool).await.unwrap();
+
+        // Let the backfill requested on startup index the empty project, then
+        // mark it unindexed again so it's backfilled once the doc has the task.
+        for _ in 0..50 {
+            let (indexed,): (bool,) = sqlx::query_as(
+                "SELECT EXISTS (SELECT 1 FROM task_index_projects WHERE project_id = $1)",
+            )
+            .bind(&project_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+            if indexed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        sqlx::query("DELETE FROM task_index_projects WHERE project_id = $1")
+            .bind(&project_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        let client = collab.register_local_client(&project_id).await.unwrap();
+
+        // Updates from other nodes are neither persisted nor indexed here, so
+        // only the loaded doc has the task.
+        {
+            let doc_box = client.project.doc_box.lock().await;
+            let doc = &DocBox::doc_or_error(doc_box.as_ref()).unwrap().ydoc;
+            let mut txn =
+                doc.transact_mut_with(YOrigin::remote("other-node", 1).as_origin().unwrap());
+            doc.set(
+                &mut txn,
+                &Task {
+                    id: "id1".to_string(),
+                    num: "1".to_string(),
+                    name: "Task 1".to_string(),
+                    ..Task::default()
+                },
+            );
+        }
+
+        collab.backfill_search_index();
+        let mut count: i64 = 0;
+        for _ in 0..50 {
+            (count,) = sqlx::query_as("SELECT COUNT(*) FROM task_index WHERE project_id = $1")
+                .bind(&project_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            if count == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(count, 1);
+
+        drop(client);
+        collab.stop().await;
+    }
+}
@@ -1,36 +1,92 @@
 use super::{
     YDocProxy,
-    txn_origin::{self, YOrigin},
+    txn_origin::{self, Actor, YOrigin},
 };
-use crate::api::model::ProjectId;
+use crate::api::model::{ProjectId, Snapshot};
 use anyhow::{Context as _, Result, anyhow};
-use sqlx::{PgPool, Postgres};
+use chrono::TimeDelta;
+use sqlx::{
+    PgPool, Postgres,
+    types::{
+        Json,
+        chrono::{DateTime, Utc},
+    },
+};
+use std::collections::HashMap;
 use yrs::{
-    Update,
+    ReadTxn as _, StateVector, Update,
     updates::{decoder::Decode, encoder::Encode},
 };
 
+/// Minimum time between two periodic snapshots of a project.
+pub(super) const SNAPSHOT_INTERVAL: TimeDelta = TimeDelta::minutes(15);
+/// Snapshots older than this are pruned, except for the most recent `SNAPSHOT_MIN_KEPT`.
+const SNAPSHOT_RETENTION: TimeDelta = TimeDelta::days(30);
+/// Number of snapshots kept regardless of age, so idle projects can still be restored.
+const SNAPSHOT_MIN_KEPT: i64 = 10;
+
 pub(in crate::api) async fn persist_update<'a, E: sqlx::Executor<'a, Database = Postgres>>(
     project_id: &ProjectId,
     data: &Vec<u8>,
     pool: E,
-) -> Result<()> {
-    sqlx::query(
+) -> Result<i32> {
+    let (seq,): (i32,) = sqlx::query_as(
         "
             INSERT INTO yupdates (project_id, seq, update_v2)
-            VALUES ($1, DEFAULT, $2)",
+            VALUES ($1, DEFAULT, $2)
+            RETURNING seq",
     )
     .bind(project_id)
     .bind(data)
-    .execute(pool)
+    .fetch_one(pool)
     .await?;
-    Ok(())
+    Ok(seq)
 }
 
 pub(super) async fn load_doc(project_id: &ProjectId, pool: &PgPool) -> Result<(YDocProxy, usize)> {
     let updates = load_raw_updates(project_id, pool).await?;
     let update_count = updates.len();
+    let ydoc = doc_from_updates(project_id, updates.into_iter().map(|(update,)| update))?;
+    Result::Ok((ydoc, update_count))
+}
+
+/// Load the docs of several projects with a single query.
+/// Projects without any updates are returned as empty docs.
+pub(super) async fn load_docs(
+    project_ids: &[ProjectId],
+    pool: &PgPool,
+) -> Result<HashMap<ProjectId, YDocProxy>> {
+    let rows: Vec<(ProjectId, Vec<u8>)> = sqlx::query_as(
+        "
+        SELECT project_id, update_v2
+        FROM yupdates
+        WHERE project_id = ANY($1)",
+    )
+    .bind(project_ids)
+    .fetch_all(pool)
+    .await
+    .context("Failed to load updates")?;
+
+    let mut updates: HashMap<ProjectId, Vec<Vec<u8>>> = project_ids
+        .iter()
+        .map(|project_id| (project_id.clone(), Vec::new()))
+        .collect();
+    for (project_id, update) in rows {
+        updates.entry(project_id).or_default().push(update);
+    }
+    updates
+        .into_iter()
+        .map(|(project_id, updates)| {
+            let ydoc = doc_from_updates(&project_id, updates)?;
+            Ok((project_id, ydoc))
+        })
+        .collect()
+}
 
+fn doc_from_updates(
+    project_id: &ProjectId,
+    updates: impl IntoIterator<Item = Vec<u8>>,
+) -> Result<YDocProxy> {
     let ydoc = YDocProxy::new();
     {
         let mut txn = ydoc.transact_mut_with(
@@ -41,12 +97,12 @@ pub(super) async fn load_doc(project_id: &ProjectId, pool: &PgPool) -> Result<(Y
             }
             .as_origin()?,
         );
-        for (update,) in updates {
+        for update in updates {
             txn.apply_update(Update::decode_v2(&update)?)
                 .context("Failed to apply loaded update")?
         }
     }
-    Result::Ok((ydoc, update_count))
+    Ok(ydoc)
 }
 
 pub async fn load_updates(project_id: &ProjectId, pool: &PgPool) -> Result<Vec<Update>> {
@@ -68,6 +124,170 @@ async fn load_raw_updates(project_id: &ProjectId, pool: &PgPool) -> Result<Vec<(
     Ok(updates)
 }
 
+/// The seq of the latest persisted update of a project, or 0 if there are none.
+pub(super) async fn latest_seq(project_id: &ProjectId, pool: &PgPool) -> Result<i32> {
+    let (seq,): (i32,) =
+        sqlx::query_as("SELECT COALESCE(MAX(seq), 0) FROM yupdates WHERE project_id=$1")
+            .bind(project_id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to query latest seq")?;
+    Ok(seq)
+}
+
+/// Load the persisted updates of a project following `seq`, in order.
+pub(super) async fn load_updates_after(
+    project_id: &ProjectId,
+    seq: i32,
+    pool: &PgPool,
+) -> Result<Vec<(i32, Vec<u8>)>> {
+    let updates: Vec<(i32, Vec<u8>)> = sqlx::query_as(
+        "SELECT seq, update_v2 FROM yupdates WHERE project_id=$1 AND seq > $2 ORDER BY seq",
+    )
+    .bind(project_id)
+    .bind(seq)
+    .fetch_all(pool)
+    .await
+    .context("Failed to load updates")?;
+    Ok(updates)
+}
+
+/// Encode the persisted updates of a project preceding `seq` as a single update.
+pub(super) async fn encode_updates_before(
+    project_id: &ProjectId,
+    seq: i32,
+    pool: &PgPool,
+) -> Result<Vec<u8>> {
+    let updates: Vec<(Vec<u8>,)> =
+        sqlx::query_as("SELECT update_v2 FROM yupdates WHERE project_id=$1 AND seq < $2")
+            .bind(project_id)
+            .bind(seq)
+            .fetch_all(pool)
+            .await
+            .context("Failed to load updates")?;
+    let ydoc = doc_from_updates(project_id, updates.into_iter().map(|(update,)| update))?;
+    Ok(ydoc
+        .transact()
+        .encode_state_as_update_v2(&StateVector::default()))
+}
+
+/// Persist the full state of a project, encoded as a single update, as a snapshot,
+/// and prune the project's expired snapshots.
+pub(super) async fn persist_snapshot(
+    project_id: &ProjectId,
+    actor: &Actor,
+    data: &Vec<u8>,
+    pool: &PgPool,
+) -> Result<Snapshot> {
+    let snapshot = sqlx::query_as(
+        "
+        INSERT INTO ysnapshots (project_id, snapshot_id, created_at, actor, update_v2)
+        VALUES ($1, DEFAULT, NOW(), $2, $3)
+        RETURNING project_id, snapshot_id, created_at, actor",
+    )
+    .bind(project_id)
+    .bind(Json(actor))
+    .bind(data)
+    .fetch_one(pool)
+    .await
+    .context("Failed to persist snapshot")?;
+    prune_snapshots(project_id, pool).await?;
+    Ok(snapshot)
+}
+
+/// Delete snapshots older than `SNAPSHOT_RETENTION`, keeping the `SNAPSHOT_MIN_KEPT` most recent.
+async fn prune_snapshots(project_id: &ProjectId, pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        "
+        DELETE FROM ysnapshots
+        WHERE project_id = $1
+        AND created_at < NOW() - make_interval(secs => $2)
+        AND snapshot_id NOT IN (
+            SELECT snapshot_id FROM ysnapshots
+            WHERE project_id = $1
+            ORDER BY snapshot_id DESC
+            LIMIT $3
+        )",
+    )
+    .bind(project_id)
+    .bind(SNAPSHOT_RETENTION.as_seconds_f64())
+    .bind(SNAPSHOT_MIN_KEPT)
+    .execute(pool)
+    .await
+    .context("Failed to prune snapshots")?;
+    Ok(result.rows_affected())
+}
+
+/// Returns when the most recent snapshot of the project was taken, if ever.
+pub(super) async fn latest_snapshot_at(
+    project_id: &ProjectId,
+    pool: &PgPool,
+) -> Result<Option<DateTime<Utc>>> {
+    let (created_at,): (Option<DateTime<Utc>>,) =
+        sqlx::query_as("SELECT MAX(created_at) FROM ysnapshots WHERE project_id = $1")
+            .bind(project_id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to find latest snapshot")?;
+    Ok(created_at)
+}
+
+pub(crate) async fn list_snapshots(project_id: &ProjectId, pool: &PgPool) -> Result<Vec<Snapshot>> {
+    sqlx::query_as(
+        "
+        SELECT project_id, snapshot_id, created_at, actor
+        FROM ysnapshots
+        WHERE project_id = $1
+        ORDER BY snapshot_id DESC",
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list snapshots")
+}
+
+/// Load the doc as it was when the given snapshot was taken.
+pub(super) async fn load_snapshot(
+    project_id: &ProjectId,
+    snapshot_id: i32,
+    pool: &PgPool,
+) -> Result<Option<(Snapshot, YDocProxy)>> {
+    let row: Option<(DateTime<Utc>, Json<Actor>, Vec<u8>)> = sqlx::query_as(
+        "
+        SELECT created_at, actor, update_v2
+        FROM ysnapshots
+        WHERE project_id = $1
+        AND snapshot_id = $2",
+    )
+    .bind(project_id)
+    .bind(snapshot_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to load snapshot")?;
+    let Some((created_at, Json(actor), update)) = row else {
+        return Ok(None);
+    };
+    let snapshot = Snapshot {
+        project_id: project_id.clone(),
+        snapshot_id,
+        created_at,
+        actor,
+    };
+
+    let ydoc = YDocProxy::new();
+    ydoc.transact_mut_with(
+        YOrigin {
+            who: "load_snapshot".to_string(),
+            id: format!("{project_id}-{snapshot_id}"),
+            actor: txn_origin::Actor::Server,
+        }
+        .as_origin()?,
+    )
+    .apply_update(Update::decode_v2(&update)?)
+    .context("Failed to apply snapshot update")?;
+    Ok(Some((snapshot, ydoc)))
+}
+
 #[tracing::instrument(skip(pool))]
 pub(crate) async fn compact(pool: &PgPool, project_id: ProjectId) {
     if let Err(e) = _compact(pool, project_id).await {
@@ -145,3 +365,49 @@ async fn _compact(pool: &PgPool, project_id: ProjectId) -> Result<()> {
     tracing::debug!("Compacted {} updates", consumed_sequences.len());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test(sqlx::test)]
+    async fn prune_snapshots_test(pool: PgPool) {
+        let project_id = "prune".to_string();
+        for _ in 0..14 {
+            persist_snapshot(&project_id, &Actor::Server, &vec![], &pool)
+                .await
+                .unwrap();
+        }
+        // Age all but the latest three snapshots past the retention period.
+        sqlx::query(
+            "
+            UPDATE ysnapshots
+            SET created_at = NOW() - INTERVAL '60 days'
+            WHERE snapshot_id NOT IN (
+                SELECT snapshot_id FROM ysnapshots ORDER BY snapshot_id DESC LIMIT 3
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        persist_snapshot(&project_id, &Actor::Server, &vec![], &pool)
+            .await
+            .unwrap();
+
+        // The latest ten are kept, even though seven of them expired.
+        let snapshots = list_snapshots(&project_id, &pool).await.unwrap();
+        assert_eq!(snapshots.len(), SNAPSHOT_MIN_KEPT as usize);
+        assert_eq!(snapshots[0].snapshot_id, 15);
+        assert_eq!(snapshots[9].snapshot_id, 6);
+
+        // Once more than ten are recent, every expired one is pruned.
+        for _ in 0..10 {
+            persist_snapshot(&project_id, &Actor::Server, &vec![], &pool)
+                .await
+                .unwrap();
+        }
+        let snapshots = list_snapshots(&project_id, &pool).await.unwrap();
+        assert_eq!(snapshots.len(), 14);
+        assert!(snapshots.iter().all(|s| s.snapshot_id >= 12));
+    }
+}
@@ -0,0 +1,134 @@
+use super::{
+    projects_state::ProjectState,
+    txn_origin::{YOrigin, from_origin},
+};
+use crate::api::model::ProjectId;
+use std::{collections::HashSet, sync::Arc};
+use tokio::sync::broadcast::Sender;
+use yrs::{
+    TransactionMut,
+    types::{Events, PathSegment},
+};
+
+/// The tasks of a project changed by a single transaction, including
+/// transactions applied on behalf of other nodes.
+#[derive(Clone, Debug)]
+pub(crate) struct TaskChanges {
+    pub(crate) project_id: ProjectId,
+    pub(crate) task_ids: Arc<HashSet<String>>,
+    /// The origin of the transaction, if it has a valid one.
+    pub(crate) origin: Option<Arc<YOrigin>>,
+}
+
+/// Callback invoked on deep graph events. Broadcasts the IDs of the tasks
+/// touched by the transaction to subscribers, if any.
+pub(super) fn handle_deep_graph_update_events(
+    txn: &TransactionMut,
+    events: &Events,
+    project: Arc<ProjectState>,
+) {
+    if project.changes_tx.receiver_count() == 0 {
+        return;
+    }
+
+    let mut task_ids = HashSet::new();
+    for event in events.iter() {
+        match event {
+            yrs::types::Event::Map(map_event) if map_event.path().is_empty() => {
+                task_ids.extend(map_event.keys(txn).keys().map(|id| id.to_string()));
+            }
+            event => {
+                if let Some(PathSegment::Key(task_id)) = event.path().front() {
+                    task_ids.insert(task_id.to_string());
+                }
+            }
+        }
+    }
+    if task_ids.is_empty() {
+        return;
+    }
+
+    // Sending only fails when every receiver was dropped in the meantime.
+    let _ = project.changes_tx.send(TaskChanges {
+        project_id: project.project_id.clone(),
+        task_ids: Arc::new(task_ids),
+        origin: from_origin(txn.origin()).ok().map(Arc::new),
+    });
+}
+
+/// Fans out task changes to subscribers. Subscribers that fall behind
+/// receive `RecvError::Lagged` and must assume any task changed.
+pub(super) fn channel() -> Sender<TaskChanges> {
+    let (changes_tx, _) = tokio::sync::broadcast::channel(100);
+    changes_tx
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{
+            collab::{
+                Collab,
+                projects_state::DocBox,
+                txn_origin::{Actor, YOrigin},
+            },
+            model::Task,
+        },
+        tests::db::UnsafePoolWrapper,
+    };
+    use sqlx::PgPool;
+    use std::collections::HashSet;
+
+    #[test_log::test(sqlx::test)]
+    async fn task_changes_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let collab = Collab::new(pool).await.unwrap();
+        let mut changes = collab.subscribe_task_changes();
+        let project_id = "changes".to_string();
+        let client = collab.register_local_client(&project_id).await.unwrap();
+        let origin = YOrigin {
+            who: "task_changes_test".into(),
+            id: "test".into(),
+            actor: Actor::None,
+        }
+        .as_origin()
+        .unwrap();
+
+        {
+            let doc_box = client.project.doc_box.lock().await;
+            let doc = &DocBox::doc_or_error(doc_box.as_ref()).unwrap().ydoc;
+            let mut txn = doc.transact_mut_with(origin.clone());
+            for id in ["1", "2"] {
+                doc.set(
+                    &mut txn,
+                    &Task {
+                        id: id.into(),
+                        num: id.into(),
+                        name: format!("Task {id}"),
+                        ..Task::default()
+                    },
+                );
+            }
+        }
+        let change = changes.recv().await.unwrap();
+        assert_eq!(change.project_id, project_id);
+        assert_eq!(change.origin.unwrap().who, "task_changes_test");
+        assert_eq!(
+            *change.task_ids,
+            HashSet::from(["1".to_string(), "2".to_string()])
+        );
+
+        {
+            let doc_box = client.project.doc_box.lock().await;
+            let doc = &DocBox::doc_or_error(doc_box.as_ref()).unwrap().ydoc;
+            let mut txn = doc.transact_mut_with(origin);
+            doc.get(&txn, "2").unwrap().set_name(&mut txn, "Renamed");
+        }
+        let change = changes.recv().await.unwrap();
+        assert_eq!(*change.task_ids, HashSet::from(["2".to_string()]));
+
+        drop(client);
+        collab.stop().await;
+    }
+}
@@ -4,12 +4,15 @@ use yrs::Origin;
 
 use crate::api::google::User;
 
+const REMOTE_PREFIX: &str = "remote-";
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) enum Actor {
     None,
     User(User),
     GitHub,
+    GitLab,
     Server,
 }
 
@@ -33,6 +36,20 @@ impl YOrigin {
         Ok(serde_json::to_string(self)?.into())
     }
 
+    /// Origin of an update applied on behalf of another collab node.
+    /// The originating node already persisted the update and handled its side effects.
+    pub(crate) fn remote(node_id: &str, seq: i32) -> YOrigin {
+        YOrigin {
+            who: format!("{REMOTE_PREFIX}{node_id}"),
+            id: format!("{REMOTE_PREFIX}{seq}"),
+            actor: Actor::Server,
+        }
+    }
+
+    pub(crate) fn is_remote(&self) -> bool {
+        self.who.starts_with(REMOTE_PREFIX)
+    }
+
     pub(crate) fn delegated(&self, prefix: &str) -> YOrigin {
         YOrigin {
             who: format!("{}-{}", prefix, self.who),
@@ -0,0 +1,142 @@
+use crate::api::{
+    collab::{Collab, projects_state::DocBox},
+    google::User,
+    model::{ProjectId, ProjectRole},
+    verify_project_access,
+};
+use anyhow::Context;
+use axum::{Extension, Json, extract::Path};
+use axum_anyhow::{ApiResult, OptionExt, bad_request, forbidden, not_found};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    postgres::PgPool,
+    types::chrono::{DateTime, Utc},
+};
+use uuid::Uuid;
+
+/// Comments longer than this are rejected.
+const MAX_COMMENT_LEN: usize = 10_000;
+
+/// A comment on a task. Commenters, and more privileged roles, may add them.
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskComment {
+    pub(crate) comment_id: String,
+    pub(crate) project_id: ProjectId,
+    pub(crate) task_id: String,
+    pub(crate) email: String,
+    pub(crate) body: String,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateTaskComment {
+    body: String,
+}
+
+/// Lists the comments on a task, oldest first.
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn list_comments_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path((project_id, task_id)): Path<(ProjectId, String)>,
+) -> ApiResult<Json<Vec<TaskComment>>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let comments = sqlx::query_as(
+        "
+        SELECT comment_id, project_id, task_id, email, body, created_at
+        FROM task_comments
+        WHERE project_id = $1 AND task_id = $2
+        ORDER BY created_at, comment_id",
+    )
+    .bind(&project_id)
+    .bind(&task_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list comments")?;
+    Ok(Json(comments))
+}
+
+#[tracing::instrument(skip(user, pool, collab, comment))]
+pub(crate) async fn create_comment_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path((project_id, task_id)): Path<(ProjectId, String)>,
+    Json(comment): Json<CreateTaskComment>,
+) -> ApiResult<Json<TaskComment>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Commenter).await?;
+
+    let body = comment.body.trim();
+    if body.is_empty() || body.chars().count() > MAX_COMMENT_LEN {
+        return Err(bad_request(
+            "INVALID_COMMENT",
+            &format!("Comments must have between 1 and {MAX_COMMENT_LEN} characters"),
+        ));
+    }
+    {
+        let client = collab.register_local_client(&project_id).await?;
+        let doc_box = client.project.doc_box.lock().await;
+        let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+        if !doc.contains(&doc.transact(), &task_id) {
+            return Err(not_found("NOT_FOUND", &format!("Task {task_id} not found")));
+        }
+    }
+
+    let comment = sqlx::query_as(
+        "
+        INSERT INTO task_comments (comment_id, project_id, task_id, email, body)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING comment_id, project_id, task_id, email, body, created_at",
+    )
+    .bind(BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4()))
+    .bind(&project_id)
+    .bind(&task_id)
+    .bind(&user.email)
+    .bind(body)
+    .fetch_one(pool)
+    .await
+    .context("Failed to insert comment")?;
+    Ok(Json(comment))
+}
+
+/// Deletes a comment. Commenters may delete their own comments, owners any.
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn delete_comment_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path((project_id, task_id, comment_id)): Path<(ProjectId, String, String)>,
+) -> ApiResult<Json<()>> {
+    let role = verify_project_access(pool, &user, &project_id, ProjectRole::Commenter).await?;
+
+    let author: Option<(String,)> = sqlx::query_as(
+        "
+        SELECT email
+        FROM task_comments
+        WHERE comment_id = $1 AND project_id = $2 AND task_id = $3",
+    )
+    .bind(&comment_id)
+    .bind(&project_id)
+    .bind(&task_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get comment")?;
+    let (author,) =
+        author.context_not_found("NOT_FOUND", &format!("Comment {comment_id} not found"))?;
+    if author != user.email && role < ProjectRole::Owner {
+        return Err(forbidden(
+            "INSUFFICIENT_ROLE",
+            "Only owners may delete the comments of others",
+        ));
+    }
+
+    sqlx::query("DELETE FROM task_comments WHERE comment_id = $1")
+        .bind(&comment_id)
+        .execute(pool)
+        .await
+        .context("Failed to delete comment")?;
+    Ok(Json(()))
+}
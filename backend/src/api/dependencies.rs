@@ -0,0 +1,110 @@
+use crate::api::{
+    collab::{
+        Collab,
+        projects_state::DocBox,
+        txn_origin::{Actor, YOrigin},
+    },
+    google::User,
+    model::{ProjectId, ProjectRole, Task},
+    verify_project_access,
+    yproxy::{YDocProxy, YTaskProxy},
+};
+use axum::{Extension, Json, extract::Path};
+use axum_anyhow::{ApiResult, bad_request, not_found};
+use serde::Deserialize;
+use sqlx::postgres::PgPool;
+use uuid::Uuid;
+use yrs::ReadTxn;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AddDependency {
+    /// The task that must be done first.
+    blocker_id: String,
+}
+
+/// Makes a task depend on another, blocking task, returning the updated task.
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn add_dependency_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path((project_id, task_id)): Path<(ProjectId, String)>,
+    Json(dependency): Json<AddDependency>,
+) -> ApiResult<Json<Task>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Editor).await?;
+
+    let origin = YOrigin {
+        who: format!("dependency-{}", user.email),
+        id: format!("add_dependency_{}", Uuid::new_v4()),
+        actor: Actor::User(user),
+    };
+    let client = collab.register_local_client(&project_id).await?;
+    let doc_box = client.project.doc_box.lock().await;
+    let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+    let mut txn = doc.transact_mut_with(origin.as_origin()?);
+    let task = get_editable_task(doc, &txn, &task_id)?;
+    get_task(doc, &txn, &dependency.blocker_id)?;
+    if !doc.can_add_dependency(&txn, &task_id, &dependency.blocker_id)? {
+        return Err(bad_request(
+            "INVALID_DEPENDENCY",
+            &format!(
+                "Task {task_id} cannot depend on {}. It already does, or the tasks would wait on each other",
+                dependency.blocker_id
+            ),
+        ));
+    }
+    doc.add_dependency(&mut txn, &task_id, &dependency.blocker_id)?;
+    Ok(Json(task.to_task(&txn)?))
+}
+
+/// Removes a task's dependency on a blocking task, returning the updated task.
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn remove_dependency_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path((project_id, task_id, blocker_id)): Path<(ProjectId, String, String)>,
+) -> ApiResult<Json<Task>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Editor).await?;
+
+    let origin = YOrigin {
+        who: format!("dependency-{}", user.email),
+        id: format!("remove_dependency_{}", Uuid::new_v4()),
+        actor: Actor::User(user),
+    };
+    let client = collab.register_local_client(&project_id).await?;
+    let doc_box = client.project.doc_box.lock().await;
+    let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+    let mut txn = doc.transact_mut_with(origin.as_origin()?);
+    let task = get_editable_task(doc, &txn, &task_id)?;
+    if !doc.remove_dependency(&mut txn, &task_id, &blocker_id)? {
+        return Err(not_found(
+            "DEPENDENCY_NOT_FOUND",
+            &format!("Task {task_id} does not depend on {blocker_id}"),
+        ));
+    }
+    Ok(Json(task.to_task(&txn)?))
+}
+
+fn get_task<T: ReadTxn>(doc: &YDocProxy, txn: &T, task_id: &str) -> ApiResult<YTaskProxy> {
+    if !doc.contains(txn, task_id) {
+        return Err(not_found(
+            "TASK_NOT_FOUND",
+            &format!("Task {task_id} not found"),
+        ));
+    }
+    Ok(doc.get(txn, task_id)?)
+}
+
+/// Tasks managed by a plugin, like GitHub PRs, may only be changed by the plugin.
+fn get_editable_task<T: ReadTxn>(doc: &YDocProxy, txn: &T, task_id: &str) -> ApiResult<YTaskProxy> {
+    let task = get_task(doc, txn, task_id)?;
+    if task_id == "root" || task.is_managed(txn)? {
+        return Err(bad_request(
+            "TASK_NOT_EDITABLE",
+            &format!("Task {task_id} is managed by Koso or a plugin and cannot be edited"),
+        ));
+    }
+    Ok(task)
+}
@@ -1,4 +1,8 @@
-use crate::api::{google::User, model::ProjectId, verify_project_access};
+use crate::api::{
+    google::User,
+    model::{ProjectId, ProjectRole},
+    verify_project_access,
+};
 use anyhow::{Context, Result};
 use axum::{Extension, Json, extract::Path};
 use axum_anyhow::{ApiResult, OptionExt, bad_request};
@@ -39,7 +43,7 @@ pub(crate) async fn list_dupes_handler(
     Extension(pool): Extension<&'static PgPool>,
     Path(project_id): Path<String>,
 ) -> ApiResult<Json<Vec<DedupeCandidate>>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
 
     let dupes = list_dedupe_candidates(&project_id, pool).await?;
     Ok(Json(dupes))
@@ -52,7 +56,7 @@ pub(crate) async fn create_dupe_handler(
     Path(project_id): Path<String>,
     Json(create_dupe): Json<CreateDupeCandidate>,
 ) -> ApiResult<Json<DedupeCandidate>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Editor).await?;
 
     // Validate that task IDs are different
     if create_dupe.task_1_id == create_dupe.task_2_id {
@@ -164,7 +168,7 @@ pub(crate) async fn get_dupe_handler(
     Extension(pool): Extension<&'static PgPool>,
     Path((project_id, dupe_id)): Path<(String, String)>,
 ) -> ApiResult<Json<DedupeCandidate>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
 
     let dupe = get_dedupe_candidate(&dupe_id, &project_id, pool).await?;
     Ok(Json(dupe))
@@ -176,7 +180,7 @@ pub(crate) async fn update_dupe_resolution_handler(
     Path((project_id, dupe_id)): Path<(String, String)>,
     Json(resolution_update): Json<ResolutionUpdate>,
 ) -> ApiResult<Json<DedupeCandidate>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Editor).await?;
 
     let updated_dupe = update_dedupe_resolution(
         &dupe_id,
@@ -0,0 +1,399 @@
+use crate::api::{
+    collab::Collab,
+    google::User,
+    import::EXPORT_VERSION,
+    model::{Graph, ProjectExport, ProjectId, ProjectRole, Task},
+    verify_project_access,
+};
+use anyhow::{Context, Result};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use axum_anyhow::{ApiResult, OptionExt};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashSet;
+
+const ROOT_ID: &str = "root";
+const DEFAULT_STATUS: &str = "Not Started";
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ExportFormat {
+    /// The project's graph, which can be imported as a new project.
+    #[default]
+    Json,
+    /// One row per task in the tree, for spreadsheets.
+    Csv,
+    /// A nested checklist of the tree, for status reports.
+    Markdown,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    /// Export only the subtree rooted at the task.
+    task_id: Option<String>,
+}
+
+#[tracing::instrument(skip(user, pool, collab))]
+pub(super) async fn export_project_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path(project_id): Path<ProjectId>,
+    Query(query): Query<ExportQuery>,
+) -> ApiResult<Response> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let graph = collab.get_graph(&project_id).await?;
+    if let Some(task_id) = &query.task_id {
+        graph.get(task_id).context_not_found(
+            "TASK_NOT_FOUND",
+            &format!("Task {task_id} not found in project {project_id}"),
+        )?;
+    }
+    let root_id = query.task_id.as_deref().unwrap_or(ROOT_ID);
+
+    let (content_type, body) = match query.format {
+        ExportFormat::Json => {
+            let graph = match &query.task_id {
+                Some(task_id) => subtree(&graph, task_id),
+                None => graph,
+            };
+            return Ok(Json(ProjectExport {
+                version: EXPORT_VERSION,
+                project_id,
+                graph,
+            })
+            .into_response());
+        }
+        ExportFormat::Csv => ("text/csv; charset=utf-8", to_csv(&graph, root_id)?),
+        ExportFormat::Markdown => ("text/markdown; charset=utf-8", to_markdown(&graph, root_id)),
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+/// Returns the task and its descendants, under a root with only that task as child,
+/// so the subtree can be imported as a new project.
+fn subtree(graph: &Graph, task_id: &str) -> Graph {
+    let mut subtree = Graph::new();
+    let mut stack = vec![task_id];
+    while let Some(id) = stack.pop() {
+        if subtree.contains_key(id) {
+            continue;
+        }
+        let Some(task) = graph.get(id) else {
+            continue;
+        };
+        stack.extend(task.children.iter().map(String::as_str));
+        subtree.insert(id.to_string(), task.clone());
+    }
+    if task_id != ROOT_ID {
+        let root = graph.get(ROOT_ID).cloned().unwrap_or_else(|| Task {
+            id: ROOT_ID.to_string(),
+            num: "0".to_string(),
+            name: ROOT_ID.to_string(),
+            ..Task::default()
+        });
+        subtree.insert(
+            ROOT_ID.to_string(),
+            Task {
+                children: vec![task_id.to_string()],
+                ..root
+            },
+        );
+    }
+    subtree
+}
+
+/// A task's place in the tree walked by [walk].
+struct Row<'a> {
+    task: &'a Task,
+    /// The names of the task's ancestors, from the top of the export.
+    path: Vec<&'a str>,
+    status: String,
+}
+
+/// Walks the tree below `root_id` depth first, skipping archived tasks.
+///
+/// Tasks with several parents appear under each of them, but their
+/// children are only listed under the first.
+fn walk<'a>(graph: &'a Graph, root_id: &str) -> Vec<Row<'a>> {
+    fn visit<'a>(
+        graph: &'a Graph,
+        task: &'a Task,
+        path: &mut Vec<&'a str>,
+        expanded: &mut HashSet<&'a str>,
+        rows: &mut Vec<Row<'a>>,
+    ) {
+        for child in &task.children {
+            let Some(child) = graph.get(child) else {
+                continue;
+            };
+            if child.archived == Some(true) {
+                continue;
+            }
+            rows.push(Row {
+                task: child,
+                path: path.clone(),
+                status: status(graph, child),
+            });
+            if expanded.insert(&child.id) {
+                path.push(&child.name);
+                visit(graph, child, path, expanded, rows);
+                path.pop();
+            }
+        }
+    }
+
+    let mut rows = vec![];
+    let Some(root) = graph.get(root_id) else {
+        return rows;
+    };
+    let mut path = vec![];
+    if root_id != ROOT_ID {
+        rows.push(Row {
+            task: root,
+            path: vec![],
+            status: status(graph, root),
+        });
+        path.push(root.name.as_str());
+    }
+    let mut expanded = HashSet::from([root.id.as_str()]);
+    visit(graph, root, &mut path, &mut expanded, &mut rows);
+    rows
+}
+
+fn to_csv(graph: &Graph, root_id: &str) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record([
+        "path", "num", "name", "status", "assignee", "estimate", "deadline",
+    ])?;
+    for row in walk(graph, root_id) {
+        writer.write_record([
+            row.path.join(" / "),
+            row.task.num.clone(),
+            row.task.name.clone(),
+            row.status,
+            row.task.assignee.clone().unwrap_or_default(),
+            row.task
+                .estimate
+                .map(|estimate| estimate.to_string())
+                .unwrap_or_default(),
+            row.task.deadline.map(format_date).unwrap_or_default(),
+        ])?;
+    }
+    String::from_utf8(writer.into_inner()?).context("Exported CSV is not UTF-8")
+}
+
+fn to_markdown(graph: &Graph, root_id: &str) -> String {
+    let mut markdown = String::new();
+    for row in walk(graph, root_id) {
+        let check = if row.status == "Done" { "x" } else { " " };
+        let mut details = vec![row.status.clone()];
+        details.extend(row.task.assignee.clone());
+        details.extend(
+            row.task
+                .estimate
+                .map(|estimate| format!("estimate {estimate}")),
+        );
+        details.extend(row.task.deadline.map(|d| format!("due {}", format_date(d))));
+        markdown.push_str(&format!(
+            "{}- [{check}] #{} {} ({})\n",
+            "  ".repeat(row.path.len()),
+            row.task.num,
+            row.task.name,
+            details.join(", ")
+        ));
+    }
+    markdown
+}
+
+fn format_date(millis: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the status of the task as shown in the app, where
+/// rollups take their status from their children.
+fn status(graph: &Graph, task: &Task) -> String {
+    progress(graph, task, &mut HashSet::new()).status
+}
+
+struct Progress {
+    in_progress: usize,
+    done: usize,
+    total: usize,
+    status: String,
+}
+
+/// Mirrors `getProgress` in the frontend's koso.svelte.ts.
+fn progress<'a>(graph: &'a Graph, task: &'a Task, visited: &mut HashSet<&'a str>) -> Progress {
+    visited.insert(&task.id);
+    let is_rollup = match task.kind.as_deref() {
+        Some(kind) => kind == "Rollup",
+        None => !task.children.is_empty(),
+    };
+
+    let (mut in_progress, mut done, mut total) = (0, 0, 0);
+    for child in &task.children {
+        let Some(child) = graph.get(child) else {
+            continue;
+        };
+        // Avoid re-counting tasks present more than once in a sub-tree.
+        if visited.contains(child.id.as_str()) {
+            continue;
+        }
+        let child_progress = if is_rollup {
+            progress(graph, child, visited)
+        } else {
+            progress(graph, child, &mut HashSet::new())
+        };
+        in_progress += child_progress.in_progress;
+        done += child_progress.done;
+        total += child_progress.total;
+    }
+    let children_status = match total {
+        0 => None,
+        _ if done == total => Some("Done"),
+        _ if in_progress > 0 || done > 0 => Some("In Progress"),
+        _ => Some(DEFAULT_STATUS),
+    };
+
+    if is_rollup {
+        return Progress {
+            in_progress,
+            done,
+            total,
+            status: children_status.unwrap_or("Done").to_string(),
+        };
+    }
+    let mut status = task.status.as_deref().unwrap_or(DEFAULT_STATUS);
+    // Tasks are no longer blocked once their children are done.
+    if status == "Blocked" && children_status.is_none_or(|s| s == "Done") {
+        status = DEFAULT_STATUS;
+    }
+    Progress {
+        in_progress: usize::from(status == "In Progress"),
+        done: usize::from(status == "Done"),
+        total: 1,
+        status: status.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, name: &str, children: &[&str], status: Option<&str>) -> (String, Task) {
+        (
+            id.to_string(),
+            Task {
+                id: id.to_string(),
+                num: id.trim_start_matches('t').to_string(),
+                name: name.to_string(),
+                children: children.iter().map(|c| c.to_string()).collect(),
+                status: status.map(|s| s.to_string()),
+                ..Task::default()
+            },
+        )
+    }
+
+    fn graph() -> Graph {
+        let mut graph = Graph::from([
+            task("root", "root", &["t1", "t3", "t5"], None),
+            task("t1", "Launch", &["t2", "t3"], None),
+            task("t2", "Design, review", &[], Some("Done")),
+            task("t3", "Build", &["t4"], Some("Blocked")),
+            task("t4", "Prototype", &[], Some("In Progress")),
+            task("t5", "Old", &[], Some("Done")),
+        ]);
+        let t3 = graph.get_mut("t3").unwrap();
+        t3.kind = Some("Task".to_string());
+        t3.assignee = Some("a@koso.app".to_string());
+        t3.estimate = Some(5);
+        t3.deadline = Some(1705276800000);
+        graph.get_mut("t5").unwrap().archived = Some(true);
+        graph
+    }
+
+    #[test_log::test]
+    fn status_rolls_up_children() {
+        let mut graph = graph();
+        assert_eq!(status(&graph, &graph["t1"]), "In Progress");
+        assert_eq!(status(&graph, &graph["t2"]), "Done");
+        assert_eq!(status(&graph, &graph["t3"]), "Blocked");
+        assert_eq!(status(&graph, &graph["t4"]), "In Progress");
+
+        graph.get_mut("t4").unwrap().status = Some("Done".to_string());
+        assert_eq!(status(&graph, &graph["t3"]), "Not Started");
+        graph.get_mut("t3").unwrap().status = Some("Done".to_string());
+        assert_eq!(status(&graph, &graph["t1"]), "Done");
+    }
+
+    #[test_log::test]
+    fn to_csv_flattens_tree() {
+        let graph = graph();
+        assert_eq!(
+            to_csv(&graph, "root").unwrap(),
+            "\
+path,num,name,status,assignee,estimate,deadline
+,1,Launch,In Progress,,,
+Launch,2,\"Design, review\",Done,,,
+Launch,3,Build,Blocked,a@koso.app,5,2024-01-15
+Launch / Build,4,Prototype,In Progress,,,
+,3,Build,Blocked,a@koso.app,5,2024-01-15
+"
+        );
+        assert_eq!(
+            to_csv(&graph, "t3").unwrap(),
+            "\
+path,num,name,status,assignee,estimate,deadline
+,3,Build,Blocked,a@koso.app,5,2024-01-15
+Build,4,Prototype,In Progress,,,
+"
+        );
+    }
+
+    #[test_log::test]
+    fn to_markdown_nests_checklist() {
+        let graph = graph();
+        assert_eq!(
+            to_markdown(&graph, "root"),
+            "\
+- [ ] #1 Launch (In Progress)
+  - [x] #2 Design, review (Done)
+  - [ ] #3 Build (Blocked, a@koso.app, estimate 5, due 2024-01-15)
+    - [ ] #4 Prototype (In Progress)
+- [ ] #3 Build (Blocked, a@koso.app, estimate 5, due 2024-01-15)
+"
+        );
+        assert_eq!(
+            to_markdown(&graph, "t3"),
+            "\
+- [ ] #3 Build (Blocked, a@koso.app, estimate 5, due 2024-01-15)
+  - [ ] #4 Prototype (In Progress)
+"
+        );
+    }
+
+    #[test_log::test]
+    fn subtree_has_root() {
+        let graph = graph();
+        let subtree = subtree(&graph, "t3");
+        let mut ids: Vec<&String> = subtree.keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["root", "t3", "t4"]);
+        assert_eq!(subtree["root"].children, vec!["t3"]);
+        assert_eq!(subtree["t3"], graph["t3"]);
+
+        assert_eq!(super::subtree(&graph, "root"), graph);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::{
-    api::{google::User, simulate::simulate, verify_premium, verify_project_access},
+    api::{
+        google::User, model::ProjectRole, simulate::simulate, verify_premium, verify_project_access,
+    },
     plugins::github::app::AppGithub,
     secrets::{Secret, read_secret},
 };
@@ -82,7 +84,7 @@ async fn generate_context_handler(
     Extension(client): Extension<GeminiClient>,
     req: Query<GenerateRepoContextRequest>,
 ) -> ApiResult<Response> {
-    verify_project_access(pool, &user, &req.project_id).await?;
+    verify_project_access(pool, &user, &req.project_id, ProjectRole::Viewer).await?;
     verify_premium(pool, &user).await?;
 
     if req.simulate.unwrap_or(false) {
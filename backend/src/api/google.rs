@@ -1,9 +1,13 @@
-use crate::settings::settings;
+use crate::{
+    api::tokens::{self, TokenScope},
+    settings::settings,
+};
 use anyhow::{Result, anyhow};
 use axum::{body::Body, extract::Request, middleware::Next, response::Response};
-use axum_anyhow::{ApiResult, ResultExt, unauthorized};
+use axum_anyhow::{ApiResult, ResultExt, forbidden, unauthorized};
 use jsonwebtoken::{DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::{
     fmt,
     sync::Arc,
@@ -214,6 +218,32 @@ pub(crate) async fn authenticate(mut request: Request, next: Next) -> ApiResult<
         ));
     };
 
+    let mut user = if bearer.starts_with(tokens::TOKEN_PREFIX) {
+        let pool = request.extensions().get::<&'static PgPool>().unwrap();
+        let user = tokens::authenticate(pool, bearer).await?;
+        // Read-only tokens may still connect to project websockets, as viewers.
+        if user.scope.as_ref().is_some_and(|scope| scope.read_only) && !request.method().is_safe() {
+            return Err(forbidden(
+                "READ_ONLY_TOKEN",
+                "Personal access token is read-only",
+            ));
+        }
+        user
+    } else {
+        authenticate_google_token(key_set, bearer).await?
+    };
+    // Canonicalize emails to lower case.
+    // Why? In Oct. 2024 Google suddenly started serving emails
+    // with upper case characters, where previously they were lower.
+    user.email = user.email.to_lowercase();
+
+    tracing::Span::current().record("email", user.email.clone());
+    assert!(request.extensions_mut().insert(user).is_none());
+
+    Ok(next.run(request).await)
+}
+
+async fn authenticate_google_token(key_set: &KeySet, bearer: &str) -> ApiResult<User> {
     let Ok(header) = jsonwebtoken::decode_header(bearer) else {
         return Err(unauthorized(
             "UNAUTHENTICATED",
@@ -231,20 +261,11 @@ pub(crate) async fn authenticate(mut request: Request, next: Next) -> ApiResult<
         .await
         .context_unauthorized("UNAUTHENTICATED", "certs is absent")?;
 
-    let mut user = if kid == KeySet::INTEG_TEST_KID {
-        decode_and_validate_test_token(bearer, &key)?
+    if kid == KeySet::INTEG_TEST_KID {
+        decode_and_validate_test_token(bearer, &key)
     } else {
-        decode_and_validate_token(bearer, &key)?
-    };
-    // Canonicalize emails to lower case.
-    // Why? In Oct. 2024 Google suddenly started serving emails
-    // with upper case characters, where previously they were lower.
-    user.email = user.email.to_lowercase();
-
-    tracing::Span::current().record("email", user.email.clone());
-    assert!(request.extensions_mut().insert(user).is_none());
-
-    Ok(next.run(request).await)
+        decode_and_validate_token(bearer, &key)
+    }
 }
 
 fn decode_and_validate_token(token: &str, key: &DecodingKey) -> ApiResult<User> {
@@ -292,6 +313,9 @@ pub(crate) struct User {
     pub(crate) name: String,
     pub(crate) picture: String,
     pub(crate) exp: usize,
+    /// Set when authenticated with a personal access token.
+    #[serde(skip)]
+    pub(crate) scope: Option<TokenScope>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,575 @@
+use crate::api::{
+    collab::{
+        Collab,
+        projects_state::DocBox,
+        txn_origin::{Actor, YOrigin},
+    },
+    google::User,
+    integrity,
+    model::{Graph, ProjectExport, ProjectId, ProjectRole, Task},
+    verify_project_access, yproxy,
+};
+use anyhow::{Result, anyhow};
+use axum::{Extension, Json, extract::Path};
+use axum_anyhow::{ApiResult, bad_request, not_found};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Version of the export schema written by this server.
+/// Bump it, and teach `migrate` to upgrade the previous version,
+/// whenever the shape of exported tasks changes.
+pub(crate) const EXPORT_VERSION: u32 = 2;
+
+/// Exports written before the schema was versioned.
+const LEGACY_VERSION: u32 = 1;
+
+const KNOWN_KINDS: &[&str] = &["Rollup", "Task"];
+
+/// The wire format of an export, prior to migrating its graph to the current version.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VersionedExport {
+    version: Option<u32>,
+    project_id: ProjectId,
+    graph: Value,
+}
+
+impl TryFrom<VersionedExport> for ProjectExport {
+    type Error = anyhow::Error;
+
+    fn try_from(export: VersionedExport) -> Result<Self> {
+        let version = export.version.unwrap_or(LEGACY_VERSION);
+        let graph = migrate(version, export.graph)?;
+        Ok(ProjectExport {
+            version: EXPORT_VERSION,
+            project_id: export.project_id,
+            graph: serde_json::from_value(graph)?,
+        })
+    }
+}
+
+/// Upgrades the graph of an export written at `version` to `EXPORT_VERSION`.
+fn migrate(version: u32, graph: Value) -> Result<Value> {
+    match version {
+        EXPORT_VERSION => Ok(graph),
+        // Version 2 added blockedBy, which tasks without dependencies may omit.
+        1 => Ok(graph),
+        v if v > EXPORT_VERSION => Err(anyhow!(
+            "Export version {v} is newer than the latest supported version, {EXPORT_VERSION}"
+        )),
+        v => Err(anyhow!("Unknown export version {v}")),
+    }
+}
+
+/// Where an import is headed, which determines how some problems are treated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ImportMode {
+    /// The graph becomes a new project, as is.
+    Project,
+    /// The tasks under the graph's root are copied into an existing project.
+    Subtree,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportProblem {
+    pub(crate) code: &'static str,
+    pub(crate) task_id: Option<String>,
+    pub(crate) message: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportReport {
+    pub(crate) valid: bool,
+    /// Number of tasks that would be imported.
+    pub(crate) tasks: usize,
+    /// Problems that prevent the import.
+    pub(crate) errors: Vec<ImportProblem>,
+    /// Problems the import works around.
+    pub(crate) warnings: Vec<ImportProblem>,
+}
+
+impl ImportReport {
+    fn error(&mut self, code: &'static str, task_id: Option<&str>, message: String) {
+        self.errors.push(ImportProblem {
+            code,
+            task_id: task_id.map(str::to_string),
+            message,
+        });
+    }
+
+    fn warn(&mut self, code: &'static str, task_id: Option<&str>, message: String) {
+        self.warnings.push(ImportProblem {
+            code,
+            task_id: task_id.map(str::to_string),
+            message,
+        });
+    }
+
+    /// Converts a report with errors into a bad request summarizing them.
+    pub(crate) fn ensure_valid(&self) -> ApiResult<()> {
+        if self.valid {
+            return Ok(());
+        }
+        let mut detail = format!("Import has {} problem(s)", self.errors.len());
+        for error in self.errors.iter().take(5) {
+            detail.push_str(&format!("; {}", error.message));
+        }
+        Err(bad_request("INVALID_IMPORT", &detail))
+    }
+}
+
+/// Checks that `graph` is a well formed task graph:
+/// a root exists, ids match their keys, children and blockers exist, there are no cycles,
+/// nums are unique numbers and kinds are known.
+pub(crate) fn validate(graph: &Graph, mode: ImportMode) -> ImportReport {
+    let mut report = ImportReport::default();
+    let mut ids: Vec<&String> = graph.keys().collect();
+    ids.sort();
+
+    if !graph.contains_key("root") {
+        report.error(
+            "MISSING_ROOT",
+            None,
+            "The graph has no root task".to_string(),
+        );
+    }
+
+    let mut nums: HashMap<&str, &str> = HashMap::new();
+    for id in &ids {
+        let task = &graph[*id];
+        if task.id != **id {
+            report.error(
+                "ID_MISMATCH",
+                Some(id),
+                format!("Task {id} has a mismatched id, {}", task.id),
+            );
+        }
+        for child in &task.children {
+            if !graph.contains_key(child) {
+                report.error(
+                    "DANGLING_CHILD",
+                    Some(id),
+                    format!("Task {id} has a child, {child}, that doesn't exist"),
+                );
+            }
+        }
+        for blocker in &task.blocked_by {
+            if !graph.contains_key(blocker) {
+                report.error(
+                    "DANGLING_DEPENDENCY",
+                    Some(id),
+                    format!("Task {id} depends on a task, {blocker}, that doesn't exist"),
+                );
+            }
+        }
+        if task.num.parse::<u64>().is_err() {
+            report.error(
+                "INVALID_NUM",
+                Some(id),
+                format!("Task {id} has a num, {}, that isn't a number", task.num),
+            );
+        } else if *id != "root"
+            && let Some(other) = nums.insert(&task.num, id)
+        {
+            report.error(
+                "DUPLICATE_NUM",
+                Some(id),
+                format!("Task {id} has the same num, {}, as task {other}", task.num),
+            );
+        }
+        if let Some(kind) = &task.kind {
+            if yproxy::MANAGED_KINDS.contains(&kind.as_str()) {
+                if mode == ImportMode::Subtree {
+                    report.warn(
+                        "MANAGED_TASK",
+                        Some(id),
+                        format!(
+                            "Task {id} is managed by a plugin and will be imported as a plain task"
+                        ),
+                    );
+                }
+            } else if !KNOWN_KINDS.contains(&kind.as_str()) {
+                report.error(
+                    "UNKNOWN_KIND",
+                    Some(id),
+                    format!("Task {id} has an unknown kind, {kind}"),
+                );
+            }
+        }
+    }
+
+    for (id, child) in integrity::find_cycles(graph) {
+        report.error(
+            "CYCLE",
+            Some(&id),
+            format!("Task {id} has a child, {child}, that is also its ancestor"),
+        );
+    }
+
+    let reachable = integrity::reachable(graph);
+    report.tasks = match mode {
+        ImportMode::Project => graph.len(),
+        ImportMode::Subtree => reachable.len().saturating_sub(1),
+    };
+    if graph.contains_key("root") {
+        for id in &ids {
+            if !reachable.contains(id.as_str()) {
+                let message = match mode {
+                    ImportMode::Project => format!("Task {id} is not reachable from the root"),
+                    ImportMode::Subtree => {
+                        format!("Task {id} is not reachable from the root and will be skipped")
+                    }
+                };
+                report.warn("ORPHANED_TASK", Some(id), message);
+            }
+        }
+    }
+
+    report.valid = report.errors.is_empty();
+    report
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ValidateImport {
+    project_export: ProjectExport,
+}
+
+/// Validates an export as if importing it into a new project, without creating it.
+#[tracing::instrument(skip(_user, import))]
+pub(crate) async fn validate_import_handler(
+    Extension(_user): Extension<User>,
+    Json(import): Json<ValidateImport>,
+) -> ApiResult<Json<ImportReport>> {
+    Ok(Json(validate(
+        &import.project_export.graph,
+        ImportMode::Project,
+    )))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportSubtree {
+    project_export: ProjectExport,
+    /// The task to import under. Defaults to the root.
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+impl std::fmt::Debug for ImportSubtree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImportSubtree")
+            .field("parent_id", &self.parent_id)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportSubtreeResponse {
+    report: ImportReport,
+    /// Ids of the new tasks linked under the parent. Empty for dry runs.
+    task_ids: Vec<String>,
+}
+
+/// Copies the tasks under the root of an export into an existing project.
+/// Imported tasks are given new ids and nums so they can't collide with existing ones.
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn import_subtree_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path(project_id): Path<ProjectId>,
+    Json(import): Json<ImportSubtree>,
+) -> ApiResult<Json<ImportSubtreeResponse>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Editor).await?;
+
+    let report = validate(&import.project_export.graph, ImportMode::Subtree);
+    if import.dry_run {
+        return Ok(Json(ImportSubtreeResponse {
+            report,
+            task_ids: Vec::new(),
+        }));
+    }
+    report.ensure_valid()?;
+
+    let parent_id = import.parent_id.as_deref().unwrap_or("root");
+    let origin = YOrigin {
+        who: format!("import-{}", user.email),
+        id: format!("import_subtree_{}", Uuid::new_v4()),
+        actor: Actor::User(user),
+    };
+
+    let client = collab.register_local_client(&project_id).await?;
+    let doc_box = client.project.doc_box.lock().await;
+    let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+    let mut txn = doc.transact_mut_with(origin.as_origin()?);
+    if !doc.contains(&txn, parent_id) {
+        return Err(not_found(
+            "TASK_NOT_FOUND",
+            &format!("Task {parent_id} not found"),
+        ));
+    }
+    let parent = doc.get(&txn, parent_id)?;
+    if parent.is_managed(&txn)? {
+        return Err(bad_request(
+            "INVALID_PARENT",
+            "Cannot import under a task managed by a plugin",
+        ));
+    }
+
+    let tasks = remap(&import.project_export.graph, doc.next_num(&txn)?);
+    let task_ids: Vec<String> = tasks
+        .iter()
+        .take_while(|(top_level, _)| *top_level)
+        .map(|(_, task)| task.id.clone())
+        .collect();
+    for (_, task) in &tasks {
+        doc.set(&mut txn, task);
+    }
+    for id in &task_ids {
+        parent.push_child(&mut txn, id)?;
+    }
+
+    Ok(Json(ImportSubtreeResponse { report, task_ids }))
+}
+
+/// Copies the tasks reachable from the root of `graph`, excluding the root,
+/// giving each a fresh id and a num counting up from `next_num`.
+/// Returns the copies in depth first order, paired with whether the task is a
+/// child of the root. The root's children come first.
+fn remap(graph: &Graph, next_num: u64) -> Vec<(bool, Task)> {
+    fn visit<'a>(graph: &'a Graph, id: &'a str, order: &mut Vec<&'a str>) {
+        if order.contains(&id) {
+            return;
+        }
+        order.push(id);
+        if let Some(task) = graph.get(id) {
+            for child in &task.children {
+                visit(graph, child, order);
+            }
+        }
+    }
+
+    let top_level: Vec<&str> = graph
+        .get("root")
+        .map(|root| root.children.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    let mut order: Vec<&str> = Vec::new();
+    for id in &top_level {
+        if !order.contains(id) {
+            order.push(id);
+        }
+    }
+    for id in &top_level {
+        if let Some(task) = graph.get(*id) {
+            for child in &task.children {
+                visit(graph, child, &mut order);
+            }
+        }
+    }
+
+    let ids: HashMap<&str, String> = order
+        .iter()
+        .map(|id| (*id, BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4())))
+        .collect();
+    order
+        .iter()
+        .zip(next_num..)
+        .map(|(id, num)| {
+            let task = &graph[*id];
+            let kind = task
+                .kind
+                .clone()
+                .filter(|kind| !yproxy::MANAGED_KINDS.contains(&kind.as_str()));
+            let task = Task {
+                id: ids[id].clone(),
+                num: num.to_string(),
+                children: task
+                    .children
+                    .iter()
+                    .filter_map(|child| ids.get(child.as_str()).cloned())
+                    .collect(),
+                // Dependencies on tasks outside the subtree are dropped.
+                blocked_by: task
+                    .blocked_by
+                    .iter()
+                    .filter_map(|blocker| ids.get(blocker.as_str()).cloned())
+                    .collect(),
+                kind,
+                ..task.clone()
+            };
+            (top_level.contains(id), task)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, num: &str, children: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            num: num.to_string(),
+            name: format!("Task {id}"),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            ..Task::default()
+        }
+    }
+
+    fn graph(tasks: Vec<Task>) -> Graph {
+        tasks.into_iter().map(|t| (t.id.clone(), t)).collect()
+    }
+
+    fn codes(problems: &[ImportProblem]) -> Vec<&'static str> {
+        problems.iter().map(|p| p.code).collect()
+    }
+
+    #[test]
+    fn validate_accepts_well_formed_graph() {
+        let graph = graph(vec![
+            task("root", "0", &["a", "b"]),
+            task("a", "1", &["c"]),
+            task("b", "2", &["c"]),
+            task("c", "3", &[]),
+        ]);
+        let report = validate(&graph, ImportMode::Project);
+        assert!(report.valid, "{report:?}");
+        assert_eq!(report.tasks, 4);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn validate_reports_problems() {
+        let mut unknown = task("d", "3", &[]);
+        unknown.kind = Some("Epic".to_string());
+        let mut blocked = task("b", "2", &["a"]);
+        blocked.blocked_by = vec!["gone".to_string()];
+        let graph = graph(vec![
+            task("a", "1", &["b", "missing"]),
+            blocked,
+            task("c", "2", &[]),
+            unknown,
+            task("e", "five", &[]),
+        ]);
+        let report = validate(&graph, ImportMode::Project);
+        assert!(!report.valid);
+        assert_eq!(
+            codes(&report.errors),
+            vec![
+                "MISSING_ROOT",
+                "DANGLING_CHILD",
+                "DANGLING_DEPENDENCY",
+                "DUPLICATE_NUM",
+                "UNKNOWN_KIND",
+                "INVALID_NUM",
+                "CYCLE"
+            ]
+        );
+        assert!(report.ensure_valid().is_err());
+    }
+
+    #[test]
+    fn validate_warns_of_orphans_and_managed_tasks() {
+        let mut managed = task("pr", "2", &[]);
+        managed.kind = Some("github_pr".to_string());
+        let graph = graph(vec![
+            task("root", "0", &["a"]),
+            task("a", "1", &["pr"]),
+            managed,
+            task("orphan", "3", &[]),
+        ]);
+
+        let report = validate(&graph, ImportMode::Project);
+        assert!(report.valid);
+        assert_eq!(codes(&report.warnings), vec!["ORPHANED_TASK"]);
+
+        let report = validate(&graph, ImportMode::Subtree);
+        assert!(report.valid);
+        assert_eq!(report.tasks, 2);
+        assert_eq!(
+            codes(&report.warnings),
+            vec!["MANAGED_TASK", "ORPHANED_TASK"]
+        );
+    }
+
+    #[test]
+    fn remap_assigns_new_ids_and_nums() {
+        let mut managed = task("pr", "4", &[]);
+        managed.kind = Some("github_pr".to_string());
+        let mut blocked = task("c", "3", &[]);
+        blocked.blocked_by = vec!["a".to_string(), "orphan".to_string()];
+        let graph = graph(vec![
+            task("root", "0", &["a", "b"]),
+            task("a", "1", &["c"]),
+            task("b", "2", &["c", "pr"]),
+            blocked,
+            managed,
+            task("orphan", "5", &[]),
+        ]);
+
+        let tasks = remap(&graph, 10);
+        let names: Vec<(bool, &str, &str)> = tasks
+            .iter()
+            .map(|(top, t)| (*top, t.name.as_str(), t.num.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (true, "Task a", "10"),
+                (true, "Task b", "11"),
+                (false, "Task c", "12"),
+                (false, "Task pr", "13"),
+            ]
+        );
+        let ids: HashMap<&str, &str> = tasks
+            .iter()
+            .map(|(_, t)| (t.name.as_str(), t.id.as_str()))
+            .collect();
+        assert!(ids.values().all(|id| !graph.contains_key(*id)));
+        assert_eq!(tasks[0].1.children, vec![ids["Task c"].to_string()]);
+        assert_eq!(
+            tasks[1].1.children,
+            vec![ids["Task c"].to_string(), ids["Task pr"].to_string()]
+        );
+        assert_eq!(tasks[2].1.blocked_by, vec![ids["Task a"].to_string()]);
+        assert_eq!(tasks[3].1.kind, None);
+    }
+
+    #[test]
+    fn legacy_exports_are_importable() {
+        let export: ProjectExport = serde_json::from_value(serde_json::json!({
+            "projectId": "p1",
+            "graph": {"root": task("root", "0", &[])},
+        }))
+        .unwrap();
+        assert_eq!(export.version, EXPORT_VERSION);
+        assert!(export.graph.contains_key("root"));
+
+        let export: ProjectExport = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "projectId": "p1",
+            "graph": {"root": {"id": "root", "num": "0", "name": "Root", "children": []}},
+        }))
+        .unwrap();
+        assert!(export.graph["root"].blocked_by.is_empty());
+
+        let err = serde_json::from_value::<ProjectExport>(serde_json::json!({
+            "version": EXPORT_VERSION + 1,
+            "projectId": "p1",
+            "graph": {},
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("newer"), "{err}");
+    }
+}
@@ -0,0 +1,587 @@
+use crate::api::{
+    collab::{
+        Collab,
+        projects_state::DocBox,
+        txn_origin::{Actor, YOrigin},
+    },
+    google::User,
+    model::{Graph, ProjectId, ProjectRole, Task},
+    verify_project_access,
+    yproxy::YDocProxy,
+};
+use anyhow::Result;
+use axum::{Extension, Json, extract::Path};
+use axum_anyhow::ApiResult;
+use serde::Serialize;
+use sqlx::postgres::PgPool;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use yrs::{ReadTxn, TransactionMut};
+
+const ROOT_ID: &str = "root";
+
+/// How a violation is repaired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Repair {
+    CreateRoot,
+    /// Remove every occurrence of `child` from the children of `parent`.
+    Unlink {
+        parent: String,
+        child: String,
+    },
+    /// Keep only the first occurrence of each child of `parent`.
+    Dedupe {
+        parent: String,
+    },
+    /// Remove every occurrence of `blocker` from the dependencies of `id`.
+    RemoveDependency {
+        id: String,
+        blocker: String,
+    },
+    Renumber {
+        id: String,
+    },
+    /// Orphans are linked under the root once every other repair is applied.
+    LinkToRoot,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Violation {
+    pub(crate) code: &'static str,
+    pub(crate) task_id: Option<String>,
+    pub(crate) message: String,
+    #[serde(skip)]
+    repair: Repair,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IntegrityReport {
+    pub(crate) violations: Vec<Violation>,
+    /// Whether the violations were repaired.
+    pub(crate) repaired: bool,
+}
+
+impl IntegrityReport {
+    fn add(&mut self, code: &'static str, task_id: Option<&str>, message: String, repair: Repair) {
+        self.violations.push(Violation {
+            code,
+            task_id: task_id.map(str::to_string),
+            message,
+            repair,
+        });
+    }
+}
+
+/// Checks that the graph of `doc` is well formed.
+pub(crate) fn check<T: ReadTxn>(doc: &YDocProxy, txn: &T) -> Result<IntegrityReport> {
+    let graph = doc.to_graph(txn)?;
+    let mut report = IntegrityReport::default();
+    let mut ids: Vec<&String> = graph.keys().collect();
+    ids.sort();
+
+    if !graph.contains_key(ROOT_ID) {
+        report.add(
+            "MISSING_ROOT",
+            None,
+            "The graph has no root task".to_string(),
+            Repair::CreateRoot,
+        );
+    }
+
+    for id in &ids {
+        let task = &graph[*id];
+        let is_managed = doc.get(txn, id)?.is_managed(txn)?;
+        let mut seen = HashSet::new();
+        let mut duplicated = false;
+        for child in &task.children {
+            if !seen.insert(child) {
+                if !duplicated {
+                    duplicated = true;
+                    report.add(
+                        "DUPLICATE_CHILD",
+                        Some(id),
+                        format!("Task {id} lists a child, {child}, more than once"),
+                        Repair::Dedupe {
+                            parent: id.to_string(),
+                        },
+                    );
+                }
+                continue;
+            }
+            let unlink = Repair::Unlink {
+                parent: id.to_string(),
+                child: child.clone(),
+            };
+            if !graph.contains_key(child) {
+                report.add(
+                    "DANGLING_CHILD",
+                    Some(id),
+                    format!("Task {id} has a child, {child}, that doesn't exist"),
+                    unlink,
+                );
+            } else if child == ROOT_ID {
+                report.add(
+                    "INVALID_PARENT",
+                    Some(id),
+                    format!("Task {id} has the root as a child"),
+                    unlink,
+                );
+            } else if is_managed && !doc.is_canonical_managed_link(txn, child, id)? {
+                report.add(
+                    "INVALID_PARENT",
+                    Some(child),
+                    format!("Task {child} is linked under {id}, which is managed by a plugin"),
+                    unlink,
+                );
+            }
+        }
+        for blocker in &task.blocked_by {
+            if !graph.contains_key(blocker) {
+                report.add(
+                    "DANGLING_DEPENDENCY",
+                    Some(id),
+                    format!("Task {id} depends on a task, {blocker}, that doesn't exist"),
+                    Repair::RemoveDependency {
+                        id: id.to_string(),
+                        blocker: blocker.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    // Links to the root are already reported as invalid.
+    for (id, child) in find_cycles(&graph)
+        .into_iter()
+        .filter(|(_, child)| child != ROOT_ID)
+    {
+        report.add(
+            "CYCLE",
+            Some(&id),
+            format!("Task {id} has a child, {child}, that is also its ancestor"),
+            Repair::Unlink {
+                parent: id.clone(),
+                child,
+            },
+        );
+    }
+
+    let reachable = reachable(&graph);
+    for id in &ids {
+        if !reachable.contains(id.as_str()) {
+            report.add(
+                "ORPHANED_TASK",
+                Some(id),
+                format!("Task {id} is not reachable from the root"),
+                Repair::LinkToRoot,
+            );
+        }
+    }
+
+    let mut nums: HashMap<&str, &str> = HashMap::new();
+    if !graph.contains_key(ROOT_ID) {
+        // Reserve the num of the root that the repair creates.
+        nums.insert("0", ROOT_ID);
+    }
+    // Check the root first so it keeps its num.
+    for id in ids
+        .iter()
+        .filter(|id| **id == ROOT_ID)
+        .chain(ids.iter().filter(|id| **id != ROOT_ID))
+    {
+        let task = &graph[*id];
+        let renumber = Repair::Renumber { id: id.to_string() };
+        if task.num.parse::<u64>().is_err() {
+            report.add(
+                "INVALID_NUM",
+                Some(id),
+                format!("Task {id} has a num, {}, that isn't a number", task.num),
+                renumber,
+            );
+        } else if let Some(other) = nums.insert(&task.num, id) {
+            report.add(
+                "DUPLICATE_NUM",
+                Some(id),
+                format!("Task {id} has the same num, {}, as task {other}", task.num),
+                renumber,
+            );
+        }
+    }
+
+    Ok(report)
+}
+
+/// Checks the graph of `doc` and repairs any violations found:
+/// invalid links are removed, orphans are linked under the root and
+/// tasks with invalid or duplicate nums are given new ones.
+pub(crate) fn repair(doc: &YDocProxy, txn: &mut TransactionMut) -> Result<IntegrityReport> {
+    let mut report = check(doc, txn)?;
+    if report.violations.is_empty() {
+        return Ok(report);
+    }
+
+    let mut renumber = Vec::new();
+    for violation in &report.violations {
+        match &violation.repair {
+            Repair::CreateRoot => {
+                doc.set(
+                    txn,
+                    &Task {
+                        id: ROOT_ID.to_string(),
+                        num: "0".to_string(),
+                        name: "Root".to_string(),
+                        ..Task::default()
+                    },
+                );
+            }
+            Repair::Unlink { parent, child } => {
+                let parent = doc.get(txn, parent)?;
+                let children: Vec<String> = parent
+                    .get_children(txn)?
+                    .into_iter()
+                    .filter(|c| c != child)
+                    .collect();
+                parent.set_children(txn, &children);
+            }
+            Repair::Dedupe { parent } => {
+                let parent = doc.get(txn, parent)?;
+                let mut seen = HashSet::new();
+                let children: Vec<String> = parent
+                    .get_children(txn)?
+                    .into_iter()
+                    .filter(|c| seen.insert(c.clone()))
+                    .collect();
+                parent.set_children(txn, &children);
+            }
+            Repair::RemoveDependency { id, blocker } => {
+                let task = doc.get(txn, id)?;
+                let blocked_by: Vec<String> = task
+                    .get_blocked_by(txn)?
+                    .into_iter()
+                    .filter(|b| b != blocker)
+                    .collect();
+                task.set_blocked_by(txn, &blocked_by);
+            }
+            Repair::Renumber { id } => renumber.push(id.clone()),
+            Repair::LinkToRoot => {}
+        }
+    }
+
+    // Unlinking may have orphaned more tasks, so look for orphans afresh.
+    // Linking those without any parents reconnects every orphan, since the
+    // graph no longer has cycles.
+    let graph = doc.to_graph(txn)?;
+    let reachable = reachable(&graph);
+    let linked: HashSet<&str> = graph
+        .values()
+        .flat_map(|task| task.children.iter().map(String::as_str))
+        .collect();
+    let mut orphans: Vec<&String> = graph
+        .keys()
+        .filter(|id| !reachable.contains(id.as_str()) && !linked.contains(id.as_str()))
+        .collect();
+    orphans.sort();
+    let root = doc.get(txn, ROOT_ID)?;
+    for id in orphans {
+        root.push_child(txn, id)?;
+    }
+
+    let mut next_num = graph
+        .values()
+        .filter_map(|task| task.num.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    for id in renumber {
+        doc.get(txn, &id)?.set_num(txn, &next_num.to_string());
+        next_num += 1;
+    }
+
+    report.repaired = true;
+    Ok(report)
+}
+
+/// Returns the links, as (parent, child) pairs, that close a cycle.
+/// Removing all of them leaves the graph acyclic. The search starts at the root
+/// so the links pointing back up the tree are the ones reported.
+pub(crate) fn find_cycles(graph: &Graph) -> Vec<(String, String)> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Visited,
+    }
+
+    let mut ids: Vec<&String> = graph.keys().collect();
+    ids.sort_by_key(|id| (*id != ROOT_ID, *id));
+    let mut marks = HashMap::new();
+    let mut cycles = Vec::new();
+    for id in ids {
+        if marks.contains_key(id.as_str()) {
+            continue;
+        }
+        // Depth-first, with an explicit stack so deep graphs can't overflow the call stack.
+        // Each entry is a task being visited and the index of its next child to visit.
+        marks.insert(id.as_str(), Mark::Visiting);
+        let mut stack: Vec<(&str, usize)> = vec![(id.as_str(), 0)];
+        while let Some((id, next)) = stack.last_mut() {
+            let id: &str = id;
+            let Some(child) = graph.get(id).and_then(|task| task.children.get(*next)) else {
+                marks.insert(id, Mark::Visited);
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+            match marks.get(child.as_str()) {
+                Some(Mark::Visiting) => {
+                    let link = (id.to_string(), child.clone());
+                    if !cycles.contains(&link) {
+                        cycles.push(link);
+                    }
+                }
+                Some(Mark::Visited) => {}
+                None => {
+                    marks.insert(child.as_str(), Mark::Visiting);
+                    stack.push((child.as_str(), 0));
+                }
+            }
+        }
+    }
+    cycles
+}
+
+/// Returns the ids of tasks reachable from the root, including the root.
+pub(crate) fn reachable(graph: &Graph) -> HashSet<&str> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![ROOT_ID];
+    while let Some(id) = stack.pop() {
+        let Some(task) = graph.get(id) else {
+            continue;
+        };
+        if seen.insert(id) {
+            stack.extend(task.children.iter().map(String::as_str));
+        }
+    }
+    seen
+}
+
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn check_integrity_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<Json<IntegrityReport>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
+
+    let client = collab.register_local_client(&project_id).await?;
+    let doc_box = client.project.doc_box.lock().await;
+    let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+    let report = check(doc, &doc.transact())?;
+    Ok(Json(report))
+}
+
+/// Repairs the project's graph in a single server transaction,
+/// so connected clients receive the repairs as an ordinary update.
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn repair_integrity_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<Json<IntegrityReport>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
+
+    let origin = YOrigin {
+        who: "integrity".to_string(),
+        id: format!("repair_{}", Uuid::new_v4()),
+        actor: Actor::Server,
+    };
+    let client = collab.register_local_client(&project_id).await?;
+    let doc_box = client.project.doc_box.lock().await;
+    let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+    let mut txn = doc.transact_mut_with(origin.as_origin()?);
+    let report = repair(doc, &mut txn)?;
+    if report.repaired {
+        tracing::info!(
+            "Repaired {} integrity violations in project {project_id} for {}",
+            report.violations.len(),
+            user.email
+        );
+    }
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yrs::Origin;
+
+    fn origin() -> Origin {
+        YOrigin {
+            who: "integrity_test".to_string(),
+            id: "test".to_string(),
+            actor: Actor::Server,
+        }
+        .as_origin()
+        .unwrap()
+    }
+
+    fn task(id: &str, num: &str, children: &[&str]) -> Task {
+        Task {
+            id: id.to_string(),
+            num: num.to_string(),
+            name: format!("Task {id}"),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            ..Task::default()
+        }
+    }
+
+    fn doc_with(tasks: Vec<Task>) -> YDocProxy {
+        let doc = YDocProxy::new();
+        {
+            let mut txn = doc.transact_mut_with(origin());
+            for task in &tasks {
+                doc.set(&mut txn, task);
+            }
+        }
+        doc
+    }
+
+    fn broken_doc() -> YDocProxy {
+        let mut github = task("github", "6", &["p"]);
+        github.kind = Some("github".to_string());
+        let mut c = task("c", "3", &["d"]);
+        c.blocked_by = vec!["gone".to_string(), "a".to_string()];
+        doc_with(vec![
+            task("root", "0", &["a", "a", "missing", "github"]),
+            task("a", "1", &["b"]),
+            task("b", "2", &["a", "root"]),
+            c,
+            task("d", "1", &[]),
+            task("e", "five", &["c"]),
+            github,
+            task("p", "7", &[]),
+        ])
+    }
+
+    fn codes(report: &IntegrityReport) -> Vec<(&'static str, Option<&str>)> {
+        report
+            .violations
+            .iter()
+            .map(|v| (v.code, v.task_id.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn check_accepts_well_formed_graph() {
+        let doc = doc_with(vec![
+            task("root", "0", &["a", "b"]),
+            task("a", "1", &["c"]),
+            task("b", "2", &["c"]),
+            task("c", "3", &[]),
+        ]);
+        let report = check(&doc, &doc.transact()).unwrap();
+        assert!(report.violations.is_empty(), "{report:?}");
+    }
+
+    #[test]
+    fn check_reports_violations() {
+        let doc = broken_doc();
+        let report = check(&doc, &doc.transact()).unwrap();
+        assert_eq!(
+            codes(&report),
+            vec![
+                ("INVALID_PARENT", Some("b")),
+                ("DANGLING_DEPENDENCY", Some("c")),
+                ("INVALID_PARENT", Some("p")),
+                ("DUPLICATE_CHILD", Some("root")),
+                ("DANGLING_CHILD", Some("root")),
+                ("CYCLE", Some("b")),
+                ("ORPHANED_TASK", Some("c")),
+                ("ORPHANED_TASK", Some("d")),
+                ("ORPHANED_TASK", Some("e")),
+                ("DUPLICATE_NUM", Some("d")),
+                ("INVALID_NUM", Some("e")),
+            ]
+        );
+        assert!(!report.repaired);
+    }
+
+    #[test]
+    fn check_reports_missing_root() {
+        let doc = doc_with(vec![task("a", "0", &[])]);
+        let report = check(&doc, &doc.transact()).unwrap();
+        assert_eq!(
+            codes(&report),
+            vec![
+                ("MISSING_ROOT", None),
+                ("ORPHANED_TASK", Some("a")),
+                ("DUPLICATE_NUM", Some("a")),
+            ]
+        );
+    }
+
+    #[test]
+    fn repair_fixes_violations() {
+        let doc = broken_doc();
+        let report = repair(&doc, &mut doc.transact_mut_with(origin())).unwrap();
+        assert!(report.repaired);
+        assert_eq!(report.violations.len(), 11);
+
+        let txn = doc.transact();
+        assert!(check(&doc, &txn).unwrap().violations.is_empty());
+        let graph = doc.to_graph(&txn).unwrap();
+        assert_eq!(graph["root"].children, vec!["a", "github", "e", "p"]);
+        assert_eq!(graph["a"].children, vec!["b"]);
+        assert!(graph["b"].children.is_empty());
+        assert!(graph["github"].children.is_empty());
+        assert_eq!(graph["c"].blocked_by, vec!["a"]);
+        assert_eq!(graph["d"].num, "8");
+        assert_eq!(graph["e"].num, "9");
+    }
+
+    #[test]
+    fn repair_creates_root() {
+        let doc = doc_with(vec![task("a", "0", &["b"]), task("b", "1", &[])]);
+        let report = repair(&doc, &mut doc.transact_mut_with(origin())).unwrap();
+        assert!(report.repaired);
+
+        let txn = doc.transact();
+        assert!(check(&doc, &txn).unwrap().violations.is_empty());
+        let graph = doc.to_graph(&txn).unwrap();
+        assert_eq!(graph["root"].children, vec!["a"]);
+        assert_eq!(graph["a"].num, "2");
+    }
+
+    #[test]
+    fn find_cycles_handles_deep_graphs() {
+        // Deep enough to overflow the stack if searched recursively.
+        const DEPTH: usize = 200_000;
+        let mut graph: Graph = (0..DEPTH)
+            .map(|i| {
+                let children = if i + 1 < DEPTH {
+                    vec![format!("t{}", i + 1)]
+                } else {
+                    vec!["t0".to_string()]
+                };
+                (
+                    format!("t{i}"),
+                    Task {
+                        id: format!("t{i}"),
+                        num: i.to_string(),
+                        children,
+                        ..Task::default()
+                    },
+                )
+            })
+            .collect();
+        graph.insert("root".to_string(), task("root", "root", &["t0"]));
+
+        assert_eq!(
+            find_cycles(&graph),
+            vec![(format!("t{}", DEPTH - 1), "t0".to_string())]
+        );
+    }
+}
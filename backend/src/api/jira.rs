@@ -0,0 +1,1042 @@
+use crate::api::{
+    collab::Collab,
+    google::User,
+    import::EXPORT_VERSION,
+    model::{CreateProject, Graph, Project, ProjectExport, ProjectRole, Task},
+    projects::create_project,
+    verify_project_access,
+    yproxy::{ESTIMATES, STATUSES},
+};
+use anyhow::{Context, Result, anyhow};
+use axum::{
+    Extension, Json,
+    extract::Path,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use axum_anyhow::{ApiResult, ResultExt, bad_request};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    time::SystemTime,
+};
+use uuid::Uuid;
+
+/// The story points field of Jira Cloud's JSON exports.
+const DEFAULT_STORY_POINTS_FIELD: &str = "customfield_10016";
+
+/// Jira's link type whose issues block their linked issues.
+const BLOCKS_LINK: &str = "Blocks";
+
+/// Default mapping of common Jira statuses, lowercased, to Koso statuses.
+const STATUS_MAPPING: &[(&str, &str)] = &[
+    ("backlog", "Not Started"),
+    ("new", "Not Started"),
+    ("not started", "Not Started"),
+    ("open", "Not Started"),
+    ("to do", "Not Started"),
+    ("ready", "Ready"),
+    ("selected for development", "Ready"),
+    ("in development", "In Progress"),
+    ("in progress", "In Progress"),
+    ("in review", "In Progress"),
+    ("blocked", "Blocked"),
+    ("closed", "Done"),
+    ("done", "Done"),
+    ("resolved", "Done"),
+];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum JiraFormat {
+    /// Issues as returned by Jira's search API, with or without the `issues` wrapper.
+    Json,
+    /// Jira's "Export Excel CSV (all fields)".
+    Csv,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JiraImport {
+    pub(crate) name: String,
+    pub(crate) format: JiraFormat,
+    pub(crate) data: String,
+    /// Maps Jira users, by email, display name or account ID, to Koso user emails.
+    #[serde(default)]
+    pub(crate) users: HashMap<String, String>,
+    /// Maps Jira statuses to Koso statuses, overriding the defaults.
+    #[serde(default)]
+    pub(crate) statuses: HashMap<String, String>,
+    /// The Jira site, like https://example.atlassian.net, used to link tasks to their issues.
+    #[serde(default)]
+    pub(crate) base_url: Option<String>,
+    #[serde(default)]
+    pub(crate) story_points_field: Option<String>,
+    /// Report on the import without creating the project.
+    #[serde(default)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct JiraImportResponse {
+    pub(crate) project: Option<Project>,
+    pub(crate) report: ImportReport,
+}
+
+/// Everything of the export that couldn't be imported as is.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportReport {
+    pub(crate) tasks: usize,
+    /// Users without a Koso account or mapping. Their issues are left unassigned.
+    pub(crate) unknown_users: BTreeSet<String>,
+    /// Statuses without a mapping. Their issues are left without a status.
+    pub(crate) unknown_statuses: BTreeSet<String>,
+    /// Links other than "Blocks", which have no equivalent in Koso.
+    pub(crate) unsupported_links: BTreeSet<String>,
+    pub(crate) warnings: Vec<String>,
+}
+
+#[tracing::instrument(skip(user, pool, request), fields(name = request.name))]
+pub(super) async fn import_jira_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Json(request): Json<JiraImport>,
+) -> ApiResult<Json<JiraImportResponse>> {
+    for status in request.statuses.values() {
+        if !STATUSES.contains(&status.as_str()) {
+            return Err(bad_request(
+                "INVALID_STATUS_MAPPING",
+                &format!("Status '{status}' must be one of {STATUSES:?}"),
+            ));
+        }
+    }
+    let issues = match request.format {
+        JiraFormat::Json => parse_json(&request.data, &request.story_points_field),
+        JiraFormat::Csv => parse_csv(&request.data),
+    }
+    .context_bad_request("INVALID_EXPORT", "Failed to parse the Jira export")?;
+
+    let known_users = list_known_users(pool, &issues).await?;
+    let (graph, report) = build_graph(&issues, &request, &known_users)?;
+    tracing::debug!("Imported {} Jira issues: {report:?}", issues.len());
+    if request.dry_run {
+        return Ok(Json(JiraImportResponse {
+            project: None,
+            report,
+        }));
+    }
+
+    let project = create_project(
+        &user,
+        pool,
+        CreateProject {
+            name: request.name,
+            project_export: Some(ProjectExport {
+                version: EXPORT_VERSION,
+                project_id: "jira".to_string(),
+                graph,
+            }),
+        },
+    )
+    .await?;
+    Ok(Json(JiraImportResponse {
+        project: Some(project),
+        report,
+    }))
+}
+
+#[tracing::instrument(skip(user, pool, collab))]
+pub(super) async fn export_jira_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path(project_id): Path<String>,
+) -> ApiResult<Response> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let graph = collab.get_graph(&project_id).await?;
+    let csv = export_csv(&graph)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"koso-{project_id}-jira.csv\""),
+            ),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+/// An issue of either export format.
+#[derive(Debug, Default, PartialEq)]
+struct JiraIssue {
+    id: Option<String>,
+    key: String,
+    summary: String,
+    description: Option<String>,
+    issue_type: Option<String>,
+    status: Option<String>,
+    assignee: Option<JiraUser>,
+    reporter: Option<JiraUser>,
+    /// The key or ID of the parent epic or issue.
+    parent: Option<String>,
+    links: Vec<JiraLink>,
+    story_points: Option<f64>,
+    due_date: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct JiraUser {
+    email: Option<String>,
+    /// The display name, or account ID when hidden.
+    name: String,
+}
+
+impl JiraUser {
+    /// CSV exports name users by display name or, when exported by Koso, email.
+    fn from_name(name: &str) -> JiraUser {
+        JiraUser {
+            email: name.contains('@').then(|| name.to_string()),
+            name: name.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct JiraLink {
+    name: String,
+    /// Whether this issue is the link's subject, e.g. this issue blocks `key`.
+    outward: bool,
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonExport {
+    Search { issues: Vec<JsonIssue> },
+    Issues(Vec<JsonIssue>),
+}
+
+#[derive(Deserialize)]
+struct JsonIssue {
+    id: Option<String>,
+    key: String,
+    fields: JsonFields,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFields {
+    summary: String,
+    /// A string in API v2 and an Atlassian Document Format document in v3.
+    description: Option<Value>,
+    issuetype: Option<JsonNamed>,
+    status: Option<JsonNamed>,
+    assignee: Option<JsonUser>,
+    reporter: Option<JsonUser>,
+    parent: Option<JsonIssueRef>,
+    #[serde(default)]
+    issuelinks: Vec<JsonLink>,
+    duedate: Option<String>,
+    /// Custom fields, including story points.
+    #[serde(flatten)]
+    other: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct JsonNamed {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonUser {
+    email_address: Option<String>,
+    display_name: Option<String>,
+    account_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonIssueRef {
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonLink {
+    #[serde(rename = "type")]
+    link_type: JsonNamed,
+    outward_issue: Option<JsonIssueRef>,
+    inward_issue: Option<JsonIssueRef>,
+}
+
+impl JsonUser {
+    fn into_user(self) -> Option<JiraUser> {
+        let name = self
+            .display_name
+            .or(self.account_id)
+            .or_else(|| self.email_address.clone())?;
+        Some(JiraUser {
+            email: self.email_address,
+            name,
+        })
+    }
+}
+
+fn parse_json(data: &str, story_points_field: &Option<String>) -> Result<Vec<JiraIssue>> {
+    let story_points_field = story_points_field
+        .as_deref()
+        .unwrap_or(DEFAULT_STORY_POINTS_FIELD);
+    let issues = match serde_json::from_str(data)? {
+        JsonExport::Search { issues } => issues,
+        JsonExport::Issues(issues) => issues,
+    };
+    Ok(issues
+        .into_iter()
+        .map(|issue| {
+            let fields = issue.fields;
+            let links = fields
+                .issuelinks
+                .into_iter()
+                .filter_map(|link| {
+                    let (outward, other) = match (link.outward_issue, link.inward_issue) {
+                        (Some(other), _) => (true, other),
+                        (None, Some(other)) => (false, other),
+                        (None, None) => return None,
+                    };
+                    Some(JiraLink {
+                        name: link.link_type.name,
+                        outward,
+                        key: other.key,
+                    })
+                })
+                .collect();
+            JiraIssue {
+                id: issue.id,
+                key: issue.key,
+                summary: fields.summary,
+                description: fields
+                    .description
+                    .map(|description| adf_text(&description).trim().to_string())
+                    .filter(|description| !description.is_empty()),
+                issue_type: fields.issuetype.map(|t| t.name),
+                status: fields.status.map(|s| s.name),
+                assignee: fields.assignee.and_then(JsonUser::into_user),
+                reporter: fields.reporter.and_then(JsonUser::into_user),
+                parent: fields.parent.map(|p| p.key),
+                links,
+                story_points: fields.other.get(story_points_field).and_then(Value::as_f64),
+                due_date: fields.duedate,
+            }
+        })
+        .collect())
+}
+
+/// Returns the text of an Atlassian Document Format node, placing blocks on their own lines.
+fn adf_text(node: &Value) -> String {
+    match node {
+        Value::String(text) => text.clone(),
+        Value::Object(node) => {
+            if let Some(Value::String(text)) = node.get("text") {
+                return text.clone();
+            }
+            let separator = match node.get("type").and_then(Value::as_str) {
+                Some("doc" | "bulletList" | "orderedList" | "listItem" | "blockquote") => "\n",
+                _ => "",
+            };
+            node.get("content")
+                .and_then(Value::as_array)
+                .map(|content| {
+                    content
+                        .iter()
+                        .map(adf_text)
+                        .collect::<Vec<_>>()
+                        .join(separator)
+                })
+                .unwrap_or_default()
+        }
+        _ => String::new(),
+    }
+}
+
+fn parse_csv(data: &str) -> Result<Vec<JiraIssue>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    // Jira repeats columns, like links, so columns are read by position rather than by name.
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|header| header.trim().to_string())
+        .collect();
+    if !headers
+        .iter()
+        .any(|header| header.eq_ignore_ascii_case("summary"))
+    {
+        return Err(anyhow!("Missing Summary column"));
+    }
+
+    let mut issues = Vec::new();
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        let mut issue = JiraIssue::default();
+        for (header, value) in headers.iter().zip(record.iter()) {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match header.to_lowercase().as_str() {
+                "summary" => issue.summary = value.to_string(),
+                "issue key" => issue.key = value.to_string(),
+                "issue id" => issue.id = Some(value.to_string()),
+                "issue type" => issue.issue_type = Some(value.to_string()),
+                "status" => issue.status = Some(value.to_string()),
+                "assignee" => issue.assignee = Some(JiraUser::from_name(value)),
+                "reporter" => issue.reporter = Some(JiraUser::from_name(value)),
+                "description" => issue.description = Some(value.to_string()),
+                "parent" | "parent id" | "custom field (epic link)" => {
+                    issue.parent = Some(value.to_string())
+                }
+                "custom field (story points)"
+                | "custom field (story point estimate)"
+                | "story points" => {
+                    issue.story_points = Some(value.parse().with_context(|| {
+                        format!("Invalid story points '{value}' in row {}", row + 1)
+                    })?)
+                }
+                "due date" => issue.due_date = Some(value.to_string()),
+                _ => {
+                    if let Some((outward, name)) = parse_link_header(header) {
+                        issue.links.push(JiraLink {
+                            name: name.to_string(),
+                            outward,
+                            key: value.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        if issue.key.is_empty() {
+            issue.key = issue
+                .id
+                .clone()
+                .with_context(|| format!("Missing Issue key and Issue id in row {}", row + 1))?;
+        }
+        issues.push(issue);
+    }
+    Ok(issues)
+}
+
+/// Returns the direction and type of link columns, like "Outward issue link (Blocks)".
+fn parse_link_header(header: &str) -> Option<(bool, &str)> {
+    let (direction, name) = header.strip_suffix(')')?.split_once(" issue link (")?;
+    match direction.to_lowercase().as_str() {
+        "outward" => Some((true, name)),
+        "inward" => Some((false, name)),
+        _ => None,
+    }
+}
+
+/// Returns the emails of users of the export who have a Koso account.
+async fn list_known_users(pool: &PgPool, issues: &[JiraIssue]) -> Result<HashSet<String>> {
+    let emails: Vec<String> = issues
+        .iter()
+        .flat_map(|issue| [&issue.assignee, &issue.reporter])
+        .flatten()
+        .filter_map(|user| user.email.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let known: Vec<(String,)> = sqlx::query_as("SELECT email FROM users WHERE email = ANY($1)")
+        .bind(&emails)
+        .fetch_all(pool)
+        .await
+        .context("Failed to query users")?;
+    Ok(known.into_iter().map(|(email,)| email).collect())
+}
+
+/// Builds the graph of a new project: each issue becomes a task and
+/// epics, parents and blocking issues become the parents of their tasks.
+fn build_graph(
+    issues: &[JiraIssue],
+    request: &JiraImport,
+    known_users: &HashSet<String>,
+) -> Result<(Graph, ImportReport)> {
+    let mut report = ImportReport::default();
+    let now: i64 = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_millis()
+        .try_into()?;
+
+    // Issues are referred to by key in links and by key or ID as parents.
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    let mut issues_to_import = Vec::new();
+    for issue in issues {
+        if indices.contains_key(issue.key.as_str()) {
+            report
+                .warnings
+                .push(format!("{}: Skipped duplicate issue", issue.key));
+            continue;
+        }
+        indices.insert(&issue.key, issues_to_import.len());
+        if let Some(id) = &issue.id {
+            indices.entry(id).or_insert(issues_to_import.len());
+        }
+        issues_to_import.push(issue);
+    }
+
+    let mut tasks: Vec<Task> = Vec::with_capacity(issues_to_import.len());
+    for (i, issue) in issues_to_import.iter().enumerate() {
+        let status = issue
+            .status
+            .as_deref()
+            .and_then(|status| map_status(status, &request.statuses, &mut report));
+        tasks.push(Task {
+            id: BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4()),
+            num: (i + 1).to_string(),
+            name: issue.summary.clone(),
+            desc: issue.description.clone(),
+            children: vec![],
+            blocked_by: vec![],
+            assignee: issue
+                .assignee
+                .as_ref()
+                .and_then(|user| map_user(user, &request.users, known_users, &mut report)),
+            reporter: issue
+                .reporter
+                .as_ref()
+                .and_then(|user| map_user(user, &request.users, known_users, &mut report)),
+            status_time: status.as_ref().map(|_| now),
+            status,
+            url: request
+                .base_url
+                .as_ref()
+                .map(|base_url| format!("{}/browse/{}", base_url.trim_end_matches('/'), issue.key)),
+            kind: None,
+            estimate: issue
+                .story_points
+                .and_then(|points| map_estimate(&issue.key, points, &mut report)),
+            deadline: issue
+                .due_date
+                .as_deref()
+                .and_then(|date| map_due_date(&issue.key, date, &mut report)),
+            archived: None,
+        });
+    }
+
+    let mut edges = Edges::new(tasks.len());
+    for (child, issue) in issues_to_import.iter().enumerate() {
+        if let Some(parent) = &issue.parent {
+            match indices.get(parent.as_str()) {
+                Some(&parent) => edges.add(parent, child, &issue.key, &mut report),
+                None => report.warnings.push(format!(
+                    "{}: Parent {parent} is not in the export",
+                    issue.key
+                )),
+            }
+        }
+        for link in &issue.links {
+            let Some(&other) = indices.get(link.key.as_str()) else {
+                report.warnings.push(format!(
+                    "{}: Linked issue {} is not in the export",
+                    issue.key, link.key
+                ));
+                continue;
+            };
+            let (from, to) = if link.outward {
+                (&issue.key, &link.key)
+            } else {
+                (&link.key, &issue.key)
+            };
+            if !link.name.eq_ignore_ascii_case(BLOCKS_LINK) {
+                report
+                    .unsupported_links
+                    .insert(format!("{from} {} {to}", link.name));
+                continue;
+            }
+            // Tasks are blocked by their children.
+            if link.outward {
+                edges.add(other, child, &issue.key, &mut report);
+            } else {
+                edges.add(child, other, &issue.key, &mut report);
+            }
+        }
+    }
+
+    let mut root = Task {
+        id: "root".to_string(),
+        num: "0".to_string(),
+        name: "root".to_string(),
+        ..Task::default()
+    };
+    for (i, children) in edges.children.iter().enumerate() {
+        tasks[i].children = children.iter().map(|&c| tasks[c].id.clone()).collect();
+        if !edges.has_parent[i] {
+            root.children.push(tasks[i].id.clone());
+        }
+    }
+    report.tasks = tasks.len();
+
+    let mut graph: Graph = tasks
+        .into_iter()
+        .map(|task| (task.id.clone(), task))
+        .collect();
+    graph.insert(root.id.clone(), root);
+    Ok((graph, report))
+}
+
+/// The parent to child edges between issues, which must remain acyclic.
+struct Edges {
+    children: Vec<Vec<usize>>,
+    has_parent: Vec<bool>,
+}
+
+impl Edges {
+    fn new(len: usize) -> Edges {
+        Edges {
+            children: vec![vec![]; len],
+            has_parent: vec![false; len],
+        }
+    }
+
+    fn add(&mut self, parent: usize, child: usize, key: &str, report: &mut ImportReport) {
+        if self.children[parent].contains(&child) {
+            return;
+        }
+        if self.reaches(child, parent) {
+            report
+                .warnings
+                .push(format!("{key}: Skipped link that would create a cycle"));
+            return;
+        }
+        self.children[parent].push(child);
+        self.has_parent[child] = true;
+    }
+
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut visited = vec![false; self.children.len()];
+        let mut stack = vec![from];
+        while let Some(i) = stack.pop() {
+            if i == to {
+                return true;
+            }
+            if !std::mem::replace(&mut visited[i], true) {
+                stack.extend(&self.children[i]);
+            }
+        }
+        false
+    }
+}
+
+fn map_status(
+    status: &str,
+    statuses: &HashMap<String, String>,
+    report: &mut ImportReport,
+) -> Option<String> {
+    if let Some(status) = statuses.get(status) {
+        return Some(status.clone());
+    }
+    let lowercase = status.to_lowercase();
+    match STATUS_MAPPING.iter().find(|(jira, _)| *jira == lowercase) {
+        Some((_, koso)) => Some(koso.to_string()),
+        None => {
+            report.unknown_statuses.insert(status.to_string());
+            None
+        }
+    }
+}
+
+fn map_user(
+    user: &JiraUser,
+    users: &HashMap<String, String>,
+    known_users: &HashSet<String>,
+    report: &mut ImportReport,
+) -> Option<String> {
+    let mapped = [user.email.as_ref(), Some(&user.name)]
+        .into_iter()
+        .flatten()
+        .find_map(|name| users.get(name));
+    if let Some(email) = mapped {
+        return Some(email.clone());
+    }
+    match &user.email {
+        Some(email) if known_users.contains(email) => Some(email.clone()),
+        _ => {
+            report.unknown_users.insert(user.name.clone());
+            None
+        }
+    }
+}
+
+/// Rounds story points up to the nearest Koso estimate.
+fn map_estimate(key: &str, points: f64, report: &mut ImportReport) -> Option<i64> {
+    if points <= 0.0 {
+        return None;
+    }
+    let estimate = ESTIMATES
+        .iter()
+        .copied()
+        .find(|&estimate| estimate as f64 >= points)
+        .unwrap_or(ESTIMATES[ESTIMATES.len() - 1]);
+    if estimate as f64 != points {
+        report.warnings.push(format!(
+            "{key}: Changed story points {points} to estimate {estimate}"
+        ));
+    }
+    Some(estimate)
+}
+
+/// Parses ISO dates and the default date format of Jira's CSV exports, like 15/Jan/24 12:00 AM.
+fn map_due_date(key: &str, date: &str, report: &mut ImportReport) -> Option<i64> {
+    let parsed = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%d/%b/%y %I:%M %p").map(|d| d.date()))
+        .or_else(|_| NaiveDate::parse_from_str(date, "%d/%b/%y"));
+    match parsed {
+        Ok(parsed) => Some(
+            parsed
+                .and_time(Default::default())
+                .and_utc()
+                .timestamp_millis(),
+        ),
+        Err(_) => {
+            report
+                .warnings
+                .push(format!("{key}: Skipped invalid due date {date:?}"));
+            None
+        }
+    }
+}
+
+/// Exports the tasks of the graph as a CSV file Jira's importer accepts.
+///
+/// Tasks are linked by "Issue id", the task number. A task's first parent becomes its
+/// "Parent id" and further parents, which its task blocks, become "Blocks" links.
+/// Dates are formatted as yyyy-MM-dd.
+fn export_csv(graph: &Graph) -> Result<String> {
+    // Visit tasks depth first from the root, then any tasks outside the tree.
+    let mut order: Vec<&Task> = Vec::with_capacity(graph.len());
+    let mut parents: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&str> = vec!["root"];
+    let mut unvisited: Vec<&Task> = graph.values().collect();
+    unvisited.sort_by_key(|task| (task.num.parse::<u64>().unwrap_or(u64::MAX), &task.id));
+    let mut unvisited = unvisited.into_iter();
+    loop {
+        let Some(id) = stack.pop().or_else(|| {
+            unvisited
+                .by_ref()
+                .find(|task| !visited.contains(task.id.as_str()))
+                .map(|task| task.id.as_str())
+        }) else {
+            break;
+        };
+        let Some(task) = graph.get(id) else {
+            continue;
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+        if id != "root" {
+            order.push(task);
+        }
+        for child in task.children.iter().rev() {
+            if id != "root" {
+                parents.entry(child).or_default().push(&task.num);
+            }
+            stack.push(child);
+        }
+    }
+    let links = parents.values().map(Vec::len).max().unwrap_or(0).max(1) - 1;
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let mut headers = vec![
+        "Issue id",
+        "Parent id",
+        "Summary",
+        "Issue Type",
+        "Status",
+        "Assignee",
+        "Reporter",
+        "Description",
+        "Custom field (Story Points)",
+        "Due Date",
+    ];
+    headers.extend(std::iter::repeat_n("Outward issue link (Blocks)", links));
+    writer.write_record(&headers)?;
+    for task in order {
+        let task_parents = parents.get(task.id.as_str());
+        let mut record = vec![
+            task.num.clone(),
+            task_parents
+                .and_then(|p| p.first())
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            task.name.clone(),
+            if task.children.is_empty() {
+                "Task"
+            } else {
+                "Epic"
+            }
+            .to_string(),
+            match task.status.as_deref() {
+                Some("Not Started") => "To Do",
+                status => status.unwrap_or_default(),
+            }
+            .to_string(),
+            task.assignee.clone().unwrap_or_default(),
+            task.reporter.clone().unwrap_or_default(),
+            task.desc.clone().unwrap_or_default(),
+            task.estimate.map(|e| e.to_string()).unwrap_or_default(),
+            task.deadline
+                .and_then(chrono::DateTime::from_timestamp_millis)
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        ];
+        let blocks = task_parents.and_then(|p| p.get(1..)).unwrap_or_default();
+        for i in 0..links {
+            record.push(blocks.get(i).map(|p| p.to_string()).unwrap_or_default());
+        }
+        writer.write_record(&record)?;
+    }
+    String::from_utf8(writer.into_inner()?).context("Exported CSV is not UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> JiraImport {
+        JiraImport {
+            name: "Jira".to_string(),
+            format: JiraFormat::Json,
+            data: String::new(),
+            users: HashMap::from([("Jane Doe".to_string(), "jane@koso.app".to_string())]),
+            statuses: HashMap::from([("QA".to_string(), "In Progress".to_string())]),
+            base_url: Some("https://example.atlassian.net/".to_string()),
+            story_points_field: None,
+            dry_run: false,
+        }
+    }
+
+    fn task_by_name<'a>(graph: &'a Graph, name: &str) -> &'a Task {
+        graph.values().find(|task| task.name == name).unwrap()
+    }
+
+    fn names(graph: &Graph, ids: &[String]) -> Vec<String> {
+        ids.iter().map(|id| graph[id].name.clone()).collect()
+    }
+
+    #[test_log::test]
+    fn parse_json_succeeds() {
+        let data = serde_json::json!({
+            "issues": [{
+                "id": "10001",
+                "key": "KOSO-1",
+                "fields": {
+                    "summary": "Epic",
+                    "description": {
+                        "type": "doc",
+                        "content": [
+                            {"type": "paragraph", "content": [{"type": "text", "text": "First"}]},
+                            {"type": "paragraph", "content": [{"type": "text", "text": "Second"}]},
+                        ],
+                    },
+                    "issuetype": {"name": "Epic"},
+                    "status": {"name": "In Progress"},
+                    "assignee": {"emailAddress": "a@koso.app", "displayName": "A"},
+                    "issuelinks": [{
+                        "type": {"name": "Relates", "inward": "relates to", "outward": "relates to"},
+                        "inwardIssue": {"key": "KOSO-2"},
+                    }],
+                    "customfield_10016": 3.0,
+                    "duedate": "2024-01-15",
+                },
+            }, {
+                "key": "KOSO-2",
+                "fields": {"summary": "Story", "description": "Plain", "parent": {"key": "KOSO-1"}},
+            }],
+        });
+        let issues = parse_json(&data.to_string(), &None).unwrap();
+        assert_eq!(
+            issues[0],
+            JiraIssue {
+                id: Some("10001".to_string()),
+                key: "KOSO-1".to_string(),
+                summary: "Epic".to_string(),
+                description: Some("First\nSecond".to_string()),
+                issue_type: Some("Epic".to_string()),
+                status: Some("In Progress".to_string()),
+                assignee: Some(JiraUser {
+                    email: Some("a@koso.app".to_string()),
+                    name: "A".to_string(),
+                }),
+                reporter: None,
+                parent: None,
+                links: vec![JiraLink {
+                    name: "Relates".to_string(),
+                    outward: false,
+                    key: "KOSO-2".to_string(),
+                }],
+                story_points: Some(3.0),
+                due_date: Some("2024-01-15".to_string()),
+            }
+        );
+        assert_eq!(issues[1].description.as_deref(), Some("Plain"));
+        assert_eq!(issues[1].parent.as_deref(), Some("KOSO-1"));
+    }
+
+    #[test_log::test]
+    fn parse_csv_succeeds() {
+        let data = "\
+Summary,Issue key,Issue id,Status,Assignee,Parent id,Custom field (Story Points),Outward issue link (Blocks),Outward issue link (Blocks),Inward issue link (Cloners)
+Epic,KOSO-1,10001,To Do,Jane Doe,,,,,
+Story,KOSO-2,10002,Done,,10001,2,KOSO-3,KOSO-1,KOSO-3
+Bug,KOSO-3,10003,QA,,,,,,
+";
+        let issues = parse_csv(data).unwrap();
+        assert_eq!(issues.len(), 3);
+        assert_eq!(
+            issues[0].assignee,
+            Some(JiraUser {
+                email: None,
+                name: "Jane Doe".to_string(),
+            })
+        );
+        assert_eq!(issues[1].parent.as_deref(), Some("10001"));
+        assert_eq!(issues[1].story_points, Some(2.0));
+        assert_eq!(
+            issues[1].links,
+            vec![
+                JiraLink {
+                    name: "Blocks".to_string(),
+                    outward: true,
+                    key: "KOSO-3".to_string(),
+                },
+                JiraLink {
+                    name: "Blocks".to_string(),
+                    outward: true,
+                    key: "KOSO-1".to_string(),
+                },
+                JiraLink {
+                    name: "Cloners".to_string(),
+                    outward: false,
+                    key: "KOSO-3".to_string(),
+                },
+            ]
+        );
+
+        assert!(parse_csv("Issue key\nKOSO-1\n").is_err());
+        assert!(parse_csv("Summary\nNo key\n").is_err());
+    }
+
+    #[test_log::test]
+    fn build_graph_succeeds() {
+        let data = "\
+Summary,Issue key,Issue id,Status,Assignee,Reporter,Parent id,Custom field (Story Points),Due Date,Outward issue link (Blocks),Inward issue link (Blocks),Outward issue link (Relates)
+Epic,KOSO-1,10001,To Do,Jane Doe,known@koso.app,,,,,,
+Story,KOSO-2,10002,Done,Unknown,,10001,4,15/Jan/24 12:00 AM,KOSO-3,,KOSO-4
+Bug,KOSO-3,10003,QA,,,,,,,KOSO-2,
+Spike,KOSO-4,10004,Triage,,,KOSO-9,,someday,KOSO-1,,
+Loop,KOSO-5,10005,,,,,,,KOSO-5,,
+";
+        let issues = parse_csv(data).unwrap();
+        let known_users = HashSet::from(["known@koso.app".to_string()]);
+        let (graph, report) = build_graph(&issues, &request(), &known_users).unwrap();
+
+        assert_eq!(graph.len(), 6);
+        let root = &graph["root"];
+        assert_eq!(names(&graph, &root.children), vec!["Epic", "Bug", "Loop"]);
+        let epic = task_by_name(&graph, "Epic");
+        assert_eq!(names(&graph, &epic.children), vec!["Story", "Spike"]);
+        assert_eq!(epic.num, "1");
+        assert_eq!(epic.status.as_deref(), Some("Not Started"));
+        assert_eq!(epic.assignee.as_deref(), Some("jane@koso.app"));
+        assert_eq!(epic.reporter.as_deref(), Some("known@koso.app"));
+        assert_eq!(
+            epic.url.as_deref(),
+            Some("https://example.atlassian.net/browse/KOSO-1")
+        );
+        let story = task_by_name(&graph, "Story");
+        assert_eq!(story.assignee, None);
+        assert_eq!(story.estimate, Some(5));
+        assert_eq!(story.deadline, Some(1705276800000));
+        let bug = task_by_name(&graph, "Bug");
+        assert_eq!(bug.status.as_deref(), Some("In Progress"));
+        assert_eq!(names(&graph, &bug.children), vec!["Story"]);
+        assert_eq!(task_by_name(&graph, "Spike").status, None);
+
+        assert_eq!(
+            report,
+            ImportReport {
+                tasks: 5,
+                unknown_users: BTreeSet::from(["Unknown".to_string()]),
+                unknown_statuses: BTreeSet::from(["Triage".to_string()]),
+                unsupported_links: BTreeSet::from(["KOSO-2 Relates KOSO-4".to_string()]),
+                warnings: vec![
+                    "KOSO-2: Changed story points 4 to estimate 5".to_string(),
+                    "KOSO-4: Skipped invalid due date \"someday\"".to_string(),
+                    "KOSO-4: Parent KOSO-9 is not in the export".to_string(),
+                    "KOSO-5: Skipped link that would create a cycle".to_string(),
+                ],
+            }
+        );
+    }
+
+    #[test_log::test]
+    fn export_csv_round_trips() {
+        let data = "\
+Summary,Issue key,Status,Assignee,Parent,Custom field (Story Points),Due Date,Outward issue link (Blocks)
+Epic,KOSO-1,In Progress,a@koso.app,,,,
+Story,KOSO-2,Blocked,,KOSO-1,8,2024-01-15,KOSO-4
+Bug,KOSO-3,Done,,KOSO-1,,,KOSO-4
+Other,KOSO-4,,,,,,
+";
+        let known_users = HashSet::from(["a@koso.app".to_string()]);
+        let (graph, report) =
+            build_graph(&parse_csv(data).unwrap(), &request(), &known_users).unwrap();
+        assert!(report.warnings.is_empty(), "{report:?}");
+
+        let csv = export_csv(&graph).unwrap();
+        assert_eq!(
+            csv,
+            "\
+Issue id,Parent id,Summary,Issue Type,Status,Assignee,Reporter,Description,Custom field (Story Points),Due Date,Outward issue link (Blocks)
+1,,Epic,Epic,In Progress,a@koso.app,,,,,
+2,1,Story,Task,Blocked,,,,8,2024-01-15,4
+3,1,Bug,Task,Done,,,,,,4
+4,,Other,Epic,,,,,,,
+"
+        );
+
+        let (round_trip, report) =
+            build_graph(&parse_csv(&csv).unwrap(), &request(), &known_users).unwrap();
+        assert!(report.warnings.is_empty(), "{report:?}");
+        let strip = |graph: &Graph| {
+            let mut tasks: Vec<_> = graph
+                .values()
+                .map(|task| {
+                    let mut children = names(graph, &task.children);
+                    children.sort();
+                    (
+                        task.name.clone(),
+                        children,
+                        task.status.clone(),
+                        task.assignee.clone(),
+                        task.estimate,
+                        task.deadline,
+                    )
+                })
+                .collect();
+            tasks.sort();
+            tasks
+        };
+        assert_eq!(strip(&round_trip), strip(&graph));
+    }
+}
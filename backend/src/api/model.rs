@@ -1,3 +1,4 @@
+use crate::api::{collab::txn_origin::Actor, import};
 use sqlx::types::chrono::{self, Utc};
 use std::{collections::HashMap, fmt};
 
@@ -9,6 +10,10 @@ pub(crate) struct Project {
     pub(crate) project_id: String,
     pub(crate) name: String,
     pub(crate) deleted_on: Option<chrono::DateTime<Utc>>,
+    /// The requesting user's role in the project, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub(crate) role: Option<ProjectRole>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -31,6 +36,37 @@ impl fmt::Debug for CreateProject {
 pub(crate) struct ProjectPermission {
     pub(crate) project_id: ProjectId,
     pub(crate) email: String,
+    pub(crate) role: ProjectRole,
+}
+
+/// The role a user holds in a project.
+///
+/// Roles are ordered from least to most privileged so that a required
+/// role can be checked with a simple comparison.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    sqlx::Type,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub(crate) enum ProjectRole {
+    /// May read the project and its tasks. Edits to tasks are rejected.
+    Viewer,
+    /// May read the project and comment on its tasks. Edits to tasks are
+    /// rejected, as for viewers.
+    Commenter,
+    /// May read and edit tasks.
+    Editor,
+    /// May edit tasks, rename, delete and share the project.
+    Owner,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -39,6 +75,10 @@ pub struct UpdateProjectUsers {
     pub project_id: ProjectId,
     pub add_emails: Vec<String>,
     pub remove_emails: Vec<String>,
+    /// Role granted to `add_emails`. When absent, new users are added as
+    /// editors and existing users keep their role.
+    #[serde(default)]
+    pub(crate) role: Option<ProjectRole>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -52,6 +92,7 @@ pub(crate) struct ProjectUser {
     pub(crate) name: String,
     pub(crate) picture: String,
     pub(crate) premium: bool,
+    pub(crate) role: ProjectRole,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, sqlx::FromRow)]
@@ -63,16 +104,37 @@ pub(crate) struct User {
     pub(crate) premium: bool,
 }
 
+/// Exports are versioned so that older exports can be migrated on import.
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Debug)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", try_from = "import::VersionedExport")]
 pub(crate) struct ProjectExport {
+    pub(crate) version: u32,
     pub(crate) project_id: ProjectId,
     pub(crate) graph: Graph,
 }
 
 pub(crate) type Graph = HashMap<String, Task>;
 
-#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Debug, Default)]
+/// A point-in-time copy of a project's doc.
+#[derive(serde::Serialize, serde::Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Snapshot {
+    pub(crate) project_id: ProjectId,
+    pub(crate) snapshot_id: i32,
+    pub(crate) created_at: chrono::DateTime<Utc>,
+    /// The actor of the last change the snapshot includes, or the server if unknown.
+    #[sqlx(json)]
+    pub(crate) actor: Actor,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProjectSnapshot {
+    pub(crate) snapshot: Snapshot,
+    pub(crate) graph: Graph,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Task {
     pub(crate) id: String,
@@ -80,6 +142,10 @@ pub(crate) struct Task {
     pub(crate) name: String,
     pub(crate) desc: Option<String>,
     pub(crate) children: Vec<String>,
+    /// IDs of the tasks that must be done before this one can start.
+    /// Absent from tasks written before dependencies existed.
+    #[serde(default)]
+    pub(crate) blocked_by: Vec<String>,
     pub(crate) assignee: Option<String>,
     pub(crate) reporter: Option<String>,
     pub(crate) status: Option<String>,
@@ -103,6 +169,7 @@ pub(crate) mod test_utils {
             name: "Task 1".to_string(),
             desc: Some("Task 1 description".to_string()),
             children: vec!["2".to_string()],
+            blocked_by: vec!["3".to_string()],
             assignee: Some("a@gmail.com".to_string()),
             reporter: Some("r@gmail.com".to_string()),
             status: Some("Done".to_string()),
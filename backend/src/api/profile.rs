@@ -1,9 +1,10 @@
 use crate::api::billing::fetch_owned_subscription;
 use crate::api::billing::model::{Subscription, SubscriptionStatus};
 use crate::api::google::User;
+use crate::api::tokens;
 use crate::notifiers::{UserNotificationConfig, fetch_notification_configs};
 use anyhow::{Context, Result};
-use axum::{Extension, Json, Router, routing::get};
+use axum::{Extension, Json, Router, middleware, routing::get};
 use axum_anyhow::{ApiResult, OptionExt};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -13,7 +14,9 @@ use sqlx::types::chrono;
 use tokio::try_join;
 
 pub(crate) fn router() -> Router {
-    Router::new().route("/", get(get_profile_handler))
+    Router::new()
+        .route("/", get(get_profile_handler))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,16 +1,18 @@
 use crate::api::{
+    activity,
     collab::{
-        Collab,
         storage::{self, persist_update},
         txn_origin::{self, YOrigin},
     },
-    dupes,
+    comments, dependencies, dupes, export,
     google::User,
+    import::{self, ImportMode},
+    integrity, jira,
     model::{
-        CreateProject, Project, ProjectExport, ProjectId, ProjectUser, UpdateProjectUsers,
+        CreateProject, Graph, Project, ProjectId, ProjectRole, ProjectUser, UpdateProjectUsers,
         UpdateProjectUsersResponse,
     },
-    verify_premium, verify_project_access,
+    search, snapshots, tokens, verify_premium, verify_project_access, webhooks,
     yproxy::YDocProxy,
 };
 use anyhow::{Context, Result};
@@ -38,7 +40,59 @@ pub(super) fn router() -> Router {
             "/{project_id}/updates",
             get(get_project_doc_updates_handler),
         )
-        .route("/{project_id}/export", get(export_project))
+        .route("/{project_id}/export", get(export::export_project_handler))
+        .route("/{project_id}/export/jira", get(jira::export_jira_handler))
+        .route("/import/jira", post(jira::import_jira_handler))
+        .route("/import/validate", post(import::validate_import_handler))
+        .route("/{project_id}/import", post(import::import_subtree_handler))
+        .route(
+            "/{project_id}/integrity",
+            get(integrity::check_integrity_handler),
+        )
+        .route(
+            "/{project_id}/integrity/repair",
+            post(integrity::repair_integrity_handler),
+        )
+        .route(
+            "/{project_id}/snapshots",
+            get(snapshots::list_snapshots_handler),
+        )
+        .route(
+            "/{project_id}/snapshots/{snapshot_id}",
+            get(snapshots::get_snapshot_handler),
+        )
+        .route(
+            "/{project_id}/snapshots/{snapshot_id}/restore",
+            post(snapshots::restore_snapshot_handler),
+        )
+        .route(
+            "/{project_id}/activity",
+            get(activity::project_activity_handler),
+        )
+        .route(
+            "/{project_id}/tasks/{task_id}/history",
+            get(activity::task_history_handler),
+        )
+        .route(
+            "/{project_id}/tasks/{task_id}/comments",
+            get(comments::list_comments_handler),
+        )
+        .route(
+            "/{project_id}/tasks/{task_id}/comments",
+            post(comments::create_comment_handler),
+        )
+        .route(
+            "/{project_id}/tasks/{task_id}/comments/{comment_id}",
+            delete(comments::delete_comment_handler),
+        )
+        .route(
+            "/{project_id}/tasks/{task_id}/dependencies",
+            post(dependencies::add_dependency_handler),
+        )
+        .route(
+            "/{project_id}/tasks/{task_id}/dependencies/{blocker_id}",
+            delete(dependencies::remove_dependency_handler),
+        )
         .route("/{project_id}/dupes", get(dupes::list_dupes_handler))
         .route("/{project_id}/dupes", post(dupes::create_dupe_handler))
         .route(
@@ -49,6 +103,22 @@ pub(super) fn router() -> Router {
             "/{project_id}/dupes/{dupe_id}",
             patch(dupes::update_dupe_resolution_handler),
         )
+        .route(
+            "/{project_id}/webhooks",
+            get(webhooks::list_webhooks_handler),
+        )
+        .route(
+            "/{project_id}/webhooks",
+            post(webhooks::create_webhook_handler),
+        )
+        .route(
+            "/{project_id}/webhooks/{webhook_id}",
+            delete(webhooks::delete_webhook_handler),
+        )
+        .route(
+            "/{project_id}/webhooks/{webhook_id}/deliveries",
+            get(webhooks::list_deliveries_handler),
+        )
 }
 
 #[tracing::instrument(skip(user, pool))]
@@ -56,7 +126,13 @@ async fn list_projects_handler(
     Extension(user): Extension<User>,
     Extension(pool): Extension<&'static PgPool>,
 ) -> ApiResult<Json<Vec<Project>>> {
-    let projects: Vec<Project> = list_projects(&user.email, pool).await?;
+    let mut projects: Vec<Project> = list_projects(&user.email, pool).await?;
+    if let Some(scope) = &user.scope {
+        projects.retain(|project| scope.allows(&project.project_id));
+        for project in &mut projects {
+            project.role = project.role.map(|role| scope.limit_role(role));
+        }
+    }
     Ok(Json(projects))
 }
 
@@ -66,7 +142,8 @@ pub(crate) async fn list_projects(email: &str, pool: &PgPool) -> Result<Vec<Proj
         SELECT
           project_id,
           projects.name,
-          projects.deleted_on
+          projects.deleted_on,
+          role
         FROM project_permissions 
         JOIN projects USING(project_id)
         WHERE email = $1",
@@ -85,6 +162,16 @@ async fn create_project_handler(
     Extension(pool): Extension<&'static PgPool>,
     Json(project): Json<CreateProject>,
 ) -> ApiResult<Json<Project>> {
+    Ok(Json(create_project(&user, pool, project).await?))
+}
+
+/// Creates a project owned by the user, seeded with the graph of `project_export`, if any.
+pub(super) async fn create_project(
+    user: &User,
+    pool: &PgPool,
+    project: CreateProject,
+) -> ApiResult<Project> {
+    tokens::verify_unscoped_token(user)?;
     let projects = list_projects(&user.email, pool).await?;
     const MAX_PROJECTS: usize = 20;
     if projects.len() >= MAX_PROJECTS {
@@ -95,7 +182,9 @@ async fn create_project_handler(
     }
     validate_project_name(&project.name)?;
 
+    let mut graph = Graph::new();
     let import_update = if let Some(import_data) = project.project_export {
+        import::validate(&import_data.graph, ImportMode::Project).ensure_valid()?;
         let ydoc = YDocProxy::new();
         let mut txn: yrs::TransactionMut<'_> = ydoc.transact_mut_with(
             YOrigin {
@@ -108,6 +197,7 @@ async fn create_project_handler(
         for import_task in import_data.graph.values() {
             ydoc.set(&mut txn, import_task);
         }
+        graph = import_data.graph;
         Some(txn.encode_state_as_update_v2(&StateVector::default()))
     } else {
         None
@@ -117,6 +207,7 @@ async fn create_project_handler(
         project_id: BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4()),
         name: project.name,
         deleted_on: None,
+        role: Some(ProjectRole::Owner),
     };
 
     let mut txn = pool.begin().await?;
@@ -125,14 +216,17 @@ async fn create_project_handler(
         .bind(&project.name)
         .execute(&mut *txn)
         .await?;
-    sqlx::query("INSERT INTO project_permissions (project_id, email) VALUES ($1, $2)")
+    sqlx::query("INSERT INTO project_permissions (project_id, email, role) VALUES ($1, $2, $3)")
         .bind(&project.project_id)
         .bind(&user.email)
+        .bind(ProjectRole::Owner)
         .execute(&mut *txn)
         .await?;
     if let Some(import_update) = import_update {
         persist_update(&project.project_id, &import_update, &mut *txn).await?;
     }
+    // Index the tasks now, rather than in the next backfill, so the project is searchable at once.
+    search::index_project(&project.project_id, &graph, &mut txn).await?;
     txn.commit().await?;
 
     tracing::debug!(
@@ -141,7 +235,7 @@ async fn create_project_handler(
         project.project_id
     );
 
-    Ok(Json(project))
+    Ok(project)
 }
 
 #[tracing::instrument(skip(user, pool))]
@@ -150,7 +244,7 @@ async fn list_project_users_handler(
     Extension(pool): Extension<&'static PgPool>,
     Path(project_id): Path<String>,
 ) -> ApiResult<Json<Vec<ProjectUser>>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
     let mut users = list_project_users(pool, &project_id).await?;
     users.sort_by(|a, b| a.name.cmp(&b.name).then(a.email.cmp(&b.email)));
 
@@ -163,11 +257,12 @@ async fn get_project_handler(
     Extension(pool): Extension<&'static PgPool>,
     Path(project_id): Path<String>,
 ) -> ApiResult<Json<Project>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    let role = verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
 
-    let project = fetch_project(pool, &project_id)
+    let mut project = fetch_project(pool, &project_id)
         .await?
         .context_not_found("NOT_FOUND", "Project not found")?;
+    project.role = Some(role);
     Ok(Json(project))
 }
 
@@ -178,7 +273,7 @@ async fn update_project_handler(
     Path(project_id): Path<String>,
     Json(project): Json<Project>,
 ) -> ApiResult<Json<Project>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
 
     if project_id != project.project_id {
         return Err(bad_request(
@@ -206,7 +301,7 @@ async fn delete_project_handler(
     Extension(pool): Extension<&'static PgPool>,
     Path(project_id): Path<String>,
 ) -> ApiResult<Json<Project>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
 
     sqlx::query(
         "
@@ -231,7 +326,7 @@ async fn update_project_users_handler(
     Path(project_id): Path<String>,
     Json(update): Json<UpdateProjectUsers>,
 ) -> ApiResult<Json<UpdateProjectUsersResponse>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
     verify_premium(pool, &user).await?;
 
     if project_id != update.project_id {
@@ -273,18 +368,47 @@ async fn update_project_users_handler(
         .await?;
     }
     if !add_emails.is_empty() {
-        sqlx::query(
-            "
-            INSERT INTO project_permissions (project_id, email) 
-            SELECT $1, * FROM UNNEST($2)
-            ON CONFLICT DO NOTHING",
-        )
+        match update.role {
+            // Without an explicit role, leave the role of existing users untouched.
+            None => sqlx::query(
+                "
+                INSERT INTO project_permissions (project_id, email, role)
+                SELECT $1, email, $3 FROM UNNEST($2) AS email
+                ON CONFLICT DO NOTHING",
+            ),
+            Some(_) => sqlx::query(
+                "
+                INSERT INTO project_permissions (project_id, email, role)
+                SELECT $1, email, $3 FROM UNNEST($2) AS email
+                ON CONFLICT (project_id, email) DO UPDATE SET role = EXCLUDED.role",
+            ),
+        }
         .bind(&update.project_id)
         .bind(&add_emails)
+        .bind(update.role.unwrap_or(ProjectRole::Editor))
         .execute(&mut *txn)
         .await?;
     }
 
+    // Don't allow a project to be orphaned by removing or demoting every owner.
+    let (owners,): (i64,) = sqlx::query_as(
+        "
+        SELECT COUNT(*)
+        FROM project_permissions
+        WHERE project_id = $1
+        AND role = $2",
+    )
+    .bind(&update.project_id)
+    .bind(ProjectRole::Owner)
+    .fetch_one(&mut *txn)
+    .await?;
+    if owners == 0 {
+        return Err(bad_request(
+            "NO_OWNER",
+            "A project must have at least one owner",
+        ));
+    }
+
     txn.commit().await?;
 
     Ok(Json(UpdateProjectUsersResponse {}))
@@ -296,7 +420,7 @@ async fn get_project_doc_updates_handler(
     Extension(pool): Extension<&'static PgPool>,
     Path(project_id): Path<String>,
 ) -> ApiResult<Json<Vec<String>>> {
-    verify_project_access(pool, &user, &project_id).await?;
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
 
     let updates = storage::load_updates(&project_id, pool)
         .await?
@@ -321,19 +445,6 @@ pub(crate) async fn fetch_project(pool: &PgPool, project_id: &str) -> Result<Opt
     .await?)
 }
 
-#[tracing::instrument(skip(user, pool, collab))]
-async fn export_project(
-    Extension(user): Extension<User>,
-    Extension(pool): Extension<&'static PgPool>,
-    Extension(collab): Extension<Collab>,
-    Path(project_id): Path<String>,
-) -> ApiResult<Json<ProjectExport>> {
-    verify_project_access(pool, &user, &project_id).await?;
-
-    let graph = collab.get_graph(&project_id).await?;
-    Ok(Json(ProjectExport { project_id, graph }))
-}
-
 fn validate_project_name(name: &str) -> ApiResult<()> {
     if name.is_empty() || name.chars().all(char::is_whitespace) {
         return Err(bad_request("EMPTY_NAME", "Project name is blank"));
@@ -354,7 +465,7 @@ pub(crate) async fn list_project_users(
 ) -> Result<Vec<ProjectUser>> {
     sqlx::query_as(
         "
-        SELECT project_id, email, name, picture, (subscription_end_time IS NOT NULL AND subscription_end_time > now()) AS premium
+        SELECT project_id, email, name, picture, (subscription_end_time IS NOT NULL AND subscription_end_time > now()) AS premium, role
         FROM project_permissions
         JOIN users USING (email)
         WHERE project_id = $1;",
@@ -0,0 +1,264 @@
+use crate::api::{
+    collab::Collab,
+    google::User,
+    model::{Graph, ProjectId, Task},
+};
+use anyhow::{Context, Result};
+use axum::{Extension, Json, Router, extract::Query, routing::get};
+use axum_anyhow::{ApiResult, bad_request};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+pub(super) fn router() -> Router {
+    Router::new().route("/tasks", get(search_tasks_handler))
+}
+
+/// Filters applied to a task search. All filters are optional and combined with AND.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SearchQuery {
+    /// Full-text query matched against task names and descriptions.
+    pub(crate) q: Option<String>,
+    /// Restrict the search to a single project.
+    pub(crate) project_id: Option<ProjectId>,
+    pub(crate) assignee: Option<String>,
+    /// Comma separated list of statuses. Tasks without a status are "Not Started".
+    pub(crate) status: Option<String>,
+    pub(crate) kind: Option<String>,
+    /// Inclusive lower bound of the deadline, in milliseconds since the epoch.
+    pub(crate) deadline_after: Option<i64>,
+    /// Inclusive upper bound of the deadline, in milliseconds since the epoch.
+    pub(crate) deadline_before: Option<i64>,
+    pub(crate) archived: Option<bool>,
+    pub(crate) limit: Option<i64>,
+}
+
+impl SearchQuery {
+    fn limit(&self) -> ApiResult<i64> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(bad_request(
+                "INVALID_LIMIT",
+                &format!("Limit must be between 1 and {MAX_LIMIT}"),
+            ));
+        }
+        Ok(limit)
+    }
+
+    fn statuses(&self) -> Option<Vec<String>> {
+        self.status.as_ref().map(|status| {
+            status
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskSearchResult {
+    pub(crate) project_id: ProjectId,
+    pub(crate) project_name: String,
+    #[sqlx(json)]
+    pub(crate) task: Task,
+}
+
+#[tracing::instrument(skip(user, pool, collab))]
+async fn search_tasks_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Query(query): Query<SearchQuery>,
+) -> ApiResult<Json<Vec<TaskSearchResult>>> {
+    let results = search_tasks(&collab, &user, &query, pool).await?;
+    Ok(Json(results))
+}
+
+/// Search the tasks of all non-deleted projects the user has access to.
+pub(crate) async fn search_tasks(
+    collab: &Collab,
+    user: &User,
+    query: &SearchQuery,
+    pool: &PgPool,
+) -> ApiResult<Vec<TaskSearchResult>> {
+    let limit = query.limit()?;
+    // Index any new projects in the background. Their tasks are
+    // missing from results until they're indexed.
+    collab.backfill_search_index();
+
+    let results = sqlx::query_as(
+        "
+        SELECT task_index.project_id, projects.name AS project_name, task
+        FROM task_index
+        JOIN project_permissions USING (project_id)
+        JOIN projects USING (project_id)
+        WHERE project_permissions.email = $1
+        AND projects.deleted_on IS NULL
+        AND ($2::varchar IS NULL OR search @@ websearch_to_tsquery('simple', $2))
+        AND ($3::varchar IS NULL OR task_index.project_id = $3)
+        AND ($4::varchar IS NULL OR assignee = $4)
+        AND ($5::varchar[] IS NULL OR COALESCE(status, 'Not Started') = ANY($5))
+        AND ($6::varchar IS NULL OR kind = $6)
+        AND ($7::bigint IS NULL OR deadline >= $7)
+        AND ($8::bigint IS NULL OR deadline <= $8)
+        AND ($9::boolean IS NULL OR archived = $9)
+        AND ($11::varchar[] IS NULL OR task_index.project_id = ANY($11))
+        ORDER BY
+          ts_rank(search, websearch_to_tsquery('simple', $2)) DESC NULLS LAST,
+          projects.name, task_index.project_id, length(task->>'num'), task->>'num'
+        LIMIT $10",
+    )
+    .bind(&user.email)
+    .bind(&query.q)
+    .bind(&query.project_id)
+    .bind(&query.assignee)
+    .bind(query.statuses())
+    .bind(&query.kind)
+    .bind(query.deadline_after)
+    .bind(query.deadline_before)
+    .bind(query.archived)
+    .bind(limit)
+    .bind(
+        user.scope
+            .as_ref()
+            .and_then(|scope| scope.project_ids.as_ref()),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search tasks")?;
+    Ok(results)
+}
+
+/// Replace the indexed copies of the given tasks and drop the removed ones.
+pub(crate) async fn index_tasks(
+    project_id: &ProjectId,
+    tasks: &[Task],
+    removed: &[String],
+    pool: &PgPool,
+) -> Result<()> {
+    let mut txn = pool.begin().await?;
+    if !removed.is_empty() {
+        sqlx::query(
+            "
+            DELETE FROM task_index
+            WHERE project_id = $1
+            AND task_id IN (SELECT unnest($2::varchar[]))",
+        )
+        .bind(project_id)
+        .bind(removed)
+        .execute(&mut *txn)
+        .await
+        .context("Failed to remove indexed tasks")?;
+    }
+    for task in tasks {
+        upsert_task(project_id, task, &mut txn).await?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+async fn upsert_task(
+    project_id: &ProjectId,
+    task: &Task,
+    txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<()> {
+    sqlx::query(
+        "
+        INSERT INTO task_index
+          (project_id, task_id, name, description, assignee, status, kind, deadline, archived, task)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (project_id, task_id) DO UPDATE SET
+          name = EXCLUDED.name,
+          description = EXCLUDED.description,
+          assignee = EXCLUDED.assignee,
+          status = EXCLUDED.status,
+          kind = EXCLUDED.kind,
+          deadline = EXCLUDED.deadline,
+          archived = EXCLUDED.archived,
+          task = EXCLUDED.task",
+    )
+    .bind(project_id)
+    .bind(&task.id)
+    .bind(&task.name)
+    .bind(&task.desc)
+    .bind(&task.assignee)
+    .bind(&task.status)
+    .bind(&task.kind)
+    .bind(task.deadline)
+    .bind(task.archived.unwrap_or(false))
+    .bind(sqlx::types::Json(task))
+    .execute(&mut **txn)
+    .await
+    .with_context(|| format!("Failed to index task {}", task.id))?;
+    Ok(())
+}
+
+/// Lists the projects that haven't been indexed yet, such as those last edited
+/// before the index existed, created via import or whose index updates were dropped.
+pub(crate) async fn list_unindexed_projects(pool: &PgPool) -> Result<Vec<ProjectId>> {
+    let project_ids: Vec<(ProjectId,)> = sqlx::query_as(
+        "
+        SELECT project_id
+        FROM projects
+        WHERE deleted_on IS NULL
+        AND project_id NOT IN (SELECT project_id FROM task_index_projects)",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list unindexed projects")?;
+    Ok(project_ids
+        .into_iter()
+        .map(|(project_id,)| project_id)
+        .collect())
+}
+
+/// Replace the indexed tasks of the project with the given graph.
+/// Afterwards, the doc observers keep the index current.
+pub(crate) async fn reindex_project(
+    project_id: &ProjectId,
+    graph: &Graph,
+    pool: &PgPool,
+) -> Result<()> {
+    tracing::debug!("Reindexing project {project_id}");
+    let mut txn = pool.begin().await?;
+    index_project(project_id, graph, &mut txn).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Replace the indexed tasks of the project, as part of the given transaction,
+/// and mark the project as indexed.
+pub(crate) async fn index_project(
+    project_id: &ProjectId,
+    graph: &Graph,
+    txn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<()> {
+    sqlx::query("DELETE FROM task_index WHERE project_id = $1")
+        .bind(project_id)
+        .execute(&mut **txn)
+        .await?;
+    for task in graph.values().filter(|task| is_indexed(task)) {
+        upsert_task(project_id, task, txn).await?;
+    }
+    sqlx::query(
+        "
+        INSERT INTO task_index_projects (project_id)
+        VALUES ($1)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(project_id)
+    .execute(&mut **txn)
+    .await?;
+    Ok(())
+}
+
+/// The root task is an implementation detail of the graph and never returned in results.
+pub(crate) fn is_indexed(task: &Task) -> bool {
+    task.id != "root"
+}
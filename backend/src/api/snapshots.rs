@@ -0,0 +1,64 @@
+use crate::api::{
+    collab::{
+        Collab, storage,
+        txn_origin::{Actor, YOrigin},
+    },
+    google::User,
+    model::{ProjectId, ProjectRole, ProjectSnapshot, Snapshot},
+    verify_project_access,
+};
+use axum::{Extension, Json, extract::Path};
+use axum_anyhow::{ApiResult, OptionExt};
+use sqlx::postgres::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn list_snapshots_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<Json<Vec<Snapshot>>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let snapshots = storage::list_snapshots(&project_id, pool).await?;
+    Ok(Json(snapshots))
+}
+
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn get_snapshot_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path((project_id, snapshot_id)): Path<(ProjectId, i32)>,
+) -> ApiResult<Json<ProjectSnapshot>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Viewer).await?;
+
+    let snapshot = collab
+        .get_snapshot(&project_id, snapshot_id)
+        .await?
+        .context_not_found("NOT_FOUND", "Snapshot not found")?;
+    Ok(Json(snapshot))
+}
+
+/// Restores the project to the given snapshot and returns a
+/// snapshot of the state prior to the restore.
+#[tracing::instrument(skip(user, pool, collab))]
+pub(crate) async fn restore_snapshot_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+    Path((project_id, snapshot_id)): Path<(ProjectId, i32)>,
+) -> ApiResult<Json<Snapshot>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Editor).await?;
+
+    let origin = YOrigin {
+        who: format!("restore-{}", user.email),
+        id: format!("restore_snapshot_{snapshot_id}_{}", Uuid::new_v4()),
+        actor: Actor::User(user),
+    };
+    let backup = collab
+        .restore_snapshot(&project_id, snapshot_id, origin)
+        .await?
+        .context_not_found("NOT_FOUND", "Snapshot not found")?;
+    Ok(Json(backup))
+}
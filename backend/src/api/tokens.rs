@@ -0,0 +1,357 @@
+use crate::api::{
+    google::User,
+    model::{ProjectId, ProjectRole},
+    verify_project_access,
+};
+use anyhow::Context;
+use axum::{
+    Extension, Json, Router,
+    body::Body,
+    extract::{Path, Request},
+    middleware::Next,
+    response::Response,
+    routing::{delete, get, patch, post},
+};
+use axum_anyhow::{ApiResult, OptionExt, bad_request, forbidden, unauthorized};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{
+    postgres::PgPool,
+    types::chrono::{DateTime, Utc},
+};
+use uuid::Uuid;
+
+/// Distinguishes personal access tokens from Google ID tokens.
+pub(crate) const TOKEN_PREFIX: &str = "koso_pat_";
+const MAX_TOKENS: i64 = 50;
+/// How stale a token's last_used_at may get before it's rewritten.
+/// Saves a write on every request.
+const TOKEN_LAST_USED_RESOLUTION: TimeDelta = TimeDelta::minutes(5);
+
+pub(super) fn router() -> Router {
+    Router::new()
+        .route("/", get(list_tokens_handler))
+        .route("/", post(create_token_handler))
+        .route("/{token_id}", patch(update_token_handler))
+        .route("/{token_id}", delete(delete_token_handler))
+}
+
+/// The restrictions of the personal access token a request was authenticated with.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TokenScope {
+    pub(crate) token_id: String,
+    /// The projects the token may access, or None for all of the user's projects.
+    pub(crate) project_ids: Option<Vec<ProjectId>>,
+    pub(crate) read_only: bool,
+}
+
+impl TokenScope {
+    pub(crate) fn allows(&self, project_id: &ProjectId) -> bool {
+        self.project_ids
+            .as_ref()
+            .is_none_or(|project_ids| project_ids.contains(project_id))
+    }
+
+    /// Read-only tokens act as viewers, whatever the user's role.
+    pub(crate) fn limit_role(&self, role: ProjectRole) -> ProjectRole {
+        if self.read_only {
+            role.min(ProjectRole::Viewer)
+        } else {
+            role
+        }
+    }
+}
+
+/// A personal access token, without the token itself.
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PersonalAccessToken {
+    pub(crate) token_id: String,
+    pub(crate) name: String,
+    pub(crate) project_ids: Option<Vec<ProjectId>>,
+    pub(crate) read_only: bool,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+    pub(crate) last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreatePersonalAccessToken {
+    pub(crate) name: String,
+    /// Defaults to all of the user's projects, including future ones.
+    pub(crate) project_ids: Option<Vec<ProjectId>>,
+    pub(crate) read_only: bool,
+    /// Defaults to never expiring.
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+}
+
+/// The token is only returned when it's created.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreatePersonalAccessTokenResponse {
+    pub(crate) token: PersonalAccessToken,
+    pub(crate) secret: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdatePersonalAccessToken {
+    pub(crate) name: String,
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn list_tokens_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+) -> ApiResult<Json<Vec<PersonalAccessToken>>> {
+    verify_interactive_user(&user)?;
+
+    let tokens = sqlx::query_as(
+        "
+        SELECT token_id, name, project_ids, read_only, created_at, expires_at, last_used_at
+        FROM personal_access_tokens
+        WHERE email = $1
+        ORDER BY created_at",
+    )
+    .bind(&user.email)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list personal access tokens")?;
+    Ok(Json(tokens))
+}
+
+#[tracing::instrument(skip(user, pool, req))]
+async fn create_token_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Json(req): Json<CreatePersonalAccessToken>,
+) -> ApiResult<Json<CreatePersonalAccessTokenResponse>> {
+    verify_interactive_user(&user)?;
+
+    validate_name(&req.name)?;
+    if let Some(project_ids) = &req.project_ids {
+        if project_ids.is_empty() {
+            return Err(bad_request(
+                "INVALID_PROJECTS",
+                "Scope the token to at least one project",
+            ));
+        }
+        for project_id in project_ids {
+            verify_project_access(pool, &user, project_id, ProjectRole::Viewer).await?;
+        }
+    }
+    if let Some(expires_at) = req.expires_at
+        && expires_at <= Utc::now()
+    {
+        return Err(bad_request(
+            "INVALID_EXPIRATION",
+            "Expiration must be in the future",
+        ));
+    }
+    let (count,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM personal_access_tokens WHERE email = $1")
+            .bind(&user.email)
+            .fetch_one(pool)
+            .await
+            .context("Failed to count personal access tokens")?;
+    if count >= MAX_TOKENS {
+        return Err(bad_request(
+            "TOO_MANY_TOKENS",
+            &format!("Cannot create more than {MAX_TOKENS} tokens"),
+        ));
+    }
+
+    let secret = format!(
+        "{TOKEN_PREFIX}{}",
+        BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+    );
+    let token = sqlx::query_as(
+        "
+        INSERT INTO personal_access_tokens (token_id, email, name, token_hash, project_ids, read_only, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING token_id, name, project_ids, read_only, created_at, expires_at, last_used_at",
+    )
+    .bind(BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4()))
+    .bind(&user.email)
+    .bind(&req.name)
+    .bind(hash_token(&secret))
+    .bind(&req.project_ids)
+    .bind(req.read_only)
+    .bind(req.expires_at)
+    .fetch_one(pool)
+    .await
+    .context("Failed to create personal access token")?;
+    Ok(Json(CreatePersonalAccessTokenResponse { token, secret }))
+}
+
+#[tracing::instrument(skip(user, pool, req))]
+async fn update_token_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(token_id): Path<String>,
+    Json(req): Json<UpdatePersonalAccessToken>,
+) -> ApiResult<Json<PersonalAccessToken>> {
+    verify_interactive_user(&user)?;
+    validate_name(&req.name)?;
+
+    let token = sqlx::query_as(
+        "
+        UPDATE personal_access_tokens
+        SET name = $3
+        WHERE token_id = $1 AND email = $2
+        RETURNING token_id, name, project_ids, read_only, created_at, expires_at, last_used_at",
+    )
+    .bind(&token_id)
+    .bind(&user.email)
+    .bind(&req.name)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to update personal access token")?
+    .context_not_found("NOT_FOUND", &format!("Token {token_id} not found"))?;
+    Ok(Json(token))
+}
+
+/// Revoke the token. Requests using it are rejected immediately.
+#[tracing::instrument(skip(user, pool))]
+async fn delete_token_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(token_id): Path<String>,
+) -> ApiResult<Json<()>> {
+    verify_interactive_user(&user)?;
+
+    let res = sqlx::query(
+        "
+        DELETE FROM personal_access_tokens
+        WHERE token_id = $1 AND email = $2",
+    )
+    .bind(&token_id)
+    .bind(&user.email)
+    .execute(pool)
+    .await
+    .context("Failed to delete personal access token")?;
+    (res.rows_affected() > 0)
+        .then_some(())
+        .context_not_found("NOT_FOUND", &format!("Token {token_id} not found"))?;
+    Ok(Json(()))
+}
+
+/// Authenticate a request bearing a personal access token.
+/// The returned user carries the token's scope.
+pub(crate) async fn authenticate(pool: &PgPool, token: &str) -> ApiResult<User> {
+    #[derive(sqlx::FromRow)]
+    struct TokenUser {
+        token_id: String,
+        project_ids: Option<Vec<ProjectId>>,
+        read_only: bool,
+        last_used_at: Option<DateTime<Utc>>,
+        email: String,
+        name: String,
+        picture: String,
+    }
+
+    let user: Option<TokenUser> = sqlx::query_as(
+        "
+            SELECT token_id, project_ids, read_only, last_used_at, users.email, users.name, users.picture
+            FROM personal_access_tokens
+            JOIN users ON personal_access_tokens.email = users.email
+            WHERE token_hash = $1
+            AND (expires_at IS NULL OR expires_at > NOW())",
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+    .context("Failed to authenticate personal access token")?;
+    let Some(user) = user else {
+        return Err(unauthorized(
+            "UNAUTHENTICATED",
+            "Personal access token is invalid, expired or revoked",
+        ));
+    };
+
+    let stale = user
+        .last_used_at
+        .is_none_or(|last_used_at| Utc::now() - last_used_at > TOKEN_LAST_USED_RESOLUTION);
+    if stale {
+        sqlx::query(
+            "
+            UPDATE personal_access_tokens
+            SET last_used_at = NOW()
+            WHERE token_id = $1",
+        )
+        .bind(&user.token_id)
+        .execute(pool)
+        .await
+        .context("Failed to use personal access token")?;
+    }
+
+    Ok(User {
+        email: user.email,
+        name: user.name,
+        picture: user.picture,
+        exp: 0,
+        scope: Some(TokenScope {
+            token_id: user.token_id,
+            project_ids: user.project_ids,
+            read_only: user.read_only,
+        }),
+    })
+}
+
+/// Tokens may not be used to manage credentials, lest a leaked token outlive its revocation.
+pub(crate) fn verify_interactive_user(user: &User) -> ApiResult<()> {
+    if user.scope.is_some() {
+        return Err(forbidden(
+            "TOKEN_NOT_ALLOWED",
+            "Personal access tokens cannot manage credentials",
+        ));
+    }
+    Ok(())
+}
+
+/// Tokens scoped to projects may only be used on endpoints tied to those projects.
+pub(crate) fn verify_unscoped_token(user: &User) -> ApiResult<()> {
+    if user
+        .scope
+        .as_ref()
+        .is_some_and(|scope| scope.project_ids.is_some())
+    {
+        return Err(forbidden(
+            "TOKEN_NOT_ALLOWED",
+            "Personal access tokens scoped to projects can only access those projects",
+        ));
+    }
+    Ok(())
+}
+
+/// Middleware rejecting project scoped tokens on routers not tied to a project.
+/// Must run after google::authenticate.
+pub(crate) async fn reject_project_scoped_tokens(
+    request: Request,
+    next: Next,
+) -> ApiResult<Response<Body>> {
+    let user = request
+        .extensions()
+        .get::<User>()
+        .context_unauthorized("UNAUTHENTICATED", "Request is not authenticated")?;
+    verify_unscoped_token(user)?;
+    Ok(next.run(request).await)
+}
+
+fn validate_name(name: &str) -> ApiResult<()> {
+    const MAX_NAME_LEN: usize = 100;
+    if name.trim().is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(bad_request(
+            "INVALID_NAME",
+            &format!("Name must be between 1 and {MAX_NAME_LEN} characters"),
+        ));
+    }
+    Ok(())
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
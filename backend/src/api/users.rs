@@ -1,5 +1,5 @@
-use crate::api::{google, model::User, verify_premium};
-use axum::{Extension, Json, Router, extract::Path, routing::get};
+use crate::api::{google, model::User, tokens, verify_premium};
+use axum::{Extension, Json, Router, extract::Path, middleware, routing::get};
 use axum_anyhow::{ApiResult, OptionExt, bad_request, forbidden};
 use sqlx::postgres::PgPool;
 
@@ -7,6 +7,7 @@ pub(super) fn router() -> Router {
     Router::new()
         .route("/", get(list_users_handler))
         .route("/{email}", get(get_user_handler))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
 }
 
 #[tracing::instrument(skip(pool, user))]
@@ -0,0 +1,183 @@
+use crate::{
+    api::{
+        google::User,
+        model::{ProjectId, ProjectRole},
+        verify_project_access,
+    },
+    notifiers::webhook::{
+        WebhookDelivery, WebhookEventKind, generate_secret, list_deliveries, validate_url,
+    },
+};
+use anyhow::{Context, Result};
+use axum::{Extension, Json, extract::Path};
+use axum_anyhow::{ApiResult, OptionExt, bad_request};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    postgres::PgPool,
+    types::chrono::{DateTime, Utc},
+};
+use uuid::Uuid;
+
+/// An outgoing webhook subscribed to the events of a project.
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProjectWebhook {
+    pub(crate) webhook_id: String,
+    pub(crate) project_id: ProjectId,
+    pub(crate) url: String,
+    /// The subscribed event types, or None for all events.
+    pub(crate) events: Option<Vec<String>>,
+    pub(crate) created_by: String,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateProjectWebhook {
+    pub(crate) url: String,
+    /// Generated when absent.
+    pub(crate) secret: Option<String>,
+    /// Defaults to all events.
+    pub(crate) events: Option<Vec<WebhookEventKind>>,
+}
+
+/// The secret is only returned when the webhook is created.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateProjectWebhookResponse {
+    pub(crate) webhook: ProjectWebhook,
+    pub(crate) secret: String,
+}
+
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn list_webhooks_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(project_id): Path<ProjectId>,
+) -> ApiResult<Json<Vec<ProjectWebhook>>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
+
+    let webhooks = sqlx::query_as(
+        "
+        SELECT webhook_id, project_id, url, events, created_by, created_at
+        FROM project_webhooks
+        WHERE project_id = $1
+        ORDER BY created_at",
+    )
+    .bind(&project_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list project webhooks")?;
+    Ok(Json(webhooks))
+}
+
+#[tracing::instrument(skip(user, pool, req))]
+pub(crate) async fn create_webhook_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(project_id): Path<ProjectId>,
+    Json(req): Json<CreateProjectWebhook>,
+) -> ApiResult<Json<CreateProjectWebhookResponse>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
+
+    validate_url(&req.url).await?;
+    let secret = match req.secret {
+        Some(secret) if secret.is_empty() => {
+            return Err(bad_request("INVALID_SECRET", "Secret must not be empty"));
+        }
+        Some(secret) => secret,
+        None => generate_secret(),
+    };
+    let events = match req.events {
+        Some(events) if events.is_empty() => {
+            return Err(bad_request(
+                "INVALID_EVENTS",
+                "Subscribe to at least one event",
+            ));
+        }
+        Some(events) => Some(
+            events
+                .iter()
+                .map(|event| event.as_str().to_string())
+                .collect::<Vec<_>>(),
+        ),
+        None => None,
+    };
+
+    let webhook_id = BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4());
+    let webhook = sqlx::query_as(
+        "
+        INSERT INTO project_webhooks (webhook_id, project_id, url, secret, events, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING webhook_id, project_id, url, events, created_by, created_at",
+    )
+    .bind(&webhook_id)
+    .bind(&project_id)
+    .bind(&req.url)
+    .bind(&secret)
+    .bind(events)
+    .bind(&user.email)
+    .fetch_one(pool)
+    .await
+    .context("Failed to create project webhook")?;
+    Ok(Json(CreateProjectWebhookResponse { webhook, secret }))
+}
+
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn delete_webhook_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path((project_id, webhook_id)): Path<(ProjectId, String)>,
+) -> ApiResult<Json<()>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
+
+    let res = sqlx::query(
+        "
+        DELETE FROM project_webhooks
+        WHERE webhook_id = $1 AND project_id = $2",
+    )
+    .bind(&webhook_id)
+    .bind(&project_id)
+    .execute(pool)
+    .await
+    .context("Failed to delete project webhook")?;
+    (res.rows_affected() > 0)
+        .then_some(())
+        .context_not_found("NOT_FOUND", &format!("Webhook {webhook_id} not found"))?;
+    Ok(Json(()))
+}
+
+#[tracing::instrument(skip(user, pool))]
+pub(crate) async fn list_deliveries_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path((project_id, webhook_id)): Path<(ProjectId, String)>,
+) -> ApiResult<Json<Vec<WebhookDelivery>>> {
+    verify_project_access(pool, &user, &project_id, ProjectRole::Owner).await?;
+    verify_webhook(&project_id, &webhook_id, pool)
+        .await?
+        .context_not_found("NOT_FOUND", &format!("Webhook {webhook_id} not found"))?;
+
+    let deliveries = list_deliveries(Some(&webhook_id), None, pool).await?;
+    Ok(Json(deliveries))
+}
+
+async fn verify_webhook(
+    project_id: &ProjectId,
+    webhook_id: &str,
+    pool: &PgPool,
+) -> Result<Option<()>> {
+    let webhook: Option<(String,)> = sqlx::query_as(
+        "
+        SELECT webhook_id
+        FROM project_webhooks
+        WHERE webhook_id = $1 AND project_id = $2",
+    )
+    .bind(webhook_id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get project webhook")?;
+    Ok(webhook.map(|_| ()))
+}
@@ -0,0 +1,123 @@
+use crate::api::{
+    collab::Collab,
+    google::User,
+    model::{Project, ProjectId, Task},
+    projects::list_projects,
+};
+use anyhow::Result;
+use axum::{Extension, Json, Router, routing::get};
+use axum_anyhow::ApiResult;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::collections::HashMap;
+
+/// Status of tasks without an explicit status.
+const DEFAULT_STATUS: &str = "Not Started";
+/// Order in which status groups are returned. Other statuses follow, sorted by name.
+const STATUS_ORDER: &[&str] = &["In Progress", "Blocked", "Not Started", "Done"];
+
+pub(super) fn router() -> Router {
+    Router::new().route("/", get(my_work_handler))
+}
+
+/// The tasks assigned to a user across all of their projects.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MyWork {
+    pub(crate) groups: Vec<StatusGroup>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StatusGroup {
+    pub(crate) status: String,
+    /// Sorted by deadline. Tasks without a deadline come last.
+    pub(crate) tasks: Vec<WorkTask>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WorkTask {
+    pub(crate) project_id: ProjectId,
+    pub(crate) project_name: String,
+    pub(crate) task: Task,
+}
+
+#[tracing::instrument(skip(user, pool, collab))]
+async fn my_work_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(collab): Extension<Collab>,
+) -> ApiResult<Json<MyWork>> {
+    let work = my_work(&collab, &user, pool).await?;
+    Ok(Json(work))
+}
+
+/// Collect the non-archived tasks assigned to the user in any of their non-deleted projects.
+pub(crate) async fn my_work(collab: &Collab, user: &User, pool: &PgPool) -> Result<MyWork> {
+    let projects: HashMap<ProjectId, Project> = list_projects(&user.email, pool)
+        .await?
+        .into_iter()
+        .filter(|project| project.deleted_on.is_none())
+        .filter(|project| {
+            user.scope
+                .as_ref()
+                .is_none_or(|scope| scope.allows(&project.project_id))
+        })
+        .map(|project| (project.project_id.clone(), project))
+        .collect();
+    let project_ids = projects.keys().cloned().collect::<Vec<_>>();
+    let graphs = collab.get_graphs(&project_ids).await?;
+
+    let mut groups: HashMap<String, Vec<WorkTask>> = HashMap::new();
+    for (project_id, graph) in graphs {
+        let project_name = &projects[&project_id].name;
+        for task in graph.into_values() {
+            if task.assignee.as_deref() != Some(user.email.as_str())
+                || task.archived.unwrap_or(false)
+            {
+                continue;
+            }
+            let status = task
+                .status
+                .clone()
+                .unwrap_or_else(|| DEFAULT_STATUS.to_string());
+            groups.entry(status).or_default().push(WorkTask {
+                project_id: project_id.clone(),
+                project_name: project_name.clone(),
+                task,
+            });
+        }
+    }
+
+    let mut groups = groups
+        .into_iter()
+        .map(|(status, mut tasks)| {
+            tasks.sort_by(|a, b| {
+                a.task
+                    .deadline
+                    .is_none()
+                    .cmp(&b.task.deadline.is_none())
+                    .then(a.task.deadline.cmp(&b.task.deadline))
+                    .then(a.project_name.cmp(&b.project_name))
+                    .then(a.project_id.cmp(&b.project_id))
+                    .then(a.task.num.len().cmp(&b.task.num.len()))
+                    .then(a.task.num.cmp(&b.task.num))
+            });
+            StatusGroup { status, tasks }
+        })
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| {
+        status_rank(&a.status)
+            .cmp(&status_rank(&b.status))
+            .then(a.status.cmp(&b.status))
+    });
+    Ok(MyWork { groups })
+}
+
+fn status_rank(status: &str) -> usize {
+    STATUS_ORDER
+        .iter()
+        .position(|s| *s == status)
+        .unwrap_or(STATUS_ORDER.len())
+}
@@ -4,14 +4,20 @@ use similar::{Algorithm, capture_diff_slices};
 use std::collections::{HashMap, HashSet};
 use yrs::{
     Any, Array, ArrayRef, DeepObservable, Doc, GetString, Map, MapRef, Observable, Origin, Out,
-    ReadTxn, Subscription, Text, TextRef, Transact, TransactionAcqError, TransactionMut,
-    UpdateEvent,
+    ReadTxn, StateVector, Subscription, Text, TextRef, Transact, TransactionAcqError,
+    TransactionMut, Update, UpdateEvent,
     types::{Events, map::MapEvent},
+    updates::decoder::Decode,
 };
 
 // Keep this in sync with the corresponding list in
 // frontend/yproxy.ts
-const MANAGED_KINDS: &[&str] = &["github", "github_pr"];
+pub(crate) const MANAGED_KINDS: &[&str] =
+    &["github", "github_pr", "github_issue", "gitlab", "gitlab_mr"];
+// Keep these in sync with the corresponding types in
+// frontend/yproxy.ts
+pub(crate) const STATUSES: &[&str] = &["Not Started", "Ready", "In Progress", "Done", "Blocked"];
+pub(crate) const ESTIMATES: &[i64] = &[1, 2, 3, 5, 8, 13, 20];
 
 pub(crate) struct YDocProxy {
     doc: Doc,
@@ -52,6 +58,7 @@ impl YDocProxy {
         y_task.set_name(txn, &task.name);
         y_task.set_desc(txn, task.desc.as_deref());
         y_task.set_children(txn, &task.children);
+        y_task.set_blocked_by(txn, &task.blocked_by);
         y_task.set_assignee(txn, task.assignee.as_deref());
         y_task.set_reporter(txn, task.reporter.as_deref());
         y_task.set_status(txn, task.status.as_deref());
@@ -64,6 +71,10 @@ impl YDocProxy {
         y_task
     }
 
+    pub fn remove(&self, txn: &mut yrs::TransactionMut, id: &str) {
+        self.graph.remove(txn, id);
+    }
+
     pub fn get<T: ReadTxn>(&self, txn: &T, id: &str) -> Result<YTaskProxy> {
         let Some(y_task) = self.graph.get(txn, id) else {
             return Err(anyhow!("task is missing: {id}"));
@@ -118,6 +129,20 @@ impl YDocProxy {
         self.graph.observe_deep(f)
     }
 
+    /// Copy the doc, sharing its client ID, so changes made to the copy can be
+    /// applied back to this doc as if they were made here.
+    pub fn fork(&self) -> Result<Self> {
+        let update = self
+            .doc
+            .transact()
+            .encode_state_as_update_v2(&StateVector::default());
+        let doc = Doc::with_client_id(self.doc.client_id());
+        let graph = doc.get_or_insert_map("graph");
+        doc.transact_mut()
+            .apply_update(Update::decode_v2(&update)?)?;
+        Ok(YDocProxy { doc, graph })
+    }
+
     pub fn transact(&self) -> yrs::Transaction<'_> {
         self.doc.transact()
     }
@@ -126,6 +151,286 @@ impl YDocProxy {
         self.doc.transact_mut_with(origin)
     }
 
+    pub fn contains<T: ReadTxn>(&self, txn: &T, id: &str) -> bool {
+        self.graph.contains_key(txn, id)
+    }
+
+    /// Returns the IDs of the tasks linking to the given task as a child.
+    pub fn parents<T: ReadTxn>(&self, txn: &T, id: &str) -> Result<Vec<String>> {
+        let mut parents = vec![];
+        for task in self.tasks(txn)? {
+            if task.get_children(txn)?.iter().any(|child| child == id) {
+                parents.push(task.get_id(txn)?);
+            }
+        }
+        Ok(parents)
+    }
+
+    /// Whether linking `child` under `parent` would create a cycle.
+    fn has_cycle<T: ReadTxn>(&self, txn: &T, parent: &str, child: &str) -> Result<bool> {
+        let mut stack = vec![child.to_string()];
+        let mut visited = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if id == parent {
+                return Ok(true);
+            }
+            if visited.insert(id.clone()) {
+                stack.extend(self.get(txn, &id)?.get_children(txn)?);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Determines if a task can be linked to a parent task.
+    pub fn can_link<T: ReadTxn>(&self, txn: &T, id: &str, parent: &str) -> Result<bool> {
+        let parent_task = self.get(txn, parent)?;
+        Ok(!self.has_cycle(txn, parent, id)?
+            && !parent_task.get_children(txn)?.iter().any(|c| c == id)
+            && !parent_task.is_managed(txn)?)
+    }
+
+    /// Determines if a task can be unlinked from a parent task.
+    pub fn can_unlink<T: ReadTxn>(&self, txn: &T, id: &str, parent: &str) -> Result<bool> {
+        Ok(!self.is_canonical_managed_link(txn, id, parent)?)
+    }
+
+    /// Determines if the given task is the canonical plugin task managed by a plugin,
+    /// as opposed to a link to the canonical task or container.
+    ///
+    /// Keep this in sync with isCanonicalManagedLink in frontend/koso.svelte.ts
+    pub fn is_canonical_managed_link<T: ReadTxn>(
+        &self,
+        txn: &T,
+        id: &str,
+        parent: &str,
+    ) -> Result<bool> {
+        if id == "root" {
+            return Ok(true);
+        }
+        let task = self.get(txn, id)?;
+        if !task.is_managed(txn)? {
+            return Ok(false);
+        }
+        let kind = task.get_kind(txn)?.unwrap_or_default();
+        // Is an immediate child of a plugin container OR is a plugin container.
+        if kind == parent {
+            return Ok(true);
+        }
+        if let Some(sub_kind) = kind.strip_prefix(&format!("{parent}_"))
+            && !sub_kind.contains('_')
+        {
+            return Ok(true);
+        }
+        // Is a top-level plugin container under root.
+        Ok(!kind.contains('_') && parent == "root")
+    }
+
+    /// Links a task to a parent task, at the given offset or, if absent,
+    /// after In Progress peers for In Progress tasks and before Done peers otherwise.
+    pub fn link(
+        &self,
+        txn: &mut TransactionMut,
+        id: &str,
+        parent: &str,
+        offset: Option<usize>,
+    ) -> Result<()> {
+        if !self.can_link(txn, id, parent)? {
+            return Err(anyhow!("Cannot link {id} to {parent}"));
+        }
+        let parent_task = self.get(txn, parent)?;
+        let mut children = parent_task.get_children(txn)?;
+        let offset = match offset {
+            Some(offset) => offset.min(children.len()),
+            None => self.best_link_offset(txn, id, &children)?,
+        };
+        children.insert(offset, id.to_string());
+        parent_task.set_children(txn, &children);
+        Ok(())
+    }
+
+    fn best_link_offset<T: ReadTxn>(&self, txn: &T, id: &str, peers: &[String]) -> Result<usize> {
+        let status = |id: &str| -> Result<Option<String>> { self.get(txn, id)?.get_status(txn) };
+        if status(id)?.as_deref() == Some("In Progress") {
+            for (i, peer) in peers.iter().enumerate() {
+                if status(peer)?.as_deref() != Some("In Progress") {
+                    return Ok(i);
+                }
+            }
+            return Ok(peers.len());
+        }
+        for (i, peer) in peers.iter().enumerate().rev() {
+            if status(peer)?.as_deref() != Some("Done") {
+                return Ok(i + 1);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Unlinks a task from a parent task. Note that this may orphan the task.
+    /// To safely remove a task, use [`YDocProxy::delete_task`] instead.
+    pub fn unlink(&self, txn: &mut TransactionMut, id: &str, parent: &str) -> Result<()> {
+        if !self.can_unlink(txn, id, parent)? {
+            return Err(anyhow!("Cannot unlink {id} from {parent}"));
+        }
+        let parent_task = self.get(txn, parent)?;
+        let mut children = parent_task.get_children(txn)?;
+        let len = children.len();
+        children.retain(|child| child != id);
+        if children.len() == len {
+            return Err(anyhow!("Task {id} is not a child of {parent}"));
+        }
+        parent_task.set_children(txn, &children);
+        Ok(())
+    }
+
+    /// Moves the given task from one parent to another.
+    pub fn move_task(
+        &self,
+        txn: &mut TransactionMut,
+        id: &str,
+        src: &str,
+        dest: &str,
+        offset: Option<usize>,
+    ) -> Result<()> {
+        self.unlink(txn, id, src)?;
+        self.link(txn, id, dest, offset)
+    }
+
+    /// Whether making `id` depend on `blocker` would leave tasks waiting on each other.
+    /// A task waits on the tasks blocking it and, like a rollup, on its children.
+    fn has_dependency_cycle<T: ReadTxn>(&self, txn: &T, id: &str, blocker: &str) -> Result<bool> {
+        let mut stack = vec![blocker.to_string()];
+        let mut visited = HashSet::new();
+        while let Some(next) = stack.pop() {
+            if next == id {
+                return Ok(true);
+            }
+            if visited.insert(next.clone()) && self.contains(txn, &next) {
+                let task = self.get(txn, &next)?;
+                stack.extend(task.get_children(txn)?);
+                stack.extend(task.get_blocked_by(txn)?);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Determines if a task can be made to depend on another, blocking task.
+    pub fn can_add_dependency<T: ReadTxn>(&self, txn: &T, id: &str, blocker: &str) -> Result<bool> {
+        Ok(id != "root"
+            && blocker != "root"
+            && !self
+                .get(txn, id)?
+                .get_blocked_by(txn)?
+                .iter()
+                .any(|b| b == blocker)
+            && !self.has_dependency_cycle(txn, id, blocker)?)
+    }
+
+    /// Makes a task depend on another, blocking task that must be done first.
+    pub fn add_dependency(&self, txn: &mut TransactionMut, id: &str, blocker: &str) -> Result<()> {
+        if !self.can_add_dependency(txn, id, blocker)? {
+            return Err(anyhow!("Cannot make {id} depend on {blocker}"));
+        }
+        let task = self.get(txn, id)?;
+        let mut blocked_by = task.get_blocked_by(txn)?;
+        blocked_by.push(blocker.to_string());
+        task.set_blocked_by(txn, &blocked_by);
+        Ok(())
+    }
+
+    /// Removes a task's dependency on a blocking task.
+    /// Returns false, leaving the task unchanged, if it didn't depend on the blocker.
+    pub fn remove_dependency(
+        &self,
+        txn: &mut TransactionMut,
+        id: &str,
+        blocker: &str,
+    ) -> Result<bool> {
+        let task = self.get(txn, id)?;
+        let blocked_by = task.get_blocked_by(txn)?;
+        if !blocked_by.iter().any(|b| b == blocker) {
+            return Ok(false);
+        }
+        let blocked_by: Vec<String> = blocked_by.into_iter().filter(|b| b != blocker).collect();
+        task.set_blocked_by(txn, &blocked_by);
+        Ok(true)
+    }
+
+    /// Determines if the given task can be deleted from all of its parents.
+    pub fn can_delete_task<T: ReadTxn>(&self, txn: &T, id: &str) -> Result<bool> {
+        for parent in self.parents(txn, id)? {
+            if !self.can_unlink(txn, id, &parent)? {
+                return Ok(false);
+            }
+        }
+        Ok(id != "root")
+    }
+
+    /// Deletes the given task and any descendants orphaned as a result,
+    /// returning the IDs of the deleted tasks.
+    ///
+    /// Keep this in sync with deleteTask in frontend/koso.svelte.ts
+    pub fn delete_task(&self, txn: &mut TransactionMut, id: &str) -> Result<Vec<String>> {
+        if !self.can_delete_task(txn, id)? {
+            return Err(anyhow!("Cannot delete task {id}"));
+        }
+
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        for task in self.tasks(txn)? {
+            let parent = task.get_id(txn)?;
+            for child in task.get_children(txn)? {
+                parents.entry(child).or_default().push(parent.clone());
+            }
+        }
+
+        // Collect all task IDs in the sub-tree.
+        let mut subtree = HashSet::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(task_id) = stack.pop() {
+            if subtree.insert(task_id.clone()) {
+                stack.extend(self.get(txn, &task_id)?.get_children(txn)?);
+            }
+        }
+
+        // Find the tasks that will become orphans, those whose parents are all in the sub-tree.
+        let mut orphans = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(task_id) = stack.pop() {
+            if !visited.insert(task_id.clone()) {
+                continue;
+            }
+            let linked_elsewhere = task_id != id
+                && parents
+                    .get(&task_id)
+                    .is_some_and(|ps| ps.iter().any(|p| !subtree.contains(p)));
+            if linked_elsewhere {
+                continue;
+            }
+            stack.extend(self.get(txn, &task_id)?.get_children(txn)?);
+            orphans.push(task_id);
+        }
+
+        for parent in parents.get(id).cloned().unwrap_or_default() {
+            self.unlink(txn, id, &parent)?;
+        }
+        for task_id in &orphans {
+            self.remove(txn, task_id);
+        }
+        // Deleted tasks no longer block anything.
+        for task in self.tasks(txn)? {
+            let blocked_by = task.get_blocked_by(txn)?;
+            if blocked_by.iter().any(|b| orphans.contains(b)) {
+                let blocked_by: Vec<String> = blocked_by
+                    .into_iter()
+                    .filter(|b| !orphans.contains(b))
+                    .collect();
+                task.set_blocked_by(txn, &blocked_by);
+            }
+        }
+        Ok(orphans)
+    }
+
     /// Returns the next available task number. i.e max(num)+1
     pub fn next_num<T: ReadTxn>(&self, txn: &T) -> Result<u64> {
         let mut max_num = 0;
@@ -155,6 +460,7 @@ impl YTaskProxy {
             name: self.get_name(txn)?,
             desc: self.get_desc(txn)?,
             children: self.get_children(txn)?,
+            blocked_by: self.get_blocked_by(txn)?,
             assignee: self.get_assignee(txn)?,
             reporter: self.get_reporter(txn)?,
             status: self.get_status(txn)?,
@@ -258,36 +564,54 @@ impl YTaskProxy {
     }
 
     pub fn get_children<T: ReadTxn>(&self, txn: &T) -> Result<Vec<String>> {
-        let Some(y_children) = self.y_task.get(txn, "children") else {
+        self.get_ids(txn, "children")
+    }
+
+    pub fn set_children(&self, txn: &mut TransactionMut, new_children: &[String]) {
+        self.set_ids(txn, "children", new_children);
+    }
+
+    pub fn get_blocked_by<T: ReadTxn>(&self, txn: &T) -> Result<Vec<String>> {
+        self.get_ids(txn, "blockedBy")
+    }
+
+    pub fn set_blocked_by(&self, txn: &mut TransactionMut, new_blocked_by: &[String]) {
+        self.set_ids(txn, "blockedBy", new_blocked_by);
+    }
+
+    /// Reads an array of task IDs, like children.
+    fn get_ids<T: ReadTxn>(&self, txn: &T, field: &str) -> Result<Vec<String>> {
+        let Some(y_ids) = self.y_task.get(txn, field) else {
             return Ok(Vec::new());
         };
-        let Out::YArray(y_children) = y_children else {
-            return Err(anyhow!("invalid field: children: {y_children}"));
+        let Out::YArray(y_ids) = y_ids else {
+            return Err(anyhow!("invalid field: {field}: {y_ids}"));
         };
-        y_children
+        y_ids
             .iter(txn)
             .map(|item| match item {
                 Out::Any(Any::String(s)) => Ok(s.to_string()),
-                e => Err(anyhow!("invalid child: {e}")),
+                e => Err(anyhow!("invalid {field} item: {e}")),
             })
             .collect()
     }
 
-    pub fn set_children(&self, txn: &mut TransactionMut, new_children: &[String]) {
-        let y_children: ArrayRef = self.y_task.get_or_init(txn, "children");
+    /// Updates an array of task IDs, like children, with minimal edits.
+    fn set_ids(&self, txn: &mut TransactionMut, field: &str, new_ids: &[String]) {
+        let y_ids: ArrayRef = self.y_task.get_or_init(txn, field);
 
-        let old_children = match self.get_children(txn) {
+        let old_ids = match self.get_ids(txn, field) {
             Ok(c) => c,
             Err(e) => {
-                tracing::warn!("invalid children, clobbering children: {e:?}");
-                y_children.remove_range(txn, 0, y_children.len(txn));
-                y_children.insert_range(txn, 0, new_children.to_vec());
+                tracing::warn!("invalid {field}, clobbering {field}: {e:?}");
+                y_ids.remove_range(txn, 0, y_ids.len(txn));
+                y_ids.insert_range(txn, 0, new_ids.to_vec());
                 return;
             }
         };
 
-        if old_children != *new_children {
-            let ops = capture_diff_slices(Algorithm::Myers, &old_children, new_children)
+        if old_ids != *new_ids {
+            let ops = capture_diff_slices(Algorithm::Myers, &old_ids, new_ids)
                 .into_iter()
                 .rev()
                 .collect::<Vec<_>>();
@@ -297,17 +621,17 @@ impl YTaskProxy {
                     similar::DiffOp::Delete {
                         old_index, old_len, ..
                     } => {
-                        y_children.remove_range(txn, old_index as u32, old_len as u32);
+                        y_ids.remove_range(txn, old_index as u32, old_len as u32);
                     }
                     similar::DiffOp::Insert {
                         old_index,
                         new_index,
                         new_len,
                     } => {
-                        y_children.insert_range(
+                        y_ids.insert_range(
                             txn,
                             old_index as u32,
-                            new_children[new_index..(new_index + new_len)].to_vec(),
+                            new_ids[new_index..(new_index + new_len)].to_vec(),
                         );
                     }
                     similar::DiffOp::Replace {
@@ -316,11 +640,11 @@ impl YTaskProxy {
                         new_index,
                         new_len,
                     } => {
-                        y_children.remove_range(txn, old_index as u32, old_len as u32);
-                        y_children.insert_range(
+                        y_ids.remove_range(txn, old_index as u32, old_len as u32);
+                        y_ids.insert_range(
                             txn,
                             old_index as u32,
-                            new_children[new_index..(new_index + new_len)].to_vec(),
+                            new_ids[new_index..(new_index + new_len)].to_vec(),
                         );
                     }
                     _ => (),
@@ -488,6 +812,201 @@ mod tests {
         }
     }
 
+    fn graph_doc(tasks: &[(&str, &[&str])]) -> YDocProxy {
+        let ydoc = YDocProxy::new();
+        let mut txn = ydoc.transact_mut_with(origin());
+        for (i, (id, children)) in tasks.iter().enumerate() {
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: id.to_string(),
+                    num: i.to_string(),
+                    name: id.to_string(),
+                    children: children.iter().map(|c| c.to_string()).collect(),
+                    kind: id.starts_with("pr").then(|| "github_pr".to_string()),
+                    ..Task::default()
+                },
+            );
+        }
+        drop(txn);
+        ydoc
+    }
+
+    fn children(ydoc: &YDocProxy, id: &str) -> Vec<String> {
+        let txn = ydoc.transact();
+        ydoc.get(&txn, id).unwrap().get_children(&txn).unwrap()
+    }
+
+    #[test]
+    fn link_and_unlink_succeeds() {
+        let ydoc = graph_doc(&[("root", &["a", "b"]), ("a", &["c"]), ("b", &[]), ("c", &[])]);
+        let mut txn = ydoc.transact_mut_with(origin());
+
+        assert_eq!(ydoc.parents(&txn, "c").unwrap(), vec!["a"]);
+        // Cycles and duplicate links are rejected.
+        assert!(!ydoc.can_link(&txn, "a", "c").unwrap());
+        assert!(!ydoc.can_link(&txn, "a", "a").unwrap());
+        assert!(!ydoc.can_link(&txn, "c", "a").unwrap());
+        assert!(ydoc.can_link(&txn, "c", "b").unwrap());
+
+        ydoc.link(&mut txn, "c", "b", None).unwrap();
+        let mut parents = ydoc.parents(&txn, "c").unwrap();
+        parents.sort();
+        assert_eq!(parents, vec!["a", "b"]);
+        assert!(ydoc.link(&mut txn, "c", "b", None).is_err());
+
+        ydoc.unlink(&mut txn, "c", "a").unwrap();
+        assert_eq!(ydoc.parents(&txn, "c").unwrap(), vec!["b"]);
+        assert!(ydoc.unlink(&mut txn, "c", "a").is_err());
+        drop(txn);
+        assert_eq!(children(&ydoc, "a"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn link_orders_by_status() {
+        let ydoc = graph_doc(&[
+            ("root", &["a", "b", "c"]),
+            ("a", &[]),
+            ("b", &[]),
+            ("c", &[]),
+            ("d", &[]),
+            ("e", &[]),
+        ]);
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.get(&txn, "a")
+            .unwrap()
+            .set_status(&mut txn, Some("In Progress"));
+        ydoc.get(&txn, "c")
+            .unwrap()
+            .set_status(&mut txn, Some("Done"));
+        ydoc.get(&txn, "e")
+            .unwrap()
+            .set_status(&mut txn, Some("In Progress"));
+
+        ydoc.link(&mut txn, "d", "root", None).unwrap();
+        ydoc.link(&mut txn, "e", "root", None).unwrap();
+        drop(txn);
+        assert_eq!(children(&ydoc, "root"), vec!["a", "e", "b", "d", "c"]);
+    }
+
+    #[test]
+    fn move_task_succeeds() {
+        let ydoc = graph_doc(&[
+            ("root", &["a", "b"]),
+            ("a", &["c"]),
+            ("b", &["d"]),
+            ("c", &[]),
+            ("d", &[]),
+        ]);
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.move_task(&mut txn, "c", "a", "b", Some(0)).unwrap();
+        drop(txn);
+        assert_eq!(children(&ydoc, "a"), Vec::<String>::new());
+        assert_eq!(children(&ydoc, "b"), vec!["c", "d"]);
+    }
+
+    #[test]
+    fn delete_task_deletes_orphans() {
+        let ydoc = graph_doc(&[
+            ("root", &["a", "b"]),
+            ("a", &["c", "d"]),
+            ("b", &["d"]),
+            ("c", &["e"]),
+            ("d", &[]),
+            ("e", &[]),
+        ]);
+        let mut txn = ydoc.transact_mut_with(origin());
+        assert!(!ydoc.can_delete_task(&txn, "root").unwrap());
+        let mut deleted = ydoc.delete_task(&mut txn, "a").unwrap();
+        deleted.sort();
+        // d is also linked under b.
+        assert_eq!(deleted, vec!["a", "c", "e"]);
+        assert!(!ydoc.contains(&txn, "c"));
+        assert!(ydoc.contains(&txn, "d"));
+        drop(txn);
+        assert_eq!(children(&ydoc, "root"), vec!["b"]);
+    }
+
+    #[test]
+    fn add_and_remove_dependency_succeeds() {
+        let ydoc = graph_doc(&[("root", &["a", "b"]), ("a", &["c"]), ("b", &[]), ("c", &[])]);
+        let mut txn = ydoc.transact_mut_with(origin());
+        assert!(ydoc.can_add_dependency(&txn, "b", "c").unwrap());
+        ydoc.add_dependency(&mut txn, "b", "c").unwrap();
+        assert_eq!(
+            ydoc.get(&txn, "b").unwrap().get_blocked_by(&txn).unwrap(),
+            vec!["c"]
+        );
+        // Duplicates, the root and tasks waiting on each other are rejected.
+        assert!(!ydoc.can_add_dependency(&txn, "b", "c").unwrap());
+        assert!(!ydoc.can_add_dependency(&txn, "b", "root").unwrap());
+        assert!(!ydoc.can_add_dependency(&txn, "b", "b").unwrap());
+        assert!(!ydoc.can_add_dependency(&txn, "c", "b").unwrap());
+        // a waits on its child c, which waits on nothing, but c can't wait on a.
+        assert!(!ydoc.can_add_dependency(&txn, "c", "a").unwrap());
+        assert!(ydoc.add_dependency(&mut txn, "c", "a").is_err());
+        assert!(ydoc.can_add_dependency(&txn, "a", "b").unwrap());
+
+        assert!(ydoc.remove_dependency(&mut txn, "b", "c").unwrap());
+        assert!(!ydoc.remove_dependency(&mut txn, "b", "c").unwrap());
+        assert!(
+            ydoc.get(&txn, "b")
+                .unwrap()
+                .get_blocked_by(&txn)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn delete_task_removes_dependencies() {
+        let ydoc = graph_doc(&[("root", &["a", "b"]), ("a", &[]), ("b", &[])]);
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.add_dependency(&mut txn, "b", "a").unwrap();
+        ydoc.delete_task(&mut txn, "a").unwrap();
+        let b = ydoc.get(&txn, "b").unwrap().to_task(&txn).unwrap();
+        assert!(b.blocked_by.is_empty());
+    }
+
+    #[test]
+    fn canonical_managed_links_are_protected() {
+        let ydoc = graph_doc(&[
+            ("root", &["github", "pr1"]),
+            ("github", &["github_pr"]),
+            ("github_pr", &["pr1"]),
+            ("pr1", &[]),
+        ]);
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.get(&txn, "github")
+            .unwrap()
+            .set_kind(&mut txn, Some("github"));
+        ydoc.get(&txn, "github_pr")
+            .unwrap()
+            .set_kind(&mut txn, Some("github_pr"));
+
+        assert!(
+            ydoc.is_canonical_managed_link(&txn, "github", "root")
+                .unwrap()
+        );
+        assert!(
+            ydoc.is_canonical_managed_link(&txn, "pr1", "github_pr")
+                .unwrap()
+        );
+        assert!(!ydoc.is_canonical_managed_link(&txn, "pr1", "root").unwrap());
+        assert!(ydoc.can_unlink(&txn, "pr1", "root").unwrap());
+        assert!(!ydoc.can_delete_task(&txn, "pr1").unwrap());
+        // Users can't link tasks under plugin managed tasks.
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "x".to_string(),
+                num: "9".to_string(),
+                ..Task::default()
+            },
+        );
+        assert!(!ydoc.can_link(&txn, "x", "pr1").unwrap());
+    }
+
     fn origin() -> Origin {
         YOrigin {
             who: "set_and_get_task_succeeds".to_string(),
@@ -8,6 +8,7 @@ mod debug;
 mod healthz;
 mod mcp;
 mod metrics_server;
+mod net;
 mod notifiers;
 mod oauth;
 mod plugins;
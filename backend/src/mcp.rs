@@ -1,29 +1,39 @@
 use crate::{
     api::{
         RmcpErrorData,
+        anthropic::{
+            BREAKDOWN_PROMPT, ITERATION_PROMPT, SUMMARIZE_PROMPT, breakdown_context,
+            serialize_tasks, subtree_ids,
+        },
         collab::{
-            Collab,
+            Collab, LocalClient,
             projects_state::DocBox,
+            task_changes::TaskChanges,
             txn_origin::{Actor, YOrigin},
         },
         google::User,
         invalid_request,
-        model::{Project, Task},
-        projects::{fetch_project, list_projects},
-        resource_not_found, verify_project_access,
+        model::{Project, ProjectId, ProjectRole, Task},
+        projects::{fetch_project, list_project_users, list_projects},
+        resource_not_found,
+        search::{self, SearchQuery, TaskSearchResult},
+        verify_project_access,
+        work::my_work,
+        yproxy::{ESTIMATES, STATUSES, YDocProxy, YTaskProxy},
     },
     oauth,
 };
 use anyhow::{Context as _, Result};
 use axum::{Extension, Router, extract::FromRequestParts, middleware};
 use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::Utc;
 use regex::Regex;
 use rmcp::{
     ErrorData, RoleServer,
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
     model::*,
     schemars::{self},
-    service::RequestContext,
+    service::{Peer, RequestContext},
     tool, tool_handler, tool_router,
     transport::{
         StreamableHttpService,
@@ -31,13 +41,31 @@ use rmcp::{
     },
 };
 use sqlx::PgPool;
-use std::{cell::LazyCell, sync::Arc};
-use tokio_util::sync::CancellationToken;
+use std::{cell::LazyCell, collections::HashMap, sync::Arc};
+use tokio::sync::{
+    Mutex,
+    broadcast::{
+        self,
+        error::{RecvError, TryRecvError},
+    },
+};
+use tokio_util::sync::{CancellationToken, DropGuard};
 use url::Url;
 use uuid::Uuid;
+use yrs::{ReadTxn, Transaction, TransactionMut, Update, updates::decoder::Decode as _};
 
 const PROMPT_CREATE_TASK: &str = r#"You are a Koso project management AI assistant. Your task is to return a tool call that invokes the Koso `create_task` tool with the `project_id` and `name` arguments. Render the output of tool call in a pleasing, human readable format, showing the creator of the task the new task. Include a perma-link to the task in koso, of the form: https://koso.app/projects/{project_id}?taskId={task_id}"#;
 
+const PROMPT_BREAK_DOWN_TASK: &str = r#"Then, after confirming the subtasks with the user, invoke the Koso `create_task` tool once per subtask with the `project_id` and the subtask as the `name` argument. Then invoke the Koso `move_task` tool once per created task to move it under the task, with the task's `task_id` as the `parent_id` argument."#;
+
+const PROMPT_STATUS_UPDATE: &str = r#"Write a brief status update in Markdown for the stakeholders of the following work. Cover what was completed, what is in progress and by whom, what is blocked and what comes next. Keep it under 200 words."#;
+
+const PROMPT_TRIAGE: &str = r#"You are a Koso project management AI assistant. The following JSON document lists the unassigned, incomplete tasks of a Koso project. For each task, propose an assignee from the collaborators and, when missing, an estimate in points (1, 2, 3, 5, 8, 13 or 20), briefly explaining why. Once the user confirms, invoke the Koso `assign_task` and `update_task` tools to apply the proposals."#;
+
+const MY_WORK_URI: &str = "work:///me";
+/// The most values returned by a completion, per the MCP spec.
+const MAX_COMPLETIONS: usize = 100;
+
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CreateTaskParam {
@@ -47,6 +75,176 @@ struct CreateTaskParam {
     name: String,
 }
 
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct SearchTasksParam {
+    #[schemars(description = "full-text query matched against task names and descriptions")]
+    query: Option<String>,
+    #[schemars(description = "only search the Koso project with this ID")]
+    project_id: Option<String>,
+    #[schemars(description = "email of the assignee")]
+    assignee: Option<String>,
+    #[schemars(
+        description = "comma separated list of statuses, e.g. \"Not Started,In Progress,Blocked\""
+    )]
+    status: Option<String>,
+    #[schemars(description = "the kind of task, e.g. \"github_pr\"")]
+    kind: Option<String>,
+    #[schemars(description = "earliest deadline, in milliseconds since the epoch")]
+    deadline_after: Option<i64>,
+    #[schemars(description = "latest deadline, in milliseconds since the epoch")]
+    deadline_before: Option<i64>,
+    #[schemars(description = "whether to only return archived or non-archived tasks")]
+    archived: Option<bool>,
+    #[schemars(description = "maximum number of tasks to return, at most 1000")]
+    limit: Option<i64>,
+}
+
+/// Distinguishes an explicit null, which clears a field, from an absent field.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct TaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task")]
+    task_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ListTasksParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "only list the children of the task with this ID")]
+    parent_id: Option<String>,
+    #[schemars(description = "email of the assignee")]
+    assignee: Option<String>,
+    #[schemars(
+        description = "comma separated list of statuses, e.g. \"Not Started,In Progress,Blocked\""
+    )]
+    status: Option<String>,
+    #[schemars(description = "the kind of task, e.g. \"Task\", \"Rollup\" or \"github_pr\"")]
+    kind: Option<String>,
+    #[schemars(description = "whether to only return archived or non-archived tasks")]
+    archived: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct UpdateTaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task")]
+    task_id: String,
+    #[schemars(description = "the new name of the task")]
+    name: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schemars(description = "the new description of the task, or null to clear it")]
+    description: Option<Option<String>>,
+    #[schemars(
+        description = "the new status of the task: \"Not Started\", \"Ready\", \"In Progress\", \"Blocked\" or \"Done\""
+    )]
+    status: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schemars(
+        description = "the new estimate of the task in points, one of 1, 2, 3, 5, 8, 13 or 20, or null to clear it"
+    )]
+    estimate: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schemars(
+        description = "the new deadline of the task, in milliseconds since the epoch, or null to clear it"
+    )]
+    deadline: Option<Option<i64>>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct AssignTaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task")]
+    task_id: String,
+    #[schemars(
+        description = "email of a collaborator on the project to assign, or null to unassign the task"
+    )]
+    assignee: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct MoveTaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task to move")]
+    task_id: String,
+    #[schemars(
+        description = "the ID of the task's current parent. Required when the task has more than one parent"
+    )]
+    from_parent_id: Option<String>,
+    #[schemars(description = "the ID of the new parent, or \"root\" for the top level")]
+    parent_id: String,
+    #[schemars(
+        description = "zero-based position among the new parent's children. Defaults to a position based on the task's status"
+    )]
+    position: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct LinkTaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task that must be done first")]
+    task_id: String,
+    #[schemars(description = "the ID of the task that depends on it")]
+    parent_id: String,
+    #[schemars(
+        description = "zero-based position among the parent's children. Defaults to a position based on the task's status"
+    )]
+    position: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct UnlinkTaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the linked task")]
+    task_id: String,
+    #[schemars(description = "the ID of the parent to unlink it from")]
+    parent_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct DependencyParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task that depends on the blocker")]
+    task_id: String,
+    #[schemars(description = "the ID of the task that must be done first")]
+    blocker_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveTaskParam {
+    #[schemars(description = "the ID of the Koso project")]
+    project_id: String,
+    #[schemars(description = "the ID of the task")]
+    task_id: String,
+    #[schemars(description = "false to unarchive the task. Defaults to true")]
+    archived: Option<bool>,
+}
+
 #[derive(Clone)]
 struct KosoTools {
     inner: Arc<Inner>,
@@ -56,13 +254,20 @@ struct KosoTools {
 struct Inner {
     collab: Collab,
     pool: &'static PgPool,
+    /// The session's resource subscriptions, keyed by URI.
+    /// Dropping a guard stops sending updates for the resource.
+    subscriptions: Mutex<HashMap<String, DropGuard>>,
 }
 
 #[tool_router]
 impl KosoTools {
     fn new(collab: Collab, pool: &'static PgPool) -> Self {
         Self {
-            inner: Arc::new(Inner { collab, pool }),
+            inner: Arc::new(Inner {
+                collab,
+                pool,
+                subscriptions: Mutex::new(HashMap::new()),
+            }),
             tool_router: Self::tool_router(),
         }
     }
@@ -92,9 +297,14 @@ impl KosoTools {
         request_id: String,
     ) -> Result<Content, RmcpErrorData> {
         let user = user_extension(&mut context).await?;
-        verify_project_access(self.inner.pool, &user, &request.project_id)
-            .await
-            .map_err(|e| e.into_error())?;
+        verify_project_access(
+            self.inner.pool,
+            &user,
+            &request.project_id,
+            ProjectRole::Editor,
+        )
+        .await
+        .map_err(|e| e.into_error())?;
 
         let client = self
             .inner
@@ -171,6 +381,757 @@ impl KosoTools {
         Ok(CallToolResult::success(projects))
     }
 
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "search_tasks",
+        description = "Search tasks across all of my Koso projects",
+        annotations(read_only_hint = true)
+    )]
+    async fn search_tasks(
+        &self,
+        Parameters(request): Parameters<SearchTasksParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tracing::Span::current().record("request_id", Uuid::new_v4().to_string());
+        Ok(self._search_tasks(request, context).await?)
+    }
+
+    async fn _search_tasks(
+        &self,
+        request: SearchTasksParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        let query = SearchQuery {
+            q: request.query,
+            project_id: request.project_id,
+            assignee: request.assignee,
+            status: request.status,
+            kind: request.kind,
+            deadline_after: request.deadline_after,
+            deadline_before: request.deadline_before,
+            archived: request.archived,
+            limit: request.limit,
+        };
+        let results = search::search_tasks(&self.inner.collab, &user, &query, self.inner.pool)
+            .await
+            .map_err(|e| e.into_error())?;
+        let tasks = results
+            .into_iter()
+            .map(Self::search_result_to_resource_content)
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to serialize tasks")?;
+        Ok(CallToolResult::success(tasks))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "get_task",
+        description = "Get a task in a Koso project, including the IDs of its children and parents",
+        annotations(read_only_hint = true)
+    )]
+    async fn get_task(
+        &self,
+        Parameters(request): Parameters<TaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tracing::Span::current().record("request_id", Uuid::new_v4().to_string());
+        Ok(self._get_task(request, context).await?)
+    }
+
+    async fn _get_task(
+        &self,
+        request: TaskParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        let (task, parents) = self
+            .read_doc(&request.project_id, &user, |doc, txn| {
+                let task = get_task(doc, txn, &request.task_id)?.to_task(txn)?;
+                let parents = doc.parents(txn, &request.task_id)?;
+                Ok((task, parents))
+            })
+            .await?;
+        Ok(CallToolResult::success(vec![
+            task_to_resource_content(&request.project_id, &task)?,
+            Content::text(format!("Parents: {}", parents.join(", "))),
+        ]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "list_tasks",
+        description = "List the tasks of a Koso project matching the given filters",
+        annotations(read_only_hint = true)
+    )]
+    async fn list_tasks(
+        &self,
+        Parameters(request): Parameters<ListTasksParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        tracing::Span::current().record("request_id", Uuid::new_v4().to_string());
+        Ok(self._list_tasks(request, context).await?)
+    }
+
+    async fn _list_tasks(
+        &self,
+        request: ListTasksParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        let statuses: Option<Vec<&str>> = request
+            .status
+            .as_deref()
+            .map(|s| s.split(',').map(str::trim).collect());
+        let mut tasks = self
+            .read_doc(&request.project_id, &user, |doc, txn| {
+                let ids = match &request.parent_id {
+                    Some(parent_id) => get_task(doc, txn, parent_id)?.get_children(txn)?,
+                    None => doc
+                        .tasks(txn)?
+                        .iter()
+                        .map(|task| task.get_id(txn))
+                        .collect::<Result<_>>()?,
+                };
+                let mut tasks = vec![];
+                for id in ids.iter().filter(|id| *id != "root") {
+                    let task = doc.get(txn, id)?.to_task(txn)?;
+                    let status = task.status.as_deref().unwrap_or("Not Started");
+                    if request
+                        .assignee
+                        .as_ref()
+                        .is_some_and(|a| task.assignee.as_ref() != Some(a))
+                        || statuses.as_ref().is_some_and(|s| !s.contains(&status))
+                        || request
+                            .kind
+                            .as_ref()
+                            .is_some_and(|k| task.kind.as_ref() != Some(k))
+                        || request
+                            .archived
+                            .is_some_and(|a| task.archived.unwrap_or(false) != a)
+                    {
+                        continue;
+                    }
+                    tasks.push(task);
+                }
+                Ok(tasks)
+            })
+            .await?;
+        // Keep the order of children, otherwise order by task number.
+        if request.parent_id.is_none() {
+            tasks.sort_by_key(|task| (task.num.len(), task.num.clone()));
+        }
+        let tasks = tasks
+            .iter()
+            .map(|task| task_to_resource_content(&request.project_id, task))
+            .collect::<Result<Vec<_>>>()
+            .context("Failed to serialize tasks")?;
+        Ok(CallToolResult::success(tasks))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "update_task",
+        description = "Update the name, description, status, estimate or deadline of a task in a Koso project. Omitted fields are left unchanged"
+    )]
+    async fn update_task(
+        &self,
+        Parameters(request): Parameters<UpdateTaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._update_task(request, context, request_id).await?)
+    }
+
+    async fn _update_task(
+        &self,
+        request: UpdateTaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        if let Some(Some(estimate)) = request.estimate
+            && !ESTIMATES.contains(&estimate)
+        {
+            return Err(invalid_request(
+                "invalid_estimate",
+                &format!("Estimate must be one of {ESTIMATES:?}"),
+            ));
+        }
+        if request.name.as_ref().is_some_and(|n| n.trim().is_empty()) {
+            return Err(invalid_request(
+                "invalid_name",
+                "Task name must not be empty",
+            ));
+        }
+
+        let user = user_extension(&mut context).await?;
+        let task = self
+            .edit_doc(
+                &request.project_id,
+                &user,
+                &context,
+                request_id,
+                |doc, txn| {
+                    let task = get_editable_task(doc, txn, &request.task_id)?;
+                    if let Some(status) = &request.status {
+                        check_status(doc, txn, &task, status)?;
+                    }
+
+                    if let Some(name) = &request.name {
+                        task.set_name(txn, name);
+                    }
+                    if let Some(desc) = &request.description {
+                        task.set_desc(txn, desc.as_deref());
+                    }
+                    if let Some(estimate) = request.estimate {
+                        task.set_estimate(txn, estimate);
+                    }
+                    if let Some(deadline) = request.deadline {
+                        task.set_deadline(txn, deadline);
+                    }
+                    if let Some(status) = &request.status {
+                        set_status(&task, txn, status, &user)?;
+                    }
+                    Ok(task.to_task(txn)?)
+                },
+            )
+            .await?;
+        Ok(CallToolResult::success(vec![task_to_resource_content(
+            &request.project_id,
+            &task,
+        )?]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "assign_task",
+        description = "Assign a task in a Koso project to a collaborator, or unassign it"
+    )]
+    async fn assign_task(
+        &self,
+        Parameters(request): Parameters<AssignTaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._assign_task(request, context, request_id).await?)
+    }
+
+    async fn _assign_task(
+        &self,
+        request: AssignTaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        if let Some(assignee) = &request.assignee {
+            verify_project_access(
+                self.inner.pool,
+                &user,
+                &request.project_id,
+                ProjectRole::Editor,
+            )
+            .await
+            .map_err(|e| e.into_error())?;
+            let users = list_project_users(self.inner.pool, &request.project_id).await?;
+            if !users.iter().any(|u| u.email == *assignee) {
+                return Err(invalid_request(
+                    "invalid_assignee",
+                    &format!("{assignee} is not a collaborator on the project"),
+                ));
+            }
+        }
+
+        let task = self
+            .edit_doc(
+                &request.project_id,
+                &user,
+                &context,
+                request_id,
+                |doc, txn| {
+                    let task = get_editable_task(doc, txn, &request.task_id)?;
+                    task.set_assignee(txn, request.assignee.as_deref());
+                    Ok(task.to_task(txn)?)
+                },
+            )
+            .await?;
+        Ok(CallToolResult::success(vec![task_to_resource_content(
+            &request.project_id,
+            &task,
+        )?]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "complete_task",
+        description = "Mark a task in a Koso project as done"
+    )]
+    async fn complete_task(
+        &self,
+        Parameters(request): Parameters<TaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self
+            ._update_task(
+                UpdateTaskParam {
+                    project_id: request.project_id,
+                    task_id: request.task_id,
+                    name: None,
+                    description: None,
+                    status: Some("Done".to_string()),
+                    estimate: None,
+                    deadline: None,
+                },
+                context,
+                request_id,
+            )
+            .await?)
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "move_task",
+        description = "Move a task in a Koso project, along with its children, under a different parent"
+    )]
+    async fn move_task(
+        &self,
+        Parameters(request): Parameters<MoveTaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._move_task(request, context, request_id).await?)
+    }
+
+    async fn _move_task(
+        &self,
+        request: MoveTaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        self.edit_doc(
+            &request.project_id,
+            &user,
+            &context,
+            request_id,
+            |doc, txn| {
+                get_task(doc, txn, &request.task_id)?;
+                get_task(doc, txn, &request.parent_id)?;
+                let parents = doc.parents(txn, &request.task_id)?;
+                let from = match &request.from_parent_id {
+                    Some(from) if parents.contains(from) => from.clone(),
+                    Some(from) => {
+                        return Err(invalid_request(
+                            "invalid_parent",
+                            &format!("Task {} is not a child of {from}", request.task_id),
+                        ));
+                    }
+                    None if parents.len() == 1 => parents[0].clone(),
+                    None => {
+                        return Err(invalid_request(
+                            "ambiguous_parent",
+                            &format!(
+                                "Task {} has parents {}. Specify fromParentId",
+                                request.task_id,
+                                parents.join(", ")
+                            ),
+                        ));
+                    }
+                };
+                if from == request.parent_id {
+                    let parent = doc.get(txn, &from)?;
+                    let mut children = parent.get_children(txn)?;
+                    children.retain(|c| *c != request.task_id);
+                    let position = request
+                        .position
+                        .unwrap_or(children.len())
+                        .min(children.len());
+                    children.insert(position, request.task_id.clone());
+                    parent.set_children(txn, &children);
+                    return Ok(());
+                }
+                if !doc.can_unlink(txn, &request.task_id, &from)?
+                    || !doc.can_link(txn, &request.task_id, &request.parent_id)?
+                {
+                    return Err(invalid_request(
+                        "invalid_move",
+                        &format!(
+                            "Task {} cannot be moved under {}",
+                            request.task_id, request.parent_id
+                        ),
+                    ));
+                }
+                Ok(doc.move_task(
+                    txn,
+                    &request.task_id,
+                    &from,
+                    &request.parent_id,
+                    request.position,
+                )?)
+            },
+        )
+        .await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Moved task {} under {}",
+            request.task_id, request.parent_id
+        ))]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "link_task",
+        description = "Link an existing task in a Koso project as a child of another task, making the parent depend on it. A task may have several parents"
+    )]
+    async fn link_task(
+        &self,
+        Parameters(request): Parameters<LinkTaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._link_task(request, context, request_id).await?)
+    }
+
+    async fn _link_task(
+        &self,
+        request: LinkTaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        self.edit_doc(&request.project_id, &user, &context, request_id, |doc, txn| {
+            get_task(doc, txn, &request.task_id)?;
+            get_task(doc, txn, &request.parent_id)?;
+            if !doc.can_link(txn, &request.task_id, &request.parent_id)? {
+                return Err(invalid_request(
+                    "invalid_link",
+                    &format!(
+                        "Task {} cannot be linked under {}. It's already linked, would create a cycle or the parent is managed by a plugin",
+                        request.task_id, request.parent_id
+                    ),
+                ));
+            }
+            Ok(doc.link(txn, &request.task_id, &request.parent_id, request.position)?)
+        })
+        .await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Linked task {} under {}",
+            request.task_id, request.parent_id
+        ))]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "unlink_task",
+        description = "Unlink a task in a Koso project from one of its parents. A task's last parent can't be unlinked, delete the task instead"
+    )]
+    async fn unlink_task(
+        &self,
+        Parameters(request): Parameters<UnlinkTaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._unlink_task(request, context, request_id).await?)
+    }
+
+    async fn _unlink_task(
+        &self,
+        request: UnlinkTaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        self.edit_doc(
+            &request.project_id,
+            &user,
+            &context,
+            request_id,
+            |doc, txn| {
+                get_task(doc, txn, &request.task_id)?;
+                let parents = doc.parents(txn, &request.task_id)?;
+                if !parents.contains(&request.parent_id) {
+                    return Err(invalid_request(
+                        "invalid_parent",
+                        &format!(
+                            "Task {} is not a child of {}",
+                            request.task_id, request.parent_id
+                        ),
+                    ));
+                }
+                if parents.len() == 1
+                    || !doc.can_unlink(txn, &request.task_id, &request.parent_id)?
+                {
+                    return Err(invalid_request(
+                        "invalid_unlink",
+                        &format!(
+                            "Task {} cannot be unlinked from {}",
+                            request.task_id, request.parent_id
+                        ),
+                    ));
+                }
+                Ok(doc.unlink(txn, &request.task_id, &request.parent_id)?)
+            },
+        )
+        .await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Unlinked task {} from {}",
+            request.task_id, request.parent_id
+        ))]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "add_dependency",
+        description = "Make a task in a Koso project depend on another task, which must be done before it can start. Unlike linking, the blocker stays where it is in the hierarchy"
+    )]
+    async fn add_dependency(
+        &self,
+        Parameters(request): Parameters<DependencyParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._add_dependency(request, context, request_id).await?)
+    }
+
+    async fn _add_dependency(
+        &self,
+        request: DependencyParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        self.edit_doc(&request.project_id, &user, &context, request_id, |doc, txn| {
+            get_editable_task(doc, txn, &request.task_id)?;
+            get_task(doc, txn, &request.blocker_id)?;
+            if !doc.can_add_dependency(txn, &request.task_id, &request.blocker_id)? {
+                return Err(invalid_request(
+                    "invalid_dependency",
+                    &format!(
+                        "Task {} cannot depend on {}. It already does, or the tasks would wait on each other",
+                        request.task_id, request.blocker_id
+                    ),
+                ));
+            }
+            Ok(doc.add_dependency(txn, &request.task_id, &request.blocker_id)?)
+        })
+        .await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Task {} now depends on {}",
+            request.task_id, request.blocker_id
+        ))]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "remove_dependency",
+        description = "Remove a task's dependency on another task in a Koso project"
+    )]
+    async fn remove_dependency(
+        &self,
+        Parameters(request): Parameters<DependencyParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self
+            ._remove_dependency(request, context, request_id)
+            .await?)
+    }
+
+    async fn _remove_dependency(
+        &self,
+        request: DependencyParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        self.edit_doc(
+            &request.project_id,
+            &user,
+            &context,
+            request_id,
+            |doc, txn| {
+                get_editable_task(doc, txn, &request.task_id)?;
+                if !doc.remove_dependency(txn, &request.task_id, &request.blocker_id)? {
+                    return Err(invalid_request(
+                        "invalid_dependency",
+                        &format!(
+                            "Task {} does not depend on {}",
+                            request.task_id, request.blocker_id
+                        ),
+                    ));
+                }
+                Ok(())
+            },
+        )
+        .await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Task {} no longer depends on {}",
+            request.task_id, request.blocker_id
+        ))]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "archive_task",
+        description = "Archive, or unarchive, a task in a Koso project. Archived tasks are hidden by default"
+    )]
+    async fn archive_task(
+        &self,
+        Parameters(request): Parameters<ArchiveTaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._archive_task(request, context, request_id).await?)
+    }
+
+    async fn _archive_task(
+        &self,
+        request: ArchiveTaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        let task = self
+            .edit_doc(
+                &request.project_id,
+                &user,
+                &context,
+                request_id,
+                |doc, txn| {
+                    let task = get_editable_task(doc, txn, &request.task_id)?;
+                    task.set_archived(txn, Some(request.archived.unwrap_or(true)));
+                    Ok(task.to_task(txn)?)
+                },
+            )
+            .await?;
+        Ok(CallToolResult::success(vec![task_to_resource_content(
+            &request.project_id,
+            &task,
+        )?]))
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    #[tool(
+        name = "delete_task",
+        description = "Delete a task from a Koso project, along with any of its children not linked elsewhere",
+        annotations(destructive_hint = true)
+    )]
+    async fn delete_task(
+        &self,
+        Parameters(request): Parameters<TaskParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let request_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("request_id", &request_id);
+        Ok(self._delete_task(request, context, request_id).await?)
+    }
+
+    async fn _delete_task(
+        &self,
+        request: TaskParam,
+        mut context: RequestContext<RoleServer>,
+        request_id: String,
+    ) -> Result<CallToolResult, RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        let deleted = self
+            .edit_doc(
+                &request.project_id,
+                &user,
+                &context,
+                request_id,
+                |doc, txn| {
+                    get_task(doc, txn, &request.task_id)?;
+                    if !doc.can_delete_task(txn, &request.task_id)? {
+                        return Err(invalid_request(
+                            "invalid_delete",
+                            &format!("Task {} cannot be deleted", request.task_id),
+                        ));
+                    }
+                    Ok(doc.delete_task(txn, &request.task_id)?)
+                },
+            )
+            .await?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Deleted tasks: {}",
+            deleted.join(", ")
+        ))]))
+    }
+
+    /// Read a project's doc on behalf of the user.
+    async fn read_doc<R>(
+        &self,
+        project_id: &ProjectId,
+        user: &User,
+        read: impl FnOnce(&YDocProxy, &Transaction) -> Result<R, RmcpErrorData>,
+    ) -> Result<R, RmcpErrorData> {
+        verify_project_access(self.inner.pool, user, project_id, ProjectRole::Viewer)
+            .await
+            .map_err(|e| e.into_error())?;
+        let client = self.inner.collab.register_local_client(project_id).await?;
+        let doc = client.project.doc_box.lock().await;
+        let doc = DocBox::doc_or_error(doc.as_ref())?;
+        let doc = &doc.ydoc;
+        let txn = doc.transact();
+        read(doc, &txn)
+    }
+
+    /// Edit a project's doc on behalf of the user, attributing the changes to them.
+    /// Edits are made to a fork of the doc and only applied once they all succeed,
+    /// so a failed request leaves the doc untouched.
+    async fn edit_doc<R>(
+        &self,
+        project_id: &ProjectId,
+        user: &User,
+        context: &RequestContext<RoleServer>,
+        request_id: String,
+        edit: impl FnOnce(&YDocProxy, &mut TransactionMut) -> Result<R, RmcpErrorData>,
+    ) -> Result<R, RmcpErrorData> {
+        verify_project_access(self.inner.pool, user, project_id, ProjectRole::Editor)
+            .await
+            .map_err(|e| e.into_error())?;
+        let client = self.inner.collab.register_local_client(project_id).await?;
+        let doc = client.project.doc_box.lock().await;
+        let doc = DocBox::doc_or_error(doc.as_ref())?;
+        let doc = &doc.ydoc;
+        let origin = YOrigin {
+            who: format!("mcp-session-{}", context.id),
+            id: request_id,
+            actor: Actor::User(user.clone()),
+        }
+        .as_origin()?;
+
+        let fork = doc.fork()?;
+        let (result, update) = {
+            let mut txn = fork.transact_mut_with(origin.clone());
+            let result = edit(&fork, &mut txn)?;
+            (result, txn.encode_update_v2())
+        };
+        doc.transact_mut_with(origin)
+            .apply_update(Update::decode_v2(&update).context("Failed to decode edit")?)
+            .context("Failed to apply edit")?;
+        Ok(result)
+    }
+
+    fn search_result_to_resource_content(result: TaskSearchResult) -> Result<Content> {
+        Ok(Content::resource(ResourceContents::TextResourceContents {
+            uri: format!(
+                "tasks:///projects/{}/tasks/{}",
+                result.project_id, result.task.id
+            ),
+            mime_type: Some("application/json".to_string()),
+            text: serde_json::to_string(&result.task)?,
+            meta: None,
+        }))
+    }
+
     fn project_to_resource_content(project: Project) -> Result<Content> {
         Ok(Content::resource(ResourceContents::TextResourceContents {
             uri: format!("projects:///projects/{}", project.project_id),
@@ -185,6 +1146,101 @@ impl KosoTools {
     }
 }
 
+fn task_to_resource_content(project_id: &ProjectId, task: &Task) -> Result<Content> {
+    Ok(Content::resource(ResourceContents::TextResourceContents {
+        uri: format!("tasks:///projects/{project_id}/tasks/{}", task.id),
+        mime_type: Some("application/json".to_string()),
+        text: serde_json::to_string(task)?,
+        meta: None,
+    }))
+}
+
+fn get_task<T: ReadTxn>(
+    doc: &YDocProxy,
+    txn: &T,
+    task_id: &str,
+) -> Result<YTaskProxy, RmcpErrorData> {
+    if !doc.contains(txn, task_id) {
+        return Err(resource_not_found(
+            "task_not_found",
+            &format!("Task {task_id} not found"),
+        ));
+    }
+    Ok(doc.get(txn, task_id)?)
+}
+
+/// Tasks managed by a plugin, like GitHub PRs, may only be changed by the plugin.
+fn get_editable_task<T: ReadTxn>(
+    doc: &YDocProxy,
+    txn: &T,
+    task_id: &str,
+) -> Result<YTaskProxy, RmcpErrorData> {
+    let task = get_task(doc, txn, task_id)?;
+    if task_id == "root" || task.is_managed(txn)? {
+        return Err(invalid_request(
+            "task_not_editable",
+            &format!("Task {task_id} is managed by Koso or a plugin and cannot be edited"),
+        ));
+    }
+    Ok(task)
+}
+
+/// Validate a status change, like the frontend does.
+fn check_status<T: ReadTxn>(
+    doc: &YDocProxy,
+    txn: &T,
+    task: &YTaskProxy,
+    status: &str,
+) -> Result<(), RmcpErrorData> {
+    if !STATUSES.contains(&status) {
+        return Err(invalid_request(
+            "invalid_status",
+            &format!("Status must be one of {STATUSES:?}"),
+        ));
+    }
+    if task.is_rollup(txn)? {
+        return Err(invalid_request(
+            "invalid_status",
+            "The status of a rollup task is derived from its children",
+        ));
+    }
+    if status == "Blocked" {
+        let mut waiting_on = task.get_blocked_by(txn)?;
+        if task.get_kind(txn)?.as_deref() == Some("Task") {
+            waiting_on.extend(task.get_children(txn)?);
+        }
+        let mut incomplete = false;
+        for id in waiting_on.iter().filter(|id| doc.contains(txn, id)) {
+            incomplete |= doc.get(txn, id)?.get_status(txn)?.as_deref() != Some("Done");
+        }
+        if !incomplete {
+            return Err(invalid_request(
+                "invalid_status",
+                "Only tasks with incomplete children or blockers can be blocked. Link the tasks it's waiting on, or add them as dependencies, first",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Set the status of a task, assigning it to the user if they start or block it unassigned.
+fn set_status(
+    task: &YTaskProxy,
+    txn: &mut TransactionMut,
+    status: &str,
+    user: &User,
+) -> Result<(), RmcpErrorData> {
+    if task.get_status(txn)?.as_deref() == Some(status) {
+        return Ok(());
+    }
+    task.set_status(txn, Some(status));
+    task.set_status_time(txn, Some(Utc::now().timestamp_millis()));
+    if (status == "In Progress" || status == "Blocked") && task.get_assignee(txn)?.is_none() {
+        task.set_assignee(txn, Some(&user.email));
+    }
+    Ok(())
+}
+
 // Implement the server handler
 #[tool_handler]
 impl rmcp::ServerHandler for KosoTools {
@@ -196,6 +1252,7 @@ impl rmcp::ServerHandler for KosoTools {
                 .enable_prompts()
                 .enable_tools()
                 .enable_resources()
+                .enable_resources_subscribe()
                 .enable_completions()
                 .build(),
             ..Default::default()
@@ -222,6 +1279,27 @@ impl rmcp::ServerHandler for KosoTools {
         Ok(self._read_resource(&uri, context).await?)
     }
 
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    async fn subscribe(
+        &self,
+        SubscribeRequestParam { uri }: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        tracing::Span::current().record("request_id", Uuid::new_v4().to_string());
+        Ok(self._subscribe(uri, context).await?)
+    }
+
+    #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
+    async fn unsubscribe(
+        &self,
+        UnsubscribeRequestParam { uri }: UnsubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        tracing::Span::current().record("request_id", Uuid::new_v4().to_string());
+        self.inner.subscriptions.lock().await.remove(&uri);
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self, context), fields(request_id, session_id=context.id.to_string()))]
     async fn list_resource_templates(
         &self,
@@ -296,31 +1374,7 @@ impl rmcp::ServerHandler for KosoTools {
 
         Ok(ListPromptsResult {
             next_cursor: None,
-            prompts: vec![Prompt {
-                name: "create_koso_task".to_string(),
-                title: Some("Create Koso task".to_string()),
-                icons: None,
-                description: Some(
-                    "Create a new Koso task in an existing Koso project.".to_string(),
-                ),
-                arguments: Some(vec![
-                    PromptArgument {
-                        name: "project_id".to_string(),
-                        title: Some("Project ID".to_string()),
-                        description: Some("ID of the Koso project".to_string()),
-                        required: Some(true),
-                    },
-                    PromptArgument {
-                        name: "name".to_string(),
-                        title: Some("Task name".to_string()),
-                        description: Some(
-                            "Name of the task. A concise description of the task.".to_string(),
-                        ),
-                        required: Some(true),
-                    },
-                ]),
-                meta: None,
-            }],
+            prompts: prompts(),
             meta: None,
         })
     }
@@ -340,18 +1394,26 @@ impl KosoTools {
         let projects: Vec<Project> = list_projects(&user.email, self.inner.pool)
             .await
             .context("Failed to list projects")?;
-        let projects = projects
-            .into_iter()
-            .map(|p| {
-                Resource::new(
-                    RawResource::new(format!("projects:///projects/{}", p.project_id), p.name),
-                    None,
-                )
-            })
-            .collect::<Vec<_>>();
+        let mut resources = vec![Resource::new(
+            RawResource {
+                description: Some(
+                    "Tasks assigned to me across all of my Koso projects, grouped by status"
+                        .to_string(),
+                ),
+                mime_type: Some("application/json".to_string()),
+                ..RawResource::new(MY_WORK_URI, "My Work")
+            },
+            None,
+        )];
+        resources.extend(projects.into_iter().map(|p| {
+            Resource::new(
+                RawResource::new(format!("projects:///projects/{}", p.project_id), p.name),
+                None,
+            )
+        }));
 
         Ok(ListResourcesResult {
-            resources: projects,
+            resources,
             next_cursor: None,
             meta: None,
         })
@@ -367,6 +1429,7 @@ impl KosoTools {
         match uri.scheme() {
             "projects" => self.read_project(uri, user).await,
             "tasks" => self.read_task(uri, user).await,
+            "work" => self.read_my_work(uri, user).await,
             scheme => Err(resource_not_found(
                 "resource_not_found",
                 &format!("Invalid scheme: {scheme}"),
@@ -387,9 +1450,14 @@ impl KosoTools {
             })
             .context("Invalid project path")?;
 
-        verify_project_access(self.inner.pool, &user, &project_id.to_string())
-            .await
-            .map_err(|e| e.into_error())?;
+        verify_project_access(
+            self.inner.pool,
+            &user,
+            &project_id.to_string(),
+            ProjectRole::Viewer,
+        )
+        .await
+        .map_err(|e| e.into_error())?;
 
         let project = fetch_project(self.inner.pool, project_id)
             .await?
@@ -416,9 +1484,14 @@ impl KosoTools {
             })
             .context("Invalid task path")?;
 
-        verify_project_access(self.inner.pool, &user, &project_id.to_string())
-            .await
-            .map_err(|e| e.into_error())?;
+        verify_project_access(
+            self.inner.pool,
+            &user,
+            &project_id.to_string(),
+            ProjectRole::Viewer,
+        )
+        .await
+        .map_err(|e| e.into_error())?;
 
         let task = {
             let client = self
@@ -444,6 +1517,67 @@ impl KosoTools {
         })
     }
 
+    async fn read_my_work(
+        &self,
+        uri: Url,
+        user: User,
+    ) -> Result<ReadResourceResult, RmcpErrorData> {
+        if uri.as_str() != MY_WORK_URI {
+            return Err(resource_not_found(
+                "resource_not_found",
+                &format!("Invalid work path: {}", uri.path()),
+            ));
+        }
+
+        let work = my_work(&self.inner.collab, &user, self.inner.pool).await?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri: MY_WORK_URI.to_string(),
+                mime_type: Some("application/json".to_string()),
+                text: serde_json::to_string(&work)?,
+                meta: None,
+            }],
+        })
+    }
+
+    /// Subscribe the session to changes of a project or task.
+    /// Subscribing again to the same URI replaces the previous subscription.
+    async fn _subscribe(
+        &self,
+        uri: String,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<(), RmcpErrorData> {
+        let user = user_extension(&mut context).await?;
+        let (project_id, task_id) = subscription_target(&uri)?;
+        self.read_doc(&project_id, &user, |doc, txn| {
+            if let Some(task_id) = &task_id {
+                get_task(doc, txn, task_id)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        // Holding a client keeps the project loaded, so that changes made
+        // by other nodes are applied to, and observed on, this node.
+        let client = self.inner.collab.register_local_client(&project_id).await?;
+        let changes = self.inner.collab.subscribe_task_changes();
+        let cancel = CancellationToken::new();
+        tokio::spawn(send_resource_updates(
+            uri.clone(),
+            task_id,
+            client,
+            changes,
+            context.peer,
+            cancel.clone(),
+        ));
+        self.inner
+            .subscriptions
+            .lock()
+            .await
+            .insert(uri, cancel.drop_guard());
+        Ok(())
+    }
+
     async fn _complete(
         &self,
         request: &CompleteRequestParam,
@@ -464,13 +1598,16 @@ impl KosoTools {
         };
 
         let argument = &request.argument;
+        if argument.name == "task_id" {
+            return self._complete_task_id(request, context).await;
+        }
         if argument.name != "project_id"
             && argument.name != "name"
             && argument.name != "project_name"
         {
             return Err(invalid_request(
                 "unsupported_argument_name",
-                "Only project_id, name and project_name argument.name values supported",
+                "Only project_id, task_id, name and project_name argument.name values supported",
             ));
         }
 
@@ -500,50 +1637,335 @@ impl KosoTools {
         })
     }
 
+    /// Complete task IDs in the project given by the previously resolved `project_id` argument.
+    async fn _complete_task_id(
+        &self,
+        request: &CompleteRequestParam,
+        mut context: RequestContext<RoleServer>,
+    ) -> Result<CompleteResult, RmcpErrorData> {
+        let Some(project_id) = request
+            .context
+            .as_ref()
+            .and_then(|c| c.get_argument("project_id"))
+        else {
+            return Err(invalid_request(
+                "missing_project_id",
+                "Complete the project_id argument first",
+            ));
+        };
+
+        let user = user_extension(&mut context).await?;
+        let value = request.argument.value.to_lowercase();
+        let mut tasks = self
+            .read_doc(project_id, &user, |doc, txn| {
+                let mut tasks = vec![];
+                for task in doc.tasks(txn)? {
+                    let (id, num, name) =
+                        (task.get_id(txn)?, task.get_num(txn)?, task.get_name(txn)?);
+                    if id != "root"
+                        && (name.to_lowercase().contains(&value)
+                            || num == value
+                            || id.to_lowercase().contains(&value))
+                    {
+                        tasks.push((num, id));
+                    }
+                }
+                Ok(tasks)
+            })
+            .await?;
+        tasks.sort_by_key(|(num, _)| (num.len(), num.clone()));
+
+        let total = tasks.len();
+        let values = tasks
+            .into_iter()
+            .take(MAX_COMPLETIONS)
+            .map(|(_, id)| id)
+            .collect::<Vec<_>>();
+        Ok(CompleteResult {
+            completion: CompletionInfo {
+                total: Some(total.try_into()?),
+                has_more: Some(total > values.len()),
+                values,
+            },
+        })
+    }
+
     async fn _get_prompt(
         &self,
         request: GetPromptRequestParam,
-        _context: RequestContext<RoleServer>,
+        mut context: RequestContext<RoleServer>,
     ) -> Result<GetPromptResult, RmcpErrorData> {
-        if request.name != "create_koso_task" {
-            return Err(invalid_request(
-                "invalid_request",
-                &format!("Prompt `{}` is not supported", request.name),
-            ));
-        }
         let Some(arguments) = request.arguments else {
             return Err(invalid_request(
                 "invalid_arguments",
                 "No arguments provided",
             ));
         };
+        let argument = |name: &str| -> Result<String, RmcpErrorData> {
+            arguments
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or_else(|| {
+                    invalid_request("invalid_arguments", &format!("Missing `{name}` argument"))
+                })
+        };
 
-        // let Some(project_id) = arguments.get("project_id").and_then(|p| p.as_str()) else {
-        //     return Err(bad_request_error(
-        //         "invalid_arguments",
-        //         "Missing `project_id` argument",
-        //     ));
-        // };
-        // let name = arguments.get("name").and_then(|p| p.as_str()) else {
-        //     return Err(bad_request_error(
-        //         "invalid_arguments",
-        //         "Missing `name` arugment",
-        //     ));
-        // };
-
-        let prompt = format!(
-            "{PROMPT_CREATE_TASK}\n\n### Input\n{}",
-            serde_json::to_string(&arguments)?
-        );
+        let (description, prompt) = match request.name.as_str() {
+            "create_koso_task" => (
+                "Create a new Koso task",
+                format!(
+                    "{PROMPT_CREATE_TASK}\n\n### Input\n{}",
+                    serde_json::to_string(&arguments)?
+                ),
+            ),
+            "break_down_task" => {
+                let (project_id, task_id) = (argument("project_id")?, argument("task_id")?);
+                let user = user_extension(&mut context).await?;
+                let context = self
+                    .read_doc(&project_id, &user, |doc, txn| {
+                        get_task(doc, txn, &task_id)?;
+                        Ok(breakdown_context(doc, txn, &task_id)?)
+                    })
+                    .await?;
+                (
+                    "Break down a Koso task into subtasks",
+                    format!(
+                        "{BREAKDOWN_PROMPT}\n\n{PROMPT_BREAK_DOWN_TASK}\n\n### Input\nproject_id: {project_id}\ntask_id: {task_id}\n\n{}",
+                        context.join("\n")
+                    ),
+                )
+            }
+            "summarize_iteration" => {
+                let (project_id, task_id) = (argument("project_id")?, argument("task_id")?);
+                let tasks = self
+                    .serialize_subtree(&mut context, &project_id, &task_id)
+                    .await?;
+                (
+                    "Summarize a Koso iteration",
+                    format!("{SUMMARIZE_PROMPT}\n\n{ITERATION_PROMPT}\n\n{tasks}"),
+                )
+            }
+            "write_status_update" => {
+                let (project_id, task_id) = (argument("project_id")?, argument("task_id")?);
+                let tasks = self
+                    .serialize_subtree(&mut context, &project_id, &task_id)
+                    .await?;
+                (
+                    "Write a status update for a Koso task",
+                    format!("{PROMPT_STATUS_UPDATE}\n\n{ITERATION_PROMPT}\n\n{tasks}"),
+                )
+            }
+            "triage_unassigned_tasks" => {
+                let project_id = argument("project_id")?;
+                let user = user_extension(&mut context).await?;
+                let tasks = self
+                    .read_doc(&project_id, &user, |doc, txn| {
+                        let mut ids = vec![];
+                        for task in doc.tasks(txn)? {
+                            let id = task.get_id(txn)?;
+                            if id != "root"
+                                && task.get_assignee(txn)?.is_none()
+                                && task.get_status(txn)?.as_deref() != Some("Done")
+                                && !task.get_archived(txn)?.unwrap_or(false)
+                                && !task.is_rollup(txn)?
+                                && !task.is_managed(txn)?
+                            {
+                                ids.push(id);
+                            }
+                        }
+                        Ok(serialize_tasks(doc, txn, &ids)?)
+                    })
+                    .await?;
+                let collaborators = list_project_users(self.inner.pool, &project_id)
+                    .await?
+                    .into_iter()
+                    .map(|u| format!("{} <{}>", u.name, u.email))
+                    .collect::<Vec<_>>();
+                (
+                    "Triage the unassigned tasks of a Koso project",
+                    format!(
+                        "{PROMPT_TRIAGE}\n\n### Input\nproject_id: {project_id}\n\nCollaborators:\n{}\n\nUnassigned tasks:\n{tasks}",
+                        collaborators.join("\n")
+                    ),
+                )
+            }
+            name => {
+                return Err(invalid_request(
+                    "invalid_request",
+                    &format!("Prompt `{name}` is not supported"),
+                ));
+            }
+        };
 
         Ok(GetPromptResult {
-            description: Some("Foo".to_string()),
+            description: Some(description.to_string()),
             messages: vec![PromptMessage {
                 role: PromptMessageRole::User,
                 content: PromptMessageContent::text(prompt),
             }],
         })
     }
+
+    /// Serializes the task and its descendants the way the in-app AI features do.
+    async fn serialize_subtree(
+        &self,
+        context: &mut RequestContext<RoleServer>,
+        project_id: &ProjectId,
+        task_id: &str,
+    ) -> Result<String, RmcpErrorData> {
+        let user = user_extension(context).await?;
+        self.read_doc(project_id, &user, |doc, txn| {
+            get_task(doc, txn, task_id)?;
+            Ok(serialize_tasks(doc, txn, &subtree_ids(doc, txn, task_id)?)?)
+        })
+        .await
+    }
+}
+
+/// Returns the project and, for task URIs, the task a subscription URI refers to.
+fn subscription_target(uri: &str) -> Result<(ProjectId, Option<String>), RmcpErrorData> {
+    let parsed = Url::parse(uri).context("Invalid URI")?;
+    let captures = match parsed.scheme() {
+        "projects" => {
+            PROJECT_RE.with(|re| re.captures(parsed.path()).map(|c| (c[1].to_string(), None)))
+        }
+        "tasks" => TASK_RE.with(|re| {
+            re.captures(parsed.path())
+                .map(|c| (c[1].to_string(), Some(c[2].to_string())))
+        }),
+        _ => None,
+    };
+    captures.ok_or_else(|| {
+        invalid_request(
+            "unsupported_uri",
+            &format!("Subscriptions are only supported for projects and tasks: {uri}"),
+        )
+    })
+}
+
+/// Notify the client whenever the subscribed project or task changes,
+/// until the subscription is cancelled or the client goes away.
+async fn send_resource_updates(
+    uri: String,
+    task_id: Option<String>,
+    client: LocalClient,
+    mut changes: broadcast::Receiver<TaskChanges>,
+    peer: Peer<RoleServer>,
+    cancel: CancellationToken,
+) {
+    loop {
+        let change = tokio::select! {
+            _ = cancel.cancelled() => break,
+            change = changes.recv() => change,
+        };
+        let updated = match change {
+            Ok(change) => {
+                change.project_id == client.project.project_id
+                    && task_id
+                        .as_ref()
+                        .is_none_or(|task_id| change.task_ids.contains(task_id))
+            }
+            // Some changes were missed, any of which may have been relevant.
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::debug!("Resource updates for {uri} lagged by {skipped} changes");
+                true
+            }
+            Err(RecvError::Closed) => break,
+        };
+        if !updated {
+            continue;
+        }
+        skip_queued_changes(&mut changes);
+        if let Err(e) = peer
+            .notify_resource_updated(ResourceUpdatedNotificationParam { uri: uri.clone() })
+            .await
+        {
+            tracing::debug!("Stopped sending resource updates for {uri}: {e:?}");
+            break;
+        }
+    }
+}
+
+/// Skip the changes already queued. The notification about to be sent covers them,
+/// and notifying once per change would leave slow clients further and further behind.
+fn skip_queued_changes(changes: &mut broadcast::Receiver<TaskChanges>) {
+    loop {
+        match changes.try_recv() {
+            Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return,
+        }
+    }
+}
+
+fn prompt_argument(name: &str, title: &str, description: &str) -> PromptArgument {
+    PromptArgument {
+        name: name.to_string(),
+        title: Some(title.to_string()),
+        description: Some(description.to_string()),
+        required: Some(true),
+    }
+}
+
+fn prompt(name: &str, title: &str, description: &str, arguments: Vec<PromptArgument>) -> Prompt {
+    Prompt {
+        name: name.to_string(),
+        title: Some(title.to_string()),
+        icons: None,
+        description: Some(description.to_string()),
+        arguments: Some(arguments),
+        meta: None,
+    }
+}
+
+fn prompts() -> Vec<Prompt> {
+    let project_id = || prompt_argument("project_id", "Project ID", "ID of the Koso project");
+    let task_id = |description| prompt_argument("task_id", "Task ID", description);
+    vec![
+        prompt(
+            "create_koso_task",
+            "Create Koso task",
+            "Create a new Koso task in an existing Koso project.",
+            vec![
+                project_id(),
+                prompt_argument(
+                    "name",
+                    "Task name",
+                    "Name of the task. A concise description of the task.",
+                ),
+            ],
+        ),
+        prompt(
+            "break_down_task",
+            "Break down task",
+            "Break down a Koso task into subtasks and add them to the project.",
+            vec![project_id(), task_id("ID of the task to break down")],
+        ),
+        prompt(
+            "summarize_iteration",
+            "Summarize iteration",
+            "Summarize the goal, progress, risks and next step of a Koso iteration or any task with children.",
+            vec![
+                project_id(),
+                task_id("ID of the iteration or task to summarize"),
+            ],
+        ),
+        prompt(
+            "triage_unassigned_tasks",
+            "Triage unassigned tasks",
+            "Propose assignees and estimates for the unassigned tasks of a Koso project.",
+            vec![project_id()],
+        ),
+        prompt(
+            "write_status_update",
+            "Write status update",
+            "Write a status update for stakeholders about the work under a Koso task.",
+            vec![
+                project_id(),
+                task_id("ID of the iteration or task to report on"),
+            ],
+        ),
+    ]
 }
 
 pub(super) fn router(
@@ -591,3 +2013,536 @@ async fn user_extension(context: &mut RequestContext<RoleServer>) -> Result<User
         .context("Missing user extension")?;
     Ok(user)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::db::UnsafePoolWrapper;
+    use rmcp::{ServerHandler as _, service::serve_directly};
+    use yrs::{Doc, StateVector, Transact};
+
+    const PROJECT_ID: &str = "mcp";
+
+    #[tokio::test]
+    async fn skip_queued_changes_test() {
+        let (changes_tx, mut changes) = broadcast::channel(2);
+        let change = |task_id: &str| TaskChanges {
+            project_id: PROJECT_ID.to_string(),
+            task_ids: Arc::new([task_id.to_string()].into()),
+            origin: None,
+        };
+        for i in 0..5 {
+            changes_tx.send(change(&i.to_string())).unwrap();
+        }
+
+        // Lagging receivers are told about the missed changes, and skip the rest
+        // of the backlog, rather than trailing behind it.
+        assert!(matches!(changes.recv().await, Err(RecvError::Lagged(3))));
+        skip_queued_changes(&mut changes);
+        assert!(matches!(changes.try_recv(), Err(TryRecvError::Empty)));
+
+        changes_tx.send(change("5")).unwrap();
+        assert_eq!(
+            *changes.recv().await.unwrap().task_ids,
+            [String::from("5")].into()
+        );
+    }
+
+    fn user(email: &str) -> User {
+        User {
+            email: email.to_string(),
+            name: "MCP User".to_string(),
+            picture: "".to_string(),
+            exp: 0,
+            scope: None,
+        }
+    }
+
+    /// Build the context of a tool call made by the given user.
+    fn context(tools: &KosoTools, user: &User) -> RequestContext<RoleServer> {
+        let (transport, _) = tokio::io::duplex(1024);
+        let running = serve_directly(tools.clone(), transport, None);
+        let (parts, _) = axum::http::Request::builder()
+            .extension(user.clone())
+            .body(())
+            .unwrap()
+            .into_parts();
+        let mut extensions = Extensions::default();
+        extensions.insert(parts);
+        RequestContext {
+            ct: CancellationToken::new(),
+            id: RequestId::Number(1),
+            meta: Meta::default(),
+            extensions,
+            peer: running.peer().clone(),
+        }
+    }
+
+    async fn insert_project(pool: &PgPool, users: &[(&User, ProjectRole)]) {
+        sqlx::query("INSERT INTO projects (project_id, name) VALUES ($1, $2)")
+            .bind(PROJECT_ID)
+            .bind("MCP")
+            .execute(pool)
+            .await
+            .unwrap();
+        for (user, role) in users {
+            sqlx::query("INSERT INTO users (email, name, picture) VALUES ($1, $2, $3)")
+                .bind(&user.email)
+                .bind(&user.name)
+                .bind(&user.picture)
+                .execute(pool)
+                .await
+                .unwrap();
+            sqlx::query(
+                "INSERT INTO project_permissions (project_id, email, role) VALUES ($1, $2, $3)",
+            )
+            .bind(PROJECT_ID)
+            .bind(&user.email)
+            .bind(role)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+    }
+
+    /// Persist a doc with the given tasks under a root task before the project is loaded.
+    async fn insert_doc(pool: &PgPool, tasks: &[Task]) {
+        let ydoc = Doc::new();
+        ydoc.get_or_insert_map("graph");
+        let update = {
+            let mut txn = ydoc.transact_mut();
+            let doc = YDocProxy::new_from_existing_doc(ydoc.clone(), &txn).unwrap();
+            doc.set(
+                &mut txn,
+                &Task {
+                    id: "root".into(),
+                    num: "0".into(),
+                    name: "Root".into(),
+                    children: tasks
+                        .iter()
+                        .filter(|t| !tasks.iter().any(|p| p.children.contains(&t.id)))
+                        .map(|t| t.id.clone())
+                        .collect(),
+                    ..Task::default()
+                },
+            );
+            for task in tasks {
+                doc.set(&mut txn, task);
+            }
+            txn.encode_state_as_update_v2(&StateVector::default())
+        };
+        sqlx::query("INSERT INTO yupdates (project_id, seq, update_v2) VALUES ($1, DEFAULT, $2)")
+            .bind(PROJECT_ID)
+            .bind(update)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    fn task(id: &str) -> Task {
+        Task {
+            id: id.into(),
+            num: id.into(),
+            name: format!("Task {id}"),
+            ..Task::default()
+        }
+    }
+
+    async fn count_updates(pool: &PgPool) -> i64 {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM yupdates WHERE project_id=$1")
+            .bind(PROJECT_ID)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        count
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn update_task_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let editor = user("editor@koso.app");
+        let viewer = user("viewer@koso.app");
+        insert_project(
+            pool,
+            &[
+                (&editor, ProjectRole::Editor),
+                (&viewer, ProjectRole::Viewer),
+            ],
+        )
+        .await;
+        insert_doc(pool, &[task("1"), task("2")]).await;
+        let collab = Collab::new(pool).await.unwrap();
+        let tools = KosoTools::new(collab.clone(), pool);
+        let update = |name: &str, status: &str| UpdateTaskParam {
+            project_id: PROJECT_ID.into(),
+            task_id: "1".into(),
+            name: Some(name.into()),
+            description: Some(Some("Details".into())),
+            status: Some(status.into()),
+            estimate: None,
+            deadline: None,
+        };
+
+        tools
+            .update_task(
+                Parameters(update("Renamed", "In Progress")),
+                context(&tools, &editor),
+            )
+            .await
+            .unwrap();
+        let read = || async {
+            tools
+                .read_doc(&PROJECT_ID.to_string(), &editor, |doc, txn| {
+                    Ok(doc.get(txn, "1")?.to_task(txn)?)
+                })
+                .await
+                .unwrap()
+        };
+        let task = read().await;
+        assert_eq!(task.name, "Renamed");
+        assert_eq!(task.desc.as_deref(), Some("Details"));
+        assert_eq!(task.status.as_deref(), Some("In Progress"));
+        assert_eq!(task.assignee.as_deref(), Some("editor@koso.app"));
+
+        // An invalid request changes nothing, including the valid parts of it.
+        let err = tools
+            .update_task(
+                Parameters(update("Renamed again", "Blocked")),
+                context(&tools, &editor),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "invalid_status");
+        assert_eq!(read().await, task);
+
+        // Viewers may not edit tasks.
+        tools
+            .update_task(
+                Parameters(update("Renamed by viewer", "Done")),
+                context(&tools, &viewer),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(read().await, task);
+
+        drop(tools);
+        collab.stop().await;
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn failed_edit_leaves_doc_untouched_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let editor = user("editor@koso.app");
+        insert_project(pool, &[(&editor, ProjectRole::Editor)]).await;
+        // Task 2 is linked to the root twice. Deleting it removes both links
+        // while unlinking it the first time, then fails to unlink it again.
+        insert_doc(pool, &[task("1"), task("2"), task("3"), task("2")]).await;
+        let collab = Collab::new(pool).await.unwrap();
+        let tools = KosoTools::new(collab.clone(), pool);
+
+        let err = tools
+            .delete_task(
+                Parameters(TaskParam {
+                    project_id: PROJECT_ID.into(),
+                    task_id: "2".into(),
+                }),
+                context(&tools, &editor),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INTERNAL_ERROR);
+
+        let (children, contains) = tools
+            .read_doc(&PROJECT_ID.to_string(), &editor, |doc, txn| {
+                Ok((
+                    doc.get(txn, "root")?.get_children(txn)?,
+                    doc.contains(txn, "2"),
+                ))
+            })
+            .await
+            .unwrap();
+        assert_eq!(children, vec!["1", "2", "3", "2"]);
+        assert!(contains);
+        assert_eq!(count_updates(pool).await, 1);
+
+        // The doc can still be edited afterwards.
+        tools
+            .move_task(
+                Parameters(MoveTaskParam {
+                    project_id: PROJECT_ID.into(),
+                    task_id: "3".into(),
+                    parent_id: "1".into(),
+                    from_parent_id: None,
+                    position: None,
+                }),
+                context(&tools, &editor),
+            )
+            .await
+            .unwrap();
+        let children = tools
+            .read_doc(&PROJECT_ID.to_string(), &editor, |doc, txn| {
+                Ok(doc.get(txn, "root")?.get_children(txn)?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(children, vec!["1", "2", "2"]);
+
+        drop(tools);
+        collab.stop().await;
+    }
+
+    /// The text of a prompt's only message.
+    fn prompt_text(result: GetPromptResult) -> String {
+        match result.messages.as_slice() {
+            [
+                PromptMessage {
+                    content: PromptMessageContent::Text { text },
+                    ..
+                },
+            ] => text.clone(),
+            messages => panic!("Expected a single text message: {messages:?}"),
+        }
+    }
+
+    /// The IDs of the tasks in a JSON document of serialized tasks.
+    fn task_ids(json: &str) -> Vec<String> {
+        let tasks: Vec<serde_json::Value> = serde_json::from_str(json).unwrap();
+        let mut ids = tasks
+            .iter()
+            .map(|t| t["id"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn prompts_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let editor = user("editor@koso.app");
+        insert_project(pool, &[(&editor, ProjectRole::Editor)]).await;
+        insert_doc(
+            pool,
+            &[
+                Task {
+                    name: "Launch".into(),
+                    desc: Some("Ship the launch".into()),
+                    children: vec!["2".into(), "3".into()],
+                    ..task("1")
+                },
+                Task {
+                    name: "Write docs".into(),
+                    status: Some("Done".into()),
+                    assignee: Some(editor.email.clone()),
+                    ..task("2")
+                },
+                Task {
+                    name: "Fix login".into(),
+                    status: Some("In Progress".into()),
+                    ..task("3")
+                },
+                task("4"),
+            ],
+        )
+        .await;
+        let collab = Collab::new(pool).await.unwrap();
+        let tools = KosoTools::new(collab.clone(), pool);
+        let get_prompt = |name: &str, arguments: serde_json::Value| {
+            let request = GetPromptRequestParam {
+                name: name.to_string(),
+                arguments: arguments.as_object().cloned(),
+            };
+            tools.get_prompt(request, context(&tools, &editor))
+        };
+
+        assert_eq!(
+            prompts()
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "create_koso_task",
+                "break_down_task",
+                "summarize_iteration",
+                "triage_unassigned_tasks",
+                "write_status_update"
+            ]
+        );
+
+        let text = prompt_text(
+            get_prompt(
+                "create_koso_task",
+                serde_json::json!({"project_id": PROJECT_ID, "name": "New task"}),
+            )
+            .await
+            .unwrap(),
+        );
+        assert_eq!(
+            text,
+            format!(
+                "{PROMPT_CREATE_TASK}\n\n### Input\n{{\"name\":\"New task\",\"project_id\":\"mcp\"}}"
+            )
+        );
+
+        let text = prompt_text(
+            get_prompt(
+                "break_down_task",
+                serde_json::json!({"project_id": PROJECT_ID, "task_id": "2"}),
+            )
+            .await
+            .unwrap(),
+        );
+        assert_eq!(
+            text,
+            format!(
+                "{BREAKDOWN_PROMPT}\n\n{PROMPT_BREAK_DOWN_TASK}\n\n### Input\nproject_id: mcp\ntask_id: 2\n\nContext:\nRoot\nLaunch\nShip the launch\nTask:\nWrite docs"
+            )
+        );
+
+        let text = prompt_text(
+            get_prompt(
+                "summarize_iteration",
+                serde_json::json!({"project_id": PROJECT_ID, "task_id": "1"}),
+            )
+            .await
+            .unwrap(),
+        );
+        let tasks = text
+            .strip_prefix(&format!("{SUMMARIZE_PROMPT}\n\n{ITERATION_PROMPT}\n\n"))
+            .unwrap();
+        assert_eq!(task_ids(tasks), vec!["1", "2", "3"]);
+
+        let text = prompt_text(
+            get_prompt(
+                "write_status_update",
+                serde_json::json!({"project_id": PROJECT_ID, "task_id": "1"}),
+            )
+            .await
+            .unwrap(),
+        );
+        let tasks = text
+            .strip_prefix(&format!("{PROMPT_STATUS_UPDATE}\n\n{ITERATION_PROMPT}\n\n"))
+            .unwrap();
+        assert_eq!(task_ids(tasks), vec!["1", "2", "3"]);
+
+        // The rollup, the assigned and the completed tasks aren't triaged.
+        let text = prompt_text(
+            get_prompt(
+                "triage_unassigned_tasks",
+                serde_json::json!({"project_id": PROJECT_ID}),
+            )
+            .await
+            .unwrap(),
+        );
+        let (preamble, tasks) = text.split_once("\n\nUnassigned tasks:\n").unwrap();
+        assert_eq!(
+            preamble,
+            format!(
+                "{PROMPT_TRIAGE}\n\n### Input\nproject_id: mcp\n\nCollaborators:\nMCP User <editor@koso.app>"
+            )
+        );
+        assert_eq!(task_ids(tasks), vec!["3", "4"]);
+
+        let err = get_prompt(
+            "break_down_task",
+            serde_json::json!({"project_id": PROJECT_ID}),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.message, "invalid_arguments");
+        let err = get_prompt(
+            "break_down_task",
+            serde_json::json!({"project_id": PROJECT_ID, "task_id": "5"}),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.message, "task_not_found");
+        let err = get_prompt("unknown", serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert_eq!(err.message, "invalid_request");
+
+        drop(tools);
+        collab.stop().await;
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn complete_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let editor = user("editor@koso.app");
+        insert_project(pool, &[(&editor, ProjectRole::Editor)]).await;
+        let mut tasks = vec![
+            Task {
+                name: "Launch".into(),
+                ..task("1")
+            },
+            Task {
+                name: "Launch party".into(),
+                ..task("2")
+            },
+        ];
+        tasks.extend((3..=110).map(|num| task(&num.to_string())));
+        insert_doc(pool, &tasks).await;
+        let collab = Collab::new(pool).await.unwrap();
+        let tools = KosoTools::new(collab.clone(), pool);
+        let complete = |name: &str, value: &str, project_id: Option<&str>| {
+            let request = CompleteRequestParam {
+                r#ref: Reference::Prompt(PromptReference {
+                    name: "break_down_task".into(),
+                    title: None,
+                }),
+                argument: ArgumentInfo {
+                    name: name.into(),
+                    value: value.into(),
+                },
+                context: project_id.map(|project_id| CompletionContext {
+                    arguments: Some(HashMap::from([(
+                        "project_id".to_string(),
+                        project_id.to_string(),
+                    )])),
+                }),
+            };
+            tools.complete(request, context(&tools, &editor))
+        };
+
+        let result = complete("project_id", "mc", None).await.unwrap();
+        assert_eq!(result.completion.values, vec!["mcp"]);
+        let result = complete("project_id", "other", None).await.unwrap();
+        assert!(result.completion.values.is_empty());
+
+        // Matches names case insensitively, ordered by number.
+        let result = complete("task_id", "launch", Some(PROJECT_ID))
+            .await
+            .unwrap();
+        assert_eq!(result.completion.values, vec!["1", "2"]);
+        assert_eq!(result.completion.total, Some(2));
+        assert_eq!(result.completion.has_more, Some(false));
+
+        // Matches IDs and numbers, ordered by number.
+        let result = complete("task_id", "10", Some(PROJECT_ID)).await.unwrap();
+        assert_eq!(
+            result.completion.values,
+            [10, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110].map(|n| n.to_string())
+        );
+
+        // Returns at most 100 values.
+        let result = complete("task_id", "", Some(PROJECT_ID)).await.unwrap();
+        assert_eq!(result.completion.values.len(), MAX_COMPLETIONS);
+        assert_eq!(result.completion.values[..3], ["1", "2", "3"]);
+        assert_eq!(result.completion.total, Some(110));
+        assert_eq!(result.completion.has_more, Some(true));
+
+        let err = complete("task_id", "launch", None).await.unwrap_err();
+        assert_eq!(err.message, "missing_project_id");
+        let err = complete("task_id", "launch", Some("other"))
+            .await
+            .unwrap_err();
+        assert_ne!(err.message, "missing_project_id");
+
+        drop(tools);
+        collab.stop().await;
+    }
+}
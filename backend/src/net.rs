@@ -0,0 +1,177 @@
+//! Guards requests to user supplied URLs, like webhooks, from reaching
+//! the loopback, private and link-local addresses of our own network.
+
+use anyhow::{Result, anyhow};
+use reqwest::{
+    Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Whether the address is reachable on the public internet.
+pub(crate) fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space, 100.64.0.0/10, used by carrier-grade NAT.
+                || (a == 100 && (b & 0b1100_0000) == 64)
+                // "This network", 0.0.0.0/8.
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let [a, b, ..] = ip.segments();
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast()
+                    // Deprecated site-local addresses, fec0::/10.
+                    || (a & 0xffc0) == 0xfec0
+                    // Local-use NAT64, 64:ff9b:1::/48, whose prefix length varies.
+                    || (a == 0x64 && b == 0xff9b))
+            }
+        },
+    }
+}
+
+/// The IPv4 address that an IPv6 address is translated to or tunnelled over, if any.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let octets = ip.octets();
+    let last = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    match ip.segments() {
+        // IPv4-mapped, ::ffff:a.b.c.d.
+        [0, 0, 0, 0, 0, 0xffff, ..] => Some(last),
+        // Deprecated IPv4-compatible, ::a.b.c.d, which also covers :: and ::1.
+        [0, 0, 0, 0, 0, 0, ..] => Some(last),
+        // Well-known NAT64, 64:ff9b::/96.
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(last),
+        // 6to4, 2002:AABB:CCDD::/48.
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// Resolve the URL's host and fail unless every address it resolves to is public.
+pub(crate) async fn check_public_url(url: &Url) -> Result<()> {
+    let host = match url.host() {
+        Some(url::Host::Ipv4(ip)) => return check_public(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => return check_public(IpAddr::V6(ip)),
+        Some(url::Host::Domain(host)) => host,
+        None => return Err(anyhow!("URL has no host")),
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        return Err(anyhow!("{host} did not resolve to any address"));
+    }
+    for addr in addrs {
+        check_public(addr.ip())?;
+    }
+    Ok(())
+}
+
+fn check_public(ip: IpAddr) -> Result<()> {
+    if !is_public(ip) {
+        return Err(anyhow!("{ip} is not a public address"));
+    }
+    Ok(())
+}
+
+/// DNS resolver for reqwest clients that fails to resolve hosts with non-public
+/// addresses, so a host can't be pointed at one after its URL was checked.
+/// Hosts given as IP addresses aren't resolved and must be checked separately.
+pub(crate) struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!("{host} resolves to non-public address {}", addr.ip()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn is_public_test() {
+        for ip in [
+            "1.1.1.1",
+            "8.8.8.8",
+            "2606:4700:4700::1111",
+            "::ffff:1.1.1.1",
+            "64:ff9b::101:101",
+            "2002:101:101::1",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            // IPv4-compatible.
+            "::127.0.0.1",
+            "::10.0.0.1",
+            // NAT64.
+            "64:ff9b::127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b:1::1.1.1.1",
+            // 6to4.
+            "2002:7f00:1::1",
+            "2002:a9fe:a9fe::1",
+            "2002:c0a8:101::1",
+            // Site-local.
+            "fec0::1",
+            "feff::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn check_public_url_test() {
+        for url in [
+            "https://127.0.0.1/hook",
+            "https://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "https://localhost/hook",
+        ] {
+            assert!(
+                check_public_url(&Url::parse(url).unwrap()).await.is_err(),
+                "{url}"
+            );
+        }
+        check_public_url(&Url::parse("https://1.1.1.1/hook").unwrap())
+            .await
+            .unwrap();
+    }
+}
@@ -2,20 +2,30 @@ use anyhow::{Context as _, Result};
 use axum::routing::post;
 use axum::{Extension, Json, Router, middleware};
 use axum_anyhow::ApiResult;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, prelude::FromRow};
 
 use crate::api::google;
 use crate::api::google::User;
+use crate::api::model::{ProjectId, Task};
+use crate::api::tokens;
+use crate::notifiers::digest::Delivery;
+use crate::notifiers::email::EmailClient;
 use crate::notifiers::slack::SlackClient;
 use crate::notifiers::teams::TeamsClient;
 use crate::notifiers::telegram::TelegramClient;
+use crate::notifiers::webhook::{WebhookClient, WebhookEvent, WebhookTarget};
 use crate::settings::settings;
 
+pub(crate) mod deadlines;
+pub(crate) mod digest;
 pub(crate) mod discord;
+pub(crate) mod email;
 pub(crate) mod slack;
 pub(crate) mod teams;
 pub(crate) mod telegram;
+pub(crate) mod webhook;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -42,6 +52,20 @@ pub(super) struct TeamsSettings {
     pub(super) channel_id: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct EmailSettings {
+    pub(super) address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct WebhookSettings {
+    pub(super) url: String,
+    /// Key of the HMAC signature sent with each event.
+    pub(super) secret: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub(super) enum NotifierSettings {
@@ -49,6 +73,8 @@ pub(super) enum NotifierSettings {
     Slack(SlackSettings),
     Telegram(TelegramSettings),
     Teams(TeamsSettings),
+    Email(EmailSettings),
+    Webhook(WebhookSettings),
 }
 
 #[derive(Serialize, Deserialize, FromRow, Debug)]
@@ -59,16 +85,55 @@ pub(super) struct UserNotificationConfig {
     pub(super) enabled: bool,
     #[sqlx(json)]
     pub(super) settings: NotifierSettings,
+    pub(super) delivery: Delivery,
+    /// Kinds of notifications the user turned off for this notifier.
+    pub(super) disabled_notifications: Vec<NotificationKind>,
+}
+
+/// The kinds of task notifications sent to users, each of which may be turned off per notifier.
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "varchar", rename_all = "camelCase")]
+pub(crate) enum NotificationKind {
+    /// A task was assigned to the user.
+    Assigned,
+    /// A task assigned to the user became actionable.
+    Unblocked,
+    /// The user was @mentioned in a task's description.
+    Mentioned,
+    /// The status of a task the user reported changed.
+    ReportedStatusChanged,
+    /// A task assigned to the user is due within a day.
+    DeadlineApproaching,
+    /// A task assigned to the user is past its deadline.
+    DeadlinePassed,
+}
+
+/// Markdown link to the task, named after the task or its number.
+pub(crate) fn task_link(project_id: &ProjectId, task: &Task) -> String {
+    let name = if task.name.is_empty() {
+        format!("Task #{}", task.num)
+    } else {
+        task.name.clone()
+    };
+    format!(
+        "[{name}](https://koso.app/projects/{project_id}?taskId={})",
+        task.id
+    )
 }
 
 pub(super) fn router() -> Result<Router> {
     Ok(Router::new()
         .route("/", post(send))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
         .layer(middleware::from_fn(google::authenticate))
+        .merge(digest::router())
         .nest("/discord", discord::router())
         .nest("/slack", slack::router())
         .nest("/telegram", telegram::router())
-        .nest("/teams", teams::router()))
+        .nest("/teams", teams::router())
+        .nest("/email", email::router())
+        .nest("/webhook", webhook::router()))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -96,6 +161,8 @@ pub(super) struct Notifier {
     slack: Option<slack::SlackClient>,
     telegram: Option<telegram::TelegramClient>,
     teams: Option<teams::TeamsClient>,
+    email: Option<email::EmailClient>,
+    webhook: WebhookClient,
 }
 
 impl Notifier {
@@ -142,24 +209,78 @@ impl Notifier {
                     }
                 }
             },
+            email: match EmailClient::new() {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    if settings().is_dev() {
+                        None
+                    } else {
+                        return Err(e.context("Failed to initialize email client"));
+                    }
+                }
+            },
+            webhook: WebhookClient::new().context("Failed to initialize webhook client")?,
         })
     }
 
+    /// Send the message immediately, regardless of the recipient's delivery preferences.
     pub(super) async fn notify(
         &self,
         recipient: &str,
         message: &str,
         notifiers: Option<Vec<String>>,
     ) -> Result<()> {
+        for config in self.fetch_configs(recipient, notifiers).await? {
+            self.send(recipient, config.settings, message, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Send the message, or queue it for a digest, according to the recipient's delivery preferences.
+    /// Webhooks always receive the event, rather than a ping, immediately.
+    /// Notifiers that turned off the kind of notification are skipped.
+    pub(crate) async fn notify_with_event(
+        &self,
+        recipient: &str,
+        kind: NotificationKind,
+        message: &str,
+        event: Option<&WebhookEvent>,
+    ) -> Result<()> {
+        let configs = self
+            .fetch_configs(recipient, None)
+            .await?
+            .into_iter()
+            .filter(|config| !config.disabled_notifications.contains(&kind));
+        let quiet = digest::fetch_preferences(recipient, self.pool)
+            .await?
+            .is_quiet(Utc::now());
+        for config in configs {
+            if !matches!(config.settings, NotifierSettings::Webhook(_))
+                && (config.delivery != Delivery::Immediate || quiet)
+            {
+                digest::queue_notification(recipient, &config.notifier, message, self.pool).await?;
+                continue;
+            }
+            self.send(recipient, config.settings, message, event)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn fetch_configs(
+        &self,
+        recipient: &str,
+        notifiers: Option<Vec<String>>,
+    ) -> Result<Vec<UserNotificationConfig>> {
         let notifiers = notifiers.unwrap_or(
-            vec!["discord", "slack", "telegram", "teams"]
+            vec!["discord", "slack", "telegram", "teams", "email", "webhook"]
                 .into_iter()
                 .map(|s| s.to_string())
                 .collect(),
         );
-        let configs: Vec<UserNotificationConfig> = sqlx::query_as(
+        sqlx::query_as(
             "
-            SELECT email, notifier, enabled, settings
+            SELECT email, notifier, enabled, settings, delivery, disabled_notifications
             FROM user_notification_configs
             WHERE email = $1
             AND notifier = ANY($2)",
@@ -167,37 +288,77 @@ impl Notifier {
         .bind(recipient)
         .bind(notifiers)
         .fetch_all(self.pool)
-        .await?;
+        .await
+        .context("Failed to query notification configs")
+    }
 
-        for config in configs {
-            match config.settings {
-                NotifierSettings::Discord(settings) => {
-                    if let Some(discord) = &self.discord {
-                        discord.send_message(&settings.channel_id, message).await?;
-                    }
+    /// Send the message to a single notifier.
+    pub(super) async fn send(
+        &self,
+        recipient: &str,
+        settings: NotifierSettings,
+        message: &str,
+        event: Option<&WebhookEvent>,
+    ) -> Result<()> {
+        match settings {
+            NotifierSettings::Discord(settings) => {
+                if let Some(discord) = &self.discord {
+                    discord.send_message(&settings.channel_id, message).await?;
                 }
-                NotifierSettings::Slack(settings) => {
-                    if let Some(slack) = &self.slack {
-                        slack.send_message(&settings.user_id, message).await?;
-                    }
+            }
+            NotifierSettings::Slack(settings) => {
+                if let Some(slack) = &self.slack {
+                    slack.send_message(&settings.user_id, message).await?;
                 }
-                NotifierSettings::Telegram(settings) => {
-                    if let Some(client) = &self.telegram {
-                        client.send_message(settings.chat_id, message).await?;
-                    }
+            }
+            NotifierSettings::Telegram(settings) => {
+                if let Some(client) = &self.telegram {
+                    client.send_message(settings.chat_id, message).await?;
                 }
-                NotifierSettings::Teams(settings) => {
-                    if let Some(client) = &self.teams {
-                        client
-                            .send_message(&settings.bot_token, &settings.channel_id, message)
-                            .await?;
-                    }
+            }
+            NotifierSettings::Teams(settings) => {
+                if let Some(client) = &self.teams {
+                    client
+                        .send_message(&settings.bot_token, &settings.channel_id, message)
+                        .await?;
                 }
             }
+            NotifierSettings::Email(settings) => {
+                if let Some(client) = &self.email {
+                    client.send_message(&settings.address, message).await?;
+                }
+            }
+            NotifierSettings::Webhook(settings) => {
+                let mut event = match event {
+                    Some(event) => event.clone(),
+                    None => WebhookEvent::ping(message)?,
+                };
+                event.message = Some(message.to_string());
+                self.webhook.spawn_delivery(
+                    WebhookTarget {
+                        url: settings.url,
+                        secret: settings.secret,
+                        webhook_id: None,
+                        email: Some(recipient.to_string()),
+                    },
+                    event,
+                    self.pool,
+                );
+            }
         }
 
         Ok(())
     }
+
+    /// Deliver the event to the project's webhooks. See `WebhookClient::publish`.
+    pub(super) async fn publish(&self, project_id: &ProjectId, event: &WebhookEvent) -> Result<()> {
+        self.webhook.publish(project_id, event, self.pool).await
+    }
+
+    /// Wait for webhook deliveries running in the background to finish.
+    pub(super) async fn close(&self) {
+        self.webhook.close().await
+    }
 }
 
 pub(crate) async fn fetch_notification_configs(
@@ -206,7 +367,7 @@ pub(crate) async fn fetch_notification_configs(
 ) -> Result<Vec<UserNotificationConfig>> {
     sqlx::query_as(
         "
-        SELECT email, notifier, enabled, settings
+        SELECT email, notifier, enabled, settings, delivery, disabled_notifications
         FROM user_notification_configs
         WHERE email = $1",
     )
@@ -226,6 +387,8 @@ pub(crate) async fn insert_notification_config(
         NotifierSettings::Slack(_) => "slack",
         NotifierSettings::Telegram(_) => "telegram",
         NotifierSettings::Teams(_) => "teams",
+        NotifierSettings::Email(_) => "email",
+        NotifierSettings::Webhook(_) => "webhook",
     };
     sqlx::query(
         "
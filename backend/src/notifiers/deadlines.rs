@@ -0,0 +1,221 @@
+use crate::{
+    api::{
+        collab::txn_origin::Actor,
+        model::{ProjectId, Task},
+    },
+    notifiers::{
+        NotificationKind, Notifier, task_link,
+        webhook::{WebhookEvent, WebhookEventKind},
+    },
+};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::postgres::PgPool;
+use tokio::task::JoinHandle;
+
+/// How often the scheduler checks for approaching and passed deadlines.
+const SCHEDULE_DELAY: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+/// How long before a deadline the assignee is reminded.
+const APPROACHING: Duration = Duration::days(1);
+
+/// DeadlineScheduler periodically notifies assignees of tasks whose deadline is
+/// approaching or has passed. Each deadline is notified once, unless it changes.
+pub(crate) struct DeadlineScheduler {
+    pool: &'static PgPool,
+    notifier: Notifier,
+}
+
+impl DeadlineScheduler {
+    pub(crate) fn new(pool: &'static PgPool) -> Result<Self> {
+        Ok(Self {
+            pool,
+            notifier: Notifier::new(pool)?,
+        })
+    }
+
+    pub(crate) fn start(self) -> JoinHandle<()> {
+        tokio::spawn(self.schedule())
+    }
+
+    async fn schedule(self) {
+        loop {
+            tokio::time::sleep(SCHEDULE_DELAY).await;
+            if let Err(e) = self.send_deadline_notifications(Utc::now()).await {
+                tracing::warn!("Failed to send deadline notifications: {e:?}");
+            }
+        }
+    }
+
+    /// Notify the assignees of incomplete tasks due by `now` plus a day.
+    /// Tasks are read from the search index, which covers indexed projects.
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn send_deadline_notifications(&self, now: DateTime<Utc>) -> Result<()> {
+        let tasks: Vec<(ProjectId, sqlx::types::Json<Task>)> = sqlx::query_as(
+            "
+            SELECT task_index.project_id, task
+            FROM task_index
+            JOIN projects USING (project_id)
+            WHERE projects.deleted_on IS NULL
+            AND NOT archived
+            AND assignee IS NOT NULL
+            AND COALESCE(status, 'Not Started') != 'Done'
+            AND deadline <= $1",
+        )
+        .bind((now + APPROACHING).timestamp_millis())
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to query task deadlines")?;
+
+        for (project_id, sqlx::types::Json(task)) in tasks {
+            if let Err(e) = self.notify_deadline(&project_id, task, now).await {
+                tracing::warn!("Failed to send deadline notification in {project_id}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn notify_deadline(
+        &self,
+        project_id: &ProjectId,
+        task: Task,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let (Some(deadline), Some(assignee)) = (task.deadline, task.assignee.clone()) else {
+            return Ok(());
+        };
+        let (kind, event_kind, msg) = if deadline <= now.timestamp_millis() {
+            (
+                "passed",
+                WebhookEventKind::TaskDeadlinePassed,
+                format!("🚨 Deadline passed:\n{}", task_link(project_id, &task)),
+            )
+        } else {
+            (
+                "approaching",
+                WebhookEventKind::TaskDeadlineApproaching,
+                format!("⏰ Due within a day:\n{}", task_link(project_id, &task)),
+            )
+        };
+
+        // Claim the notification, so that it's sent once across nodes and runs.
+        let claimed: Option<(String,)> = sqlx::query_as(
+            "
+            INSERT INTO deadline_notifications (project_id, task_id, kind, deadline)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (project_id, task_id, kind)
+            DO UPDATE SET deadline = EXCLUDED.deadline, notified_at = NOW()
+            WHERE deadline_notifications.deadline != EXCLUDED.deadline
+            RETURNING task_id",
+        )
+        .bind(project_id)
+        .bind(&task.id)
+        .bind(kind)
+        .bind(deadline)
+        .fetch_optional(self.pool)
+        .await
+        .context("Failed to claim deadline notification")?;
+        if claimed.is_none() {
+            return Ok(());
+        }
+
+        tracing::debug!("Sending deadline {kind} notification for {}", task.id);
+        let webhook_event = WebhookEvent::task(event_kind, project_id, task, &Actor::Server)?;
+        self.notifier.publish(project_id, &webhook_event).await?;
+        let kind = match event_kind {
+            WebhookEventKind::TaskDeadlinePassed => NotificationKind::DeadlinePassed,
+            _ => NotificationKind::DeadlineApproaching,
+        };
+        self.notifier
+            .notify_with_event(&assignee, kind, &msg, Some(&webhook_event))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_task(pool: &PgPool, task_id: &str, deadline: i64, status: &str) {
+        let task = Task {
+            id: task_id.to_string(),
+            num: task_id.to_string(),
+            name: format!("Task {task_id}"),
+            assignee: Some("deadline@koso.app".to_string()),
+            status: Some(status.to_string()),
+            deadline: Some(deadline),
+            ..Task::default()
+        };
+        sqlx::query(
+            "
+            INSERT INTO task_index (project_id, task_id, name, assignee, status, deadline, archived, task)
+            VALUES ('deadlines', $1, $2, $3, $4, $5, false, $6)
+            ON CONFLICT (project_id, task_id)
+            DO UPDATE SET deadline = EXCLUDED.deadline, task = EXCLUDED.task",
+        )
+        .bind(&task.id)
+        .bind(&task.name)
+        .bind(&task.assignee)
+        .bind(&task.status)
+        .bind(deadline)
+        .bind(sqlx::types::Json(&task))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn notified(pool: &PgPool) -> Vec<(String, String)> {
+        sqlx::query_as(
+            "
+            SELECT task_id, kind
+            FROM deadline_notifications
+            ORDER BY task_id, kind",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn send_deadline_notifications_test(pool: PgPool) {
+        let pool: &'static PgPool = Box::leak(Box::new(pool));
+        sqlx::query("INSERT INTO projects (project_id, name) VALUES ('deadlines', 'Deadlines')")
+            .execute(pool)
+            .await
+            .unwrap();
+        let now = Utc::now();
+        let ms = |d: Duration| (now + d).timestamp_millis();
+        insert_task(pool, "1", ms(-Duration::hours(1)), "In Progress").await;
+        insert_task(pool, "2", ms(Duration::hours(2)), "Not Started").await;
+        insert_task(pool, "3", ms(Duration::days(3)), "Not Started").await;
+        insert_task(pool, "4", ms(-Duration::hours(1)), "Done").await;
+
+        let scheduler = DeadlineScheduler::new(pool).unwrap();
+        scheduler.send_deadline_notifications(now).await.unwrap();
+        let expected = vec![
+            ("1".to_string(), "passed".to_string()),
+            ("2".to_string(), "approaching".to_string()),
+        ];
+        assert_eq!(notified(pool).await, expected);
+
+        // Already notified deadlines aren't notified again.
+        let notified_at = || async {
+            sqlx::query_as::<_, (DateTime<Utc>,)>(
+                "SELECT max(notified_at) FROM deadline_notifications",
+            )
+            .fetch_one(pool)
+            .await
+            .unwrap()
+            .0
+        };
+        let before = notified_at().await;
+        scheduler.send_deadline_notifications(now).await.unwrap();
+        assert_eq!(notified_at().await, before);
+
+        // Moved deadlines are notified again.
+        insert_task(pool, "3", ms(Duration::hours(3)), "Not Started").await;
+        scheduler.send_deadline_notifications(now).await.unwrap();
+        let mut expected = expected;
+        expected.push(("3".to_string(), "approaching".to_string()));
+        assert_eq!(notified(pool).await, expected);
+    }
+}
@@ -0,0 +1,722 @@
+use crate::{
+    api::{
+        google::{self, User},
+        tokens,
+    },
+    notifiers::{NotificationKind, Notifier, NotifierSettings},
+};
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    middleware,
+    routing::{get, patch, put},
+};
+use axum_anyhow::{ApiResult, OptionExt, bad_request};
+use chrono::{
+    DateTime, Datelike as _, Duration, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, postgres::PgPool};
+use tokio::task::JoinHandle;
+
+/// How often the scheduler checks for digests that are due.
+const SCHEDULE_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
+/// Local time at which daily and weekly digests are sent.
+const DIGEST_TIME: NaiveTime = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+/// How long a node may take to send a digest before another may claim its notifications.
+const CLAIM_TIMEOUT: Duration = Duration::minutes(5);
+/// Delay before the first retry of a digest that failed to send.
+const RETRY_DELAY: Duration = Duration::minutes(5);
+/// Digests that fail to send this many times are dropped.
+const MAX_ATTEMPTS: i32 = 6;
+
+/// How notifications are delivered to a notifier.
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub(crate) enum Delivery {
+    /// Sent as soon as they happen, unless during quiet hours.
+    #[default]
+    Immediate,
+    /// Batched and sent at the top of every hour.
+    Hourly,
+    /// Batched and sent every morning.
+    Daily,
+    /// Batched and sent every Monday morning.
+    Weekly,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NotificationPreferences {
+    /// IANA time zone name, like "America/New_York", used for quiet hours and digest times.
+    pub(crate) time_zone: String,
+    /// Local time at which quiet hours begin. Quiet hours may span midnight.
+    pub(crate) quiet_hours_start: Option<NaiveTime>,
+    /// Local time at which quiet hours end.
+    pub(crate) quiet_hours_end: Option<NaiveTime>,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            time_zone: "UTC".to_string(),
+            quiet_hours_start: None,
+            quiet_hours_end: None,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    fn tz(&self) -> Tz {
+        self.time_zone.parse().unwrap_or(Tz::UTC)
+    }
+
+    /// Notifications are held, rather than sent, during quiet hours.
+    pub(crate) fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        let (Some(start), Some(end)) = (self.quiet_hours_start, self.quiet_hours_end) else {
+            return false;
+        };
+        let time = now.with_timezone(&self.tz()).time();
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+
+    /// Whether notifications queued for the given delivery, the oldest at `oldest`, should be sent now.
+    pub(crate) fn is_due(
+        &self,
+        delivery: Delivery,
+        oldest: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if self.is_quiet(now) {
+            return false;
+        }
+        match self.period_start(delivery, now) {
+            Some(start) => oldest < start,
+            None => true,
+        }
+    }
+
+    /// The start of the digest period containing `now`, or None for immediate delivery.
+    fn period_start(&self, delivery: Delivery, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let tz = self.tz();
+        let local = now.with_timezone(&tz).naive_local();
+        let start = match delivery {
+            Delivery::Immediate => return None,
+            Delivery::Hourly => local.date().and_hms_opt(local.hour(), 0, 0)?,
+            Delivery::Daily => {
+                let today = local.date().and_time(DIGEST_TIME);
+                if local >= today {
+                    today
+                } else {
+                    today - Duration::days(1)
+                }
+            }
+            Delivery::Weekly => {
+                let monday = (local.date()
+                    - Duration::days(local.weekday().num_days_from_monday().into()))
+                .and_time(DIGEST_TIME);
+                if local >= monday {
+                    monday
+                } else {
+                    monday - Duration::weeks(1)
+                }
+            }
+        };
+        Some(to_utc(tz, start))
+    }
+}
+
+/// Resolve a local time, preferring the earlier time when ambiguous.
+/// Times skipped by a DST transition are resolved as if they were UTC.
+fn to_utc(tz: Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|| local.and_utc())
+}
+
+/// Combine queued messages into a single message, in the markup the notifier renders.
+pub(crate) fn render_digest(settings: &NotifierSettings, messages: &[String]) -> String {
+    let heading = format!("{} Koso notifications", messages.len());
+    let heading = match settings {
+        // Slack and Telegram render *bold*, as does the email renderer.
+        NotifierSettings::Slack(_) | NotifierSettings::Telegram(_) | NotifierSettings::Email(_) => {
+            format!("*{heading}*")
+        }
+        NotifierSettings::Discord(_) => format!("**{heading}**"),
+        // Teams messages and webhook events are plain text.
+        NotifierSettings::Teams(_) | NotifierSettings::Webhook(_) => heading,
+    };
+    match messages {
+        [message] => message.clone(),
+        messages => format!("📬 {heading}\n\n{}", messages.join("\n\n")),
+    }
+}
+
+pub(crate) async fn fetch_preferences(
+    email: &str,
+    pool: &PgPool,
+) -> Result<NotificationPreferences> {
+    let preferences: Option<NotificationPreferences> = sqlx::query_as(
+        "
+        SELECT time_zone, quiet_hours_start, quiet_hours_end
+        FROM user_notification_preferences
+        WHERE email = $1",
+    )
+    .bind(email)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to query notification preferences")?;
+    Ok(preferences.unwrap_or_default())
+}
+
+/// Hold the message until the notifier's next digest or the end of quiet hours.
+pub(crate) async fn queue_notification(
+    email: &str,
+    notifier: &str,
+    message: &str,
+    pool: &PgPool,
+) -> Result<()> {
+    sqlx::query(
+        "
+        INSERT INTO queued_notifications (email, notifier, message)
+        VALUES ($1, $2, $3)",
+    )
+    .bind(email)
+    .bind(notifier)
+    .bind(message)
+    .execute(pool)
+    .await
+    .context("Failed to queue notification")?;
+    Ok(())
+}
+
+/// Delivers digests. Implemented by `Notifier`, and faked in tests.
+#[async_trait]
+pub(crate) trait DigestSender: Sync + Send {
+    async fn send_digest(
+        &self,
+        email: &str,
+        settings: NotifierSettings,
+        message: &str,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl DigestSender for Notifier {
+    async fn send_digest(
+        &self,
+        email: &str,
+        settings: NotifierSettings,
+        message: &str,
+    ) -> Result<()> {
+        self.send(email, settings, message, None).await
+    }
+}
+
+/// DigestScheduler periodically sends queued notifications that are due.
+pub(crate) struct DigestScheduler<S = Notifier> {
+    pool: &'static PgPool,
+    sender: S,
+}
+
+impl DigestScheduler {
+    pub(crate) fn new(pool: &'static PgPool) -> Result<Self> {
+        Ok(Self::with_sender(pool, Notifier::new(pool)?))
+    }
+}
+
+impl<S: DigestSender + 'static> DigestScheduler<S> {
+    pub(crate) fn with_sender(pool: &'static PgPool, sender: S) -> Self {
+        Self { pool, sender }
+    }
+
+    pub(crate) fn start(self) -> JoinHandle<()> {
+        tokio::spawn(self.schedule())
+    }
+
+    async fn schedule(self) {
+        loop {
+            tokio::time::sleep(SCHEDULE_DELAY).await;
+            if let Err(e) = self.send_due_digests(Utc::now()).await {
+                tracing::warn!("Failed to send digests: {e:?}");
+            }
+        }
+    }
+
+    /// Send a digest to every notifier with queued notifications that are due.
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn send_due_digests(&self, now: DateTime<Utc>) -> Result<()> {
+        let queues: Vec<(String, String, DateTime<Utc>)> = sqlx::query_as(
+            "
+            SELECT email, notifier, min(queued_at)
+            FROM queued_notifications
+            WHERE retry_at IS NULL OR retry_at <= $1
+            GROUP BY email, notifier",
+        )
+        .bind(now)
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to list queued notifications")?;
+
+        for (email, notifier, oldest) in queues {
+            if let Err(e) = self.send_digest(&email, &notifier, oldest, now).await {
+                tracing::warn!("Failed to send {notifier} digest to {email}: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_digest(
+        &self,
+        email: &str,
+        notifier: &str,
+        oldest: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let config: Option<(Delivery, sqlx::types::Json<NotifierSettings>)> = sqlx::query_as(
+            "
+            SELECT delivery, settings
+            FROM user_notification_configs
+            WHERE email = $1 AND notifier = $2",
+        )
+        .bind(email)
+        .bind(notifier)
+        .fetch_optional(self.pool)
+        .await
+        .context("Failed to query notification config")?;
+        let preferences = fetch_preferences(email, self.pool).await?;
+        if let Some((delivery, _)) = &config
+            && !preferences.is_due(*delivery, oldest, now)
+        {
+            return Ok(());
+        }
+
+        // Claim the queued notifications until the send is expected to have finished.
+        // Rows claimed by another node are skipped, and no locks are held while sending.
+        let mut messages: Vec<(i64, String, i32)> = sqlx::query_as(
+            "
+            UPDATE queued_notifications
+            SET retry_at = $3
+            WHERE notification_id IN (
+              SELECT notification_id
+              FROM queued_notifications
+              WHERE email = $1 AND notifier = $2
+                AND (retry_at IS NULL OR retry_at <= $4)
+              FOR UPDATE SKIP LOCKED)
+            RETURNING notification_id, message, attempts",
+        )
+        .bind(email)
+        .bind(notifier)
+        .bind(now + CLAIM_TIMEOUT)
+        .bind(now)
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to claim queued notifications")?;
+        if messages.is_empty() {
+            return Ok(());
+        }
+        messages.sort();
+        let ids = messages.iter().map(|(id, _, _)| *id).collect::<Vec<_>>();
+
+        // Notifications queued for a notifier that has since been disconnected are dropped.
+        let res = match config {
+            Some((_, sqlx::types::Json(settings))) => {
+                let attempts = messages.iter().map(|(_, _, a)| *a).max().unwrap_or(0);
+                let messages = messages.into_iter().map(|(_, m, _)| m).collect::<Vec<_>>();
+                tracing::debug!("Sending {notifier} digest of {} to {email}", messages.len());
+                let message = render_digest(&settings, &messages);
+                match self.sender.send_digest(email, settings, &message).await {
+                    Ok(()) => Ok(()),
+                    Err(e) if attempts + 1 < MAX_ATTEMPTS => {
+                        self.retry_later(&ids, attempts, now).await?;
+                        return Err(e);
+                    }
+                    Err(e) => Err(e.context(format!(
+                        "Dropping digest of {} after {MAX_ATTEMPTS} attempts",
+                        ids.len()
+                    ))),
+                }
+            }
+            None => Ok(()),
+        };
+
+        sqlx::query("DELETE FROM queued_notifications WHERE notification_id = ANY($1)")
+            .bind(&ids)
+            .execute(self.pool)
+            .await
+            .context("Failed to delete sent notifications")?;
+        res
+    }
+
+    /// Release the claimed notifications, to be retried after a delay that doubles with each attempt.
+    async fn retry_later(&self, ids: &[i64], attempts: i32, now: DateTime<Utc>) -> Result<()> {
+        let retry_at = now + RETRY_DELAY * 2_i32.pow(attempts.clamp(0, 16) as u32);
+        sqlx::query(
+            "
+            UPDATE queued_notifications
+            SET attempts = attempts + 1, retry_at = $2
+            WHERE notification_id = ANY($1)",
+        )
+        .bind(ids)
+        .bind(retry_at)
+        .execute(self.pool)
+        .await
+        .context("Failed to release queued notifications")?;
+        Ok(())
+    }
+}
+
+pub(super) fn router() -> Router {
+    Router::new()
+        .route("/preferences", get(get_preferences))
+        .route("/preferences", put(update_preferences))
+        .route("/configs/{notifier}", patch(update_config))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
+        .layer(middleware::from_fn(google::authenticate))
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn get_preferences(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+) -> ApiResult<Json<NotificationPreferences>> {
+    Ok(Json(fetch_preferences(&user.email, pool).await?))
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn update_preferences(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Json(preferences): Json<NotificationPreferences>,
+) -> ApiResult<Json<NotificationPreferences>> {
+    if preferences.time_zone.parse::<Tz>().is_err() {
+        return Err(bad_request("INVALID_TIME_ZONE", "Unknown time zone"));
+    }
+    if preferences.quiet_hours_start.is_some() != preferences.quiet_hours_end.is_some() {
+        return Err(bad_request(
+            "INVALID_QUIET_HOURS",
+            "Quiet hours need both a start and an end",
+        ));
+    }
+
+    sqlx::query(
+        "
+        INSERT INTO user_notification_preferences (email, time_zone, quiet_hours_start, quiet_hours_end)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (email)
+        DO UPDATE SET
+          time_zone = EXCLUDED.time_zone,
+          quiet_hours_start = EXCLUDED.quiet_hours_start,
+          quiet_hours_end = EXCLUDED.quiet_hours_end",
+    )
+    .bind(&user.email)
+    .bind(&preferences.time_zone)
+    .bind(preferences.quiet_hours_start)
+    .bind(preferences.quiet_hours_end)
+    .execute(pool)
+    .await
+    .context("Failed to update notification preferences")?;
+
+    Ok(Json(preferences))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct UpdateConfig {
+    /// Left unchanged when absent.
+    delivery: Option<Delivery>,
+    /// Replaces the kinds of notifications turned off. Left unchanged when absent.
+    disabled_notifications: Option<Vec<NotificationKind>>,
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn update_config(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Path(notifier): Path<String>,
+    Json(req): Json<UpdateConfig>,
+) -> ApiResult<Json<()>> {
+    if notifier == "webhook" && req.delivery.is_some_and(|d| d != Delivery::Immediate) {
+        return Err(bad_request(
+            "INVALID_DELIVERY",
+            "Webhooks are always delivered immediately",
+        ));
+    }
+
+    let res = sqlx::query(
+        "
+        UPDATE user_notification_configs
+        SET
+          delivery = COALESCE($3, delivery),
+          disabled_notifications = COALESCE($4, disabled_notifications)
+        WHERE email = $1 AND notifier = $2",
+    )
+    .bind(&user.email)
+    .bind(&notifier)
+    .bind(req.delivery)
+    .bind(req.disabled_notifications)
+    .execute(pool)
+    .await
+    .context("Failed to update notification config")?;
+    (res.rows_affected() > 0)
+        .then_some(())
+        .context_not_found("NOT_FOUND", &format!("{notifier} is not configured"))?;
+    Ok(Json(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::db::UnsafePoolWrapper;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn preferences(time_zone: &str, quiet_hours: Option<(u32, u32)>) -> NotificationPreferences {
+        NotificationPreferences {
+            time_zone: time_zone.to_string(),
+            quiet_hours_start: quiet_hours
+                .map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+            quiet_hours_end: quiet_hours
+                .map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+        }
+    }
+
+    #[test_log::test]
+    fn is_quiet_test() {
+        let prefs = preferences("America/New_York", Some((22, 7)));
+        // 23:00 and 06:59 in New York, during EDT.
+        assert!(prefs.is_quiet(utc("2025-06-02T03:00:00Z")));
+        assert!(prefs.is_quiet(utc("2025-06-02T10:59:00Z")));
+        assert!(!prefs.is_quiet(utc("2025-06-02T11:00:00Z")));
+        assert!(!prefs.is_quiet(utc("2025-06-02T01:59:00Z")));
+
+        let prefs = preferences("UTC", Some((12, 13)));
+        assert!(prefs.is_quiet(utc("2025-06-02T12:30:00Z")));
+        assert!(!prefs.is_quiet(utc("2025-06-02T13:00:00Z")));
+
+        assert!(!preferences("UTC", None).is_quiet(utc("2025-06-02T12:30:00Z")));
+    }
+
+    #[test_log::test]
+    fn is_due_test() {
+        let prefs = preferences("Europe/Berlin", None);
+        let now = utc("2025-06-04T08:30:00Z"); // Wednesday 10:30 in Berlin.
+
+        assert!(prefs.is_due(Delivery::Immediate, now, now));
+
+        assert!(prefs.is_due(Delivery::Hourly, utc("2025-06-04T07:59:00Z"), now));
+        assert!(!prefs.is_due(Delivery::Hourly, utc("2025-06-04T08:00:00Z"), now));
+
+        // Daily digests go out at 09:00 Berlin time, 07:00 UTC.
+        assert!(prefs.is_due(Delivery::Daily, utc("2025-06-04T06:59:00Z"), now));
+        assert!(!prefs.is_due(Delivery::Daily, utc("2025-06-04T07:00:00Z"), now));
+        assert!(!prefs.is_due(
+            Delivery::Daily,
+            utc("2025-06-04T07:00:00Z"),
+            utc("2025-06-05T06:59:00Z")
+        ));
+
+        // Weekly digests go out Monday at 09:00.
+        assert!(prefs.is_due(Delivery::Weekly, utc("2025-06-02T06:59:00Z"), now));
+        assert!(!prefs.is_due(Delivery::Weekly, utc("2025-06-02T07:00:00Z"), now));
+
+        // Nothing is due during quiet hours.
+        let prefs = preferences("Europe/Berlin", Some((10, 11)));
+        assert!(!prefs.is_due(Delivery::Immediate, now, now));
+        assert!(!prefs.is_due(Delivery::Daily, utc("2025-06-01T00:00:00Z"), now));
+    }
+
+    #[test_log::test]
+    fn render_digest_test() {
+        let slack = NotifierSettings::Slack(crate::notifiers::SlackSettings {
+            user_id: "U1".to_string(),
+        });
+        let discord = NotifierSettings::Discord(crate::notifiers::DiscordSettings {
+            channel_id: "C1".to_string(),
+        });
+        let teams = NotifierSettings::Teams(crate::notifiers::TeamsSettings {
+            bot_token: "token".to_string(),
+            channel_id: "C1".to_string(),
+        });
+        let messages = ["one".to_string(), "two".to_string()];
+
+        assert_eq!(render_digest(&slack, &messages[..1]), "one");
+        assert_eq!(
+            render_digest(&slack, &messages),
+            "📬 *2 Koso notifications*\n\none\n\ntwo"
+        );
+        assert_eq!(
+            render_digest(&discord, &messages),
+            "📬 **2 Koso notifications**\n\none\n\ntwo"
+        );
+        assert_eq!(
+            render_digest(&teams, &messages),
+            "📬 2 Koso notifications\n\none\n\ntwo"
+        );
+    }
+
+    /// Records digests instead of sending them, failing while `fail` is set.
+    #[derive(Default)]
+    struct FakeSender {
+        sent: std::sync::Mutex<Vec<(String, String)>>,
+        fail: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl DigestSender for FakeSender {
+        async fn send_digest(
+            &self,
+            email: &str,
+            _settings: NotifierSettings,
+            message: &str,
+        ) -> Result<()> {
+            if self.fail.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("Failed to send"));
+            }
+            self.sent
+                .lock()
+                .unwrap()
+                .push((email.to_string(), message.to_string()));
+            Ok(())
+        }
+    }
+
+    async fn queued_count(pool: &PgPool) -> i64 {
+        sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM queued_notifications")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+            .0
+    }
+
+    async fn setup_digest(pool: &PgPool, email: &str) {
+        sqlx::query(
+            "
+            INSERT INTO user_notification_configs (email, notifier, enabled, settings, delivery)
+            VALUES ($1, 'slack', true, '{\"type\": \"slack\", \"userId\": \"U1\"}', 'daily')",
+        )
+        .bind(email)
+        .execute(pool)
+        .await
+        .unwrap();
+        queue_notification(email, "slack", "first", pool)
+            .await
+            .unwrap();
+        queue_notification(email, "slack", "second", pool)
+            .await
+            .unwrap();
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn send_due_digests_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let email = "digest@koso.app";
+        setup_digest(pool, email).await;
+        assert_eq!(queued_count(pool).await, 2);
+
+        // Not yet due.
+        let scheduler = DigestScheduler::with_sender(pool, FakeSender::default());
+        scheduler.send_due_digests(Utc::now()).await.unwrap();
+        assert_eq!(queued_count(pool).await, 2);
+        assert!(scheduler.sender.sent.lock().unwrap().is_empty());
+
+        scheduler
+            .send_due_digests(Utc::now() + Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(queued_count(pool).await, 0);
+        assert_eq!(
+            *scheduler.sender.sent.lock().unwrap(),
+            vec![(
+                email.to_string(),
+                "📬 *2 Koso notifications*\n\nfirst\n\nsecond".to_string()
+            )]
+        );
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn failed_digests_are_retried_with_backoff_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let email = "digest@koso.app";
+        setup_digest(pool, email).await;
+
+        let scheduler = DigestScheduler::with_sender(pool, FakeSender::default());
+        scheduler
+            .sender
+            .fail
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut now = Utc::now() + Duration::days(1);
+        scheduler.send_due_digests(now).await.unwrap();
+        assert_eq!(queued_count(pool).await, 2);
+        let retry_at = || async {
+            sqlx::query_as::<_, (DateTime<Utc>, i32)>(
+                "SELECT min(retry_at), min(attempts) FROM queued_notifications",
+            )
+            .fetch_one(pool)
+            .await
+            .unwrap()
+        };
+        assert_eq!(retry_at().await.1, 1);
+
+        // The digest isn't retried before the delay, which doubles after each failure.
+        let mut delay = RETRY_DELAY;
+        for attempts in 1..MAX_ATTEMPTS - 1 {
+            let (at, _) = retry_at().await;
+            assert!((at - (now + delay)).abs() < Duration::seconds(1));
+            scheduler
+                .send_due_digests(now + delay - Duration::seconds(1))
+                .await
+                .unwrap();
+            assert_eq!(retry_at().await.1, attempts);
+
+            now += delay;
+            scheduler.send_due_digests(now).await.unwrap();
+            assert_eq!(retry_at().await.1, attempts + 1);
+            delay = delay * 2;
+        }
+
+        // The last attempt drops the digest.
+        now += delay;
+        scheduler.send_due_digests(now).await.unwrap();
+        assert_eq!(queued_count(pool).await, 0);
+        assert!(scheduler.sender.sent.lock().unwrap().is_empty());
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn retried_digest_is_sent_test(pool: PgPool) {
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let email = "digest@koso.app";
+        setup_digest(pool, email).await;
+
+        let scheduler = DigestScheduler::with_sender(pool, FakeSender::default());
+        scheduler
+            .sender
+            .fail
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let now = Utc::now() + Duration::days(1);
+        scheduler.send_due_digests(now).await.unwrap();
+        assert_eq!(queued_count(pool).await, 2);
+
+        scheduler
+            .sender
+            .fail
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        scheduler.send_due_digests(now + RETRY_DELAY).await.unwrap();
+        assert_eq!(queued_count(pool).await, 0);
+        assert_eq!(scheduler.sender.sent.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::{
+    api::{
+        google::{self, User},
+        tokens,
+    },
+    notifiers::{
+        EmailSettings, NotifierSettings, delete_notification_config, insert_notification_config,
+    },
+    secrets::read_secret,
+    settings::{self, settings},
+};
+use anyhow::{Context as _, Result};
+use axum::{
+    Extension, Json, Router, middleware,
+    routing::{delete, post},
+};
+use axum_anyhow::{ApiResult, ResultExt, bad_request, forbidden, too_many_requests};
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use lettre::{
+    Address, AsyncSmtpTransport, AsyncTransport as _, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+};
+use regex::Regex;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::{
+    cell::LazyCell,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone)]
+pub(super) struct EmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailClient {
+    pub fn new() -> Result<Self> {
+        Self::from_settings(&settings().notifiers.email)
+    }
+
+    fn from_settings(email: &settings::Email) -> Result<Self> {
+        let mut builder = if email.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.smtp_host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&email.smtp_host)
+        }
+        .port(email.smtp_port);
+        if let (Ok(username), Ok(password)) = (
+            read_secret::<String>("smtp/username"),
+            read_secret::<String>("smtp/password"),
+        ) {
+            builder = builder.credentials(Credentials::new(username.data, password.data));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: email.from.parse().context("Invalid from mailbox")?,
+        })
+    }
+
+    /// Send a notification, given in the same markdown sent to the other notifiers,
+    /// as a multipart HTML and plain text email. The first line becomes the subject.
+    pub async fn send_message(&self, address: &str, markdown: &str) -> Result<()> {
+        let subject = render_text(markdown.lines().next().unwrap_or_default());
+        let subject = subject.trim().trim_end_matches(':');
+        self.send(address, subject, markdown).await
+    }
+
+    async fn send(&self, address: &str, subject: &str, markdown: &str) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(Mailbox::new(None, address.parse()?))
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(
+                render_text(markdown),
+                render_html(markdown),
+            ))
+            .context("Failed to build email")?;
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send email")?;
+        Ok(())
+    }
+}
+
+thread_local! {
+    static LINK_RE: LazyCell<Regex> = LazyCell::new(|| Regex::new(r"\[([^\]]*)\]\(([^)\s]*)\)").unwrap());
+    static BOLD_RE: LazyCell<Regex> = LazyCell::new(|| Regex::new(r"\*([^*\n]+)\*").unwrap());
+}
+
+/// Render the markdown subset used in notifications, bold text and links, as plain text.
+fn render_text(markdown: &str) -> String {
+    let text = LINK_RE.with(|re| re.replace_all(markdown, "$1 ($2)").into_owned());
+    BOLD_RE.with(|re| re.replace_all(&text, "$1").into_owned())
+}
+
+/// Render the markdown subset used in notifications, bold text and links, as HTML.
+fn render_html(markdown: &str) -> String {
+    let html = markdown
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+    let html = LINK_RE.with(|re| re.replace_all(&html, r#"<a href="$2">$1</a>"#).into_owned());
+    let html = BOLD_RE.with(|re| re.replace_all(&html, "<b>$1</b>").into_owned());
+    format!("<html><body>{}</body></html>", html.replace('\n', "<br>\n"))
+}
+
+pub(super) fn router() -> Router {
+    Router::new()
+        .route("/", post(authorize_email))
+        .route("/", delete(deauthorize_email))
+        .route("/verification", post(send_verification))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
+        .layer(middleware::from_fn(google::authenticate))
+}
+
+const ISSUER: &str = "koso-notifiers-email";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Claims {
+    exp: u64,
+    iss: String,
+    /// The Koso user who requested the verification.
+    email: String,
+    /// The address being verified.
+    address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SendVerification {
+    address: String,
+}
+
+/// Maximum verification emails a user may request per `VERIFICATION_WINDOW`.
+const MAX_VERIFICATIONS_PER_USER: i64 = 5;
+/// Maximum verification emails sent to an address per `VERIFICATION_WINDOW`,
+/// limiting how often any one address can be mailed, whoever asks.
+const MAX_VERIFICATIONS_PER_ADDRESS: i64 = 3;
+const VERIFICATION_WINDOW: TimeDelta = TimeDelta::hours(1);
+
+/// Email a link, containing a signed token, to the given address.
+/// Following the link proves the user controls the address and completes the authorization.
+#[tracing::instrument(skip(user, pool, key))]
+async fn send_verification(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(key): Extension<EncodingKey>,
+    Json(req): Json<SendVerification>,
+) -> ApiResult<Json<()>> {
+    let Ok(address) = req.address.parse::<Address>() else {
+        return Err(bad_request("INVALID_ADDRESS", "Invalid email address"));
+    };
+    let address = address.to_string();
+    record_verification(&user.email, &address, pool).await?;
+
+    let url = get_auth_url(key, &user.email, &address)?;
+    let message = format!(
+        "Follow this link to receive Koso notifications for *{}* at this address: [{url}]({url})\n\nIf you didn't request this, you can ignore this email.",
+        user.email
+    );
+    EmailClient::new()?
+        .send(
+            &address,
+            "Verify your email for Koso notifications",
+            &message,
+        )
+        .await?;
+    Ok(Json(()))
+}
+
+/// Record a requested verification email, rejecting it if the user or address
+/// has requested too many recently. Rejected requests count too, so retrying
+/// doesn't shorten the wait.
+async fn record_verification(email: &str, address: &str, pool: &PgPool) -> ApiResult<()> {
+    let address = address.to_lowercase();
+    let since = Utc::now() - VERIFICATION_WINDOW;
+    sqlx::query("DELETE FROM email_verifications WHERE requested_at < $1")
+        .bind(since)
+        .execute(pool)
+        .await
+        .context("Failed to prune email verifications")?;
+    // Insert before counting so concurrent requests can't all slip under the limit.
+    sqlx::query("INSERT INTO email_verifications (email, address) VALUES ($1, $2)")
+        .bind(email)
+        .bind(&address)
+        .execute(pool)
+        .await
+        .context("Failed to record email verification")?;
+    let (by_user, by_address): (i64, i64) = sqlx::query_as(
+        "
+        SELECT
+          COUNT(*) FILTER (WHERE email = $1),
+          COUNT(*) FILTER (WHERE address = $2)
+        FROM email_verifications
+        WHERE (email = $1 OR address = $2)
+        AND requested_at >= $3",
+    )
+    .bind(email)
+    .bind(&address)
+    .bind(since)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count email verifications")?;
+
+    if by_user > MAX_VERIFICATIONS_PER_USER || by_address > MAX_VERIFICATIONS_PER_ADDRESS {
+        return Err(too_many_requests(
+            "TOO_MANY_VERIFICATIONS",
+            "Too many verification emails requested. Try again later",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AuthorizeEmail {
+    token: String,
+}
+
+#[tracing::instrument(skip(user, pool, key))]
+async fn authorize_email(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Extension(key): Extension<DecodingKey>,
+    Json(req): Json<AuthorizeEmail>,
+) -> ApiResult<Json<NotifierSettings>> {
+    let mut validation = Validation::default();
+    validation.set_issuer(&[ISSUER]);
+    validation.required_spec_claims.insert("iss".to_string());
+    let token = decode::<Claims>(&req.token, &key, &validation).context_status(
+        StatusCode::PRECONDITION_FAILED,
+        "VALIDATION_FAILED",
+        "Invalid token",
+    )?;
+    if token.claims.email != user.email {
+        return Err(forbidden(
+            "VALIDATION_FAILED",
+            "Token was issued to a different user",
+        ));
+    }
+
+    let settings = NotifierSettings::Email(EmailSettings {
+        address: token.claims.address,
+    });
+
+    insert_notification_config(&user.email, &settings, pool).await?;
+
+    Ok(Json(settings))
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn deauthorize_email(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+) -> ApiResult<Json<()>> {
+    delete_notification_config(&user.email, "email", pool).await?;
+    Ok(Json(()))
+}
+
+fn get_auth_url(key: EncodingKey, email: &str, address: &str) -> Result<String> {
+    let host = &settings().host;
+    let timer = SystemTime::now() + Duration::from_secs(60 * 60);
+    let claims = Claims {
+        exp: timer.duration_since(UNIX_EPOCH)?.as_secs(),
+        iss: ISSUER.to_string(),
+        email: email.into(),
+        address: address.into(),
+    };
+    let token = encode(&Header::default(), &claims, &key)?;
+    tracing::debug!("Generated auth token {token} for {address}");
+    Ok(format!("{host}/connections/email?token={token}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    #[test_log::test]
+    fn render_text_test() {
+        assert_eq!(
+            render_text(
+                "🎁 *Alice* assigned to you:\n[Task 1](https://koso.app/projects/p?taskId=t)"
+            ),
+            "🎁 Alice assigned to you:\nTask 1 (https://koso.app/projects/p?taskId=t)"
+        );
+    }
+
+    #[test_log::test]
+    fn render_html_test() {
+        assert_eq!(
+            render_html(
+                "🎁 *Alice* assigned to you:\n[<Task> & 1](https://koso.app/projects/p?taskId=t&a=b)"
+            ),
+            "<html><body>🎁 <b>Alice</b> assigned to you:<br>\n<a href=\"https://koso.app/projects/p?taskId=t&amp;a=b\">&lt;Task&gt; &amp; 1</a></body></html>"
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn send_message_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = serve_smtp(listener);
+
+        let client = EmailClient::from_settings(&settings::Email {
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: port,
+            starttls: false,
+            from: "Koso <notifications@koso.app>".to_string(),
+        })
+        .unwrap();
+        client
+            .send_message(
+                "assignee@koso.app",
+                "🎁 *Alice* assigned to you:\n[Task 1](https://koso.app/projects/p?taskId=t)",
+            )
+            .await
+            .unwrap();
+
+        let (commands, data) = server.await.unwrap();
+        assert!(commands.contains(&"MAIL FROM:<notifications@koso.app>".to_string()));
+        assert!(commands.contains(&"RCPT TO:<assignee@koso.app>".to_string()));
+        assert!(data.contains("Subject:"), "Unexpected data: {data}");
+        assert!(
+            data.contains("multipart/alternative"),
+            "Unexpected data: {data}"
+        );
+        assert!(data.contains("text/plain"), "Unexpected data: {data}");
+        assert!(data.contains("text/html"), "Unexpected data: {data}");
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn send_message_rejects_invalid_address() {
+        let client = EmailClient::from_settings(&settings::Email {
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: 1,
+            starttls: false,
+            from: "Koso <notifications@koso.app>".to_string(),
+        })
+        .unwrap();
+        assert!(client.send_message("not an address", "hi").await.is_err());
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn record_verification_test(pool: PgPool) {
+        let user = "user@koso.app";
+        for _ in 0..MAX_VERIFICATIONS_PER_ADDRESS {
+            record_verification(user, "Address@koso.app", &pool)
+                .await
+                .unwrap();
+        }
+        // Addresses are limited regardless of case.
+        let err = record_verification(user, "address@koso.app", &pool)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(err.title(), "TOO_MANY_VERIFICATIONS");
+
+        // The user, counting the rejected request, may request one more.
+        record_verification(user, "other@koso.app", &pool)
+            .await
+            .unwrap();
+        assert!(
+            record_verification(user, "another@koso.app", &pool)
+                .await
+                .is_err()
+        );
+
+        // Other users are limited by address only.
+        record_verification("other-user@koso.app", "other@koso.app", &pool)
+            .await
+            .unwrap();
+        assert!(
+            record_verification("other-user@koso.app", "address@koso.app", &pool)
+                .await
+                .is_err()
+        );
+
+        // Requests age out of the window.
+        sqlx::query("UPDATE email_verifications SET requested_at = $1")
+            .bind(Utc::now() - VERIFICATION_WINDOW - TimeDelta::minutes(1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        record_verification(user, "address@koso.app", &pool)
+            .await
+            .unwrap();
+    }
+
+    /// A minimal SMTP server accepting a single message.
+    /// Returns the commands received and the message data.
+    fn serve_smtp(listener: TcpListener) -> JoinHandle<(Vec<String>, String)> {
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            let mut commands = vec![];
+            let mut data = String::new();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                commands.push(line.clone());
+                let reply: &[u8] = match line.split(' ').next().unwrap().to_uppercase().as_str() {
+                    "EHLO" | "HELO" => b"250 localhost\r\n",
+                    "DATA" => {
+                        writer.write_all(b"354 Go ahead\r\n").await.unwrap();
+                        while let Some(line) = lines.next_line().await.unwrap() {
+                            if line == "." {
+                                break;
+                            }
+                            data.push_str(&line);
+                            data.push('\n');
+                        }
+                        b"250 OK\r\n"
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 Bye\r\n").await.unwrap();
+                        break;
+                    }
+                    _ => b"250 OK\r\n",
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            (commands, data)
+        })
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    api::google::{self, User},
+    api::{
+        google::{self, User},
+        tokens,
+    },
     notifiers::{
         NotifierSettings, SlackSettings, delete_notification_config, insert_notification_config,
     },
@@ -72,6 +75,7 @@ pub(super) fn router() -> Router {
     let routes = Router::new()
         .route("/", post(authorize_slack))
         .route("/", delete(deauthorize_slack))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
         .layer(middleware::from_fn(google::authenticate));
 
     let webhooks = Router::new()
@@ -1,5 +1,6 @@
 use crate::api::google;
 use crate::api::google::User;
+use crate::api::tokens;
 use crate::notifiers::{
     NotifierSettings, TelegramSettings, delete_notification_config, insert_notification_config,
 };
@@ -74,6 +75,7 @@ pub(super) fn router() -> Router {
     let routes = Router::new()
         .route("/", post(authorize_telegram))
         .route("/", delete(deauthorize_telegram))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
         .layer(middleware::from_fn(google::authenticate));
 
     let webhooks = Router::new()
@@ -0,0 +1,820 @@
+use crate::{
+    api::{
+        collab::txn_origin::Actor,
+        google::{self, User},
+        model::{ProjectId, Task},
+        tokens,
+    },
+    net::{self, PublicResolver},
+    notifiers::{
+        NotifierSettings, WebhookSettings, delete_notification_config, insert_notification_config,
+    },
+    settings::settings,
+};
+use anyhow::{Context as _, Result};
+use axum::{
+    Extension, Json, Router, middleware,
+    routing::{delete, get, post},
+};
+use axum_anyhow::{ApiResult, bad_request};
+use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac as _};
+use reqwest::{StatusCode, Url, redirect};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::{
+    postgres::PgPool,
+    types::chrono::{DateTime, Utc},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio_util::task::TaskTracker;
+use uuid::Uuid;
+
+/// Total number of attempts made to deliver an event, including the first.
+const MAX_ATTEMPTS: i32 = 3;
+/// Delay before the first retry. Doubled for each subsequent retry.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of deliveries listed, and kept, per webhook.
+const DELIVERY_LOG_LIMIT: i64 = 100;
+/// Deliveries older than this are pruned.
+const DELIVERY_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// HMAC-SHA256 of the body, keyed with the webhook's secret, in the same
+/// `sha256=<hex>` format as GitHub's `X-Hub-Signature-256`.
+pub(crate) const SIGNATURE_HEADER: &str = "X-Koso-Signature-256";
+pub(crate) const EVENT_HEADER: &str = "X-Koso-Event";
+pub(crate) const DELIVERY_HEADER: &str = "X-Koso-Delivery";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WebhookEventKind {
+    /// Sent by test notifications.
+    #[serde(rename = "ping")]
+    Ping,
+    #[serde(rename = "task.created")]
+    TaskCreated,
+    #[serde(rename = "task.deleted")]
+    TaskDeleted,
+    #[serde(rename = "task.assigned")]
+    TaskAssigned,
+    #[serde(rename = "task.status_changed")]
+    TaskStatusChanged,
+    /// A blocked task became actionable once the tasks it was waiting on were done.
+    #[serde(rename = "task.unblocked")]
+    TaskUnblocked,
+    /// A user was @mentioned in the task's description.
+    #[serde(rename = "task.mentioned")]
+    TaskMentioned,
+    /// The task is due within a day.
+    #[serde(rename = "task.deadline_approaching")]
+    TaskDeadlineApproaching,
+    /// The task is past its deadline and not done.
+    #[serde(rename = "task.deadline_passed")]
+    TaskDeadlinePassed,
+}
+
+impl WebhookEventKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventKind::Ping => "ping",
+            WebhookEventKind::TaskCreated => "task.created",
+            WebhookEventKind::TaskDeleted => "task.deleted",
+            WebhookEventKind::TaskAssigned => "task.assigned",
+            WebhookEventKind::TaskStatusChanged => "task.status_changed",
+            WebhookEventKind::TaskUnblocked => "task.unblocked",
+            WebhookEventKind::TaskMentioned => "task.mentioned",
+            WebhookEventKind::TaskDeadlineApproaching => "task.deadline_approaching",
+            WebhookEventKind::TaskDeadlinePassed => "task.deadline_passed",
+        }
+    }
+}
+
+/// The JSON body POSTed to webhooks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebhookEvent {
+    pub(crate) event: WebhookEventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) project_id: Option<ProjectId>,
+    /// The task after the change. Only the ID is known for deleted tasks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) task: Option<Task>,
+    /// The value of the changed field before the change, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) previous: Option<String>,
+    /// The email of the user who made the change, or "koso" or "github" for automated changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) actor: Option<String>,
+    /// The message sent to the recipient's other notifiers, for events delivered to a user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+    /// Milliseconds since the epoch.
+    pub(crate) timestamp: i64,
+}
+
+impl WebhookEvent {
+    pub(crate) fn task(
+        event: WebhookEventKind,
+        project_id: &ProjectId,
+        task: Task,
+        actor: &Actor,
+    ) -> Result<Self> {
+        Ok(WebhookEvent {
+            event,
+            project_id: Some(project_id.clone()),
+            task: Some(task),
+            previous: None,
+            actor: Some(
+                match actor {
+                    Actor::User(user) => user.email.as_str(),
+                    Actor::GitHub => "github",
+                    Actor::GitLab => "gitlab",
+                    Actor::None | Actor::Server => "koso",
+                }
+                .to_string(),
+            ),
+            message: None,
+            timestamp: now()?,
+        })
+    }
+
+    pub(crate) fn ping(message: &str) -> Result<Self> {
+        Ok(WebhookEvent {
+            event: WebhookEventKind::Ping,
+            project_id: None,
+            task: None,
+            previous: None,
+            actor: None,
+            message: Some(message.to_string()),
+            timestamp: now()?,
+        })
+    }
+
+    pub(crate) fn with_previous(mut self, previous: Option<String>) -> Self {
+        self.previous = previous;
+        self
+    }
+}
+
+/// Where an event is delivered.
+#[derive(Debug, Clone)]
+pub(crate) struct WebhookTarget {
+    pub(crate) url: String,
+    pub(crate) secret: String,
+    /// The project webhook, if delivering to one.
+    pub(crate) webhook_id: Option<String>,
+    /// The user, if delivering to their webhook notifier.
+    pub(crate) email: Option<String>,
+}
+
+/// An entry in the delivery log.
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WebhookDelivery {
+    pub(crate) delivery_id: String,
+    pub(crate) url: String,
+    pub(crate) event: String,
+    pub(crate) payload: Value,
+    pub(crate) attempts: i32,
+    /// The status of the last response, if any.
+    pub(crate) status_code: Option<i32>,
+    /// Why the last attempt failed, if it did.
+    pub(crate) error: Option<String>,
+    pub(crate) succeeded: bool,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub(crate) struct WebhookClient {
+    client: reqwest::Client,
+    retry_delay: Duration,
+    /// Whether webhooks may be delivered to non-public addresses, like localhost.
+    allow_private_addresses: bool,
+    /// Tracks deliveries running in the background.
+    tracker: TaskTracker,
+}
+
+impl WebhookClient {
+    pub fn new() -> Result<Self> {
+        Self::build(settings().is_dev())
+    }
+
+    fn build(allow_private_addresses: bool) -> Result<Self> {
+        // Redirects aren't followed since they could lead anywhere,
+        // including to the non-public addresses webhooks are barred from.
+        let mut builder = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent("Koso-Webhook")
+            .redirect(redirect::Policy::none());
+        if !allow_private_addresses {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        Ok(Self {
+            client: builder.build()?,
+            retry_delay: INITIAL_RETRY_DELAY,
+            allow_private_addresses,
+            tracker: TaskTracker::new(),
+        })
+    }
+
+    /// Deliver the event in the background. See `deliver`.
+    pub(crate) fn spawn_delivery(
+        &self,
+        target: WebhookTarget,
+        event: WebhookEvent,
+        pool: &'static PgPool,
+    ) {
+        let client = self.clone();
+        self.tracker.spawn(async move {
+            if let Err(e) = client.deliver(&target, &event, pool).await {
+                tracing::warn!("Failed to deliver webhook event to {}: {e:?}", target.url);
+            }
+        });
+    }
+
+    /// Deliver the event to each of the project's webhooks subscribed to it, in the background.
+    pub(crate) async fn publish(
+        &self,
+        project_id: &ProjectId,
+        event: &WebhookEvent,
+        pool: &'static PgPool,
+    ) -> Result<()> {
+        let webhooks: Vec<(String, String, String)> = sqlx::query_as(
+            "
+            SELECT webhook_id, url, secret
+            FROM project_webhooks
+            WHERE project_id = $1
+            AND (events IS NULL OR $2 = ANY(events))",
+        )
+        .bind(project_id)
+        .bind(event.event.as_str())
+        .fetch_all(pool)
+        .await
+        .context("Failed to list project webhooks")?;
+
+        for (webhook_id, url, secret) in webhooks {
+            self.spawn_delivery(
+                WebhookTarget {
+                    url,
+                    secret,
+                    webhook_id: Some(webhook_id),
+                    email: None,
+                },
+                event.clone(),
+                pool,
+            );
+        }
+        Ok(())
+    }
+
+    /// POST the signed event to the target, retrying with exponential backoff
+    /// on connection failures, server errors and rate limiting.
+    /// Every delivery, successful or not, is recorded in the delivery log.
+    /// Targets are checked again on delivery, since their addresses may have changed.
+    /// The log records the category of errors only, so it can't be used to probe
+    /// the network the webhooks are delivered from.
+    pub(crate) async fn deliver(
+        &self,
+        target: &WebhookTarget,
+        event: &WebhookEvent,
+        pool: &PgPool,
+    ) -> Result<WebhookDelivery> {
+        let delivery_id = BASE64_URL_SAFE_NO_PAD.encode(Uuid::new_v4());
+        let body = serde_json::to_vec(event)?;
+        let signature = sign(&body, &target.secret)?;
+
+        let mut attempts = 0;
+        let mut status_code = None;
+        let mut error = None;
+        let mut succeeded = false;
+        let mut delay = self.retry_delay;
+        let blocked = !self.allow_private_addresses
+            && match check_target_url(&target.url).await {
+                Ok(()) => false,
+                Err(e) => {
+                    tracing::debug!("Blocked delivery to {}: {e:?}", target.url);
+                    error = Some(ERROR_BLOCKED.to_string());
+                    true
+                }
+            };
+        while !blocked && attempts < MAX_ATTEMPTS {
+            if attempts > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            attempts += 1;
+
+            match self
+                .client
+                .post(&target.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event.event.as_str())
+                .header(DELIVERY_HEADER, &delivery_id)
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await
+            {
+                Ok(res) => {
+                    let status = res.status();
+                    status_code = Some(i32::from(status.as_u16()));
+                    if status.is_success() {
+                        error = None;
+                        succeeded = true;
+                        break;
+                    }
+                    error = Some(format!("Unexpected status {status}"));
+                    // Redirects and other client errors won't succeed on retry.
+                    if status.is_redirection()
+                        || (status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS)
+                    {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!("Failed to deliver to {}: {e:?}", target.url);
+                    status_code = None;
+                    error = Some(error_category(&e).to_string());
+                }
+            }
+        }
+        if !succeeded {
+            tracing::debug!(
+                "Failed to deliver {} to {} after {attempts} attempts: {error:?}",
+                event.event.as_str(),
+                target.url
+            );
+        }
+
+        let delivery = sqlx::query_as(
+            "
+            INSERT INTO webhook_deliveries
+              (delivery_id, webhook_id, email, url, event, payload, attempts, status_code, error, succeeded)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING delivery_id, url, event, payload, attempts, status_code, error, succeeded, created_at",
+        )
+        .bind(&delivery_id)
+        .bind(&target.webhook_id)
+        .bind(&target.email)
+        .bind(&target.url)
+        .bind(event.event.as_str())
+        .bind(sqlx::types::Json(event))
+        .bind(attempts)
+        .bind(status_code)
+        .bind(&error)
+        .bind(succeeded)
+        .fetch_one(pool)
+        .await
+        .context("Failed to record webhook delivery")?;
+        prune_deliveries(target, pool).await?;
+        Ok(delivery)
+    }
+
+    /// Wait for background deliveries to finish.
+    pub(crate) async fn close(&self) {
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+}
+
+const ERROR_BLOCKED: &str = "Blocked: the URL does not resolve to a public address";
+const ERROR_TIMEOUT: &str = "Timed out";
+const ERROR_CONNECT: &str = "Failed to connect";
+const ERROR_REQUEST: &str = "Request failed";
+
+fn error_category(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        ERROR_TIMEOUT
+    } else if e.is_connect() {
+        ERROR_CONNECT
+    } else {
+        ERROR_REQUEST
+    }
+}
+
+async fn check_target_url(url: &str) -> Result<()> {
+    net::check_public_url(&Url::parse(url)?).await
+}
+
+/// Sign the body with the secret, in the format of the `SIGNATURE_HEADER`.
+pub(crate) fn sign(body: &[u8], secret: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(body);
+    Ok(format!(
+        "sha256={}",
+        hex::encode(mac.finalize().into_bytes())
+    ))
+}
+
+pub(crate) fn generate_secret() -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+}
+
+/// Webhooks must be HTTPS and resolve to public addresses, except in development.
+pub(crate) async fn validate_url(url: &str) -> ApiResult<()> {
+    let Ok(parsed) = Url::parse(url) else {
+        return Err(bad_request("INVALID_URL", "Invalid webhook URL"));
+    };
+    match parsed.scheme() {
+        "https" => {}
+        "http" if settings().is_dev() => {}
+        _ => return Err(bad_request("INVALID_URL", "Webhook URL must use https")),
+    }
+    if !settings().is_dev()
+        && let Err(e) = check_target_url(url).await
+    {
+        tracing::debug!("Rejected webhook URL {url}: {e:?}");
+        return Err(bad_request(
+            "INVALID_URL",
+            "Webhook URL must resolve to a public address",
+        ));
+    }
+    Ok(())
+}
+
+/// List the most recent deliveries to the given project webhook or user's webhook notifier.
+pub(crate) async fn list_deliveries(
+    webhook_id: Option<&str>,
+    email: Option<&str>,
+    pool: &PgPool,
+) -> Result<Vec<WebhookDelivery>> {
+    sqlx::query_as(
+        "
+        SELECT delivery_id, url, event, payload, attempts, status_code, error, succeeded, created_at
+        FROM webhook_deliveries
+        WHERE ($1::varchar IS NOT NULL AND webhook_id = $1)
+        OR ($2::varchar IS NOT NULL AND webhook_id IS NULL AND email = $2)
+        ORDER BY created_at DESC
+        LIMIT $3",
+    )
+    .bind(webhook_id)
+    .bind(email)
+    .bind(DELIVERY_LOG_LIMIT)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list webhook deliveries")
+}
+
+/// Delete deliveries older than `DELIVERY_RETENTION`, and the target's deliveries
+/// beyond the `DELIVERY_LOG_LIMIT` most recent, which are never listed.
+async fn prune_deliveries(target: &WebhookTarget, pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        "
+        DELETE FROM webhook_deliveries
+        WHERE created_at < NOW() - make_interval(secs => $3)
+        OR delivery_id IN (
+            SELECT delivery_id FROM webhook_deliveries
+            WHERE ($1::varchar IS NOT NULL AND webhook_id = $1)
+            OR ($1::varchar IS NULL AND webhook_id IS NULL AND email = $2)
+            ORDER BY created_at DESC
+            OFFSET $4
+        )",
+    )
+    .bind(&target.webhook_id)
+    .bind(&target.email)
+    .bind(DELIVERY_RETENTION.as_secs_f64())
+    .bind(DELIVERY_LOG_LIMIT)
+    .execute(pool)
+    .await
+    .context("Failed to prune webhook deliveries")?;
+    Ok(result.rows_affected())
+}
+
+fn now() -> Result<i64> {
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_millis()
+        .try_into()?)
+}
+
+pub(super) fn router() -> Router {
+    Router::new()
+        .route("/", post(authorize_webhook))
+        .route("/", delete(deauthorize_webhook))
+        .route("/deliveries", get(list_deliveries_handler))
+        .layer(middleware::from_fn(tokens::reject_project_scoped_tokens))
+        .layer(middleware::from_fn(google::authenticate))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AuthorizeWebhook {
+    url: String,
+    /// Generated when absent.
+    secret: Option<String>,
+}
+
+/// Configure the user's webhook notifier. The response includes the signing secret.
+#[tracing::instrument(skip(user, pool, req))]
+async fn authorize_webhook(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+    Json(req): Json<AuthorizeWebhook>,
+) -> ApiResult<Json<NotifierSettings>> {
+    validate_url(&req.url).await?;
+    let secret = match req.secret {
+        Some(secret) if secret.is_empty() => {
+            return Err(bad_request("INVALID_SECRET", "Secret must not be empty"));
+        }
+        Some(secret) => secret,
+        None => generate_secret(),
+    };
+
+    let settings = NotifierSettings::Webhook(WebhookSettings {
+        url: req.url,
+        secret,
+    });
+
+    insert_notification_config(&user.email, &settings, pool).await?;
+
+    Ok(Json(settings))
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn deauthorize_webhook(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+) -> ApiResult<Json<()>> {
+    delete_notification_config(&user.email, "webhook", pool).await?;
+    Ok(Json(()))
+}
+
+#[tracing::instrument(skip(user, pool))]
+async fn list_deliveries_handler(
+    Extension(user): Extension<User>,
+    Extension(pool): Extension<&'static PgPool>,
+) -> ApiResult<Json<Vec<WebhookDelivery>>> {
+    let deliveries = list_deliveries(None, Some(&user.email), pool).await?;
+    Ok(Json(deliveries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+    };
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    #[test_log::test]
+    fn sign_test() {
+        let signature = sign(b"{\"event\":\"ping\"}", "secret").unwrap();
+        let hex = signature.strip_prefix("sha256=").unwrap();
+        Hmac::<Sha256>::new_from_slice(b"secret")
+            .unwrap()
+            .chain_update(b"{\"event\":\"ping\"}")
+            .verify_slice(&hex::decode(hex).unwrap())
+            .unwrap();
+    }
+
+    #[test_log::test]
+    fn serialize_event_test() {
+        let event = WebhookEvent::task(
+            WebhookEventKind::TaskStatusChanged,
+            &"project-1".to_string(),
+            Task {
+                id: "t1".to_string(),
+                ..Task::default()
+            },
+            &Actor::GitHub,
+        )
+        .unwrap()
+        .with_previous(Some("In Progress".to_string()));
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["event"], "task.status_changed");
+        assert_eq!(value["projectId"], "project-1");
+        assert_eq!(value["task"]["id"], "t1");
+        assert_eq!(value["previous"], "In Progress");
+        assert_eq!(value["actor"], "github");
+        assert!(value.get("message").is_none());
+    }
+
+    /// Serve a webhook endpoint that responds with the given statuses, in order,
+    /// recording the headers and body of every request.
+    async fn serve_webhook(
+        statuses: Vec<StatusCode>,
+    ) -> (String, Arc<Mutex<Vec<(HeaderMap, Bytes)>>>) {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = {
+            let requests = requests.clone();
+            axum::Router::new().route(
+                "/hook",
+                post(move |headers: HeaderMap, body: Bytes| async move {
+                    let mut requests = requests.lock().unwrap();
+                    requests.push((headers, body));
+                    statuses
+                        .get(requests.len() - 1)
+                        .copied()
+                        .unwrap_or(StatusCode::OK)
+                }),
+            )
+        };
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    fn client() -> WebhookClient {
+        WebhookClient {
+            retry_delay: Duration::from_millis(1),
+            ..WebhookClient::build(true).unwrap()
+        }
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn deliver_blocks_private_addresses_test(pool: PgPool) {
+        let (url, requests) = serve_webhook(vec![]).await;
+        let target = WebhookTarget {
+            url,
+            secret: "secret".to_string(),
+            webhook_id: Some("webhook-1".to_string()),
+            email: None,
+        };
+
+        let client = WebhookClient {
+            retry_delay: Duration::from_millis(1),
+            ..WebhookClient::build(false).unwrap()
+        };
+        let delivery = client
+            .deliver(&target, &WebhookEvent::ping("hi").unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(!delivery.succeeded);
+        assert_eq!(delivery.attempts, 0);
+        assert_eq!(delivery.error.as_deref(), Some(ERROR_BLOCKED));
+        assert!(requests.lock().unwrap().is_empty());
+
+        // Hosts resolving to private addresses are refused by the resolver too.
+        let err = client
+            .client
+            .get("http://localhost:1/hook")
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(error_category(&err), ERROR_CONNECT);
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn deliver_does_not_follow_redirects_test(pool: PgPool) {
+        let (url, requests) = serve_webhook(vec![StatusCode::FOUND]).await;
+        let target = WebhookTarget {
+            url,
+            secret: "secret".to_string(),
+            webhook_id: Some("webhook-1".to_string()),
+            email: None,
+        };
+
+        let delivery = client()
+            .deliver(&target, &WebhookEvent::ping("hi").unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(!delivery.succeeded);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.status_code, Some(302));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn deliver_test(pool: PgPool) {
+        let (url, requests) = serve_webhook(vec![StatusCode::BAD_GATEWAY]).await;
+        let target = WebhookTarget {
+            url: url.clone(),
+            secret: "secret".to_string(),
+            webhook_id: None,
+            email: Some("a@koso.app".to_string()),
+        };
+
+        let delivery = client()
+            .deliver(&target, &WebhookEvent::ping("hi").unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(delivery.succeeded);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status_code, Some(200));
+        assert_eq!(delivery.error, None);
+        assert_eq!(delivery.payload["message"], "hi");
+
+        let deliveries = list_deliveries(None, Some("a@koso.app"), &pool)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].delivery_id, delivery.delivery_id);
+        assert_eq!(deliveries[0].url, url);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let (headers, body) = &requests[1];
+        assert_eq!(headers[EVENT_HEADER], "ping");
+        assert_eq!(headers[DELIVERY_HEADER], delivery.delivery_id.as_str());
+        assert_eq!(
+            headers[SIGNATURE_HEADER],
+            sign(body, "secret").unwrap().as_str()
+        );
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn deliver_gives_up_test(pool: PgPool) {
+        let (url, requests) = serve_webhook(vec![
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::OK,
+        ])
+        .await;
+        let target = WebhookTarget {
+            url,
+            secret: "secret".to_string(),
+            webhook_id: Some("webhook-1".to_string()),
+            email: None,
+        };
+
+        let delivery = client()
+            .deliver(&target, &WebhookEvent::ping("hi").unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(!delivery.succeeded);
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS);
+        assert_eq!(delivery.status_code, Some(500));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn deliver_does_not_retry_client_errors_test(pool: PgPool) {
+        let (url, requests) = serve_webhook(vec![StatusCode::NOT_FOUND]).await;
+        let target = WebhookTarget {
+            url,
+            secret: "secret".to_string(),
+            webhook_id: Some("webhook-1".to_string()),
+            email: None,
+        };
+
+        let delivery = client()
+            .deliver(&target, &WebhookEvent::ping("hi").unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(!delivery.succeeded);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.status_code, Some(404));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn deliver_prunes_deliveries_test(pool: PgPool) {
+        let insert = |delivery_id: String, webhook_id: &'static str, age_days: i32| {
+            sqlx::query(
+                "
+                INSERT INTO webhook_deliveries
+                  (delivery_id, webhook_id, url, event, payload, attempts, succeeded, created_at)
+                VALUES ($1, $2, 'https://example.com', 'ping', '{}', 1, true, NOW() - make_interval(days => $3))",
+            )
+            .bind(delivery_id)
+            .bind(webhook_id)
+            .bind(age_days)
+            .execute(&pool)
+        };
+        // An expired delivery of another webhook, and a full log for this one.
+        insert("expired".to_string(), "webhook-2", 31)
+            .await
+            .unwrap();
+        insert("recent".to_string(), "webhook-2", 1).await.unwrap();
+        for i in 0..DELIVERY_LOG_LIMIT {
+            insert(format!("old-{i}"), "webhook-1", 2).await.unwrap();
+        }
+
+        let (url, _) = serve_webhook(vec![]).await;
+        let target = WebhookTarget {
+            url,
+            secret: "secret".to_string(),
+            webhook_id: Some("webhook-1".to_string()),
+            email: None,
+        };
+        let delivery = client()
+            .deliver(&target, &WebhookEvent::ping("hi").unwrap(), &pool)
+            .await
+            .unwrap();
+
+        let deliveries = list_deliveries(Some("webhook-1"), None, &pool)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len() as i64, DELIVERY_LOG_LIMIT);
+        assert_eq!(deliveries[0].delivery_id, delivery.delivery_id);
+        let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM webhook_deliveries")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, DELIVERY_LOG_LIMIT + 1);
+        let remaining = list_deliveries(Some("webhook-2"), None, &pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].delivery_id, "recent");
+    }
+}
@@ -5,6 +5,7 @@ use crate::{
     api::{
         self,
         google::{self, User},
+        tokens::verify_interactive_user,
     },
     settings::settings,
 };
@@ -16,17 +17,21 @@ use axum::{
     http::{HeaderMap, HeaderValue, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
 };
-use axum_anyhow::{ApiResult, ResultExt, bad_request, unauthorized};
+use axum_anyhow::{ApiResult, OptionExt, ResultExt, bad_request, unauthorized};
 use base64::{
     Engine as _,
     prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
 };
+use chrono::TimeDelta;
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use sha2::{Digest, Sha256};
-use sqlx::PgPool;
+use sqlx::{
+    PgPool,
+    types::chrono::{DateTime, Utc},
+};
 use std::{
     collections::{HashMap, hash_map::Entry},
     sync::Arc,
@@ -80,6 +85,11 @@ pub(crate) fn router(pool: &'static PgPool) -> Result<Router> {
             Router::new()
                 .route("/register", post(oauth_register).options(oauth_register))
                 .route("/token", post(oauth_token).options(oauth_token))
+                .route("/revoke", post(oauth_revoke).options(oauth_revoke))
+                .route(
+                    "/introspect",
+                    post(oauth_introspect).options(oauth_introspect),
+                )
                 .layer(cors_layer)
                 .route(
                     "/authorization_details",
@@ -92,6 +102,14 @@ pub(crate) fn router(pool: &'static PgPool) -> Result<Router> {
                         .options(oauth_approve)
                         .layer(middleware::from_fn(google::authenticate)),
                 )
+                .route(
+                    "/connections",
+                    get(list_connections).layer(middleware::from_fn(google::authenticate)),
+                )
+                .route(
+                    "/connections/{client_id}",
+                    delete(delete_connection).layer(middleware::from_fn(google::authenticate)),
+                )
                 .layer((
                     Extension(Store::new(pool)),
                     middleware::from_fn(set_cache_control),
@@ -102,13 +120,15 @@ pub(crate) fn router(pool: &'static PgPool) -> Result<Router> {
 
 /// Middleware function that authenticates requests to the MCP server
 /// by looking for a Bearer token in the Authorization header.
-#[tracing::instrument(skip(decoding_key, req, next), fields(email))]
+#[tracing::instrument(skip(decoding_key, pool, req, next), fields(email))]
 pub(crate) async fn authenticate(
     Extension(decoding_key): Extension<DecodingKey>,
+    Extension(pool): Extension<&'static PgPool>,
     mut req: extract::Request,
     next: Next,
 ) -> Response<Body> {
-    let access_token_claims: AccessTokenClaims = match _authenticate(&decoding_key, &mut req)
+    let access_token_claims: AccessTokenClaims = match _authenticate(&decoding_key, pool, &mut req)
+        .await
         .context_status(
             StatusCode::UNAUTHORIZED,
             // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-v2-1-13#name-error-codes
@@ -135,8 +155,9 @@ pub(crate) async fn authenticate(
     next.run(req).await
 }
 
-fn _authenticate(
+async fn _authenticate(
     decoding_key: &DecodingKey,
+    pool: &PgPool,
     request: &mut extract::Request,
 ) -> Result<AccessTokenClaims> {
     // Parse the access token out of the Authorization header.
@@ -158,8 +179,12 @@ fn _authenticate(
         parts[1].into()
     };
 
-    // Decode the access token.
-    decode_access_token(decoding_key, &access_token)
+    // Decode the access token and check that its grant wasn't revoked.
+    let access_token_claims = decode_access_token(decoding_key, &access_token)?;
+    if !use_grant(pool, &access_token_claims.clone().into()).await? {
+        return Err(anyhow!("Grant was revoked or expired"));
+    }
+    Ok(access_token_claims)
 }
 
 const READ_WRITE_SCOPE: &str = "read_write";
@@ -204,6 +229,10 @@ struct AuthorizationServerMetadata {
     token_endpoint: String,
     token_endpoint_auth_methods_supported: Vec<String>,
     registration_endpoint: String,
+    revocation_endpoint: String,
+    revocation_endpoint_auth_methods_supported: Vec<String>,
+    introspection_endpoint: String,
+    introspection_endpoint_auth_methods_supported: Vec<String>,
     issuer: String,
     scopes_supported: Vec<String>,
     grant_types_supported: Vec<String>,
@@ -223,6 +252,16 @@ async fn get_authorization_server_metadata() -> ApiResult<Json<AuthorizationServ
             .map(|s| s.to_string())
             .collect(),
         registration_endpoint: format!("{host}/oauth/register"),
+        revocation_endpoint: format!("{host}/oauth/revoke"),
+        revocation_endpoint_auth_methods_supported: AUTH_METHODS_SUPPORTED
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        introspection_endpoint: format!("{host}/oauth/introspect"),
+        introspection_endpoint_auth_methods_supported: AUTH_METHODS_SUPPORTED
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
         issuer: host.clone(),
         scopes_supported: vec![READ_WRITE_SCOPE.to_string()],
         grant_types_supported: vec![CODE_GRANT_TYPE.to_string()],
@@ -579,6 +618,7 @@ async fn oauth_approve(
 ) -> ApiResult<Json<ApprovalResponse>> {
     let req: ApprovalRequest = trim_approval_request(req);
     tracing::debug!("Approving authorization: {req:?}");
+    verify_interactive_user(&user)?;
 
     let auth_token_metadata = issue_auth_token(req, user, &store).await?;
 
@@ -716,18 +756,25 @@ async fn oauth_token(
     headers: HeaderMap,
     Form(req): Form<TokenRequest>,
 ) -> Response<Body> {
-    match _oauth_token(store, decoding_key, encoding_key, headers, req).await {
+    with_client_authenticate_header(
+        _oauth_token(store, decoding_key, encoding_key, headers, req).await,
+    )
+}
+
+/// Append the WWW-Authenticate header to client authentication failures
+/// so the client knows how to proceed.
+/// https://datatracker.ietf.org/doc/html/draft-ietf-oauth-v2-1-13#section-3.2.4
+fn with_client_authenticate_header(res: ApiResult<impl IntoResponse>) -> Response<Body> {
+    match res {
         Ok(res) => res.into_response(),
         Err(err) => {
             let mut res = err.into_response();
-            if res.status() == StatusCode::UNAUTHORIZED {
-                // Append the WWW-Authenticate header so the client knows how to proceed.
-                // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-v2-1-13#section-3.2.4
-                if let Err(err) = add_www_authenticate_header(&mut res) {
-                    return err.into_response();
-                }
+            if res.status() == StatusCode::UNAUTHORIZED
+                && let Err(err) = add_www_authenticate_header(&mut res)
+            {
+                return err.into_response();
             }
-            return res;
+            res
         }
     }
 }
@@ -777,7 +824,14 @@ async fn issue_from_authorization_code(
     req: TokenRequest,
 ) -> ApiResult<(AccessTokenClaims, RefreshTokenClaims)> {
     // Authenticate the client or allow unauthenticated clients.
-    let client_metadata = authenticate_token_client(&headers, &req, &store, &decoding_key).await?;
+    let client_metadata = authenticate_token_client(
+        &headers,
+        &req.client_id,
+        &req.client_secret,
+        &store,
+        &decoding_key,
+    )
+    .await?;
 
     // Validate the authorization token.
     let (auth_token_claims, auth_token) =
@@ -800,6 +854,10 @@ async fn issue_from_authorization_code(
         user: access_claims.user.clone(),
         auth_token_claims: access_claims.auth_token_claims.clone(),
     };
+
+    // Persist the grant, so that it may be listed and revoked.
+    insert_grant(store.pool, &refresh_claims).await?;
+
     Ok((access_claims, refresh_claims))
 }
 
@@ -907,10 +965,20 @@ async fn issue_from_refresh_token(
     req: TokenRequest,
 ) -> ApiResult<(AccessTokenClaims, RefreshTokenClaims)> {
     // Authenticate the client or allow unauthenticated clients.
-    let client_metadata = authenticate_token_client(&headers, &req, &store, &decoding_key).await?;
+    let client_metadata = authenticate_token_client(
+        &headers,
+        &req.client_id,
+        &req.client_secret,
+        &store,
+        &decoding_key,
+    )
+    .await?;
 
     // Validate the refresh token.
     let req_refresh_claims = validate_refresh_token(&req, &client_metadata, &decoding_key)?;
+    if !use_grant(store.pool, &req_refresh_claims.clone().into()).await? {
+        return Err(bad_request("invalid_grant", "Grant was revoked or expired"));
+    }
 
     // Create the token claims.
     let access_claims = AccessTokenClaims {
@@ -973,35 +1041,37 @@ fn validate_refresh_token(
     Ok(refresh_token)
 }
 
+/// Authenticate the client of a token, revocation or introspection request.
 async fn authenticate_token_client(
     headers: &HeaderMap,
-    req: &TokenRequest,
+    req_client_id: &Option<String>,
+    req_client_secret: &Option<Secret>,
     store: &Store,
     decoding_key: &DecodingKey,
 ) -> ApiResult<ClientMetadata> {
-    match (headers.get("Authorization"), &req.client_secret) {
+    match (headers.get("Authorization"), req_client_secret) {
         // client_secret_basic
         // Prefer to use the Authorization header.
         (Some(header), _) => {
-            authenticate_token_client_basic(req, header, store, decoding_key).await
+            authenticate_token_client_basic(req_client_id, header, store, decoding_key).await
         }
 
         // client_secret_post
         // Use the request body when the Authorization header is absent.
         (None, Some(client_secret)) => {
-            authenticate_token_client_post(req, client_secret, store, decoding_key).await
+            authenticate_token_client_post(req_client_id, client_secret, store, decoding_key).await
         }
 
         // none
         // Unauthenticated clients.
-        (None, None) => validate_unauthenticated_client(req, store).await,
+        (None, None) => validate_unauthenticated_client(req_client_id, store).await,
     }
 }
 
 /// Authenticate the client using client_secret_basic auth.
 /// i.e. Authorization header Basic
 async fn authenticate_token_client_basic(
-    req: &TokenRequest,
+    req_client_id: &Option<String>,
     header: &HeaderValue,
     store: &Store,
     decoding_key: &DecodingKey,
@@ -1039,7 +1109,7 @@ async fn authenticate_token_client_basic(
             "Invalid authorization client id",
         ));
     }
-    if let Some(client_id) = &req.client_id
+    if let Some(client_id) = req_client_id
         && client_id != &client_secret_claims.client_id
     {
         return Err(unauthorized(
@@ -1060,7 +1130,7 @@ async fn authenticate_token_client_basic(
 /// Authenticate the client using client_secret_post auth.
 /// i.e. using the the client_secret field.
 async fn authenticate_token_client_post(
-    req: &TokenRequest,
+    req_client_id: &Option<String>,
     client_secret: &Secret,
     store: &Store,
     decoding_key: &DecodingKey,
@@ -1068,7 +1138,7 @@ async fn authenticate_token_client_post(
     tracing::debug!("Authenticating with client_secret_post client_secret parameter");
 
     let client_secret_claims = decode_client_secret(decoding_key, client_secret)?;
-    if let Some(client_id) = &req.client_id
+    if let Some(client_id) = req_client_id
         && client_id != &client_secret_claims.client_id
     {
         return Err(unauthorized(
@@ -1087,12 +1157,12 @@ async fn authenticate_token_client_post(
 }
 
 async fn validate_unauthenticated_client(
-    req: &TokenRequest,
+    req_client_id: &Option<String>,
     store: &Store,
 ) -> ApiResult<ClientMetadata> {
     tracing::debug!("Unauthenticated client");
 
-    let Some(client_id) = &req.client_id else {
+    let Some(client_id) = req_client_id else {
         return Err(bad_request(
             "invalid_request",
             "Client id required for unauthenticated clients",
@@ -1114,6 +1184,405 @@ async fn validate_unauthenticated_client(
     Ok(client_metadata)
 }
 
+/// Note: All fields must be optional. Validation should occur with the handler.
+/// See `swap_empty_with_none` below.
+#[derive(Clone, Debug, Deserialize)]
+struct RevocationRequest {
+    token: Option<Secret>,
+    /// access_token or refresh_token
+    token_type_hint: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<Secret>,
+    #[allow(dead_code)]
+    #[serde(flatten)]
+    other: serde_json::Value,
+}
+
+/// Handle token revocation requests from the MCP client.
+/// Revoking either token revokes the grant and with it, all tokens issued for it.
+/// https://datatracker.ietf.org/doc/html/rfc7009#section-2
+#[tracing::instrument(skip(store, decoding_key, req, headers))]
+async fn oauth_revoke(
+    Extension(store): Extension<Store>,
+    Extension(decoding_key): Extension<DecodingKey>,
+    headers: HeaderMap,
+    Form(req): Form<RevocationRequest>,
+) -> Response<Body> {
+    with_client_authenticate_header(_oauth_revoke(store, decoding_key, headers, req).await)
+}
+
+async fn _oauth_revoke(
+    store: Store,
+    decoding_key: DecodingKey,
+    headers: HeaderMap,
+    req: RevocationRequest,
+) -> ApiResult<Json<()>> {
+    let req = trim_revocation_request(req);
+    tracing::debug!("Handling revocation request: {req:?}");
+
+    let client_metadata = authenticate_token_client(
+        &headers,
+        &req.client_id,
+        &req.client_secret,
+        &store,
+        &decoding_key,
+    )
+    .await?;
+    let Some(token) = &req.token else {
+        return Err(bad_request("invalid_request", "token required"));
+    };
+    // Invalid tokens, including expired ones, don't warrant an error.
+    let Some(grant_token) = decode_grant_token(&decoding_key, token, &req.token_type_hint) else {
+        return Ok(Json(()));
+    };
+    if grant_token.auth_token_claims.client_id != client_metadata.client_id {
+        return Err(bad_request(
+            "unauthorized_client",
+            "Token issued to another client",
+        ));
+    }
+
+    revoke_grant(store.pool, &grant_token).await?;
+    Ok(Json(()))
+}
+
+/// Note: All fields must be optional. Validation should occur with the handler.
+/// See `swap_empty_with_none` below.
+#[derive(Clone, Debug, Deserialize)]
+struct IntrospectionRequest {
+    token: Option<Secret>,
+    /// access_token or refresh_token
+    token_type_hint: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<Secret>,
+    #[allow(dead_code)]
+    #[serde(flatten)]
+    other: serde_json::Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+struct IntrospectionResponse {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+}
+
+/// Handle token introspection requests from the MCP client.
+/// Clients may only introspect their own tokens. Others are reported as inactive.
+/// https://datatracker.ietf.org/doc/html/rfc7662#section-2
+#[tracing::instrument(skip(store, decoding_key, req, headers))]
+async fn oauth_introspect(
+    Extension(store): Extension<Store>,
+    Extension(decoding_key): Extension<DecodingKey>,
+    headers: HeaderMap,
+    Form(req): Form<IntrospectionRequest>,
+) -> Response<Body> {
+    with_client_authenticate_header(_oauth_introspect(store, decoding_key, headers, req).await)
+}
+
+async fn _oauth_introspect(
+    store: Store,
+    decoding_key: DecodingKey,
+    headers: HeaderMap,
+    req: IntrospectionRequest,
+) -> ApiResult<Json<IntrospectionResponse>> {
+    let req = trim_introspection_request(req);
+    tracing::debug!("Handling introspection request: {req:?}");
+
+    let client_metadata = authenticate_token_client(
+        &headers,
+        &req.client_id,
+        &req.client_secret,
+        &store,
+        &decoding_key,
+    )
+    .await?;
+    let Some(token) = &req.token else {
+        return Err(bad_request("invalid_request", "token required"));
+    };
+    let Some(grant_token) = decode_grant_token(&decoding_key, token, &req.token_type_hint) else {
+        return Ok(Json(IntrospectionResponse::default()));
+    };
+    if grant_token.auth_token_claims.client_id != client_metadata.client_id
+        || !grant_active(store.pool, &grant_token.auth_token_claims.token_id).await?
+    {
+        return Ok(Json(IntrospectionResponse::default()));
+    }
+
+    Ok(Json(IntrospectionResponse {
+        active: true,
+        scope: Some(grant_token.scope),
+        client_id: Some(grant_token.auth_token_claims.client_id),
+        username: Some(grant_token.user.email),
+        token_type: Some(grant_token.token_type.to_string()),
+        iat: Some(grant_token.iat),
+        exp: Some(grant_token.exp),
+    }))
+}
+
+/// An MCP client the user authorized to access Koso on their behalf.
+#[derive(Debug, Serialize, Deserialize, PartialEq, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+struct Connection {
+    client_id: String,
+    client_name: String,
+    authorized_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: DateTime<Utc>,
+}
+
+/// List the MCP clients holding an unexpired grant from the user.
+#[tracing::instrument(skip(user, store))]
+async fn list_connections(
+    Extension(user): Extension<User>,
+    Extension(store): Extension<Store>,
+) -> ApiResult<Json<Vec<Connection>>> {
+    verify_interactive_user(&user)?;
+
+    let connections = sqlx::query_as(
+        "
+        SELECT
+          client_id,
+          client_metadata->>'client_name' AS client_name,
+          MIN(created_at) AS authorized_at,
+          MAX(last_used_at) AS last_used_at,
+          MAX(expires_at) AS expires_at
+        FROM oauth_grants
+        JOIN oauth_clients USING (client_id)
+        WHERE email = $1
+        AND revoked_at IS NULL
+        AND expires_at > NOW()
+        GROUP BY client_id, client_metadata->>'client_name'
+        ORDER BY authorized_at",
+    )
+    .bind(&user.email)
+    .fetch_all(store.pool)
+    .await
+    .context("Failed to list connections")?;
+    Ok(Json(connections))
+}
+
+/// Disconnect an MCP client by revoking all of the user's grants to it.
+#[tracing::instrument(skip(user, store))]
+async fn delete_connection(
+    Extension(user): Extension<User>,
+    Extension(store): Extension<Store>,
+    extract::Path(client_id): extract::Path<String>,
+) -> ApiResult<Json<()>> {
+    verify_interactive_user(&user)?;
+
+    let res = sqlx::query(
+        "
+        UPDATE oauth_grants
+        SET revoked_at = NOW()
+        WHERE client_id = $1 AND email = $2
+        AND revoked_at IS NULL
+        AND expires_at > NOW()",
+    )
+    .bind(&client_id)
+    .bind(&user.email)
+    .execute(store.pool)
+    .await
+    .context("Failed to delete connection")?;
+    (res.rows_affected() > 0)
+        .then_some(())
+        .context_not_found("NOT_FOUND", &format!("Connection {client_id} not found"))?;
+    Ok(Json(()))
+}
+
+/// The claims common to access and refresh tokens.
+struct GrantToken {
+    token_type: &'static str,
+    iat: u64,
+    exp: u64,
+    scope: String,
+    user: User,
+    auth_token_claims: AuthTokenClaims,
+}
+
+impl GrantToken {
+    /// When the token's grant expires. That's with the refresh token, which for
+    /// an access token is at the latest the refresh token lifetime after it was issued.
+    fn grant_expires_at(&self) -> u64 {
+        match self.token_type {
+            "refresh_token" => self.exp,
+            _ => self.iat + REFRESH_TOKEN_EXPIRY_SECS,
+        }
+    }
+}
+
+impl From<AccessTokenClaims> for GrantToken {
+    fn from(c: AccessTokenClaims) -> Self {
+        GrantToken {
+            token_type: "access_token",
+            iat: c.iat,
+            exp: c.exp,
+            scope: c.scope,
+            user: c.user,
+            auth_token_claims: c.auth_token_claims,
+        }
+    }
+}
+
+impl From<RefreshTokenClaims> for GrantToken {
+    fn from(c: RefreshTokenClaims) -> Self {
+        GrantToken {
+            token_type: "refresh_token",
+            iat: c.iat,
+            exp: c.exp,
+            scope: c.scope,
+            user: c.user,
+            auth_token_claims: c.auth_token_claims,
+        }
+    }
+}
+
+/// Decode an access or refresh token, trying the hinted type first.
+fn decode_grant_token(
+    key: &DecodingKey,
+    token: &Secret,
+    token_type_hint: &Option<String>,
+) -> Option<GrantToken> {
+    let access = || decode_access_token(key, token).ok().map(GrantToken::from);
+    let refresh = || {
+        decode_token::<RefreshTokenClaims>(key, token, REFRESH_TOKEN_ISS)
+            .ok()
+            .map(GrantToken::from)
+    };
+    match token_type_hint.as_deref() {
+        Some("refresh_token") => refresh().or_else(access),
+        _ => access().or_else(refresh),
+    }
+}
+
+/// Persist the grant of a newly exchanged authorization code.
+/// The grant expires with the refresh tokens issued for it.
+async fn insert_grant(pool: &PgPool, claims: &RefreshTokenClaims) -> Result<()> {
+    sqlx::query(
+        "
+        INSERT INTO oauth_grants (grant_id, client_id, email, scope, expires_at)
+        VALUES ($1, $2, $3, $4, to_timestamp($5))",
+    )
+    .bind(&claims.auth_token_claims.token_id)
+    .bind(&claims.auth_token_claims.client_id)
+    .bind(claims.user.email.to_lowercase())
+    .bind(&claims.scope)
+    .bind(claims.exp as f64)
+    .execute(pool)
+    .await
+    .context("Failed to insert grant")?;
+    Ok(())
+}
+
+/// How often to record the use of a grant. Tokens are used on every MCP request,
+/// so writing each use would add a write to every request.
+const GRANT_LAST_USED_RESOLUTION: TimeDelta = TimeDelta::minutes(5);
+
+/// Record a use of the token's grant. Returns false if it was revoked or expired.
+///
+/// Tokens issued before grants were introduced have none. Rather than reject them,
+/// their grant is backfilled on first use so they keep working until they expire
+/// and can be listed and revoked like any other.
+async fn use_grant(pool: &PgPool, token: &GrantToken) -> Result<bool> {
+    let grant_id = &token.auth_token_claims.token_id;
+    let grant: Option<(bool, Option<DateTime<Utc>>)> = sqlx::query_as(
+        "
+        SELECT revoked_at IS NULL AND expires_at > NOW(), last_used_at
+        FROM oauth_grants
+        WHERE grant_id = $1",
+    )
+    .bind(grant_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get grant")?;
+
+    match grant {
+        None => {
+            sqlx::query(
+                "
+                INSERT INTO oauth_grants (grant_id, client_id, email, scope, last_used_at, expires_at)
+                VALUES ($1, $2, $3, $4, NOW(), to_timestamp($5))
+                ON CONFLICT (grant_id) DO NOTHING",
+            )
+            .bind(grant_id)
+            .bind(&token.auth_token_claims.client_id)
+            .bind(token.user.email.to_lowercase())
+            .bind(&token.scope)
+            .bind(token.grant_expires_at() as f64)
+            .execute(pool)
+            .await
+            .context("Failed to backfill grant")?;
+            tracing::debug!("Backfilled grant {grant_id} of a token issued before grants");
+            Ok(true)
+        }
+        Some((false, _)) => Ok(false),
+        Some((true, last_used_at)) => {
+            let stale = last_used_at
+                .is_none_or(|last_used_at| Utc::now() - last_used_at > GRANT_LAST_USED_RESOLUTION);
+            if stale {
+                sqlx::query(
+                    "
+                    UPDATE oauth_grants
+                    SET last_used_at = NOW()
+                    WHERE grant_id = $1",
+                )
+                .bind(grant_id)
+                .execute(pool)
+                .await
+                .context("Failed to use grant")?;
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Whether the token's grant is neither revoked nor expired.
+/// Tokens issued before grants were introduced, and so without one, are active.
+async fn grant_active(pool: &PgPool, grant_id: &str) -> Result<bool> {
+    let grant: Option<(bool,)> = sqlx::query_as(
+        "
+        SELECT revoked_at IS NULL AND expires_at > NOW()
+        FROM oauth_grants
+        WHERE grant_id = $1",
+    )
+    .bind(grant_id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to get grant")?;
+    Ok(grant.is_none_or(|(active,)| active))
+}
+
+/// Revoke the token's grant, backfilling a revoked grant for tokens issued before
+/// grants were introduced. Revoked grants are kept so they aren't backfilled again.
+async fn revoke_grant(pool: &PgPool, token: &GrantToken) -> Result<()> {
+    sqlx::query(
+        "
+        INSERT INTO oauth_grants (grant_id, client_id, email, scope, expires_at, revoked_at)
+        VALUES ($1, $2, $3, $4, to_timestamp($5), NOW())
+        ON CONFLICT (grant_id)
+        DO UPDATE SET revoked_at = COALESCE(oauth_grants.revoked_at, NOW())",
+    )
+    .bind(&token.auth_token_claims.token_id)
+    .bind(&token.auth_token_claims.client_id)
+    .bind(token.user.email.to_lowercase())
+    .bind(&token.scope)
+    .bind(token.grant_expires_at() as f64)
+    .execute(pool)
+    .await
+    .context("Failed to revoke grant")?;
+    Ok(())
+}
+
 fn validate_redirect_uri(valid_redirect_uris: &[String], redirect_uri: &String) -> ApiResult<()> {
     // TODO: ignore ports for localhost.
     // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-v2-1-13#section-4.1.1
@@ -1321,6 +1790,28 @@ fn trim_token_request(req: TokenRequest) -> TokenRequest {
     }
 }
 
+/// Replace all empty strings with None.
+fn trim_revocation_request(req: RevocationRequest) -> RevocationRequest {
+    RevocationRequest {
+        token: trim_secret_to_none(req.token),
+        token_type_hint: trim_to_none(req.token_type_hint),
+        client_id: trim_to_none(req.client_id),
+        client_secret: trim_secret_to_none(req.client_secret),
+        other: req.other,
+    }
+}
+
+/// Replace all empty strings with None.
+fn trim_introspection_request(req: IntrospectionRequest) -> IntrospectionRequest {
+    IntrospectionRequest {
+        token: trim_secret_to_none(req.token),
+        token_type_hint: trim_to_none(req.token_type_hint),
+        client_id: trim_to_none(req.client_id),
+        client_secret: trim_secret_to_none(req.client_secret),
+        other: req.other,
+    }
+}
+
 /// If the options value is the empty string, return None.
 /// Implements https://datatracker.ietf.org/doc/html/rfc6749
 /// >  Parameters sent without a value
@@ -1900,6 +2391,283 @@ mod tests {
         }
     }
 
+    #[test_log::test(sqlx::test)]
+    async fn test_oauth_revoke_and_introspect(pool: PgPool) {
+        let user = default_user();
+        let key = EncodingKey::from_secret(KEY);
+        let decoding_key = DecodingKey::from_secret(KEY);
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let store = Store::new(pool);
+        let client_secret = encode_client_secret(
+            &key,
+            &ClientSecretClaims {
+                iat: 123,
+                exp: 99999999999999,
+                iss: CLIENT_SECRET_ISS.to_string(),
+                client_id: CLIENT_ID.to_string(),
+            },
+        )
+        .unwrap();
+        store.insert_client(&default_client()).await.unwrap();
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "Authorization",
+            HeaderValue::from_str(&format!(
+                "Basic {}",
+                BASE64_STANDARD.encode(format!("{CLIENT_ID}:{}", client_secret.data()))
+            ))
+            .unwrap(),
+        );
+        store
+            .insert_auth_token(&AuthTokenMetadata {
+                issued_at: 123,
+                expires_at: 99999999999999,
+                client_id: CLIENT_ID.to_string(),
+                token_id: TOKEN_ID.to_string(),
+                scope: READ_WRITE_SCOPE.to_string(),
+                response_type: CODE_RESPONSE_TYPE.to_string(),
+                redirect_uri: REDIRECT_URI1.to_string(),
+                code_challenge_method: Some(S256_CHALLENGE_METHOD.to_string()),
+                code_challenge: Some(CODE_CHALLENGE.to_string()),
+                user: user.clone(),
+            })
+            .await
+            .unwrap();
+        let code = encode_auth_token(
+            &key,
+            &AuthTokenClaims {
+                iat: 123,
+                exp: 99999999999999,
+                iss: AUTH_TOKEN_ISS.to_string(),
+                client_id: CLIENT_ID.to_string(),
+                token_id: TOKEN_ID.to_string(),
+            },
+        )
+        .unwrap();
+
+        // Exchange the code, creating a grant.
+        let tokens: TokenResponse = {
+            let res = oauth_token(
+                Extension(store.clone()),
+                Extension(decoding_key.clone()),
+                Extension(key.clone()),
+                headers.clone(),
+                Form(TokenRequest {
+                    grant_type: Some(CODE_GRANT_TYPE.to_string()),
+                    client_id: None,
+                    client_secret: None,
+                    scope: None,
+                    resource: None,
+                    redirect_uri: Some(REDIRECT_URI1.to_string()),
+                    code: Some(code),
+                    code_verifier: Some(Secret::from(CODE_VERIFIER)),
+                    refresh_token: None,
+                    other: Value::Null,
+                }),
+            )
+            .await;
+            assert_eq!(res.status(), StatusCode::OK);
+            parse_response(res).await
+        };
+        let access_claims: GrantToken = decode_access_token(&decoding_key, &tokens.access_token)
+            .unwrap()
+            .into();
+        assert!(use_grant(pool, &access_claims).await.unwrap());
+
+        // The connection is listed for the user.
+        let Json(connections) = list_connections(Extension(user.clone()), Extension(store.clone()))
+            .await
+            .unwrap();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].client_id, CLIENT_ID);
+        assert_eq!(connections[0].client_name, CLIENT_NAME);
+        assert!(connections[0].last_used_at.is_some());
+
+        let introspect = |token: Secret, headers: HeaderMap| {
+            let store = store.clone();
+            let decoding_key = decoding_key.clone();
+            async move {
+                let res = oauth_introspect(
+                    Extension(store),
+                    Extension(decoding_key),
+                    headers,
+                    Form(IntrospectionRequest {
+                        token: Some(token),
+                        token_type_hint: None,
+                        client_id: None,
+                        client_secret: None,
+                        other: Value::Null,
+                    }),
+                )
+                .await;
+                assert_eq!(res.status(), StatusCode::OK);
+                parse_response::<IntrospectionResponse>(res).await
+            }
+        };
+
+        // Introspect both tokens.
+        let res = introspect(tokens.access_token.clone(), headers.clone()).await;
+        assert!(res.active);
+        assert_eq!(res.client_id.as_deref(), Some(CLIENT_ID));
+        assert_eq!(res.username.as_deref(), Some(user.email.as_str()));
+        assert_eq!(res.token_type.as_deref(), Some("access_token"));
+        let res = introspect(tokens.refresh_token.clone(), headers.clone()).await;
+        assert!(res.active);
+        assert_eq!(res.token_type.as_deref(), Some("refresh_token"));
+        let res = introspect(Secret::from("garbage"), headers.clone()).await;
+        assert_eq!(res, IntrospectionResponse::default());
+
+        // Unauthenticated clients may not introspect.
+        let res = oauth_introspect(
+            Extension(store.clone()),
+            Extension(decoding_key.clone()),
+            HeaderMap::new(),
+            Form(IntrospectionRequest {
+                token: Some(tokens.access_token.clone()),
+                token_type_hint: None,
+                client_id: Some(CLIENT_ID.to_string()),
+                client_secret: Some(Secret::from("wrong")),
+                other: Value::Null,
+            }),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(res.headers().contains_key("WWW-Authenticate"));
+
+        // Revoke the refresh token.
+        let res = oauth_revoke(
+            Extension(store.clone()),
+            Extension(decoding_key.clone()),
+            headers.clone(),
+            Form(RevocationRequest {
+                token: Some(tokens.refresh_token.clone()),
+                token_type_hint: Some("refresh_token".to_string()),
+                client_id: None,
+                client_secret: None,
+                other: Value::Null,
+            }),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // Both tokens are now inactive and the grant can't be refreshed.
+        assert!(!use_grant(pool, &access_claims).await.unwrap());
+        let res = introspect(tokens.access_token.clone(), headers.clone()).await;
+        assert!(!res.active);
+        let res = oauth_token(
+            Extension(store.clone()),
+            Extension(decoding_key.clone()),
+            Extension(key.clone()),
+            headers.clone(),
+            Form(TokenRequest {
+                grant_type: Some(REFRESH_GRANT_TYPE.to_string()),
+                client_id: None,
+                client_secret: None,
+                scope: None,
+                resource: None,
+                redirect_uri: None,
+                code: None,
+                code_verifier: None,
+                refresh_token: Some(tokens.refresh_token),
+                other: Value::Null,
+            }),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let err: Value = parse_response(res).await;
+        assert_eq!(err.get("title").unwrap().as_str().unwrap(), "invalid_grant");
+
+        // The connection is gone too.
+        let Json(connections) = list_connections(Extension(user.clone()), Extension(store.clone()))
+            .await
+            .unwrap();
+        assert!(connections.is_empty());
+        assert!(
+            delete_connection(
+                Extension(user),
+                Extension(store),
+                extract::Path(CLIENT_ID.to_string())
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[test_log::test(sqlx::test)]
+    async fn test_grant_backfilled_for_tokens_without_one(pool: PgPool) {
+        let user = default_user();
+        let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+        let pool = pool_wrapper.pool;
+        let store = Store::new(pool);
+        store.insert_client(&default_client()).await.unwrap();
+        let token = |token_id: &str| -> GrantToken {
+            AccessTokenClaims {
+                iat: now().unwrap(),
+                exp: expires_at(ACCESS_TOKEN_EXPIRY_SECS).unwrap(),
+                iss: ACCESS_TOKEN_ISS.to_string(),
+                scope: READ_WRITE_SCOPE.to_string(),
+                user: user.clone(),
+                auth_token_claims: AuthTokenClaims {
+                    iat: 123,
+                    exp: 99999999999999,
+                    iss: AUTH_TOKEN_ISS.to_string(),
+                    client_id: CLIENT_ID.to_string(),
+                    token_id: token_id.to_string(),
+                },
+            }
+            .into()
+        };
+        let last_used_at = |grant_id: &'static str| async move {
+            let (last_used_at,): (Option<DateTime<Utc>>,) =
+                sqlx::query_as("SELECT last_used_at FROM oauth_grants WHERE grant_id = $1")
+                    .bind(grant_id)
+                    .fetch_one(pool)
+                    .await
+                    .unwrap();
+            last_used_at.unwrap()
+        };
+
+        // A token issued before grants is accepted and its grant backfilled.
+        let legacy = token("legacy");
+        assert!(grant_active(pool, "legacy").await.unwrap());
+        assert!(use_grant(pool, &legacy).await.unwrap());
+        let Json(connections) = list_connections(Extension(user.clone()), Extension(store.clone()))
+            .await
+            .unwrap();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].client_id, CLIENT_ID);
+
+        // Uses are only recorded once the last one is stale.
+        let first_used_at = last_used_at("legacy").await;
+        assert!(use_grant(pool, &legacy).await.unwrap());
+        assert_eq!(last_used_at("legacy").await, first_used_at);
+        sqlx::query(
+            "UPDATE oauth_grants SET last_used_at = NOW() - INTERVAL '10 minutes' WHERE grant_id = $1",
+        )
+        .bind("legacy")
+        .execute(pool)
+        .await
+        .unwrap();
+        assert!(use_grant(pool, &legacy).await.unwrap());
+        assert!(last_used_at("legacy").await > first_used_at);
+
+        // Revoked grants aren't backfilled again.
+        revoke_grant(pool, &legacy).await.unwrap();
+        assert!(!use_grant(pool, &legacy).await.unwrap());
+        assert!(!grant_active(pool, "legacy").await.unwrap());
+
+        // Nor are revoked tokens that were never used.
+        let unused = token("unused");
+        revoke_grant(pool, &unused).await.unwrap();
+        assert!(!use_grant(pool, &unused).await.unwrap());
+
+        let Json(connections) = list_connections(Extension(user), Extension(store))
+            .await
+            .unwrap();
+        assert!(connections.is_empty());
+    }
+
     #[test_log::test(sqlx::test)]
     async fn test_oauth_token_public_client(pool: PgPool) {
         let user = default_user();
@@ -2108,6 +2876,7 @@ mod tests {
             name: "User".to_string(),
             picture: "pic".to_string(),
             exp: 99999999999999,
+            scope: None,
         }
     }
 
@@ -1,5 +1,7 @@
 mod config;
 pub mod github;
+pub mod gitlab;
+mod tasks;
 
 #[derive(Default, Clone)]
 pub(crate) struct PluginSettings {
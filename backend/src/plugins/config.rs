@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, types::Json};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+};
 
 #[derive(Clone)]
 pub(super) struct ConfigStorage {
@@ -20,10 +24,136 @@ pub(crate) struct Config {
 #[serde(rename_all = "camelCase", tag = "type")]
 pub(crate) enum Settings {
     Github(GithubSettings),
+    Gitlab(GitlabSettings),
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-pub(crate) struct GithubSettings {}
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct GithubSettings {
+    /// Push status and assignee changes of issue tasks back to GitHub.
+    pub(crate) two_way_sync: bool,
+    /// With two way sync, tasks created under this task open an issue in `issue_repo`.
+    pub(crate) issue_parent_id: Option<String>,
+    /// The repository, as `owner/name`, new issues are opened in.
+    pub(crate) issue_repo: Option<String>,
+    /// Repositories, as `owner/name`, to import from. Empty imports from all of them.
+    pub(crate) include_repos: Vec<String>,
+    /// Repositories, as `owner/name`, to never import from.
+    pub(crate) exclude_repos: Vec<String>,
+    /// The task, by repository, new PRs and issues are also filed under.
+    pub(crate) repo_parents: BTreeMap<String, String>,
+    pub(crate) import_drafts: bool,
+    pub(crate) pr_statuses: PrStatuses,
+}
+
+impl Default for GithubSettings {
+    fn default() -> Self {
+        GithubSettings {
+            two_way_sync: false,
+            issue_parent_id: None,
+            issue_repo: None,
+            include_repos: vec![],
+            exclude_repos: vec![],
+            repo_parents: BTreeMap::new(),
+            import_drafts: true,
+            pr_statuses: PrStatuses::default(),
+        }
+    }
+}
+
+impl GithubSettings {
+    /// Repository names are case insensitive.
+    pub(crate) fn includes_repo(&self, repo: &str) -> bool {
+        (self.include_repos.is_empty()
+            || self
+                .include_repos
+                .iter()
+                .any(|r| r.eq_ignore_ascii_case(repo)))
+            && !self
+                .exclude_repos
+                .iter()
+                .any(|r| r.eq_ignore_ascii_case(repo))
+    }
+
+    pub(crate) fn parent_for_repo(&self, repo: &str) -> Option<&String> {
+        self.repo_parents
+            .iter()
+            .find(|(r, _)| r.eq_ignore_ascii_case(repo))
+            .map(|(_, parent_id)| parent_id)
+    }
+}
+
+/// The task status of open PRs in each review and check state.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct PrStatuses {
+    pub(crate) open: String,
+    pub(crate) draft: String,
+    pub(crate) review_requested: String,
+    pub(crate) approved: String,
+    pub(crate) changes_requested: String,
+    pub(crate) checks_failed: String,
+}
+
+impl Default for PrStatuses {
+    fn default() -> Self {
+        PrStatuses {
+            open: "In Progress".to_string(),
+            draft: "In Progress".to_string(),
+            review_requested: "In Progress".to_string(),
+            approved: "In Progress".to_string(),
+            changes_requested: "Blocked".to_string(),
+            checks_failed: "Blocked".to_string(),
+        }
+    }
+}
+
+impl PrStatuses {
+    pub(crate) fn all(&self) -> [&String; 6] {
+        [
+            &self.open,
+            &self.draft,
+            &self.review_requested,
+            &self.approved,
+            &self.changes_requested,
+            &self.checks_failed,
+        ]
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GitlabSettings {
+    /// The URL of the GitLab instance, e.g. `https://gitlab.example.com`.
+    pub(crate) base_url: String,
+    /// Authenticates calls to the GitLab API. Never returned to clients.
+    pub(crate) access_token: String,
+    /// The SHA-256 hash of the token GitLab sends with webhook deliveries.
+    pub(crate) webhook_token_hash: String,
+    /// GitLab projects, as `namespace/name`, to import merge requests from.
+    pub(crate) projects: Vec<String>,
+}
+
+impl GitlabSettings {
+    /// Project paths are case insensitive.
+    pub(crate) fn includes_project(&self, project: &str) -> bool {
+        self.projects
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(project))
+    }
+}
+
+// Configs are logged, so keep the access token out of them.
+impl Debug for GitlabSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GitlabSettings")
+            .field("base_url", &self.base_url)
+            .field("access_token", &"[REDACTED]")
+            .field("webhook_token_hash", &self.webhook_token_hash)
+            .field("projects", &self.projects)
+            .finish()
+    }
+}
 
 type ConfigRow = (String, String, String, Json<Settings>);
 
@@ -81,6 +211,35 @@ impl ConfigStorage {
         Ok(rows_to_configs(configs))
     }
 
+    /// Lists the configurations of the given plugin for the project.
+    pub(super) async fn list_for_project(
+        &self,
+        plugin_id: &str,
+        project_id: &str,
+    ) -> Result<Vec<Config>> {
+        let configs: Vec<ConfigRow> = sqlx::query_as(
+            "
+            SELECT
+                project_id,
+                plugin_id,
+                external_id,
+                settings
+            FROM plugin_configs
+            JOIN projects USING(project_id)
+            WHERE plugin_id=$1 AND project_id=$2 AND deleted_on IS NULL
+            ORDER BY external_id",
+        )
+        .bind(plugin_id)
+        .bind(project_id)
+        .fetch_all(self.pool)
+        .await
+        .context(format!(
+            "Failed to list plugin configs for {plugin_id} in {project_id}"
+        ))?;
+
+        Ok(rows_to_configs(configs))
+    }
+
     pub(super) async fn insert_or_update(&self, config: &Config) -> Result<()> {
         sqlx::query(
             "
@@ -97,6 +256,27 @@ impl ConfigStorage {
         .await?;
         Ok(())
     }
+
+    /// Deletes the configuration. Returns false if it didn't exist.
+    pub(super) async fn delete(
+        &self,
+        project_id: &str,
+        plugin_id: &str,
+        external_id: &str,
+    ) -> Result<bool> {
+        let res = sqlx::query(
+            "
+        DELETE FROM plugin_configs
+        WHERE project_id = $1 AND plugin_id = $2 AND external_id = $3",
+        )
+        .bind(project_id)
+        .bind(plugin_id)
+        .bind(external_id)
+        .execute(self.pool)
+        .await
+        .context("Failed to delete plugin config")?;
+        Ok(res.rows_affected() > 0)
+    }
 }
 
 fn rows_to_configs(configs: Vec<ConfigRow>) -> Vec<Config> {
@@ -135,7 +315,7 @@ mod tests {
                 project_id: "project_id_1".to_string(),
                 plugin_id: "plugin_id_1".to_string(),
                 external_id: "external_id_1".to_string(),
-                settings: Settings::Github(GithubSettings {}),
+                settings: Settings::Github(GithubSettings::default()),
             })
             .await?;
 
@@ -143,7 +323,7 @@ mod tests {
             project_id: "project_id_1".to_string(),
             plugin_id: "plugin_id_1".to_string(),
             external_id: "external_id_1".to_string(),
-            settings: Settings::Github(GithubSettings {}),
+            settings: Settings::Github(GithubSettings::default()),
         }];
 
         let actual: Vec<Config> = storage.list_for_plugin("plugin_id_1").await.unwrap();
@@ -155,6 +335,12 @@ mod tests {
             .unwrap();
         assert_eq!(actual, expected);
 
+        let actual: Vec<Config> = storage
+            .list_for_project("plugin_id_1", "project_id_1")
+            .await
+            .unwrap();
+        assert_eq!(actual, expected);
+
         let actual: Vec<Config> = storage
             .list_for_plugin("plugin_id_not_found")
             .await
@@ -164,6 +350,51 @@ mod tests {
         Ok(())
     }
 
+    #[test_log::test]
+    fn github_settings_deserialize() {
+        let settings: Settings = serde_json::from_str(r#"{"type":"github"}"#).unwrap();
+        assert_eq!(settings, Settings::Github(GithubSettings::default()));
+
+        let settings: Settings = serde_json::from_str(
+            r#"{"type":"github","twoWaySync":true,"issueParentId":"p1","issueRepo":"kosolabs/koso","importDrafts":false,"prStatuses":{"approved":"Ready"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings,
+            Settings::Github(GithubSettings {
+                two_way_sync: true,
+                issue_parent_id: Some("p1".to_string()),
+                issue_repo: Some("kosolabs/koso".to_string()),
+                import_drafts: false,
+                pr_statuses: PrStatuses {
+                    approved: "Ready".to_string(),
+                    ..PrStatuses::default()
+                },
+                ..GithubSettings::default()
+            })
+        );
+    }
+
+    #[test_log::test]
+    fn github_settings_repos() {
+        let settings = GithubSettings {
+            exclude_repos: vec!["kosolabs/secret".to_string()],
+            repo_parents: BTreeMap::from([("kosolabs/Koso".to_string(), "p1".to_string())]),
+            ..GithubSettings::default()
+        };
+        assert!(settings.includes_repo("kosolabs/koso"));
+        assert!(!settings.includes_repo("KosoLabs/Secret"));
+        assert_eq!(settings.parent_for_repo("kosolabs/koso").unwrap(), "p1");
+        assert_eq!(settings.parent_for_repo("kosolabs/other"), None);
+
+        let settings = GithubSettings {
+            include_repos: vec!["kosolabs/koso".to_string()],
+            ..GithubSettings::default()
+        };
+        assert!(settings.includes_repo("kosolabs/koso"));
+        assert!(!settings.includes_repo("kosolabs/other"));
+    }
+
     #[test_log::test(sqlx::test)]
     async fn list_excludes_deleted_projects(pool: PgPool) -> Result<()> {
         let pool_wrapper = UnsafePoolWrapper::wrap(pool);
@@ -181,7 +412,7 @@ mod tests {
                 project_id: "project_id_1".to_string(),
                 plugin_id: "plugin_id_1".to_string(),
                 external_id: "external_id_1".to_string(),
-                settings: Settings::Github(GithubSettings {}),
+                settings: Settings::Github(GithubSettings::default()),
             })
             .await?;
 
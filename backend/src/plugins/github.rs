@@ -5,17 +5,22 @@ use crate::{
         model::Task,
         yproxy::{YDocProxy, YTaskProxy},
     },
-    plugins::{PluginSettings, config::ConfigStorage, github::app::AppGithub},
+    plugins::{
+        PluginSettings,
+        config::{ConfigStorage, GithubSettings},
+        github::app::{AppGithub, InstallationGithub},
+        tasks::{Kind, now},
+    },
 };
 use anyhow::{Context, Result, anyhow};
 use axum::{Router, middleware};
 use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
 use connect::ConnectHandler;
-use octocrab::models::pulls::PullRequest;
+use octocrab::models::{IssueState, issues::Issue, pulls::PullRequest};
 use poller::Poller;
-use regex::Regex;
+use pr_state::PrState;
+use pusher::Pusher;
 use sqlx::PgPool;
-use std::{cell::LazyCell, collections::HashSet, time::SystemTime};
 use tokio::task::JoinHandle;
 use webhook::Webhook;
 use yrs::TransactionMut;
@@ -23,10 +28,15 @@ use yrs::TransactionMut;
 pub mod app;
 mod connect;
 mod poller;
+mod pr_state;
+mod pusher;
 mod webhook;
 
 const PLUGIN_KIND: &Kind = &Kind::new("github", "GitHub");
 const PR_KIND: &Kind = &Kind::new_nested(PLUGIN_KIND, "github_pr", "GitHub PR");
+const ISSUE_KIND: &Kind = &Kind::new_nested(PLUGIN_KIND, "github_issue", "GitHub Issue");
+/// The kinds of task synced from GitHub.
+const SYNCED_KINDS: &[&Kind] = &[PR_KIND, ISSUE_KIND];
 
 #[derive(Clone)]
 pub(crate) struct Plugin {
@@ -44,7 +54,9 @@ impl Plugin {
         pool: &'static PgPool,
     ) -> Result<Plugin> {
         PLUGIN_KIND.validate()?;
-        PR_KIND.validate()?;
+        for kind in SYNCED_KINDS {
+            kind.validate()?;
+        }
         let client: AppGithub = AppGithub::new().await?;
         let config_storage = ConfigStorage::new(pool)?;
         Ok(Plugin {
@@ -66,6 +78,23 @@ impl Plugin {
         }
     }
 
+    /// Start a background task that pushes Koso changes to GitHub for projects
+    /// with two way sync enabled.
+    /// Return a handle to the task, useful for aborting the task on shutdown.
+    pub(crate) fn start_pushing(&self) -> JoinHandle<()> {
+        // Subscribe before spawning to not miss any changes.
+        let changes = self.collab.subscribe_task_changes();
+        tokio::spawn(
+            Pusher::new(
+                self.collab.clone(),
+                self.client.clone(),
+                self.config_storage.clone(),
+                self.pool,
+            )
+            .push(changes),
+        )
+    }
+
     /// Returns a router that binds webhook (push) and poll endpoints.
     pub(crate) fn router(&self) -> Result<Router> {
         Ok(Router::new()
@@ -80,7 +109,13 @@ impl Plugin {
             .layer(middleware::from_fn(google::authenticate))
             // Webhook and poller are unauthenticated, so add it AFTER adding the authentication layers.
             .merge(
-                Webhook::new(self.collab.clone(), self.config_storage.clone(), self.pool)?.router(),
+                Webhook::new(
+                    self.collab.clone(),
+                    self.config_storage.clone(),
+                    self.client.clone(),
+                    self.pool,
+                )?
+                .router(),
             )
             .merge(self.poller().router()))
     }
@@ -90,92 +125,149 @@ impl Plugin {
             self.collab.clone(),
             self.client.clone(),
             self.config_storage.clone(),
+            self.pool,
         )
     }
 }
 
-fn get_or_create_kind_parent(
-    txn: &mut TransactionMut,
-    doc: &YDocProxy,
-    kind: &Kind,
-) -> Result<YTaskProxy> {
-    let parent_kind = kind.parent_kind.unwrap_or(kind);
-    let plugin_parent = match doc.get(txn, parent_kind.id) {
-        Ok(parent) => parent,
-        Err(_) => create_container(txn, &doc.get(txn, "root")?, doc, parent_kind)?,
-    };
-    if kind.parent_kind.is_none() {
-        return Ok(plugin_parent);
-    }
-    match doc.get(txn, kind.id) {
-        Ok(kind_parent) => Ok(kind_parent),
-        Err(_) => create_container(txn, &plugin_parent, doc, kind),
-    }
-}
-
-fn create_container(
-    txn: &mut TransactionMut,
-    container_parent: &YTaskProxy,
-    doc: &YDocProxy,
-    kind: &Kind,
-) -> Result<YTaskProxy> {
-    tracing::debug!("Creating new kind container: {}", kind.id);
-    let mut plugin_children = container_parent.get_children(txn)?;
-    plugin_children.push(kind.id.to_string());
-
-    let kind_parent = doc.set(
-        txn,
-        &Task {
-            id: kind.id.to_string(),
-            num: doc.next_num(txn)?.to_string(),
-            name: kind.name.to_string(),
-            kind: Some(kind.id.to_string()),
-            ..Task::default()
-        },
-    );
-    container_parent.set_children(txn, &plugin_children);
-    Ok(kind_parent)
-}
-
 #[derive(Clone, Debug)]
 struct ExternalTask {
+    kind: &'static Kind<'static>,
     url: String,
+    /// The repository, as `owner/name`.
+    repo: String,
     name: String,
     description: String,
+    /// The GitHub user the task belongs to: the author of a PR
+    /// or the first assignee of an issue.
     user_id: Option<String>,
     koso_user_email: Option<String>,
     status: String,
+    draft: bool,
+    /// The review and check status of an open PR, if fetched.
+    pr_state: Option<PrState>,
 }
 
 impl ExternalTask {
-    fn new(pr: PullRequest) -> Result<ExternalTask> {
+    fn from_pull_request(pr: PullRequest) -> Result<ExternalTask> {
         let name = pr.title.unwrap_or_default();
         let url: String = pr.html_url.map(Into::into).unwrap_or_default();
         if url.is_empty() {
             return Err(anyhow!("Found PR with empty html_url: {}", pr.url));
         }
+        let repo = repo_from_url(&url)?;
         let description = pr.body.unwrap_or_default();
         let user_id = pr.user.as_ref().map(|u| u.id.to_string());
         let koso_user_email = pr.user.and_then(|u| u.email);
         let status = match pr.state {
-            Some(octocrab::models::IssueState::Open) => "In Progress".to_string(),
-            Some(octocrab::models::IssueState::Closed) => "Done".to_string(),
+            Some(IssueState::Open) => "In Progress".to_string(),
+            Some(IssueState::Closed) => "Done".to_string(),
             v => {
                 return Err(anyhow!("Invalid issue state {v:?} for PR {}", pr.number));
             }
         };
         Ok(ExternalTask {
+            kind: PR_KIND,
             url,
+            repo,
             name,
             description,
             user_id,
             koso_user_email,
             status,
+            draft: pr.draft.unwrap_or(false),
+            pr_state: None,
+        })
+    }
+
+    /// Converts the PR, including the review and check status of open PRs.
+    /// The status is left out, rather than failing, if it can't be fetched.
+    async fn from_pull_request_with_state(
+        client: &InstallationGithub,
+        pr: PullRequest,
+    ) -> Result<ExternalTask> {
+        let pr_state = if pr.state == Some(IssueState::Open) {
+            match PrState::fetch(client, &pr).await {
+                Ok(pr_state) => Some(pr_state),
+                Err(e) => {
+                    tracing::warn!("Failed to fetch review and check status: {e:?}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Ok(ExternalTask {
+            pr_state,
+            ..ExternalTask::from_pull_request(pr)?
+        })
+    }
+
+    fn from_issue(issue: Issue) -> Result<ExternalTask> {
+        if issue.pull_request.is_some() {
+            return Err(anyhow!("Issue {} is a PR", issue.html_url));
+        }
+        let assignee = issue.assignees.into_iter().next();
+        let status = match issue.state {
+            IssueState::Open if assignee.is_some() => "In Progress".to_string(),
+            IssueState::Open => "Not Started".to_string(),
+            IssueState::Closed => "Done".to_string(),
+            v => {
+                return Err(anyhow!(
+                    "Invalid issue state {v:?} for issue {}",
+                    issue.number
+                ));
+            }
+        };
+        let url: String = issue.html_url.into();
+        Ok(ExternalTask {
+            kind: ISSUE_KIND,
+            repo: repo_from_url(&url)?,
+            url,
+            name: issue.title,
+            description: issue.body.unwrap_or_default(),
+            user_id: assignee.as_ref().map(|u| u.id.to_string()),
+            koso_user_email: assignee.and_then(|u| u.email),
+            status,
+            draft: false,
+            pr_state: None,
         })
     }
+
+    /// Applies the project's settings. Returns None if the task shouldn't be imported.
+    fn with_settings(&self, settings: &GithubSettings) -> Option<ExternalTask> {
+        if !settings.includes_repo(&self.repo) || (self.draft && !settings.import_drafts) {
+            return None;
+        }
+        let mut task = self.clone();
+        if task.kind.id == PR_KIND.id && task.status != "Done" {
+            task.status = if task.draft {
+                settings.pr_statuses.draft.clone()
+            } else if let Some(pr_state) = &task.pr_state {
+                pr_state.status(&settings.pr_statuses).clone()
+            } else {
+                settings.pr_statuses.open.clone()
+            };
+        }
+        Some(task)
+    }
+}
+
+/// Returns the `owner/name` of the repository of a PR or issue URL.
+fn repo_from_url(url: &str) -> Result<String> {
+    let mut parts = url
+        .strip_prefix("https://github.com/")
+        .with_context(|| format!("Not a GitHub URL: {url}"))?
+        .split('/');
+    match (parts.next(), parts.next()) {
+        (Some(owner), Some(repo)) if !owner.is_empty() && !repo.is_empty() => {
+            Ok(format!("{owner}/{repo}"))
+        }
+        _ => Err(anyhow!("URL has no repository: {url}")),
+    }
 }
 
-fn new_task(external_task: &ExternalTask, num: u64, kind: &Kind) -> Result<Task> {
+fn new_task(external_task: &ExternalTask, num: u64) -> Result<Task> {
     let id = BASE64_URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4());
     tracing::trace!("Creating new task {} ({num}): {}", id, external_task.url);
     Ok(Task {
@@ -187,7 +279,8 @@ fn new_task(external_task: &ExternalTask, num: u64, kind: &Kind) -> Result<Task>
         status: Some(external_task.status.clone()),
         status_time: Some(now()?),
         url: Some(external_task.url.clone()),
-        kind: Some(kind.id.to_string()),
+        kind: Some(external_task.kind.id.to_string()),
+        desc: external_task.pr_state.as_ref().map(PrState::summary),
         ..Task::default()
     })
 }
@@ -210,7 +303,25 @@ fn update_task(
         task.set_status(txn, Some(&external_task.status));
         task.set_status_time(txn, Some(now()?));
     }
-    if task.get_assignee(txn)?.is_none() && external_task.koso_user_email.is_some() {
+    if let Some(pr_state) = &external_task.pr_state {
+        let summary = pr_state.summary();
+        if task.get_desc(txn)?.is_none_or(|desc| desc != summary) {
+            task.set_desc(txn, Some(&summary));
+        }
+    }
+    if external_task.kind.id == ISSUE_KIND.id {
+        // Issues are assigned on GitHub. Follow the assignee unless it isn't a Koso user.
+        let unknown_user =
+            external_task.user_id.is_some() && external_task.koso_user_email.is_none();
+        if !unknown_user && task.get_assignee(txn)? != external_task.koso_user_email {
+            tracing::trace!(
+                "Reassigning task {}: {}",
+                task.get_id(txn)?,
+                external_task.url
+            );
+            task.set_assignee(txn, external_task.koso_user_email.as_deref());
+        }
+    } else if task.get_assignee(txn)?.is_none() && external_task.koso_user_email.is_some() {
         tracing::trace!(
             "Updating assignee {}: {}",
             task.get_id(txn)?,
@@ -221,60 +332,28 @@ fn update_task(
     Ok(())
 }
 
-fn resolve_task(txn: &mut TransactionMut, task: &YTaskProxy) -> Result<()> {
-    if task.get_status(txn)?.is_none_or(|s| s != "Done") {
-        tracing::trace!(
-            "Resolving task {}: {}",
-            task.get_id(txn)?,
-            task.get_url(txn)?.unwrap_or_default()
-        );
-        task.set_status(txn, Some("Done"));
-        task.set_status_time(txn, Some(now()?));
-    }
-    Ok(())
-}
-
-/// Adds the given task ID as a child of any tasks referenced in the github task.
-fn add_referenced_task_links(
+/// Files a new task under the task configured for its repository, if any.
+fn add_repo_parent_link(
     txn: &mut TransactionMut,
     doc: &YDocProxy,
     task_id: &str,
     github_task: &ExternalTask,
+    settings: &GithubSettings,
 ) -> Result<()> {
-    for link_task in doc.get_by_nums(txn, &find_referenced_task_nums(github_task))? {
-        // Disallow linking to managed links this, additionally, prevents circular links
-        // because the given task is itself always managed.
-        if link_task.is_managed(txn)? {
-            continue;
+    let Some(parent_id) = settings.parent_for_repo(&github_task.repo) else {
+        return Ok(());
+    };
+    match doc.get(txn, parent_id) {
+        // Disallow linking to managed tasks, which are only changed by their plugin.
+        Ok(parent) if !parent.is_managed(txn)? => {
+            parent.push_child(txn, task_id)?;
         }
-
-        link_task.push_child(txn, task_id)?;
+        Ok(_) => tracing::warn!("Configured parent {parent_id} is managed"),
+        Err(_) => tracing::warn!("Configured parent {parent_id} doesn't exist"),
     }
     Ok(())
 }
 
-thread_local! {
-    static RE: LazyCell<Regex> = LazyCell::new(|| Regex::new(r"(?i)(?-u:\b)koso[#_-](\d+)").unwrap());
-}
-
-/// Searches the external task's name and description for references to Koso Tasks
-/// of the form: koso#<num>, koso_<num> or koso-<num>
-fn find_referenced_task_nums(github_task: &ExternalTask) -> HashSet<String> {
-    RE.with(|re| {
-        re.captures_iter(&github_task.description)
-            .chain(re.captures_iter(&github_task.name))
-            .map(|g| g[1].to_owned())
-            .collect()
-    })
-}
-
-fn now() -> Result<i64> {
-    Ok(SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)?
-        .as_millis()
-        .try_into()?)
-}
-
 async fn lookup_by_github_user_id(github_user_id: &str, pool: &PgPool) -> Result<Option<String>> {
     // TODO: Cache and batch these lookups.
     let email: Option<(String,)> =
@@ -286,65 +365,19 @@ async fn lookup_by_github_user_id(github_user_id: &str, pool: &PgPool) -> Result
     Ok(email.map(|e| e.0))
 }
 
-struct Kind<'a> {
-    id: &'a str,
-    name: &'a str,
-    parent_kind: Option<&'a Kind<'a>>,
-}
-
-impl Kind<'_> {
-    /// Creates a new kind.
-    /// `id` must NOT contain underscores as they're used
-    /// as separators in the kind hierarchy. For example, 'github_pr'
-    /// is a child kind of 'github'.
-    const fn new<'a>(id: &'a str, name: &'a str) -> Kind<'a> {
-        Kind {
-            id,
-            name,
-            parent_kind: None,
-        }
-    }
-
-    /// Creates a new nested kind.
-    /// For consistent namespacing, `id` must have `{parent_kind.id}_` as a prefix.
-    /// The remaining suffix must NOT contain underscores.
-    /// For example, a kind of 'github_pr' should have a parent_kind of 'github'.
-    const fn new_nested<'a>(parent_kind: &'a Kind, id: &'a str, name: &'a str) -> Kind<'a> {
-        Kind {
-            id,
-            name,
-            parent_kind: Some(parent_kind),
-        }
-    }
-
-    fn validate(&self) -> Result<()> {
-        let sub_id = match self.parent_kind {
-            Some(parent_kind) => {
-                parent_kind.validate()?;
-                let Some(sub_id) = self.id.strip_prefix(&format!("{}_", parent_kind.id)) else {
-                    return Err(anyhow!(
-                        "Kind id ({}) does not start with parent kind id ({})",
-                        self.id,
-                        parent_kind.id
-                    ));
-                };
-                sub_id
-            }
-            None => self.id,
-        };
-        if sub_id.contains("_") {
-            return Err(anyhow!(
-                "Kind id ({}) must not contain underscores which separate parent and children kinds",
-                sub_id,
-            ));
-        }
-        Ok(())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        api::collab::txn_origin::{Actor, YOrigin},
+        plugins::{
+            config::PrStatuses,
+            github::pr_state::{ChecksStatus, ReviewStatus},
+            tasks::find_referenced_task_nums,
+        },
+    };
+    use octocrab::models::webhook_events::{WebhookEvent, WebhookEventPayload};
+    use std::collections::HashSet;
 
     #[test_log::test(tokio::test)]
     async fn validate_pr_kind() {
@@ -352,56 +385,158 @@ mod tests {
         assert!(res.is_ok(), "PR_KIND is invalid {res:?}");
     }
 
+    #[test_log::test(tokio::test)]
+    async fn validate_issue_kind() {
+        let res = ISSUE_KIND.validate();
+        assert!(res.is_ok(), "ISSUE_KIND is invalid {res:?}");
+    }
+
     #[test_log::test(tokio::test)]
     async fn validate_plugin_kind() {
         let res = PLUGIN_KIND.validate();
         assert!(res.is_ok(), "PLUGIN_KIND is invalid {res:?}");
     }
 
+    fn assigned_issue() -> Issue {
+        let event = WebhookEvent::try_from_header_and_body(
+            "issues",
+            include_str!("../testdata/assigned_issue.json"),
+        )
+        .unwrap();
+        let WebhookEventPayload::Issues(issue_event) = event.specific else {
+            panic!("Unexpected event: {:?}", event.specific);
+        };
+        issue_event.issue
+    }
+
     #[test_log::test]
-    fn find_referenced_task_nums_matches_name() {
+    fn external_task_from_issue() {
+        let task = ExternalTask::from_issue(assigned_issue()).unwrap();
+        assert_eq!(task.kind.id, ISSUE_KIND.id);
+        assert_eq!(task.url, "https://github.com/kosolabs/koso/issues/612");
+        assert_eq!(task.name, "Rust analyzer is slow to start in VSCode");
+        assert_eq!(task.user_id, Some("4945355".to_string()));
+        assert_eq!(task.status, "In Progress");
         assert_eq!(
-            find_referenced_task_nums(&ExternalTask {
-                url: "https://github.com/kosolabs/koso/pull/121".into(),
-                name: "koso-15: Something else".into(),
-                description: "Something something".into(),
-                user_id: Some("123".to_string()),
-                koso_user_email: Some("foo@example.com".to_string()),
-                status: "In Progress".to_string(),
-            }),
-            HashSet::from_iter(vec!["15".to_string()].into_iter())
+            find_referenced_task_nums(&task.name, &task.description),
+            HashSet::from(["15".into()])
         );
+
+        let mut issue = assigned_issue();
+        issue.assignees.clear();
+        let task = ExternalTask::from_issue(issue).unwrap();
+        assert_eq!(task.user_id, None);
+        assert_eq!(task.status, "Not Started");
+
+        let mut issue = assigned_issue();
+        issue.state = IssueState::Closed;
+        assert_eq!(ExternalTask::from_issue(issue).unwrap().status, "Done");
     }
 
     #[test_log::test]
-    fn find_referenced_task_nums_matches_description() {
-        assert_eq!(
-            find_referenced_task_nums(&ExternalTask {
-                url: "https://github.com/kosolabs/koso/pull/121".into(),
-                name: "Something else".into(),
-                description: "Something something koso#17, koso#19".into(),
-                user_id: Some("123".to_string()),
-                koso_user_email: Some("foo@example.com".to_string()),
-                status: "In Progress".to_string(),
+    fn with_settings_filters_and_maps_status() {
+        let pr = ExternalTask {
+            kind: PR_KIND,
+            url: "https://github.com/kosolabs/koso/pull/121".into(),
+            repo: repo_from_url("https://github.com/kosolabs/koso/pull/121").unwrap(),
+            name: "Something".into(),
+            description: "".into(),
+            user_id: None,
+            koso_user_email: None,
+            status: "In Progress".to_string(),
+            draft: true,
+            pr_state: None,
+        };
+        assert_eq!(pr.repo, "kosolabs/koso");
+
+        let settings = GithubSettings {
+            pr_statuses: PrStatuses {
+                draft: "Not Started".into(),
+                ..PrStatuses::default()
+            },
+            ..GithubSettings::default()
+        };
+        assert_eq!(pr.with_settings(&settings).unwrap().status, "Not Started");
+        let closed = ExternalTask {
+            status: "Done".into(),
+            ..pr.clone()
+        };
+        assert_eq!(closed.with_settings(&settings).unwrap().status, "Done");
+
+        let reviewed = ExternalTask {
+            draft: false,
+            pr_state: Some(PrState {
+                review: ReviewStatus::Approved,
+                checks: ChecksStatus::Failed,
+                failed_checks: vec!["build".into()],
             }),
-            HashSet::from_iter(vec!["17".to_string(), "19".to_string()].into_iter())
+            ..pr.clone()
+        };
+        assert_eq!(reviewed.with_settings(&settings).unwrap().status, "Blocked");
+        let passing = ExternalTask {
+            pr_state: Some(PrState {
+                checks: ChecksStatus::Passed,
+                failed_checks: vec![],
+                ..reviewed.pr_state.clone().unwrap()
+            }),
+            ..reviewed.clone()
+        };
+        assert_eq!(
+            passing.with_settings(&settings).unwrap().status,
+            "In Progress"
+        );
+        let unknown = ExternalTask {
+            pr_state: None,
+            ..reviewed
+        };
+        assert_eq!(
+            unknown.with_settings(&settings).unwrap().status,
+            "In Progress"
         );
+
+        let settings = GithubSettings {
+            import_drafts: false,
+            ..GithubSettings::default()
+        };
+        assert!(pr.with_settings(&settings).is_none());
+
+        let settings = GithubSettings {
+            exclude_repos: vec!["KosoLabs/Koso".into()],
+            ..GithubSettings::default()
+        };
+        assert!(pr.with_settings(&settings).is_none());
     }
 
     #[test_log::test]
-    fn find_referenced_task_nums_matches_description_and_name() {
-        assert_eq!(
-            find_referenced_task_nums(&ExternalTask {
-                url: "https://github.com/kosolabs/koso/pull/121".into(),
-                name: "Something else KoSo_18".into(),
-                description: "Somethingkoso#14 something KOSO-17, koso#19".into(),
-                user_id: Some("123".to_string()),
-                koso_user_email: Some("foo@example.com".to_string()),
-                status: "In Progress".to_string(),
-            }),
-            HashSet::from_iter(
-                vec!["17".to_string(), "18".to_string(), "19".to_string()].into_iter()
-            )
+    fn update_task_follows_issue_assignee() {
+        let doc = YDocProxy::new();
+        let mut txn = doc.transact_mut_with(
+            YOrigin {
+                who: "update_task_follows_issue_assignee".to_string(),
+                id: "test".to_string(),
+                actor: Actor::GitHub,
+            }
+            .as_origin()
+            .unwrap(),
         );
+        let mut external_task = ExternalTask::from_issue(assigned_issue()).unwrap();
+        external_task.koso_user_email = Some("foo@example.com".to_string());
+        let task = doc.set(&mut txn, &new_task(&external_task, 1).unwrap());
+        assert_eq!(task.get_assignee(&txn).unwrap().unwrap(), "foo@example.com");
+
+        // Reassigned to another Koso user.
+        external_task.koso_user_email = Some("bar@example.com".to_string());
+        update_task(&mut txn, &task, &external_task).unwrap();
+        assert_eq!(task.get_assignee(&txn).unwrap().unwrap(), "bar@example.com");
+
+        // Reassigned to someone who isn't a Koso user.
+        external_task.koso_user_email = None;
+        update_task(&mut txn, &task, &external_task).unwrap();
+        assert_eq!(task.get_assignee(&txn).unwrap().unwrap(), "bar@example.com");
+
+        // Unassigned.
+        external_task.user_id = None;
+        update_task(&mut txn, &task, &external_task).unwrap();
+        assert_eq!(task.get_assignee(&txn).unwrap(), None);
     }
 }
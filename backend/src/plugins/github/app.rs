@@ -7,8 +7,18 @@ use base64::{Engine, prelude::BASE64_STANDARD};
 use futures::StreamExt;
 use octocrab::{
     Octocrab, OctocrabBuilder, Page,
-    models::{AppId, InstallationId, Repository, pulls::PullRequest, repos::Object},
-    params::{Direction, State, pulls::Sort, repos::Reference},
+    models::{
+        AppId, InstallationId, Repository,
+        checks::CheckRun,
+        issues::Issue,
+        pulls::{PullRequest, Review},
+        repos::Object,
+    },
+    params::{
+        Direction, State, issues,
+        pulls::Sort,
+        repos::{Commitish, Reference},
+    },
 };
 
 pub enum InstallationRef {
@@ -98,8 +108,96 @@ impl InstallationGithub {
             .context("Failed to paginate through PRs")
     }
 
+    pub async fn fetch_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+    ) -> Result<PullRequest> {
+        self.installation_crab
+            .pulls(owner, repo)
+            .get(number)
+            .await
+            .with_context(|| format!("Failed to fetch PR {owner}/{repo}#{number}"))
+    }
+
+    /// Returns the reviews of a PR, in chronological order.
+    pub async fn fetch_reviews(&self, owner: &str, repo: &str, number: u64) -> Result<Vec<Review>> {
+        let page = self
+            .installation_crab
+            .pulls(owner, repo)
+            .list_reviews(number)
+            .per_page(100)
+            .send()
+            .await?;
+        self.installation_crab
+            .all_pages(page)
+            .await
+            .context("Failed to paginate through reviews")
+    }
+
+    /// Returns the latest check runs of a commit.
+    pub async fn fetch_check_runs(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+    ) -> Result<Vec<CheckRun>> {
+        Ok(self
+            .installation_crab
+            .checks(owner, repo)
+            .list_check_runs_for_git_ref(Commitish(sha.to_string()))
+            .per_page(100)
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch check runs for {owner}/{repo}@{sha}"))?
+            .check_runs)
+    }
+
+    /// Returns open issues, excluding PRs which GitHub also considers to be issues.
+    pub async fn fetch_issues(&self, owner: &str, repo: &str) -> Result<Vec<Issue>> {
+        let page = self
+            .installation_crab
+            .issues(owner, repo)
+            .list()
+            .state(State::Open)
+            .sort(issues::Sort::Updated)
+            .direction(Direction::Descending)
+            .per_page(100)
+            .send()
+            .await?;
+        // Paginate through additional pages, if any, collecting all results.
+        Ok(self
+            .installation_crab
+            .all_pages(page)
+            .await
+            .context("Failed to paginate through issues")?
+            .into_iter()
+            .filter(|issue| issue.pull_request.is_none())
+            .collect())
+    }
+
     /// Returns all open PRs from all of the installation's repositories.
     pub async fn fetch_install_pull_requests(&self) -> Result<Vec<PullRequest>> {
+        self.fetch_from_install_repos("PRs", |owner, name| async move {
+            self.fetch_pull_requests(&owner, &name).await
+        })
+        .await
+    }
+
+    /// Returns all open issues from all of the installation's repositories.
+    pub async fn fetch_install_issues(&self) -> Result<Vec<Issue>> {
+        self.fetch_from_install_repos("issues", |owner, name| async move {
+            self.fetch_issues(&owner, &name).await
+        })
+        .await
+    }
+
+    async fn fetch_from_install_repos<T, F, Fut>(&self, what: &str, fetch: F) -> Result<Vec<T>>
+    where
+        F: Fn(String, String) -> Fut,
+        Fut: Future<Output = Result<Vec<T>>>,
+    {
         let installed_repos = self.fetch_install_repos().await?;
         let mut results = Vec::new();
         for repo in installed_repos {
@@ -108,21 +206,22 @@ impl InstallationGithub {
                 None => return Err(anyhow!("No owner set for repo: {repo:?}")),
             };
             let name = repo.name;
-            tracing::trace!("Fetching PRs for {owner}/{name}");
+            tracing::trace!("Fetching {what} for {owner}/{name}");
+            let fetched = fetch(owner.clone(), name.clone());
             results.push(async move {
-                match self.fetch_pull_requests(&owner, &name).await {
-                    Ok(prs) => {
-                        tracing::trace!("Fetched {} PRs for {owner}/{name}", prs.len());
-                        Ok(prs)
+                match fetched.await {
+                    Ok(items) => {
+                        tracing::trace!("Fetched {} {what} for {owner}/{name}", items.len());
+                        Ok(items)
                     }
-                    Err(e) => Err(e.context("Failed to fetch prs for {owner}/{name}")),
+                    Err(e) => Err(e.context(format!("Failed to fetch {what} for {owner}/{name}"))),
                 }
             });
         }
         let results = futures::future::join_all(results).await;
         Ok(results
             .into_iter()
-            .collect::<Result<Vec<Vec<PullRequest>>>>()?
+            .collect::<Result<Vec<Vec<T>>>>()?
             .into_iter()
             .flatten()
             .collect())
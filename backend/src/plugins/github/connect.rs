@@ -1,5 +1,5 @@
 use crate::{
-    api::{self, google::User},
+    api::{self, google::User, model::ProjectRole, yproxy::STATUSES},
     plugins::{
         config::{Config, ConfigStorage, GithubSettings, Settings},
         github::{self, Poller},
@@ -10,7 +10,8 @@ use crate::{
 use anyhow::{Context as _, Result, anyhow};
 use axum::{
     Extension, Json, Router,
-    routing::{delete, get, post},
+    extract::Query,
+    routing::{delete, get, post, put},
 };
 use axum_anyhow::{ApiResult, bad_request, forbidden, not_found};
 use octocrab::{Octocrab, OctocrabBuilder, models::Installation};
@@ -31,6 +32,28 @@ struct ConnectRequest {
 #[serde(rename_all = "camelCase")]
 struct ConnectResponse {}
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SettingsQuery {
+    project_id: String,
+}
+
+/// The settings of one of the project's connected installations.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct InstallationSettings {
+    installation_id: String,
+    settings: GithubSettings,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct UpdateSettingsRequest {
+    project_id: String,
+    installation_id: String,
+    settings: GithubSettings,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct ConnectUserRequest {
@@ -110,6 +133,8 @@ impl ConnectHandler {
         Router::new()
             .route("/connect", post(Self::connect_project_handler))
             .route("/init", get(Self::init_handler))
+            .route("/settings", get(Self::get_settings_handler))
+            .route("/settings", put(Self::update_settings_handler))
             .route("/userConnections", post(Self::connect_user_handler))
             .route(
                 "/userConnections",
@@ -145,7 +170,8 @@ impl ConnectHandler {
         request: ConnectRequest,
         user: User,
     ) -> ApiResult<Json<ConnectResponse>> {
-        api::verify_project_access(self.pool, &user, &request.project_id).await?;
+        api::verify_project_access(self.pool, &user, &request.project_id, ProjectRole::Owner)
+            .await?;
         self.verify_installation_access(&request).await?;
 
         tracing::debug!(
@@ -153,11 +179,17 @@ impl ConnectHandler {
             request.project_id,
             request.installation_id
         );
+        // Keep the settings of installations connected before.
+        let settings = self
+            .find_config(&request.project_id, &request.installation_id)
+            .await?
+            .map(|config| config.settings)
+            .unwrap_or(Settings::Github(GithubSettings::default()));
         let config = Config {
             project_id: request.project_id,
             plugin_id: github::PLUGIN_KIND.id.to_string(),
             external_id: request.installation_id,
-            settings: Settings::Github(GithubSettings {}),
+            settings,
         };
         self.storage.insert_or_update(&config).await?;
 
@@ -168,6 +200,78 @@ impl ConnectHandler {
         Ok(Json(ConnectResponse {}))
     }
 
+    #[tracing::instrument(skip(user, handler))]
+    async fn get_settings_handler(
+        Extension(user): Extension<User>,
+        Extension(handler): Extension<ConnectHandler>,
+        Query(query): Query<SettingsQuery>,
+    ) -> ApiResult<Json<Vec<InstallationSettings>>> {
+        api::verify_project_access(handler.pool, &user, &query.project_id, ProjectRole::Viewer)
+            .await?;
+
+        let settings = handler
+            .storage
+            .list_for_project(github::PLUGIN_KIND.id, &query.project_id)
+            .await?
+            .into_iter()
+            .filter_map(|config| match config.settings {
+                Settings::Github(settings) => Some(InstallationSettings {
+                    installation_id: config.external_id,
+                    settings,
+                }),
+                Settings::Gitlab(_) => None,
+            })
+            .collect();
+        Ok(Json(settings))
+    }
+
+    #[tracing::instrument(
+        skip(user, handler, request),
+        fields(project_id=request.project_id, installation_id=request.installation_id)
+    )]
+    async fn update_settings_handler(
+        Extension(user): Extension<User>,
+        Extension(handler): Extension<ConnectHandler>,
+        Json(request): Json<UpdateSettingsRequest>,
+    ) -> ApiResult<Json<InstallationSettings>> {
+        api::verify_project_access(handler.pool, &user, &request.project_id, ProjectRole::Owner)
+            .await?;
+        validate_settings(&request.settings)?;
+
+        let Some(mut config) = handler
+            .find_config(&request.project_id, &request.installation_id)
+            .await?
+        else {
+            return Err(not_found(
+                "NOT_FOUND",
+                &format!(
+                    "Installation {} is not connected to the project",
+                    request.installation_id
+                ),
+            ));
+        };
+        config.settings = Settings::Github(request.settings.clone());
+        handler.storage.insert_or_update(&config).await?;
+
+        // Poll in the background to apply the new settings.
+        let poller = handler.poller.clone();
+        tokio::spawn(async move { poller.poll_installation(config).await }.in_current_span());
+
+        Ok(Json(InstallationSettings {
+            installation_id: request.installation_id,
+            settings: request.settings,
+        }))
+    }
+
+    async fn find_config(&self, project_id: &str, installation_id: &str) -> Result<Option<Config>> {
+        Ok(self
+            .storage
+            .list_for_project(github::PLUGIN_KIND.id, project_id)
+            .await?
+            .into_iter()
+            .find(|config| config.external_id == installation_id))
+    }
+
     async fn verify_installation_access(&self, request: &ConnectRequest) -> ApiResult<()> {
         if request.code.is_empty() {
             return Err(bad_request("EMPTY_CODE", "Code is blank"));
@@ -309,3 +413,53 @@ impl ConnectHandler {
         Ok(oauth)
     }
 }
+
+fn validate_settings(settings: &GithubSettings) -> ApiResult<()> {
+    let repos = settings
+        .include_repos
+        .iter()
+        .chain(settings.exclude_repos.iter())
+        .chain(settings.repo_parents.keys())
+        .chain(settings.issue_repo.iter());
+    for repo in repos {
+        if !is_repo_name(repo) {
+            return Err(bad_request(
+                "INVALID_SETTINGS",
+                &format!("Repository '{repo}' must be of the form owner/name"),
+            ));
+        }
+    }
+    if settings
+        .repo_parents
+        .values()
+        .chain(settings.issue_parent_id.iter())
+        .any(|parent_id| parent_id.is_empty() || parent_id == "root")
+    {
+        return Err(bad_request(
+            "INVALID_SETTINGS",
+            "Parent tasks must be set and not the root",
+        ));
+    }
+    if settings.issue_parent_id.is_some() && settings.issue_repo.is_none() {
+        return Err(bad_request(
+            "INVALID_SETTINGS",
+            "Set the repository to open issues in",
+        ));
+    }
+    for status in settings.pr_statuses.all() {
+        if !STATUSES.contains(&status.as_str()) {
+            return Err(bad_request(
+                "INVALID_SETTINGS",
+                &format!("PR status '{status}' must be one of {STATUSES:?}"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn is_repo_name(repo: &str) -> bool {
+    matches!(
+        repo.split('/').collect::<Vec<_>>().as_slice(),
+        [owner, name] if !owner.is_empty() && !name.is_empty()
+    )
+}
@@ -8,40 +8,55 @@ use crate::{
         yproxy::{YDocProxy, YTaskProxy},
     },
     plugins::{
-        config::{Config, ConfigStorage},
+        config::{Config, ConfigStorage, GithubSettings, Settings},
         github::{
-            ExternalTask, Kind, PLUGIN_KIND, PR_KIND, add_referenced_task_links,
+            ExternalTask, ISSUE_KIND, PLUGIN_KIND, PR_KIND, add_repo_parent_link,
             app::{AppGithub, InstallationRef},
-            get_or_create_kind_parent, new_task, resolve_task, update_task,
+            lookup_by_github_user_id, new_task, update_task,
         },
+        tasks::{Kind, add_referenced_task_links, get_or_create_kind_parent, resolve_task},
     },
     settings::settings,
 };
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use axum::{Extension, Router, routing::post};
 use axum_anyhow::ApiResult;
+use futures::StreamExt;
+use sqlx::PgPool;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     time::{Duration, Instant},
 };
-use yrs::{Origin, ReadTxn};
+use yrs::{Origin, ReadTxn, TransactionMut};
 
 const INIT_POLL_DELAY: Duration = Duration::from_secs(2 * 60);
 const POLL_DELAY: Duration = Duration::from_secs(16 * 60);
+/// The number of PRs to fetch reviews and check runs for at once.
+const PR_STATE_CONCURRENCY: usize = 10;
+
+/// The open tasks fetched from GitHub, keyed by URL, for each kind that was fetched.
+type GithubTasks = Vec<(&'static Kind<'static>, HashMap<String, ExternalTask>)>;
 
 #[derive(Clone)]
 pub(super) struct Poller {
     collab: Collab,
     client: AppGithub,
     config_storage: ConfigStorage,
+    pool: &'static PgPool,
 }
 
 impl Poller {
-    pub(super) fn new(collab: Collab, client: AppGithub, config_storage: ConfigStorage) -> Poller {
+    pub(super) fn new(
+        collab: Collab,
+        client: AppGithub,
+        config_storage: ConfigStorage,
+        pool: &'static PgPool,
+    ) -> Poller {
         Poller {
             collab,
             client,
             config_storage,
+            pool,
         }
     }
 
@@ -123,8 +138,8 @@ impl Poller {
     async fn poll_installation_internal(&self, config: Config) -> Result<()> {
         tracing::debug!("Polling installation");
 
-        let github_tasks_by_url = self.fetch_tasks_from_github(&config).await?;
-        tracing::trace!("Fetched Github tasks: {:?}", github_tasks_by_url.values());
+        let github_tasks = self.fetch_tasks_from_github(&config).await?;
+        tracing::trace!("Fetched Github tasks: {github_tasks:?}");
 
         let client = self
             .collab
@@ -134,7 +149,7 @@ impl Poller {
             // Avoid any expensive, async work while holding the doc_box lock.
             let doc_box = client.project.doc_box.lock().await;
             self.merge_tasks(
-                &github_tasks_by_url,
+                &github_tasks,
                 &config,
                 &DocBox::doc_or_error(doc_box.as_ref())?.ydoc,
             )?
@@ -142,38 +157,81 @@ impl Poller {
 
         tracing::debug!(
             "Finished polling installation with {} active and {} total tasks",
-            github_tasks_by_url.len(),
+            github_tasks
+                .iter()
+                .map(|(_, tasks_by_url)| tasks_by_url.len())
+                .sum::<usize>(),
             task_count
         );
 
         Ok(())
     }
 
-    async fn fetch_tasks_from_github(
-        &self,
-        config: &Config,
-    ) -> Result<HashMap<String, ExternalTask>> {
+    async fn fetch_tasks_from_github(&self, config: &Config) -> Result<GithubTasks> {
         let client = self
             .client
             .installation_github(InstallationRef::InstallationId {
                 id: config.external_id.parse::<u64>()?,
             })
             .await?;
-        let prs: Vec<octocrab::models::pulls::PullRequest> =
-            client.fetch_install_pull_requests().await?;
 
-        let mut results = HashMap::with_capacity(prs.len());
-        for pr in prs {
-            match ExternalTask::new(pr) {
-                Ok(task) => {
-                    if results.insert(task.url.clone(), task).is_some() {
-                        tracing::warn!("Found multiple PRs with same url");
+        let mut prs = Vec::new();
+        let fetched: Vec<Result<ExternalTask>> =
+            futures::stream::iter(client.fetch_install_pull_requests().await?)
+                .map(|pr| ExternalTask::from_pull_request_with_state(&client, pr))
+                .buffer_unordered(PR_STATE_CONCURRENCY)
+                .collect()
+                .await;
+        for task in fetched {
+            match task {
+                Ok(task) => prs.push(task),
+                Err(e) => tracing::warn!("Skipping malformed PR: {e:?}"),
+            }
+        }
+        let mut results = vec![(PR_KIND, prs)];
+
+        // Installations predating issue sync may lack permission to read issues.
+        // Skip issues entirely, rather than resolving every issue task, when they can't be fetched.
+        match client.fetch_install_issues().await {
+            Ok(fetched) => {
+                let mut issues = Vec::new();
+                for issue in fetched {
+                    match ExternalTask::from_issue(issue) {
+                        Ok(task) => issues.push(task),
+                        Err(e) => tracing::warn!("Skipping malformed issue: {e:?}"),
                     }
                 }
-                Err(e) => tracing::warn!("Skipping malformed PR: {e:?}"),
+                results.push((ISSUE_KIND, issues));
             }
+            Err(e) => tracing::warn!("Failed to fetch issues, skipping: {e:?}"),
         }
-        Ok(results)
+
+        // Populate the email of the task's user if we're able to.
+        let mut emails: HashMap<String, Option<String>> = HashMap::new();
+        let mut github_tasks = Vec::with_capacity(results.len());
+        for (kind, tasks) in results {
+            let mut tasks_by_url = HashMap::with_capacity(tasks.len());
+            for mut task in tasks {
+                if let Some(user_id) = &task.user_id {
+                    let email = match emails.get(user_id) {
+                        Some(email) => email.clone(),
+                        None => {
+                            let email = lookup_by_github_user_id(user_id, self.pool).await?;
+                            emails.insert(user_id.clone(), email.clone());
+                            email
+                        }
+                    };
+                    if email.is_some() {
+                        task.koso_user_email = email;
+                    }
+                }
+                if tasks_by_url.insert(task.url.clone(), task).is_some() {
+                    tracing::warn!("Found multiple {} tasks with same url", kind.id);
+                }
+            }
+            github_tasks.push((kind, tasks_by_url));
+        }
+        Ok(github_tasks)
     }
 
     fn list_doc_tasks<T: ReadTxn>(
@@ -203,52 +261,98 @@ impl Poller {
     // Note: This function should remain synchronous to avoid blocking the doc_box lock.
     fn merge_tasks(
         &self,
-        github_tasks_by_url: &HashMap<String, ExternalTask>,
+        github_tasks: &GithubTasks,
         config: &Config,
         doc: &YDocProxy,
     ) -> Result<usize> {
+        let Settings::Github(settings) = &config.settings else {
+            return Err(anyhow!("Config has no GitHub settings"));
+        };
         let mut txn = doc.transact_mut_with(origin(config)?);
+        let mut task_count = 0;
+        for (kind, github_tasks_by_url) in github_tasks {
+            let mut included = HashMap::with_capacity(github_tasks_by_url.len());
+            let mut excluded = HashSet::new();
+            for (url, github_task) in github_tasks_by_url {
+                match github_task.with_settings(settings) {
+                    Some(github_task) => {
+                        included.insert(url.clone(), github_task);
+                    }
+                    None => {
+                        excluded.insert(url.clone());
+                    }
+                }
+            }
+            task_count +=
+                self.merge_kind_tasks(&mut txn, doc, kind, &included, &excluded, settings)?;
+        }
+        Ok(task_count)
+    }
 
-        let parent = get_or_create_kind_parent(&mut txn, doc, PR_KIND)?;
-        let doc_tasks_by_url = self.list_doc_tasks(&txn, doc, &parent, PR_KIND)?;
+    fn merge_kind_tasks(
+        &self,
+        txn: &mut TransactionMut,
+        doc: &YDocProxy,
+        kind: &Kind,
+        github_tasks_by_url: &HashMap<String, ExternalTask>,
+        excluded_urls: &HashSet<String>,
+        settings: &GithubSettings,
+    ) -> Result<usize> {
+        let parent = get_or_create_kind_parent(txn, doc, kind)?;
+        let doc_tasks_by_url = self.list_doc_tasks(txn, doc, &parent, kind)?;
 
         // Resolve or update tasks that already exist in the doc.
         for (url, task) in doc_tasks_by_url.iter() {
             match github_tasks_by_url.get(url) {
                 Some(github_task) => {
-                    update_task(&mut txn, task, github_task)?;
+                    update_task(txn, task, github_task)?;
 
-                    let task_id = task.get_id(&txn)?;
-                    add_referenced_task_links(&mut txn, doc, &task_id, github_task)?;
+                    let task_id = task.get_id(txn)?;
+                    add_referenced_task_links(
+                        txn,
+                        doc,
+                        &task_id,
+                        &github_task.name,
+                        &github_task.description,
+                    )?;
                 }
+                // Leave tasks excluded by the settings, but still open, as they are.
+                None if excluded_urls.contains(url) => {}
                 None => {
-                    // Note: we didn't fetch the closed PR so we can't call add_referenced_task_links
+                    // Note: we didn't fetch the closed PR or issue so we can't call add_referenced_task_links
                     // to add links. In most cases this won't matter because the webhook
                     // will have done it already
                     // TODO: If this is a problem, we could fetch the closed PR here and add reference links.
-                    resolve_task(&mut txn, task)?
+                    resolve_task(txn, task)?
                 }
             }
         }
 
         // Create any new tasks that don't already exist.
-        let mut next_num: u64 = doc.next_num(&txn)?;
-        let mut children = parent.get_children(&txn)?;
+        let mut next_num: u64 = doc.next_num(txn)?;
+        let mut children = parent.get_children(txn)?;
         for github_task in github_tasks_by_url.values() {
             match doc_tasks_by_url.get(&github_task.url) {
                 Some(_) => {}
                 None => {
-                    let task = new_task(github_task, next_num, PR_KIND)?;
+                    let task = new_task(github_task, next_num)?;
 
                     next_num += 1;
-                    doc.set(&mut txn, &task);
+                    doc.set(txn, &task);
                     children.push(task.id.clone());
 
-                    add_referenced_task_links(&mut txn, doc, &task.id, github_task)?;
+                    add_referenced_task_links(
+                        txn,
+                        doc,
+                        &task.id,
+                        &github_task.name,
+                        &github_task.description,
+                    )?;
+                    add_repo_parent_link(txn, doc, &task.id, github_task, settings)?;
                 }
             }
         }
-        parent.set_children(&mut txn, &children);
+        parent.set_children(txn, &children);
 
         Ok(children.len())
     }
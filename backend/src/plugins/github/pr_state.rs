@@ -0,0 +1,333 @@
+use crate::plugins::{
+    config::PrStatuses,
+    github::{app::InstallationGithub, repo_from_url},
+};
+use anyhow::{Context, Result};
+use octocrab::models::{
+    UserId,
+    checks::CheckRun,
+    pulls::{PullRequest, Review, ReviewState},
+};
+use std::collections::HashMap;
+
+/// Check run conclusions that need action before the PR can merge.
+const FAILED_CONCLUSIONS: &[&str] = &["failure", "timed_out", "action_required", "startup_failure"];
+
+/// The review and CI status of an open PR.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct PrState {
+    pub(super) review: ReviewStatus,
+    pub(super) checks: ChecksStatus,
+    /// The names of the failed check runs, sorted.
+    pub(super) failed_checks: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum ReviewStatus {
+    None,
+    Requested,
+    Approved,
+    ChangesRequested,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum ChecksStatus {
+    None,
+    Pending,
+    Passed,
+    Failed,
+}
+
+impl PrState {
+    /// Fetches the reviews and check runs of the PR's head commit.
+    pub(super) async fn fetch(client: &InstallationGithub, pr: &PullRequest) -> Result<PrState> {
+        let url = pr.html_url.as_ref().context("PR has no html_url")?;
+        let repo = repo_from_url(url.as_str())?;
+        let (owner, name) = repo.split_once('/').context("Invalid repository")?;
+        let (reviews, check_runs) = futures::try_join!(
+            client.fetch_reviews(owner, name, pr.number),
+            client.fetch_check_runs(owner, name, &pr.head.sha)
+        )?;
+        Ok(PrState::new(pr, &reviews, &check_runs))
+    }
+
+    pub(super) fn new(pr: &PullRequest, reviews: &[Review], check_runs: &[CheckRun]) -> PrState {
+        // The latest approval or change request of each reviewer counts. Comments don't.
+        let mut latest_reviews: HashMap<UserId, ReviewState> = HashMap::new();
+        for review in reviews {
+            let (Some(user), Some(state)) = (&review.user, review.state) else {
+                continue;
+            };
+            if matches!(
+                state,
+                ReviewState::Approved | ReviewState::ChangesRequested | ReviewState::Dismissed
+            ) {
+                latest_reviews.insert(user.id, state);
+            }
+        }
+        let requested = pr
+            .requested_reviewers
+            .as_ref()
+            .is_some_and(|reviewers| !reviewers.is_empty())
+            || pr
+                .requested_teams
+                .as_ref()
+                .is_some_and(|teams| !teams.is_empty());
+        let review = if latest_reviews
+            .values()
+            .any(|state| *state == ReviewState::ChangesRequested)
+        {
+            ReviewStatus::ChangesRequested
+        } else if requested {
+            ReviewStatus::Requested
+        } else if latest_reviews
+            .values()
+            .any(|state| *state == ReviewState::Approved)
+        {
+            ReviewStatus::Approved
+        } else {
+            ReviewStatus::None
+        };
+
+        let mut failed_checks: Vec<String> = check_runs
+            .iter()
+            .filter(|run| {
+                run.conclusion
+                    .as_deref()
+                    .is_some_and(|conclusion| FAILED_CONCLUSIONS.contains(&conclusion))
+            })
+            .map(|run| run.name.clone())
+            .collect();
+        failed_checks.sort();
+        failed_checks.dedup();
+        let checks = if !failed_checks.is_empty() {
+            ChecksStatus::Failed
+        } else if check_runs.is_empty() {
+            ChecksStatus::None
+        } else if check_runs.iter().any(|run| run.conclusion.is_none()) {
+            ChecksStatus::Pending
+        } else {
+            ChecksStatus::Passed
+        };
+
+        PrState {
+            review,
+            checks,
+            failed_checks,
+        }
+    }
+
+    /// Returns the task status configured for this state.
+    /// Requested changes take precedence over failed checks, which take precedence over the review.
+    pub(super) fn status<'a>(&self, statuses: &'a PrStatuses) -> &'a String {
+        match (self.review, self.checks) {
+            (ReviewStatus::ChangesRequested, _) => &statuses.changes_requested,
+            (_, ChecksStatus::Failed) => &statuses.checks_failed,
+            (ReviewStatus::Requested, _) => &statuses.review_requested,
+            (ReviewStatus::Approved, _) => &statuses.approved,
+            (ReviewStatus::None, _) => &statuses.open,
+        }
+    }
+
+    /// Summarizes the state as Markdown, for the task's description.
+    pub(super) fn summary(&self) -> String {
+        let review = match self.review {
+            ReviewStatus::None => "Not requested",
+            ReviewStatus::Requested => "Awaiting review",
+            ReviewStatus::Approved => "Approved",
+            ReviewStatus::ChangesRequested => "Changes requested",
+        };
+        let checks = match self.checks {
+            ChecksStatus::None => "None".to_string(),
+            ChecksStatus::Pending => "Pending".to_string(),
+            ChecksStatus::Passed => "Passed".to_string(),
+            ChecksStatus::Failed => format!("Failed ({})", self.failed_checks.join(", ")),
+        };
+        format!("- **Review:** {review}\n- **Checks:** {checks}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pull_request(requested_reviewers: serde_json::Value) -> PullRequest {
+        serde_json::from_value(json!({
+            "url": "https://api.github.com/repos/kosolabs/koso/pulls/1",
+            "id": 1,
+            "number": 1,
+            "html_url": "https://github.com/kosolabs/koso/pull/1",
+            "head": {"ref": "feature", "sha": "abc"},
+            "base": {"ref": "main", "sha": "def"},
+            "requested_reviewers": requested_reviewers,
+        }))
+        .unwrap()
+    }
+
+    fn review(user_id: u64, state: &str) -> Review {
+        serde_json::from_value(json!({
+            "id": user_id,
+            "node_id": "node",
+            "html_url": "https://github.com/kosolabs/koso/pull/1#review",
+            "user": author(user_id),
+            "state": state,
+        }))
+        .unwrap()
+    }
+
+    fn author(id: u64) -> serde_json::Value {
+        let url = format!("https://api.github.com/users/user{id}");
+        json!({
+            "login": format!("user{id}"),
+            "id": id,
+            "node_id": "node",
+            "avatar_url": url,
+            "gravatar_id": "",
+            "url": url,
+            "html_url": url,
+            "followers_url": url,
+            "following_url": url,
+            "gists_url": url,
+            "starred_url": url,
+            "subscriptions_url": url,
+            "organizations_url": url,
+            "repos_url": url,
+            "events_url": url,
+            "received_events_url": url,
+            "type": "User",
+            "site_admin": false,
+        })
+    }
+
+    fn check_run(name: &str, conclusion: Option<&str>) -> CheckRun {
+        serde_json::from_value(json!({
+            "id": 1,
+            "node_id": "node",
+            "head_sha": "abc",
+            "url": "https://api.github.com/repos/kosolabs/koso/check-runs/1",
+            "conclusion": conclusion,
+            "output": {"annotations_count": 0, "annotations_url": ""},
+            "name": name,
+            "pull_requests": [],
+        }))
+        .unwrap()
+    }
+
+    #[test_log::test]
+    fn review_status() {
+        let pr = pull_request(json!([]));
+        assert_eq!(PrState::new(&pr, &[], &[]).review, ReviewStatus::None);
+        assert_eq!(
+            PrState::new(&pr, &[review(1, "APPROVED"), review(2, "COMMENTED")], &[]).review,
+            ReviewStatus::Approved
+        );
+        assert_eq!(
+            PrState::new(
+                &pr,
+                &[review(1, "APPROVED"), review(2, "CHANGES_REQUESTED")],
+                &[]
+            )
+            .review,
+            ReviewStatus::ChangesRequested
+        );
+        // Later reviews supersede earlier ones of the same reviewer.
+        assert_eq!(
+            PrState::new(
+                &pr,
+                &[review(1, "CHANGES_REQUESTED"), review(1, "APPROVED")],
+                &[]
+            )
+            .review,
+            ReviewStatus::Approved
+        );
+        assert_eq!(
+            PrState::new(
+                &pr,
+                &[review(1, "CHANGES_REQUESTED"), review(1, "DISMISSED")],
+                &[]
+            )
+            .review,
+            ReviewStatus::None
+        );
+
+        let pr = pull_request(json!([author(2)]));
+        assert_eq!(
+            PrState::new(&pr, &[review(1, "APPROVED")], &[]).review,
+            ReviewStatus::Requested
+        );
+    }
+
+    #[test_log::test]
+    fn checks_status() {
+        let pr = pull_request(json!([]));
+        let state = PrState::new(&pr, &[], &[]);
+        assert_eq!(state.checks, ChecksStatus::None);
+
+        let state = PrState::new(
+            &pr,
+            &[],
+            &[
+                check_run("build", Some("success")),
+                check_run("lint", Some("skipped")),
+            ],
+        );
+        assert_eq!(state.checks, ChecksStatus::Passed);
+
+        let state = PrState::new(
+            &pr,
+            &[],
+            &[check_run("build", Some("success")), check_run("test", None)],
+        );
+        assert_eq!(state.checks, ChecksStatus::Pending);
+
+        let state = PrState::new(
+            &pr,
+            &[],
+            &[
+                check_run("test", Some("failure")),
+                check_run("build", Some("timed_out")),
+                check_run("lint", None),
+            ],
+        );
+        assert_eq!(state.checks, ChecksStatus::Failed);
+        assert_eq!(state.failed_checks, vec!["build", "test"]);
+        assert_eq!(
+            state.summary(),
+            "- **Review:** Not requested\n- **Checks:** Failed (build, test)"
+        );
+    }
+
+    #[test_log::test]
+    fn status_precedence() {
+        let statuses = PrStatuses {
+            open: "In Progress".into(),
+            draft: "Not Started".into(),
+            review_requested: "Ready".into(),
+            approved: "Ready".into(),
+            changes_requested: "Blocked".into(),
+            checks_failed: "Not Started".into(),
+        };
+        let state = |review, checks| PrState {
+            review,
+            checks,
+            failed_checks: vec![],
+        };
+        assert_eq!(
+            state(ReviewStatus::ChangesRequested, ChecksStatus::Failed).status(&statuses),
+            "Blocked"
+        );
+        assert_eq!(
+            state(ReviewStatus::Approved, ChecksStatus::Failed).status(&statuses),
+            "Not Started"
+        );
+        assert_eq!(
+            state(ReviewStatus::Requested, ChecksStatus::Pending).status(&statuses),
+            "Ready"
+        );
+        assert_eq!(
+            state(ReviewStatus::None, ChecksStatus::Passed).status(&statuses),
+            "In Progress"
+        );
+    }
+}
@@ -0,0 +1,614 @@
+use crate::{
+    api::{
+        collab::{
+            Collab,
+            projects_state::DocBox,
+            task_changes::TaskChanges,
+            txn_origin::{Actor, YOrigin},
+        },
+        model::ProjectId,
+        yproxy::YDocProxy,
+    },
+    plugins::{
+        config::{ConfigStorage, GithubSettings, Settings},
+        github::{ISSUE_KIND, PLUGIN_KIND, app::AppGithub},
+        tasks::get_or_create_kind_parent,
+    },
+};
+use anyhow::{Context, Result, anyhow};
+use octocrab::{
+    Octocrab,
+    models::{IssueState, UserId},
+};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        broadcast::{Receiver, error::RecvError},
+        mpsc::{self, UnboundedSender},
+    },
+    time::Instant,
+};
+use tokio_util::task::AbortOnDropHandle;
+use yrs::{Origin, ReadTxn};
+
+/// How long after a project's first pending change its changes are pushed.
+/// Avoids opening an issue named after the first few keystrokes of a new task.
+const SETTLE_DELAY: Duration = Duration::from_secs(5);
+
+/// Pushes Koso changes to GitHub for projects with two way sync enabled:
+/// the status and assignee of issue tasks and new tasks under the configured parent.
+#[derive(Clone)]
+pub(super) struct Pusher {
+    collab: Collab,
+    client: AppGithub,
+    config_storage: ConfigStorage,
+    pool: &'static PgPool,
+}
+
+/// The state of an issue task to push to GitHub.
+#[derive(Debug)]
+struct IssueUpdate {
+    url: String,
+    done: bool,
+    assignee: Option<String>,
+}
+
+/// A project with changes to push.
+#[derive(Debug)]
+struct PendingProject {
+    /// When to push the changes: SETTLE_DELAY after the first of them. Later
+    /// changes don't postpone it, so a project that's always being edited is still pushed.
+    deadline: Instant,
+    /// The changed tasks, or None if changes were missed and all tasks must be pushed.
+    task_ids: Option<HashSet<String>>,
+}
+
+impl PendingProject {
+    fn new() -> PendingProject {
+        PendingProject {
+            deadline: Instant::now() + SETTLE_DELAY,
+            task_ids: Some(HashSet::new()),
+        }
+    }
+}
+
+/// A change forwarded to the pusher.
+enum Change {
+    Tasks(TaskChanges),
+    /// Changes were missed, so any project may have changed.
+    Missed,
+}
+
+/// A task to open an issue for.
+#[derive(Debug)]
+struct NewIssue {
+    task_id: String,
+    name: String,
+    desc: Option<String>,
+    assignee: Option<String>,
+}
+
+impl Pusher {
+    pub(super) fn new(
+        collab: Collab,
+        client: AppGithub,
+        config_storage: ConfigStorage,
+        pool: &'static PgPool,
+    ) -> Pusher {
+        Pusher {
+            collab,
+            client,
+            config_storage,
+            pool,
+        }
+    }
+
+    #[tracing::instrument(skip(self, changes))]
+    pub(super) async fn push(self, changes: Receiver<TaskChanges>) {
+        // Drain the changes on another task so none are missed while pushing.
+        let (change_tx, mut change_rx) = mpsc::unbounded_channel();
+        let _drain = AbortOnDropHandle::new(tokio::spawn(drain(changes, change_tx)));
+
+        let mut pending: HashMap<ProjectId, PendingProject> = HashMap::new();
+        loop {
+            let next_deadline = pending.values().map(|project| project.deadline).min();
+            tokio::select! {
+                change = change_rx.recv() => match change {
+                    Some(Change::Tasks(change)) => add_change(&mut pending, change),
+                    Some(Change::Missed) => match self.two_way_projects().await {
+                        Ok(project_ids) => {
+                            for project_id in project_ids {
+                                add_project(&mut pending, project_id);
+                            }
+                        }
+                        Err(e) => tracing::warn!("Failed to list two way sync projects: {e:?}"),
+                    },
+                    None => {
+                        for (project_id, project) in pending {
+                            self.push_pending(&project_id, project.task_ids.as_ref()).await;
+                        }
+                        return;
+                    }
+                },
+                _ = tokio::time::sleep_until(next_deadline.unwrap_or_else(Instant::now)),
+                    if next_deadline.is_some() =>
+                {
+                    let now = Instant::now();
+                    let due: Vec<ProjectId> = pending
+                        .iter()
+                        .filter(|(_, project)| project.deadline <= now)
+                        .map(|(project_id, _)| project_id.clone())
+                        .collect();
+                    for project_id in due {
+                        if let Some(project) = pending.remove(&project_id) {
+                            self.push_pending(&project_id, project.task_ids.as_ref()).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn push_pending(&self, project_id: &ProjectId, task_ids: Option<&HashSet<String>>) {
+        if let Err(e) = self.push_project(project_id, task_ids).await {
+            tracing::warn!("Failed to push changes of project {project_id}: {e:?}");
+        }
+    }
+
+    /// Pushes the changed tasks of the project or, given None, all of its tasks.
+    async fn push_project(
+        &self,
+        project_id: &ProjectId,
+        task_ids: Option<&HashSet<String>>,
+    ) -> Result<()> {
+        let Some(settings) = self.two_way_settings(project_id).await? else {
+            return Ok(());
+        };
+
+        let (updates, new_issues) = {
+            let client = self.collab.register_local_client(project_id).await?;
+            // Avoid any expensive, async work while holding the doc_box lock.
+            let doc_box = client.project.doc_box.lock().await;
+            let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+            let txn = doc.transact();
+            match task_ids {
+                Some(task_ids) => collect_changes(doc, &txn, task_ids, &settings)?,
+                None => {
+                    let task_ids = doc
+                        .tasks(&txn)?
+                        .iter()
+                        .map(|task| task.get_id(&txn))
+                        .collect::<Result<HashSet<_>>>()?;
+                    collect_changes(doc, &txn, &task_ids, &settings)?
+                }
+            }
+        };
+
+        for update in updates {
+            if let Err(e) = self.push_issue_update(&update).await {
+                tracing::warn!("Failed to push update of issue {}: {e:?}", update.url);
+            }
+        }
+        if let Some(issue_repo) = &settings.issue_repo {
+            for new_issue in new_issues {
+                if let Err(e) = self.open_issue(project_id, issue_repo, &new_issue).await {
+                    tracing::warn!("Failed to open issue for task {}: {e:?}", new_issue.task_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the projects with two way sync enabled.
+    async fn two_way_projects(&self) -> Result<HashSet<ProjectId>> {
+        Ok(self
+            .config_storage
+            .list_for_plugin(PLUGIN_KIND.id)
+            .await?
+            .into_iter()
+            .filter_map(|config| match config.settings {
+                Settings::Github(settings) if settings.two_way_sync => Some(config.project_id),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Returns the settings of the project's GitHub config with two way sync enabled, if any.
+    async fn two_way_settings(&self, project_id: &ProjectId) -> Result<Option<GithubSettings>> {
+        Ok(self
+            .config_storage
+            .list_for_project(PLUGIN_KIND.id, project_id)
+            .await?
+            .into_iter()
+            .find_map(|config| match config.settings {
+                Settings::Github(settings) if settings.two_way_sync => Some(settings),
+                _ => None,
+            }))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn push_issue_update(&self, update: &IssueUpdate) -> Result<()> {
+        let (owner, repo, number) = parse_issue_url(&update.url)?;
+        let crab = self.client.repo_github(&owner, &repo).await?.octocrab;
+        let issue = crab.issues(&owner, &repo).get(number).await?;
+
+        let open = issue.state == IssueState::Open;
+        let state = (update.done == open).then_some(if update.done {
+            IssueState::Closed
+        } else {
+            IssueState::Open
+        });
+        // Unassigning in Koso leaves GitHub's assignees, who may not be Koso users, alone.
+        let assignees = match &update.assignee {
+            Some(email) => match self.github_login(&crab, email).await? {
+                Some(login) if !issue.assignees.iter().any(|a| a.login == login) => {
+                    Some(vec![login])
+                }
+                _ => None,
+            },
+            None => None,
+        };
+        if state.is_none() && assignees.is_none() {
+            return Ok(());
+        }
+
+        tracing::debug!("Updating issue: state={state:?}, assignees={assignees:?}");
+        let handler = crab.issues(&owner, &repo);
+        let mut builder = handler.update(number);
+        if let Some(state) = state {
+            builder = builder.state(state);
+        }
+        if let Some(assignees) = &assignees {
+            builder = builder.assignees(assignees);
+        }
+        builder.send().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self, new_issue), fields(task_id=new_issue.task_id))]
+    async fn open_issue(
+        &self,
+        project_id: &ProjectId,
+        issue_repo: &str,
+        new_issue: &NewIssue,
+    ) -> Result<()> {
+        let Some((owner, repo)) = issue_repo.split_once('/') else {
+            return Err(anyhow!("Invalid issue repo: {issue_repo}"));
+        };
+        let crab = self.client.repo_github(owner, repo).await?.octocrab;
+        let assignees = match &new_issue.assignee {
+            Some(email) => self.github_login(&crab, email).await?.map(|l| vec![l]),
+            None => None,
+        };
+        let issue = crab
+            .issues(owner, repo)
+            .create(&new_issue.name)
+            .body::<String>(new_issue.desc.clone())
+            .assignees(assignees)
+            .send()
+            .await?;
+        let url: String = issue.html_url.into();
+        tracing::debug!("Opened issue {url}");
+
+        // Link the task to the issue, making it an issue task.
+        let client = self.collab.register_local_client(project_id).await?;
+        let doc_box = client.project.doc_box.lock().await;
+        let doc = &DocBox::doc_or_error(doc_box.as_ref())?.ydoc;
+        let mut txn = doc.transact_mut_with(origin(project_id)?);
+        let Ok(task) = doc.get(&txn, &new_issue.task_id) else {
+            tracing::warn!("Task deleted before issue {url} was opened");
+            return Ok(());
+        };
+        task.set_url(&mut txn, Some(&url));
+        task.set_kind(&mut txn, Some(ISSUE_KIND.id));
+        get_or_create_kind_parent(&mut txn, doc, ISSUE_KIND)?
+            .push_child(&mut txn, &new_issue.task_id)?;
+        Ok(())
+    }
+
+    /// Returns the GitHub login of the Koso user, if they connected their GitHub account.
+    async fn github_login(&self, crab: &Octocrab, email: &str) -> Result<Option<String>> {
+        let github_user_id: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT github_user_id FROM users WHERE email=$1;")
+                .bind(email)
+                .fetch_optional(self.pool)
+                .await
+                .context("Failed to query github user id by email")?;
+        let Some((Some(github_user_id),)) = github_user_id else {
+            return Ok(None);
+        };
+        let profile = crab
+            .users_by_id(UserId(github_user_id.parse()?))
+            .profile()
+            .await?;
+        Ok(Some(profile.login))
+    }
+}
+
+/// Forwards changes to the pusher, reporting missed ones, until either side closes.
+async fn drain(mut changes: Receiver<TaskChanges>, change_tx: UnboundedSender<Change>) {
+    loop {
+        let change = match changes.recv().await {
+            Ok(change) => Change::Tasks(change),
+            Err(RecvError::Lagged(n)) => {
+                tracing::warn!("Missed {n} task changes");
+                Change::Missed
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if change_tx.send(change).is_err() {
+            return;
+        }
+    }
+}
+
+/// Pending changes made by GitHub, or already pushed by another node, aren't pushed.
+fn add_change(pending: &mut HashMap<ProjectId, PendingProject>, change: TaskChanges) {
+    let Some(origin) = &change.origin else {
+        return;
+    };
+    if origin.is_remote() || matches!(origin.actor, Actor::GitHub) {
+        return;
+    }
+    let project = pending
+        .entry(change.project_id)
+        .or_insert_with(PendingProject::new);
+    if let Some(task_ids) = &mut project.task_ids {
+        task_ids.extend(change.task_ids.iter().cloned());
+    }
+}
+
+/// Marks all of the project's tasks as pending.
+fn add_project(pending: &mut HashMap<ProjectId, PendingProject>, project_id: ProjectId) {
+    pending
+        .entry(project_id)
+        .or_insert_with(PendingProject::new)
+        .task_ids = None;
+}
+
+/// Collects the issue tasks to push and the tasks to open issues for.
+fn collect_changes<T: ReadTxn>(
+    doc: &YDocProxy,
+    txn: &T,
+    task_ids: &HashSet<String>,
+    settings: &GithubSettings,
+) -> Result<(Vec<IssueUpdate>, Vec<NewIssue>)> {
+    let issue_parent_children = match &settings.issue_parent_id {
+        Some(parent_id) => match doc.get(txn, parent_id) {
+            Ok(parent) => parent.get_children(txn)?,
+            Err(_) => vec![],
+        },
+        None => vec![],
+    };
+
+    let mut updates = vec![];
+    let mut new_issues = vec![];
+    for task_id in task_ids {
+        // The task may have been deleted.
+        let Ok(task) = doc.get(txn, task_id) else {
+            continue;
+        };
+        let kind = task.get_kind(txn)?;
+        let url = task.get_url(txn)?;
+        if kind.as_deref() == Some(ISSUE_KIND.id)
+            && let Some(url) = url
+        {
+            updates.push(IssueUpdate {
+                url,
+                done: task.get_status(txn)?.is_some_and(|s| s == "Done"),
+                assignee: task.get_assignee(txn)?,
+            });
+        } else if kind.is_none()
+            && url.is_none()
+            && issue_parent_children.contains(task_id)
+            && !task.get_archived(txn)?.unwrap_or(false)
+            && task.get_children(txn)?.is_empty()
+        {
+            let name = task.get_name(txn)?;
+            if name.trim().is_empty() {
+                continue;
+            }
+            new_issues.push(NewIssue {
+                task_id: task_id.clone(),
+                name,
+                desc: task.get_desc(txn)?,
+                assignee: task.get_assignee(txn)?,
+            });
+        }
+    }
+    Ok((updates, new_issues))
+}
+
+/// Parses URLs of the form https://github.com/{owner}/{repo}/issues/{number}.
+fn parse_issue_url(url: &str) -> Result<(String, String, u64)> {
+    let parts: Vec<&str> = url
+        .strip_prefix("https://github.com/")
+        .context("Not a GitHub URL")?
+        .split('/')
+        .collect();
+    match parts.as_slice() {
+        [owner, repo, "issues", number] => Ok((
+            owner.to_string(),
+            repo.to_string(),
+            number.parse().context("Invalid issue number")?,
+        )),
+        _ => Err(anyhow!("Not an issue URL: {url}")),
+    }
+}
+
+fn origin(project_id: &ProjectId) -> Result<Origin> {
+    YOrigin {
+        who: "github_pusher".to_string(),
+        id: format!("project_{project_id}"),
+        actor: Actor::GitHub,
+    }
+    .as_origin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::model::Task;
+    use std::sync::Arc;
+
+    fn test_origin(actor: Actor) -> Option<Arc<YOrigin>> {
+        Some(Arc::new(YOrigin {
+            who: "test".to_string(),
+            id: "test".to_string(),
+            actor,
+        }))
+    }
+
+    #[test_log::test]
+    fn parse_issue_url_succeeds() {
+        assert_eq!(
+            parse_issue_url("https://github.com/kosolabs/koso/issues/612").unwrap(),
+            ("kosolabs".to_string(), "koso".to_string(), 612)
+        );
+        assert!(parse_issue_url("https://github.com/kosolabs/koso/pull/611").is_err());
+        assert!(parse_issue_url("https://example.com/kosolabs/koso/issues/612").is_err());
+    }
+
+    #[test_log::test]
+    fn add_change_skips_github_and_remote_changes() {
+        let mut pending = HashMap::new();
+        let change = |origin| TaskChanges {
+            project_id: "p1".to_string(),
+            task_ids: Arc::new(HashSet::from(["1".to_string()])),
+            origin,
+        };
+        add_change(&mut pending, change(test_origin(Actor::GitHub)));
+        add_change(
+            &mut pending,
+            change(Some(Arc::new(YOrigin::remote("node", 1)))),
+        );
+        add_change(&mut pending, change(None));
+        assert!(pending.is_empty());
+
+        add_change(&mut pending, change(test_origin(Actor::Server)));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            pending["p1"].task_ids,
+            Some(HashSet::from(["1".to_string()]))
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn add_change_keeps_first_deadline() {
+        let mut pending = HashMap::new();
+        let change = |task_id: &str| TaskChanges {
+            project_id: "p1".to_string(),
+            task_ids: Arc::new(HashSet::from([task_id.to_string()])),
+            origin: test_origin(Actor::Server),
+        };
+        add_change(&mut pending, change("1"));
+        let deadline = pending["p1"].deadline;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        add_change(&mut pending, change("2"));
+        assert_eq!(pending["p1"].deadline, deadline);
+        assert_eq!(
+            pending["p1"].task_ids,
+            Some(HashSet::from(["1".to_string(), "2".to_string()]))
+        );
+
+        // Once all tasks are pending, changes to some don't matter.
+        add_project(&mut pending, "p1".to_string());
+        add_change(&mut pending, change("3"));
+        assert_eq!(pending["p1"].task_ids, None);
+        assert_eq!(pending["p1"].deadline, deadline);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn drain_reports_missed_changes() {
+        let (changes_tx, changes) = tokio::sync::broadcast::channel(2);
+        let (change_tx, mut change_rx) = mpsc::unbounded_channel();
+        for task_id in ["1", "2", "3"] {
+            changes_tx
+                .send(TaskChanges {
+                    project_id: "p1".to_string(),
+                    task_ids: Arc::new(HashSet::from([task_id.to_string()])),
+                    origin: test_origin(Actor::Server),
+                })
+                .unwrap();
+        }
+        drop(changes_tx);
+        drain(changes, change_tx).await;
+
+        assert!(matches!(change_rx.recv().await, Some(Change::Missed)));
+        for task_id in ["2", "3"] {
+            let Some(Change::Tasks(change)) = change_rx.recv().await else {
+                panic!("Expected the changes of task {task_id}");
+            };
+            assert_eq!(*change.task_ids, HashSet::from([task_id.to_string()]));
+        }
+        assert!(change_rx.recv().await.is_none());
+    }
+
+    #[test_log::test]
+    fn collect_changes_finds_updates_and_new_issues() {
+        let doc = YDocProxy::new();
+        let mut txn = doc.transact_mut_with(origin(&"p1".to_string()).unwrap());
+        for task in [
+            Task {
+                id: "parent".into(),
+                num: "1".into(),
+                name: "Issues".into(),
+                children: vec!["new".into(), "unnamed".into(), "issue".into()],
+                ..Task::default()
+            },
+            Task {
+                id: "new".into(),
+                num: "2".into(),
+                name: "Fix the thing".into(),
+                assignee: Some("a@koso.test".into()),
+                ..Task::default()
+            },
+            Task {
+                id: "unnamed".into(),
+                num: "3".into(),
+                ..Task::default()
+            },
+            Task {
+                id: "issue".into(),
+                num: "4".into(),
+                name: "Some issue".into(),
+                kind: Some(ISSUE_KIND.id.into()),
+                url: Some("https://github.com/kosolabs/koso/issues/612".into()),
+                status: Some("Done".into()),
+                ..Task::default()
+            },
+            Task {
+                id: "elsewhere".into(),
+                num: "5".into(),
+                name: "Not under the parent".into(),
+                ..Task::default()
+            },
+        ] {
+            doc.set(&mut txn, &task);
+        }
+        let task_ids =
+            HashSet::from(["new", "unnamed", "issue", "elsewhere", "deleted"].map(String::from));
+
+        let (updates, new_issues) =
+            collect_changes(&doc, &txn, &task_ids, &GithubSettings::default()).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert!(updates[0].done);
+        assert!(new_issues.is_empty());
+
+        let settings = GithubSettings {
+            two_way_sync: true,
+            issue_parent_id: Some("parent".into()),
+            issue_repo: Some("kosolabs/koso".into()),
+            ..GithubSettings::default()
+        };
+        let (_, new_issues) = collect_changes(&doc, &txn, &task_ids, &settings).unwrap();
+        assert_eq!(new_issues.len(), 1);
+        assert_eq!(new_issues[0].task_id, "new");
+        assert_eq!(new_issues[0].name, "Fix the thing");
+        assert_eq!(new_issues[0].assignee.as_deref(), Some("a@koso.test"));
+    }
+}
@@ -8,12 +8,13 @@ use crate::{
         yproxy::{YDocProxy, YTaskProxy},
     },
     plugins::{
-        config::{Config, ConfigStorage},
+        config::{Config, ConfigStorage, GithubSettings, Settings},
         github::{
-            ExternalTask, Kind, PLUGIN_KIND, PR_KIND, add_referenced_task_links,
-            get_or_create_kind_parent, lookup_by_github_user_id, new_task, resolve_task,
-            update_task,
+            ExternalTask, PLUGIN_KIND, add_repo_parent_link,
+            app::{AppGithub, InstallationGithub, InstallationRef},
+            lookup_by_github_user_id, new_task, update_task,
         },
+        tasks::{Kind, add_referenced_task_links, get_or_create_kind_parent, resolve_task},
     },
     secrets::{Secret, read_secret},
 };
@@ -26,8 +27,15 @@ use axum::{
 };
 use axum_anyhow::{ApiResult, OptionExt, ResultExt};
 use hmac::{Hmac, Mac};
-use octocrab::models::webhook_events::{
-    WebhookEvent, WebhookEventPayload, payload::PullRequestWebhookEventAction,
+use octocrab::models::{
+    pulls::PullRequest,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload,
+        payload::{
+            CheckSuiteWebhookEventAction, IssuesWebhookEventAction,
+            PullRequestReviewWebhookEventAction, PullRequestWebhookEventAction,
+        },
+    },
 };
 use sha2::Sha256;
 use sqlx::PgPool;
@@ -58,6 +66,18 @@ struct KosoGithubEvent {
     task: ExternalTask,
 }
 
+/// The PRs or issue a webhook event is about.
+enum EventSubject {
+    Task(ExternalTask),
+    /// A PR whose review and check status is yet to be fetched.
+    PullRequest(Box<PullRequest>),
+    /// PRs of the `owner/name` repository, by number, to be fetched.
+    PullRequests {
+        repo: String,
+        numbers: Vec<u64>,
+    },
+}
+
 #[derive(Clone, Debug)]
 enum KosoGithubEventAction {
     Opened,
@@ -69,6 +89,7 @@ enum KosoGithubEventAction {
 pub(super) struct Webhook {
     collab: Collab,
     config_storage: ConfigStorage,
+    client: AppGithub,
     secret: WebhookSecret,
     pool: &'static PgPool,
 }
@@ -77,11 +98,13 @@ impl Webhook {
     pub(super) fn new(
         collab: Collab,
         config_storage: ConfigStorage,
+        client: AppGithub,
         pool: &'static PgPool,
     ) -> Result<Webhook> {
         Ok(Webhook {
             collab,
             config_storage,
+            client,
             secret: read_secret("github/webhook_secret")?,
             pool,
         })
@@ -184,25 +207,19 @@ impl Webhook {
         event: WebhookEvent,
         request_id: String,
     ) -> ApiResult<()> {
-        match event.specific {
+        let (subject, action) = match event.specific {
             WebhookEventPayload::PullRequest(pr_event) => {
-                let installation_id: u64 = match event
-                    .installation
-                    .ok_or_else(|| anyhow!("Missing installation field."))?
-                {
-                    octocrab::models::webhook_events::EventInstallation::Full(installation) => {
-                        *installation.id
-                    }
-                    octocrab::models::webhook_events::EventInstallation::Minimal(
-                        installation_id,
-                    ) => *installation_id.id,
-                };
-                let task = ExternalTask::new(pr_event.pull_request)?;
                 let action = match pr_event.action {
                     PullRequestWebhookEventAction::Opened
                     | PullRequestWebhookEventAction::Reopened => KosoGithubEventAction::Opened,
                     PullRequestWebhookEventAction::Closed => KosoGithubEventAction::Closed,
-                    PullRequestWebhookEventAction::Edited => KosoGithubEventAction::Edited,
+                    // Refresh the task, including the review and check status, from the PR.
+                    PullRequestWebhookEventAction::Edited
+                    | PullRequestWebhookEventAction::ReadyForReview
+                    | PullRequestWebhookEventAction::ConvertedToDraft
+                    | PullRequestWebhookEventAction::ReviewRequested
+                    | PullRequestWebhookEventAction::ReviewRequestRemoved
+                    | PullRequestWebhookEventAction::Synchronize => KosoGithubEventAction::Edited,
                     _ => {
                         tracing::trace!(
                             "Discarding unhandled PR action type: {:?}",
@@ -211,51 +228,215 @@ impl Webhook {
                         return Ok(());
                     }
                 };
-                tracing::Span::current().record("target", &task.url);
-
-                let event = KosoGithubEvent {
-                    request_id,
-                    installation_id,
+                (
+                    EventSubject::PullRequest(Box::new(pr_event.pull_request)),
                     action,
-                    task,
-                };
-
-                if let Err(e) = self.process_koso_event(event).await {
-                    tracing::warn!("Failed to process koso event: {e:?}")
+                )
+            }
+            WebhookEventPayload::PullRequestReview(review_event) => {
+                match review_event.action {
+                    PullRequestReviewWebhookEventAction::Submitted
+                    | PullRequestReviewWebhookEventAction::Dismissed => {}
+                    _ => {
+                        tracing::trace!(
+                            "Discarding unhandled PR review action type: {:?}",
+                            review_event.action
+                        );
+                        return Ok(());
+                    }
+                }
+                (
+                    EventSubject::PullRequest(Box::new(review_event.pull_request)),
+                    KosoGithubEventAction::Edited,
+                )
+            }
+            WebhookEventPayload::CheckSuite(check_suite_event) => {
+                if check_suite_event.action != CheckSuiteWebhookEventAction::Completed {
+                    tracing::trace!(
+                        "Discarding unhandled check suite action type: {:?}",
+                        check_suite_event.action
+                    );
+                    return Ok(());
+                }
+                let repo = event
+                    .repository
+                    .as_ref()
+                    .and_then(|repo| repo.full_name.clone())
+                    .context("Missing repository field.")?;
+                let numbers = check_suite_pull_requests(&check_suite_event.check_suite);
+                if numbers.is_empty() {
+                    tracing::trace!("Discarding check suite without PRs");
+                    return Ok(());
+                }
+                (
+                    EventSubject::PullRequests { repo, numbers },
+                    KosoGithubEventAction::Edited,
+                )
+            }
+            WebhookEventPayload::Issues(issue_event) => {
+                if issue_event.issue.pull_request.is_some() {
+                    tracing::trace!("Discarding issue event for PR");
+                    return Ok(());
                 }
+                let action = match issue_event.action {
+                    IssuesWebhookEventAction::Opened | IssuesWebhookEventAction::Reopened => {
+                        KosoGithubEventAction::Opened
+                    }
+                    IssuesWebhookEventAction::Closed => KosoGithubEventAction::Closed,
+                    // The issue in the payload reflects the change, so refresh the task from it.
+                    IssuesWebhookEventAction::Edited
+                    | IssuesWebhookEventAction::Assigned
+                    | IssuesWebhookEventAction::Unassigned
+                    | IssuesWebhookEventAction::Labeled
+                    | IssuesWebhookEventAction::Unlabeled => KosoGithubEventAction::Edited,
+                    _ => {
+                        tracing::trace!(
+                            "Discarding unhandled issue action type: {:?}",
+                            issue_event.action
+                        );
+                        return Ok(());
+                    }
+                };
+                (
+                    EventSubject::Task(ExternalTask::from_issue(issue_event.issue)?),
+                    action,
+                )
+            }
+            _ => {
+                tracing::trace!("Discarding unhandled event.");
+                return Ok(());
             }
-            _ => tracing::trace!("Discarding unhandled event."),
         };
 
-        Ok(())
-    }
-
-    async fn process_koso_event(&self, mut event: KosoGithubEvent) -> Result<()> {
-        tracing::debug!("Processing Koso event: {event:?}");
+        let installation_id: u64 = match event
+            .installation
+            .ok_or_else(|| anyhow!("Missing installation field."))?
+        {
+            octocrab::models::webhook_events::EventInstallation::Full(installation) => {
+                *installation.id
+            }
+            octocrab::models::webhook_events::EventInstallation::Minimal(installation_id) => {
+                *installation_id.id
+            }
+        };
 
-        // Populate the email of the author if we're able to.
-        if let Some(user_id) = &event.task.user_id
-            && let Some(email) = lookup_by_github_user_id(user_id, self.pool).await?
+        if let Err(e) = self
+            .process_subject(subject, action, installation_id, request_id)
+            .await
         {
-            event.task.koso_user_email = Some(email);
+            tracing::warn!("Failed to process koso event: {e:?}")
         }
 
+        Ok(())
+    }
+
+    async fn process_subject(
+        &self,
+        subject: EventSubject,
+        action: KosoGithubEventAction,
+        installation_id: u64,
+        request_id: String,
+    ) -> Result<()> {
         let configs = self
             .config_storage
-            .list_for_external_id(PLUGIN_KIND.id, &event.installation_id.to_string())
+            .list_for_external_id(PLUGIN_KIND.id, &installation_id.to_string())
             .await?;
         if configs.is_empty() {
             tracing::debug!(
-                "No config registered for installation '{}'. Discarding event.",
-                event.installation_id
+                "No config registered for installation '{installation_id}'. Discarding event."
             );
             return Ok(());
         };
 
+        // Only fetch from GitHub once we know the event is relevant.
+        let tasks = self.fetch_tasks(subject, &action, installation_id).await?;
+        tracing::Span::current().record(
+            "target",
+            tasks
+                .iter()
+                .map(|task| task.url.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+
+        for task in tasks {
+            let event = KosoGithubEvent {
+                request_id: request_id.clone(),
+                installation_id,
+                action: action.clone(),
+                task,
+            };
+            self.process_koso_event(event, &configs).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the tasks the subject refers to, fetching the review and check status of open PRs.
+    async fn fetch_tasks(
+        &self,
+        subject: EventSubject,
+        action: &KosoGithubEventAction,
+        installation_id: u64,
+    ) -> Result<Vec<ExternalTask>> {
+        match subject {
+            EventSubject::Task(task) => Ok(vec![task]),
+            // Closed PRs are resolved regardless of their reviews and checks.
+            EventSubject::PullRequest(pr) if matches!(action, KosoGithubEventAction::Closed) => {
+                Ok(vec![ExternalTask::from_pull_request(*pr)?])
+            }
+            EventSubject::PullRequest(pr) => {
+                match self.installation_github(installation_id).await {
+                    Ok(client) => Ok(vec![
+                        ExternalTask::from_pull_request_with_state(&client, *pr).await?,
+                    ]),
+                    // The PR itself is still worth syncing.
+                    Err(e) => {
+                        tracing::warn!("Failed to fetch review and check status: {e:?}");
+                        Ok(vec![ExternalTask::from_pull_request(*pr)?])
+                    }
+                }
+            }
+            EventSubject::PullRequests { repo, numbers } => {
+                let client = self.installation_github(installation_id).await?;
+                let (owner, name) = repo
+                    .split_once('/')
+                    .with_context(|| format!("Invalid repository: {repo}"))?;
+                let mut tasks = Vec::with_capacity(numbers.len());
+                for number in numbers {
+                    let pr = client.fetch_pull_request(owner, name, number).await?;
+                    tasks.push(ExternalTask::from_pull_request_with_state(&client, pr).await?);
+                }
+                Ok(tasks)
+            }
+        }
+    }
+
+    async fn installation_github(&self, installation_id: u64) -> Result<InstallationGithub> {
+        self.client
+            .installation_github(InstallationRef::InstallationId {
+                id: installation_id,
+            })
+            .await
+    }
+
+    async fn process_koso_event(
+        &self,
+        mut event: KosoGithubEvent,
+        configs: &[Config],
+    ) -> Result<()> {
+        tracing::debug!("Processing Koso event: {event:?}");
+
+        // Populate the email of the author if we're able to.
+        if let Some(user_id) = &event.task.user_id
+            && let Some(email) = lookup_by_github_user_id(user_id, self.pool).await?
+        {
+            event.task.koso_user_email = Some(email);
+        }
+
         futures::future::join_all(
             configs
-                .into_iter()
-                .map(|config| self.merge_task(event.clone(), config)),
+                .iter()
+                .map(|config| self.merge_task(event.clone(), config.clone())),
         )
         .await;
 
@@ -273,6 +454,15 @@ impl Webhook {
     }
 
     async fn merge_task_internal(&self, event: KosoGithubEvent, config: Config) -> Result<()> {
+        let Settings::Github(settings) = &config.settings else {
+            return Err(anyhow!("Config has no GitHub settings"));
+        };
+        let Some(task) = event.task.with_settings(settings) else {
+            tracing::debug!("Discarding event excluded by the project's settings");
+            return Ok(());
+        };
+        let event = KosoGithubEvent { task, ..event };
+
         let client = self
             .collab
             .register_local_client(&config.project_id)
@@ -281,29 +471,50 @@ impl Webhook {
         // Avoid any expensive, async work while holding the doc_box lock.
         {
             let doc_box = client.project.doc_box.lock().await;
-            self.apply_task_changes(&event, &DocBox::doc_or_error(doc_box.as_ref())?.ydoc)
+            self.apply_task_changes(
+                &event,
+                settings,
+                &DocBox::doc_or_error(doc_box.as_ref())?.ydoc,
+            )
         }
     }
 
     // Note: This function should remain synchronous to avoid blocking the doc_box lock.
-    fn apply_task_changes(&self, event: &KosoGithubEvent, doc: &YDocProxy) -> Result<()> {
+    fn apply_task_changes(
+        &self,
+        event: &KosoGithubEvent,
+        settings: &GithubSettings,
+        doc: &YDocProxy,
+    ) -> Result<()> {
         let mut txn = doc.transact_mut_with(origin(event)?);
         match (
-            get_doc_task(&txn, doc, &event.task.url, PR_KIND)?,
+            get_doc_task(&txn, doc, &event.task.url, event.task.kind)?,
             &event.action,
         ) {
             (Some(task), KosoGithubEventAction::Opened | KosoGithubEventAction::Edited) => {
                 update_task(&mut txn, &task, &event.task)?;
 
                 let task_id = task.get_id(&txn)?;
-                add_referenced_task_links(&mut txn, doc, &task_id, &event.task)?;
+                add_referenced_task_links(
+                    &mut txn,
+                    doc,
+                    &task_id,
+                    &event.task.name,
+                    &event.task.description,
+                )?;
             }
             (None, KosoGithubEventAction::Opened | KosoGithubEventAction::Edited) => {
-                create_task(&mut txn, doc, &event.task)?;
+                create_task(&mut txn, doc, &event.task, settings)?;
             }
             (Some(task), KosoGithubEventAction::Closed) => {
                 let task_id = task.get_id(&txn)?;
-                add_referenced_task_links(&mut txn, doc, &task_id, &event.task)?;
+                add_referenced_task_links(
+                    &mut txn,
+                    doc,
+                    &task_id,
+                    &event.task.name,
+                    &event.task.description,
+                )?;
 
                 resolve_task(&mut txn, &task)?;
             }
@@ -315,6 +526,17 @@ impl Webhook {
     }
 }
 
+/// Returns the numbers of the PRs in a check suite.
+fn check_suite_pull_requests(check_suite: &serde_json::Value) -> Vec<u64> {
+    check_suite
+        .get("pull_requests")
+        .and_then(|prs| prs.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|pr| pr.get("number").and_then(|number| number.as_u64()))
+        .collect()
+}
+
 fn get_doc_task<T: ReadTxn>(
     txn: &T,
     doc: &YDocProxy,
@@ -340,18 +562,26 @@ fn create_task(
     txn: &mut TransactionMut,
     doc: &YDocProxy,
     external_task: &ExternalTask,
+    settings: &GithubSettings,
 ) -> Result<()> {
-    let parent = get_or_create_kind_parent(txn, doc, PR_KIND)?;
+    let parent = get_or_create_kind_parent(txn, doc, external_task.kind)?;
     let mut children: Vec<String> = parent.get_children(txn)?;
 
-    let task = new_task(external_task, doc.next_num(txn)?, PR_KIND)?;
+    let task = new_task(external_task, doc.next_num(txn)?)?;
     doc.set(txn, &task);
 
     // Add the new task as a child of the plugin parent.
     children.push(task.id.clone());
     parent.set_children(txn, &children);
 
-    add_referenced_task_links(txn, doc, &task.id, external_task)?;
+    add_referenced_task_links(
+        txn,
+        doc,
+        &task.id,
+        &external_task.name,
+        &external_task.description,
+    )?;
+    add_repo_parent_link(txn, doc, &task.id, external_task, settings)?;
 
     Ok(())
 }
@@ -0,0 +1,235 @@
+use crate::{
+    api::{collab::Collab, google, model::Task, yproxy::YTaskProxy},
+    plugins::{
+        PluginSettings,
+        config::ConfigStorage,
+        gitlab::client::MergeRequest,
+        tasks::{Kind, now},
+    },
+};
+use anyhow::{Result, anyhow};
+use axum::{Router, middleware};
+use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
+use connect::ConnectHandler;
+use poller::Poller;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use webhook::Webhook;
+use yrs::TransactionMut;
+
+mod client;
+mod connect;
+mod poller;
+mod webhook;
+
+const PLUGIN_KIND: &Kind = &Kind::new("gitlab", "GitLab");
+const MR_KIND: &Kind = &Kind::new_nested(PLUGIN_KIND, "gitlab_mr", "GitLab MR");
+
+#[derive(Clone)]
+pub(crate) struct Plugin {
+    collab: Collab,
+    config_storage: ConfigStorage,
+    client: reqwest::Client,
+    pool: &'static PgPool,
+    settings: PluginSettings,
+}
+
+impl Plugin {
+    pub(crate) fn new(
+        settings: PluginSettings,
+        collab: Collab,
+        pool: &'static PgPool,
+    ) -> Result<Plugin> {
+        MR_KIND.validate()?;
+        Ok(Plugin {
+            collab,
+            config_storage: ConfigStorage::new(pool)?,
+            client: client::http_client(crate::settings::settings().is_dev())?,
+            pool,
+            settings,
+        })
+    }
+
+    /// Start a background task that polls GitLab periodically.
+    /// Return a handle to the task, useful for aborting the task on shutdown.
+    pub(crate) fn start_polling(&self) -> JoinHandle<()> {
+        if !self.settings.disable_polling {
+            tokio::spawn(self.poller().poll())
+        } else {
+            tokio::spawn(async { tracing::debug!("Plugin polling disabled") })
+        }
+    }
+
+    /// Returns a router that binds webhook (push) and poll endpoints.
+    pub(crate) fn router(&self) -> Router {
+        Router::new()
+            .merge(
+                ConnectHandler::new(
+                    self.pool,
+                    self.config_storage.clone(),
+                    self.client.clone(),
+                    self.poller(),
+                )
+                .router(),
+            )
+            .layer(middleware::from_fn(google::authenticate))
+            // Webhook and poller are unauthenticated, so add it AFTER adding the authentication layers.
+            .merge(Webhook::new(self.collab.clone(), self.config_storage.clone()).router())
+            .merge(self.poller().router())
+    }
+
+    fn poller(&self) -> Poller {
+        Poller::new(
+            self.collab.clone(),
+            self.config_storage.clone(),
+            self.client.clone(),
+        )
+    }
+}
+
+/// A merge request, as synced to a task.
+#[derive(Clone, Debug)]
+struct ExternalTask {
+    url: String,
+    /// The GitLab project, as `namespace/name`.
+    project: String,
+    name: String,
+    description: String,
+    status: String,
+}
+
+impl ExternalTask {
+    fn from_merge_request(project: &str, mr: MergeRequest) -> Result<ExternalTask> {
+        if mr.web_url.is_empty() {
+            return Err(anyhow!("Found merge request with empty URL in {project}"));
+        }
+        let status = match mr.state.as_str() {
+            // Locked merge requests are being merged.
+            "opened" | "locked" => "In Progress",
+            "closed" | "merged" => "Done",
+            state => {
+                return Err(anyhow!(
+                    "Invalid state {state} for merge request {}",
+                    mr.web_url
+                ));
+            }
+        };
+        Ok(ExternalTask {
+            url: mr.web_url,
+            project: project.to_string(),
+            name: mr.title,
+            description: mr.description.unwrap_or_default(),
+            status: status.to_string(),
+        })
+    }
+}
+
+/// Returns the `namespace/name` of the project of a merge request URL
+/// on the GitLab instance at `base_url`.
+fn project_from_url<'a>(base_url: &str, url: &'a str) -> Option<&'a str> {
+    url.strip_prefix(base_url.trim_end_matches('/'))?
+        .strip_prefix('/')?
+        .split_once("/-/merge_requests/")
+        .map(|(project, _)| project)
+        .filter(|project| !project.is_empty())
+}
+
+fn new_task(external_task: &ExternalTask, num: u64) -> Result<Task> {
+    let id = BASE64_URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4());
+    tracing::trace!("Creating new task {} ({num}): {}", id, external_task.url);
+    Ok(Task {
+        id,
+        num: num.to_string(),
+        name: external_task.name.clone(),
+        status: Some(external_task.status.clone()),
+        status_time: Some(now()?),
+        url: Some(external_task.url.clone()),
+        kind: Some(MR_KIND.id.to_string()),
+        ..Task::default()
+    })
+}
+
+fn update_task(
+    txn: &mut TransactionMut,
+    task: &YTaskProxy,
+    external_task: &ExternalTask,
+) -> Result<()> {
+    task.set_name(txn, &external_task.name);
+    if task
+        .get_status(txn)?
+        .is_none_or(|s| s != external_task.status)
+    {
+        tracing::trace!(
+            "Updating task status {}: {}",
+            task.get_id(txn)?,
+            external_task.url
+        );
+        task.set_status(txn, Some(&external_task.status));
+        task.set_status_time(txn, Some(now()?));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge_request(state: &str) -> MergeRequest {
+        MergeRequest {
+            title: "Add GitLab plugin".to_string(),
+            description: Some("Implements koso#12".to_string()),
+            state: state.to_string(),
+            web_url: "https://gitlab.example.com/kosolabs/koso/-/merge_requests/7".to_string(),
+        }
+    }
+
+    #[test_log::test]
+    fn validate_mr_kind() {
+        let res = MR_KIND.validate();
+        assert!(res.is_ok(), "MR_KIND is invalid {res:?}");
+    }
+
+    #[test_log::test]
+    fn external_task_from_merge_request() {
+        let task =
+            ExternalTask::from_merge_request("kosolabs/koso", merge_request("opened")).unwrap();
+        assert_eq!(
+            task.url,
+            "https://gitlab.example.com/kosolabs/koso/-/merge_requests/7"
+        );
+        assert_eq!(task.project, "kosolabs/koso");
+        assert_eq!(task.name, "Add GitLab plugin");
+        assert_eq!(task.description, "Implements koso#12");
+        assert_eq!(task.status, "In Progress");
+
+        for state in ["merged", "closed"] {
+            let task =
+                ExternalTask::from_merge_request("kosolabs/koso", merge_request(state)).unwrap();
+            assert_eq!(task.status, "Done");
+        }
+        assert!(
+            ExternalTask::from_merge_request("kosolabs/koso", merge_request("unknown")).is_err()
+        );
+    }
+
+    #[test_log::test]
+    fn project_from_url_succeeds() {
+        let url = "https://gitlab.example.com/kosolabs/tools/koso/-/merge_requests/7";
+        assert_eq!(
+            project_from_url("https://gitlab.example.com", url),
+            Some("kosolabs/tools/koso")
+        );
+        assert_eq!(
+            project_from_url("https://gitlab.example.com/", url),
+            Some("kosolabs/tools/koso")
+        );
+        assert_eq!(project_from_url("https://gitlab.other.com", url), None);
+        assert_eq!(
+            project_from_url(
+                "https://gitlab.example.com",
+                "https://gitlab.example.com/kosolabs/koso/-/issues/7"
+            ),
+            None
+        );
+    }
+}
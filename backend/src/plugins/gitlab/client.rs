@@ -0,0 +1,130 @@
+use crate::{net::PublicResolver, plugins::config::GitlabSettings};
+use anyhow::{Context, Result};
+use reqwest::{Client, RequestBuilder, redirect};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::sync::Arc;
+
+/// The number of items to request per page. GitLab's maximum.
+const PER_PAGE: &str = "100";
+
+/// Calls the REST API of a GitLab instance.
+/// See https://docs.gitlab.com/api/rest/
+#[derive(Clone)]
+pub(super) struct GitlabClient {
+    client: Client,
+    base_url: String,
+    access_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct GitlabUser {
+    pub(super) username: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct GitlabProject {
+    pub(super) path_with_namespace: String,
+}
+
+/// See https://docs.gitlab.com/api/merge_requests/#list-project-merge-requests
+#[derive(Deserialize, Debug, Clone)]
+pub(super) struct MergeRequest {
+    pub(super) title: String,
+    pub(super) description: Option<String>,
+    /// One of opened, closed, locked or merged.
+    pub(super) state: String,
+    pub(super) web_url: String,
+}
+
+/// Builds the HTTP client used to call GitLab instances. Instances are user
+/// supplied, so unless allowed, like in dev, hosts resolving to non-public
+/// addresses are refused. Redirects aren't followed since they'd carry the
+/// access token along to wherever they lead.
+pub(super) fn http_client(allow_private_addresses: bool) -> Result<Client> {
+    let mut builder = Client::builder().redirect(redirect::Policy::none());
+    if !allow_private_addresses {
+        builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+    Ok(builder.build()?)
+}
+
+impl GitlabClient {
+    pub(super) fn new(client: Client, base_url: &str, access_token: &str) -> GitlabClient {
+        GitlabClient {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            access_token: access_token.to_string(),
+        }
+    }
+
+    pub(super) fn for_settings(client: Client, settings: &GitlabSettings) -> GitlabClient {
+        GitlabClient::new(client, &settings.base_url, &settings.access_token)
+    }
+
+    /// Returns the user the access token belongs to.
+    pub(super) async fn current_user(&self) -> Result<GitlabUser> {
+        self.send(self.get("/user")).await
+    }
+
+    pub(super) async fn project(&self, path: &str) -> Result<GitlabProject> {
+        self.send(self.get(&format!("/projects/{}", encode_path(path))))
+            .await
+            .with_context(|| format!("Failed to fetch project {path}"))
+    }
+
+    /// Returns the open merge requests of the project.
+    pub(super) async fn fetch_merge_requests(&self, path: &str) -> Result<Vec<MergeRequest>> {
+        let mut merge_requests = Vec::new();
+        let mut page = "1".to_string();
+        loop {
+            let res = self
+                .get(&format!("/projects/{}/merge_requests", encode_path(path)))
+                .query(&[
+                    ("state", "opened"),
+                    ("per_page", PER_PAGE),
+                    ("page", page.as_str()),
+                ])
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch merge requests of {path}"))?
+                .error_for_status()
+                .with_context(|| format!("Failed to fetch merge requests of {path}"))?;
+            // GitLab sets an empty next page header on the last page.
+            let next_page = res
+                .headers()
+                .get("x-next-page")
+                .and_then(|next_page| next_page.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            merge_requests.extend(
+                res.json::<Vec<MergeRequest>>()
+                    .await
+                    .context("Failed to parse merge requests")?,
+            );
+            if next_page.is_empty() {
+                return Ok(merge_requests);
+            }
+            page = next_page;
+        }
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.client
+            .get(format!("{}/api/v4{path}", self.base_url))
+            .header("PRIVATE-TOKEN", &self.access_token)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        Ok(request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<T>()
+            .await?)
+    }
+}
+
+/// Projects are addressed by their URL-encoded path.
+fn encode_path(path: &str) -> String {
+    url::form_urlencoded::byte_serialize(path.as_bytes()).collect()
+}
@@ -0,0 +1,301 @@
+use crate::{
+    api::{self, google::User, model::ProjectRole},
+    net,
+    plugins::{
+        config::{Config, ConfigStorage, GitlabSettings, Settings},
+        gitlab::{PLUGIN_KIND, Poller, client::GitlabClient, webhook::hash_token},
+    },
+    settings::settings,
+};
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query},
+    routing::{delete, get, post},
+};
+use axum_anyhow::{ApiResult, OptionExt, bad_request};
+use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::Instrument;
+use url::Url;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectRequest {
+    project_id: String,
+    base_url: String,
+    access_token: String,
+    projects: Vec<String>,
+}
+
+/// The webhook token is only returned when the connection is created.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ConnectResponse {
+    connection: Connection,
+    webhook_url: String,
+    webhook_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionsQuery {
+    project_id: String,
+}
+
+/// A GitLab instance connected to the project, without its tokens.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Connection {
+    connection_id: String,
+    base_url: String,
+    projects: Vec<String>,
+}
+
+impl Connection {
+    fn from_config(config: Config) -> Option<Connection> {
+        match config.settings {
+            Settings::Gitlab(settings) => Some(Connection {
+                connection_id: config.external_id,
+                base_url: settings.base_url,
+                projects: settings.projects,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct ConnectHandler {
+    pool: &'static PgPool,
+    storage: ConfigStorage,
+    client: reqwest::Client,
+    poller: Poller,
+}
+
+impl ConnectHandler {
+    pub(super) fn new(
+        pool: &'static PgPool,
+        storage: ConfigStorage,
+        client: reqwest::Client,
+        poller: Poller,
+    ) -> ConnectHandler {
+        ConnectHandler {
+            pool,
+            storage,
+            client,
+            poller,
+        }
+    }
+
+    pub(super) fn router(self) -> Router {
+        Router::new()
+            .route("/connect", post(Self::connect_project_handler))
+            .route("/connections", get(Self::list_connections_handler))
+            .route(
+                "/connections/{connection_id}",
+                delete(Self::delete_connection_handler),
+            )
+            .layer(Extension(self))
+    }
+
+    #[tracing::instrument(
+        skip(user, handler, request),
+        fields(project_id=request.project_id, base_url=request.base_url)
+    )]
+    async fn connect_project_handler(
+        Extension(user): Extension<User>,
+        Extension(handler): Extension<ConnectHandler>,
+        Json(request): Json<ConnectRequest>,
+    ) -> ApiResult<Json<ConnectResponse>> {
+        api::verify_project_access(handler.pool, &user, &request.project_id, ProjectRole::Owner)
+            .await?;
+        let base_url = validate_base_url(&request.base_url, settings().is_dev()).await?;
+        if request.projects.is_empty() || !request.projects.iter().all(|p| is_project_path(p)) {
+            return Err(bad_request(
+                "INVALID_PROJECTS",
+                "Projects must be one or more paths like namespace/name",
+            ));
+        }
+
+        // Verify the token and projects, recording the projects' canonical paths.
+        let client = GitlabClient::new(handler.client.clone(), &base_url, &request.access_token);
+        let gitlab_user = client.current_user().await.map_err(|e| {
+            tracing::debug!("Failed to authenticate with GitLab: {e:?}");
+            bad_request("INVALID_TOKEN", "Failed to authenticate with GitLab")
+        })?;
+        let mut projects = Vec::with_capacity(request.projects.len());
+        for path in &request.projects {
+            let project = client.project(path).await.map_err(|e| {
+                tracing::debug!("Failed to fetch GitLab project: {e:?}");
+                bad_request("INVALID_PROJECT", &format!("Project {path} not found"))
+            })?;
+            projects.push(project.path_with_namespace);
+        }
+
+        let connection_id = BASE64_URL_SAFE_NO_PAD.encode(uuid::Uuid::new_v4());
+        let webhook_token = BASE64_URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+        tracing::debug!(
+            "Connecting project {} to {base_url} as {} with connection {connection_id}",
+            request.project_id,
+            gitlab_user.username,
+        );
+        let config = Config {
+            project_id: request.project_id,
+            plugin_id: PLUGIN_KIND.id.to_string(),
+            external_id: connection_id.clone(),
+            settings: Settings::Gitlab(GitlabSettings {
+                base_url: base_url.clone(),
+                access_token: request.access_token,
+                webhook_token_hash: hash_token(&webhook_token),
+                projects: projects.clone(),
+            }),
+        };
+        handler.storage.insert_or_update(&config).await?;
+
+        // Trigger an initial poll in the background.
+        let poller = handler.poller.clone();
+        tokio::spawn(async move { poller.poll_connection(config).await }.in_current_span());
+
+        Ok(Json(ConnectResponse {
+            webhook_url: format!("{}/plugins/gitlab/webhook/{connection_id}", settings().host),
+            webhook_token,
+            connection: Connection {
+                connection_id,
+                base_url,
+                projects,
+            },
+        }))
+    }
+
+    #[tracing::instrument(skip(user, handler))]
+    async fn list_connections_handler(
+        Extension(user): Extension<User>,
+        Extension(handler): Extension<ConnectHandler>,
+        Query(query): Query<ConnectionsQuery>,
+    ) -> ApiResult<Json<Vec<Connection>>> {
+        api::verify_project_access(handler.pool, &user, &query.project_id, ProjectRole::Viewer)
+            .await?;
+
+        let connections = handler
+            .storage
+            .list_for_project(PLUGIN_KIND.id, &query.project_id)
+            .await?
+            .into_iter()
+            .filter_map(Connection::from_config)
+            .collect();
+        Ok(Json(connections))
+    }
+
+    #[tracing::instrument(skip(user, handler))]
+    async fn delete_connection_handler(
+        Extension(user): Extension<User>,
+        Extension(handler): Extension<ConnectHandler>,
+        Path(connection_id): Path<String>,
+        Query(query): Query<ConnectionsQuery>,
+    ) -> ApiResult<Json<()>> {
+        api::verify_project_access(handler.pool, &user, &query.project_id, ProjectRole::Owner)
+            .await?;
+
+        // Tasks already synced are kept, and no longer updated.
+        handler
+            .storage
+            .delete(&query.project_id, PLUGIN_KIND.id, &connection_id)
+            .await?
+            .then_some(())
+            .context_not_found(
+                "NOT_FOUND",
+                &format!("Connection {connection_id} not found"),
+            )?;
+        Ok(Json(()))
+    }
+}
+
+/// Returns the URL of the GitLab instance, without a trailing slash.
+/// Unless allowed, like in dev, the URL must be https and resolve to a public address,
+/// since the access token is sent along with every request.
+async fn validate_base_url(base_url: &str, allow_private_addresses: bool) -> ApiResult<String> {
+    let url = match Url::parse(base_url) {
+        Ok(url)
+            if (url.scheme() == "https" || (allow_private_addresses && url.scheme() == "http"))
+                && url.host().is_some()
+                && url.query().is_none()
+                && url.fragment().is_none() =>
+        {
+            url
+        }
+        _ => {
+            return Err(bad_request(
+                "INVALID_URL",
+                "Base URL must be an https URL like https://gitlab.example.com",
+            ));
+        }
+    };
+    if !allow_private_addresses && let Err(e) = net::check_public_url(&url).await {
+        tracing::debug!("Blocked GitLab base URL {base_url}: {e:?}");
+        return Err(bad_request(
+            "INVALID_URL",
+            "Base URL must resolve to a public address",
+        ));
+    }
+    Ok(base_url.trim_end_matches('/').to_string())
+}
+
+/// GitLab projects may be nested in subgroups: `group/subgroup/name`.
+fn is_project_path(path: &str) -> bool {
+    let segments: Vec<&str> = path.split('/').collect();
+    segments.len() >= 2 && segments.iter().all(|segment| !segment.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn validate_base_url_trims_trailing_slash() {
+        assert_eq!(
+            validate_base_url("https://1.1.1.1/", false).await.unwrap(),
+            "https://1.1.1.1"
+        );
+        assert_eq!(
+            validate_base_url("http://localhost:8080/gitlab", true)
+                .await
+                .unwrap(),
+            "http://localhost:8080/gitlab"
+        );
+        assert!(validate_base_url("gitlab.example.com", true).await.is_err());
+        assert!(
+            validate_base_url("ftp://gitlab.example.com", true)
+                .await
+                .is_err()
+        );
+        assert!(
+            validate_base_url("https://gitlab.example.com?x=1", true)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn validate_base_url_requires_https_and_public_address() {
+        for url in [
+            "http://1.1.1.1",
+            "https://localhost:8080/gitlab",
+            "https://127.0.0.1",
+            "https://169.254.169.254",
+            "https://[::1]",
+            "https://10.0.0.1/gitlab",
+        ] {
+            assert!(validate_base_url(url, false).await.is_err(), "{url}");
+        }
+    }
+
+    #[test_log::test]
+    fn is_project_path_requires_namespace() {
+        assert!(is_project_path("kosolabs/koso"));
+        assert!(is_project_path("kosolabs/tools/koso"));
+        assert!(!is_project_path("koso"));
+        assert!(!is_project_path("kosolabs/"));
+        assert!(!is_project_path("/koso"));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    api::{
+        collab::{
+            Collab,
+            projects_state::DocBox,
+            txn_origin::{Actor, YOrigin},
+        },
+        yproxy::{YDocProxy, YTaskProxy},
+    },
+    plugins::{
+        config::{Config, ConfigStorage, GitlabSettings, Settings},
+        gitlab::{
+            ExternalTask, MR_KIND, PLUGIN_KIND, client::GitlabClient, new_task, project_from_url,
+            update_task,
+        },
+        tasks::{add_referenced_task_links, get_or_create_kind_parent, resolve_task},
+    },
+    settings::settings,
+};
+use anyhow::{Result, anyhow};
+use axum::{Extension, Router, routing::post};
+use axum_anyhow::ApiResult;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use yrs::{Origin, ReadTxn};
+
+const INIT_POLL_DELAY: Duration = Duration::from_secs(2 * 60);
+const POLL_DELAY: Duration = Duration::from_secs(16 * 60);
+
+#[derive(Clone)]
+pub(super) struct Poller {
+    collab: Collab,
+    config_storage: ConfigStorage,
+    client: reqwest::Client,
+}
+
+impl Poller {
+    pub(super) fn new(
+        collab: Collab,
+        config_storage: ConfigStorage,
+        client: reqwest::Client,
+    ) -> Poller {
+        Poller {
+            collab,
+            config_storage,
+            client,
+        }
+    }
+
+    pub(super) fn router(self) -> Router {
+        if settings().is_dev() {
+            return Router::new()
+                .route("/poll", post(Poller::poll_handler))
+                .layer(Extension(self));
+        }
+        Router::new()
+    }
+
+    #[tracing::instrument(skip(poller))]
+    async fn poll_handler(Extension(poller): Extension<Poller>) -> ApiResult<String> {
+        poller.poll_all_connections().await?;
+        Ok("OK".to_string())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub(super) async fn poll(self) {
+        // Wait awhile before starting polling to avoid
+        // competing with client reconnections after a server restart.
+        tokio::time::sleep(INIT_POLL_DELAY).await;
+        loop {
+            if let Err(e) = self.poll_all_connections().await {
+                tracing::warn!("Failed poll: {e:?}");
+            }
+            tokio::time::sleep(POLL_DELAY).await;
+        }
+    }
+
+    async fn poll_all_connections(&self) -> Result<()> {
+        let configs: Vec<Config> = self.config_storage.list_for_plugin(PLUGIN_KIND.id).await?;
+
+        let now = Instant::now();
+        let results = futures::future::join_all(
+            configs
+                .into_iter()
+                .map(|config| self.poll_connection(config)),
+        )
+        .await;
+        let failed = results.iter().filter(|res| res.is_err()).count();
+        tracing::info!(
+            "Finished polling in {} ms. Successful: {}, Failed: {}",
+            now.elapsed().as_millis(),
+            results.len() - failed,
+            failed
+        );
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        skip(self, config),
+        fields(gl_connection_id=config.external_id, project_id=config.project_id)
+    )]
+    pub(super) async fn poll_connection(&self, config: Config) -> Result<()> {
+        if let Err(e) = self.poll_connection_internal(config).await {
+            tracing::warn!("Failed connection poll: {e:?}");
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn poll_connection_internal(&self, config: Config) -> Result<()> {
+        tracing::debug!("Polling connection");
+        let Settings::Gitlab(settings) = &config.settings else {
+            return Err(anyhow!("Config has no GitLab settings"));
+        };
+
+        let gitlab_tasks = self.fetch_tasks_from_gitlab(settings).await?;
+        tracing::trace!("Fetched GitLab tasks: {gitlab_tasks:?}");
+
+        let client = self
+            .collab
+            .register_local_client(&config.project_id)
+            .await?;
+        let task_count = {
+            // Avoid any expensive, async work while holding the doc_box lock.
+            let doc_box = client.project.doc_box.lock().await;
+            self.merge_tasks(
+                &gitlab_tasks,
+                &config,
+                settings,
+                &DocBox::doc_or_error(doc_box.as_ref())?.ydoc,
+            )?
+        };
+
+        tracing::debug!(
+            "Finished polling connection with {} active and {} total tasks",
+            gitlab_tasks.len(),
+            task_count
+        );
+        Ok(())
+    }
+
+    /// Returns the open merge requests of the configured projects, keyed by URL.
+    async fn fetch_tasks_from_gitlab(
+        &self,
+        settings: &GitlabSettings,
+    ) -> Result<HashMap<String, ExternalTask>> {
+        let client = GitlabClient::for_settings(self.client.clone(), settings);
+        let mut tasks_by_url = HashMap::new();
+        for project in &settings.projects {
+            for mr in client.fetch_merge_requests(project).await? {
+                match ExternalTask::from_merge_request(project, mr) {
+                    Ok(task) => {
+                        tasks_by_url.insert(task.url.clone(), task);
+                    }
+                    Err(e) => tracing::warn!("Skipping malformed merge request: {e:?}"),
+                }
+            }
+        }
+        Ok(tasks_by_url)
+    }
+
+    fn list_doc_tasks<T: ReadTxn>(
+        &self,
+        txn: &T,
+        doc: &YDocProxy,
+        parent: &YTaskProxy,
+    ) -> Result<HashMap<String, YTaskProxy>> {
+        let mut results = HashMap::new();
+        for child_id in parent.get_children(txn)? {
+            let child = doc.get(txn, &child_id)?;
+            if child.get_kind(txn)?.is_some_and(|k| k == MR_KIND.id) {
+                let url = child.get_url(txn)?.unwrap_or_default();
+                if url.is_empty() {
+                    tracing::warn!("Omitting doc task with empty URL: {child_id}");
+                    continue;
+                }
+                if results.insert(url, child).is_some() {
+                    tracing::warn!("Found multiple tasks with same url: {child_id}");
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    // Note: This function should remain synchronous to avoid blocking the doc_box lock.
+    fn merge_tasks(
+        &self,
+        gitlab_tasks_by_url: &HashMap<String, ExternalTask>,
+        config: &Config,
+        settings: &GitlabSettings,
+        doc: &YDocProxy,
+    ) -> Result<usize> {
+        let mut txn = doc.transact_mut_with(origin(config)?);
+        let parent = get_or_create_kind_parent(&mut txn, doc, MR_KIND)?;
+        let doc_tasks_by_url = self.list_doc_tasks(&txn, doc, &parent)?;
+
+        // Resolve or update tasks that already exist in the doc.
+        for (url, task) in doc_tasks_by_url.iter() {
+            match gitlab_tasks_by_url.get(url) {
+                Some(gitlab_task) => {
+                    update_task(&mut txn, task, gitlab_task)?;
+
+                    let task_id = task.get_id(&txn)?;
+                    add_referenced_task_links(
+                        &mut txn,
+                        doc,
+                        &task_id,
+                        &gitlab_task.name,
+                        &gitlab_task.description,
+                    )?;
+                }
+                // Merge requests that are no longer open in a polled project were merged or closed.
+                // Leave those of other connections or removed projects as they are.
+                None if project_from_url(&settings.base_url, url)
+                    .is_some_and(|project| settings.includes_project(project)) =>
+                {
+                    resolve_task(&mut txn, task)?
+                }
+                None => {}
+            }
+        }
+
+        // Create any new tasks that don't already exist.
+        let mut next_num: u64 = doc.next_num(&txn)?;
+        let mut children = parent.get_children(&txn)?;
+        for gitlab_task in gitlab_tasks_by_url.values() {
+            if doc_tasks_by_url.contains_key(&gitlab_task.url) {
+                continue;
+            }
+            let task = new_task(gitlab_task, next_num)?;
+            next_num += 1;
+            doc.set(&mut txn, &task);
+            children.push(task.id.clone());
+
+            add_referenced_task_links(
+                &mut txn,
+                doc,
+                &task.id,
+                &gitlab_task.name,
+                &gitlab_task.description,
+            )?;
+        }
+        parent.set_children(&mut txn, &children);
+
+        Ok(children.len())
+    }
+}
+
+fn origin(config: &Config) -> Result<Origin> {
+    YOrigin {
+        who: "gitlab_poller".to_string(),
+        id: format!("connection_{}", config.external_id),
+        actor: Actor::GitLab,
+    }
+    .as_origin()
+}
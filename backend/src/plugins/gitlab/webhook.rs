@@ -0,0 +1,227 @@
+use crate::{
+    api::{
+        collab::{
+            Collab,
+            projects_state::DocBox,
+            txn_origin::{Actor, YOrigin},
+        },
+        yproxy::{YDocProxy, YTaskProxy},
+    },
+    plugins::{
+        config::{Config, ConfigStorage, Settings},
+        gitlab::{ExternalTask, MR_KIND, PLUGIN_KIND, client::MergeRequest, new_task, update_task},
+        tasks::{add_referenced_task_links, get_or_create_kind_parent},
+    },
+};
+use anyhow::{Result, anyhow};
+use axum::{Extension, Router, body::Bytes, extract::Path, http::HeaderMap, routing::post};
+use axum_anyhow::{ApiResult, OptionExt, ResultExt, forbidden};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tower_http::request_id::RequestId;
+use yrs::{Origin, ReadTxn};
+
+const MERGE_REQUEST_EVENT: &str = "Merge Request Hook";
+
+/// The parts of a merge request event Koso uses.
+/// See https://docs.gitlab.com/user/project/integrations/webhook_events/#merge-request-events
+#[derive(Deserialize, Debug)]
+struct MergeRequestEvent {
+    project: EventProject,
+    object_attributes: MergeRequestAttributes,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventProject {
+    path_with_namespace: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MergeRequestAttributes {
+    title: String,
+    description: Option<String>,
+    state: String,
+    url: String,
+}
+
+#[derive(Clone)]
+pub(super) struct Webhook {
+    collab: Collab,
+    config_storage: ConfigStorage,
+}
+
+impl Webhook {
+    pub(super) fn new(collab: Collab, config_storage: ConfigStorage) -> Webhook {
+        Webhook {
+            collab,
+            config_storage,
+        }
+    }
+
+    pub(super) fn router(self) -> Router {
+        Router::new().route(
+            "/webhook/{connection_id}",
+            post(gitlab_webhook).layer(Extension(self)),
+        )
+    }
+}
+
+#[tracing::instrument(skip(webhook, request_id, headers, body), fields(gl_event, target))]
+async fn gitlab_webhook(
+    Extension(webhook): Extension<Webhook>,
+    Extension(request_id): Extension<RequestId>,
+    Path(connection_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<String> {
+    let event = headers
+        .get("X-Gitlab-Event")
+        .context_bad_request("MISSING_HEADER", "Missing X-Gitlab-Event header")?
+        .to_str()?;
+    let token = headers
+        .get("X-Gitlab-Token")
+        .context_bad_request("MISSING_HEADER", "Missing X-Gitlab-Token header")?
+        .to_str()?;
+    let configs = webhook.authenticate(&connection_id, token).await?;
+    tracing::Span::current().record("gl_event", event);
+
+    if event != MERGE_REQUEST_EVENT {
+        tracing::trace!("Discarding unhandled event.");
+        return Ok("OK".to_string());
+    }
+    let event: MergeRequestEvent =
+        serde_json::from_slice(&body).context_bad_request("INVALID_BODY", "Invalid body")?;
+    let task = ExternalTask::from_merge_request(
+        &event.project.path_with_namespace,
+        MergeRequest {
+            title: event.object_attributes.title,
+            description: event.object_attributes.description,
+            state: event.object_attributes.state,
+            web_url: event.object_attributes.url,
+        },
+    )?;
+    tracing::Span::current().record("target", &task.url);
+
+    let request_id = request_id
+        .header_value()
+        .to_str()
+        .unwrap_or("INVALID")
+        .to_string();
+    for config in configs {
+        if let Err(e) = webhook.merge_task(&task, &config, &request_id).await {
+            tracing::warn!(
+                "Failed to process event for project {}: {e:?}",
+                config.project_id
+            );
+        }
+    }
+
+    Ok("OK".to_string())
+}
+
+impl Webhook {
+    /// Returns the configs of the connection, if the token is the connection's webhook token.
+    async fn authenticate(&self, connection_id: &str, token: &str) -> ApiResult<Vec<Config>> {
+        let token_hash = hash_token(token);
+        let configs: Vec<Config> = self
+            .config_storage
+            .list_for_external_id(PLUGIN_KIND.id, connection_id)
+            .await?
+            .into_iter()
+            .filter(|config| match &config.settings {
+                Settings::Gitlab(settings) => settings.webhook_token_hash == token_hash,
+                _ => false,
+            })
+            .collect();
+        // Don't reveal whether the connection exists.
+        if configs.is_empty() {
+            return Err(forbidden("UNAUTHORIZED", "Invalid token"));
+        }
+        Ok(configs)
+    }
+
+    async fn merge_task(
+        &self,
+        task: &ExternalTask,
+        config: &Config,
+        request_id: &str,
+    ) -> Result<()> {
+        let Settings::Gitlab(settings) = &config.settings else {
+            return Err(anyhow!("Config has no GitLab settings"));
+        };
+        if !settings.includes_project(&task.project) {
+            tracing::debug!("Discarding event of unconnected project {}", task.project);
+            return Ok(());
+        }
+
+        let client = self
+            .collab
+            .register_local_client(&config.project_id)
+            .await?;
+
+        // Avoid any expensive, async work while holding the doc_box lock.
+        let doc_box = client.project.doc_box.lock().await;
+        apply_task_changes(
+            task,
+            origin(config, request_id)?,
+            &DocBox::doc_or_error(doc_box.as_ref())?.ydoc,
+        )
+    }
+}
+
+// Note: This function should remain synchronous to avoid blocking the doc_box lock.
+fn apply_task_changes(external_task: &ExternalTask, origin: Origin, doc: &YDocProxy) -> Result<()> {
+    let mut txn = doc.transact_mut_with(origin);
+    let task_id = match get_doc_task(&txn, doc, &external_task.url)? {
+        Some(task) => {
+            update_task(&mut txn, &task, external_task)?;
+            task.get_id(&txn)?
+        }
+        None if external_task.status == "Done" => {
+            tracing::trace!("Discarding close event without associated task");
+            return Ok(());
+        }
+        None => {
+            let parent = get_or_create_kind_parent(&mut txn, doc, MR_KIND)?;
+            let task = new_task(external_task, doc.next_num(&txn)?)?;
+            doc.set(&mut txn, &task);
+            parent.push_child(&mut txn, &task.id)?;
+            task.id
+        }
+    };
+    add_referenced_task_links(
+        &mut txn,
+        doc,
+        &task_id,
+        &external_task.name,
+        &external_task.description,
+    )
+}
+
+fn get_doc_task<T: ReadTxn>(txn: &T, doc: &YDocProxy, url: &str) -> Result<Option<YTaskProxy>> {
+    let Ok(parent) = doc.get(txn, MR_KIND.id) else {
+        return Ok(None);
+    };
+    for child in parent.get_children(txn)? {
+        let child = doc.get(txn, &child)?;
+        if child.get_url(txn)?.is_some_and(|u| u == url)
+            && child.get_kind(txn)?.is_some_and(|k| k == MR_KIND.id)
+        {
+            return Ok(Some(child));
+        }
+    }
+    Ok(None)
+}
+
+pub(super) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn origin(config: &Config, request_id: &str) -> Result<Origin> {
+    YOrigin {
+        who: "gitlab_webhook".to_string(),
+        id: format!("connection_{}_request_{request_id}", config.external_id),
+        actor: Actor::GitLab,
+    }
+    .as_origin()
+}
@@ -0,0 +1,202 @@
+use crate::api::{
+    model::Task,
+    yproxy::{YDocProxy, YTaskProxy},
+};
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::{cell::LazyCell, collections::HashSet, time::SystemTime};
+use yrs::TransactionMut;
+
+#[derive(Debug)]
+pub(super) struct Kind<'a> {
+    pub(super) id: &'a str,
+    pub(super) name: &'a str,
+    pub(super) parent_kind: Option<&'a Kind<'a>>,
+}
+
+impl Kind<'_> {
+    /// Creates a new kind.
+    /// `id` must NOT contain underscores as they're used
+    /// as separators in the kind hierarchy. For example, 'github_pr'
+    /// is a child kind of 'github'.
+    pub(super) const fn new<'a>(id: &'a str, name: &'a str) -> Kind<'a> {
+        Kind {
+            id,
+            name,
+            parent_kind: None,
+        }
+    }
+
+    /// Creates a new nested kind.
+    /// For consistent namespacing, `id` must have `{parent_kind.id}_` as a prefix.
+    /// The remaining suffix must NOT contain underscores.
+    /// For example, a kind of 'github_pr' should have a parent_kind of 'github'.
+    pub(super) const fn new_nested<'a>(
+        parent_kind: &'a Kind,
+        id: &'a str,
+        name: &'a str,
+    ) -> Kind<'a> {
+        Kind {
+            id,
+            name,
+            parent_kind: Some(parent_kind),
+        }
+    }
+
+    pub(super) fn validate(&self) -> Result<()> {
+        let sub_id = match self.parent_kind {
+            Some(parent_kind) => {
+                parent_kind.validate()?;
+                let Some(sub_id) = self.id.strip_prefix(&format!("{}_", parent_kind.id)) else {
+                    return Err(anyhow!(
+                        "Kind id ({}) does not start with parent kind id ({})",
+                        self.id,
+                        parent_kind.id
+                    ));
+                };
+                sub_id
+            }
+            None => self.id,
+        };
+        if sub_id.contains("_") {
+            return Err(anyhow!(
+                "Kind id ({}) must not contain underscores which separate parent and children kinds",
+                sub_id,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the container of the kind's tasks, creating it and its parent container if needed.
+pub(super) fn get_or_create_kind_parent(
+    txn: &mut TransactionMut,
+    doc: &YDocProxy,
+    kind: &Kind,
+) -> Result<YTaskProxy> {
+    let parent_kind = kind.parent_kind.unwrap_or(kind);
+    let plugin_parent = match doc.get(txn, parent_kind.id) {
+        Ok(parent) => parent,
+        Err(_) => create_container(txn, &doc.get(txn, "root")?, doc, parent_kind)?,
+    };
+    if kind.parent_kind.is_none() {
+        return Ok(plugin_parent);
+    }
+    match doc.get(txn, kind.id) {
+        Ok(kind_parent) => Ok(kind_parent),
+        Err(_) => create_container(txn, &plugin_parent, doc, kind),
+    }
+}
+
+fn create_container(
+    txn: &mut TransactionMut,
+    container_parent: &YTaskProxy,
+    doc: &YDocProxy,
+    kind: &Kind,
+) -> Result<YTaskProxy> {
+    tracing::debug!("Creating new kind container: {}", kind.id);
+    let mut plugin_children = container_parent.get_children(txn)?;
+    plugin_children.push(kind.id.to_string());
+
+    let kind_parent = doc.set(
+        txn,
+        &Task {
+            id: kind.id.to_string(),
+            num: doc.next_num(txn)?.to_string(),
+            name: kind.name.to_string(),
+            kind: Some(kind.id.to_string()),
+            ..Task::default()
+        },
+    );
+    container_parent.set_children(txn, &plugin_children);
+    Ok(kind_parent)
+}
+
+pub(super) fn resolve_task(txn: &mut TransactionMut, task: &YTaskProxy) -> Result<()> {
+    if task.get_status(txn)?.is_none_or(|s| s != "Done") {
+        tracing::trace!(
+            "Resolving task {}: {}",
+            task.get_id(txn)?,
+            task.get_url(txn)?.unwrap_or_default()
+        );
+        task.set_status(txn, Some("Done"));
+        task.set_status_time(txn, Some(now()?));
+    }
+    Ok(())
+}
+
+/// Adds the given task ID as a child of any tasks referenced in the external task's name or description.
+pub(super) fn add_referenced_task_links(
+    txn: &mut TransactionMut,
+    doc: &YDocProxy,
+    task_id: &str,
+    name: &str,
+    description: &str,
+) -> Result<()> {
+    for link_task in doc.get_by_nums(txn, &find_referenced_task_nums(name, description))? {
+        // Disallow linking to managed links this, additionally, prevents circular links
+        // because the given task is itself always managed.
+        if link_task.is_managed(txn)? {
+            continue;
+        }
+
+        link_task.push_child(txn, task_id)?;
+    }
+    Ok(())
+}
+
+thread_local! {
+    static RE: LazyCell<Regex> = LazyCell::new(|| Regex::new(r"(?i)(?-u:\b)koso[#_-](\d+)").unwrap());
+}
+
+/// Searches the external task's name and description for references to Koso Tasks
+/// of the form: koso#<num>, koso_<num> or koso-<num>
+pub(super) fn find_referenced_task_nums(name: &str, description: &str) -> HashSet<String> {
+    RE.with(|re| {
+        re.captures_iter(description)
+            .chain(re.captures_iter(name))
+            .map(|g| g[1].to_owned())
+            .collect()
+    })
+}
+
+pub(super) fn now() -> Result<i64> {
+    Ok(SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_millis()
+        .try_into()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn find_referenced_task_nums_matches_name() {
+        assert_eq!(
+            find_referenced_task_nums("koso-15: Something else", "Something something"),
+            HashSet::from_iter(vec!["15".to_string()].into_iter())
+        );
+    }
+
+    #[test_log::test]
+    fn find_referenced_task_nums_matches_description() {
+        assert_eq!(
+            find_referenced_task_nums("Something else", "Something something koso#17, koso#19"),
+            HashSet::from_iter(vec!["17".to_string(), "19".to_string()].into_iter())
+        );
+    }
+
+    #[test_log::test]
+    fn find_referenced_task_nums_matches_description_and_name() {
+        assert_eq!(
+            find_referenced_task_nums(
+                "Something else KoSo_18",
+                "Somethingkoso#14 something KOSO-17, koso#19"
+            ),
+            HashSet::from_iter(
+                vec!["17".to_string(), "18".to_string(), "19".to_string()].into_iter()
+            )
+        );
+    }
+}
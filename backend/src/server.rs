@@ -4,10 +4,13 @@ use crate::{
         collab::Collab,
         google::{self, KeySet},
     },
-    debug, healthz, mcp, oauth,
+    debug, healthz, mcp,
+    notifiers::{deadlines::DeadlineScheduler, digest::DigestScheduler},
+    oauth,
     plugins::{
         PluginSettings,
         github::{self},
+        gitlab,
     },
     secrets::read_secret,
     settings::settings,
@@ -80,19 +83,20 @@ pub async fn start_main_server(config: Config) -> Result<(SocketAddr, JoinHandle
         }
     };
 
-    let collab = Collab::new(pool).context("Failed to init collab")?;
+    let collab = Collab::new(pool).await.context("Failed to init collab")?;
     let key_set = match config.key_set {
         Some(key_set) => key_set,
         None => google::KeySet::new().await?,
     };
 
-    let github_plugin = github::Plugin::new(
-        config.plugin_settings.unwrap_or_default(),
-        collab.clone(),
-        pool,
-    )
-    .await?;
+    let plugin_settings = config.plugin_settings.unwrap_or_default();
+    let github_plugin = github::Plugin::new(plugin_settings.clone(), collab.clone(), pool).await?;
     let github_poll_handle = github_plugin.start_polling();
+    let github_push_handle = github_plugin.start_pushing();
+    let gitlab_plugin = gitlab::Plugin::new(plugin_settings, collab.clone(), pool)?;
+    let gitlab_poll_handle = gitlab_plugin.start_polling();
+    let digest_handle = DigestScheduler::new(pool)?.start();
+    let deadline_handle = DeadlineScheduler::new(pool)?.start();
 
     let hmac = read_secret::<String>("koso/hmac")?;
     let encoding_key = EncodingKey::from_base64_secret(&hmac.data)?;
@@ -112,6 +116,7 @@ pub async fn start_main_server(config: Config) -> Result<(SocketAddr, JoinHandle
         )
         .nest("/healthz", healthz::router())
         .nest("/plugins/github", github_plugin.router()?)
+        .nest("/plugins/gitlab", gitlab_plugin.router())
         .merge(oauth::router(pool)?)
         // Apply these layers to all non-static routes.
         // Layers that are applied first will be called first.
@@ -196,6 +201,10 @@ pub async fn start_main_server(config: Config) -> Result<(SocketAddr, JoinHandle
 
         // Now that the server is shutdown, it's safe to clean things up.
         github_poll_handle.abort();
+        github_push_handle.abort();
+        gitlab_poll_handle.abort();
+        digest_handle.abort();
+        deadline_handle.abort();
         collab.stop().await;
         tracing::info!("Closing database pool...");
         pool.close().await;
@@ -11,6 +11,7 @@ pub(crate) struct Settings {
     pub(crate) database_url: String,
     pub(crate) secrets_dir: String,
     pub(crate) plugins: Plugins,
+    pub(crate) notifiers: Notifiers,
     pub(crate) stripe: Stripe,
     pub(crate) debug_path: Option<Regex>,
 }
@@ -22,6 +23,7 @@ struct SettingsRaw {
     pub(crate) database_url: String,
     pub(crate) secrets_dir: String,
     pub(crate) plugins: Plugins,
+    pub(crate) notifiers: Notifiers,
     pub(crate) stripe: Stripe,
     pub(crate) debug_path: Option<String>,
 }
@@ -40,6 +42,23 @@ pub(crate) struct Github {
     pub(crate) app_name: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Notifiers {
+    pub(crate) email: Email,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Email {
+    pub(crate) smtp_host: String,
+    pub(crate) smtp_port: u16,
+    /// Whether to upgrade SMTP connections with STARTTLS.
+    pub(crate) starttls: bool,
+    /// Mailbox messages are sent from, e.g. "Koso <notifications@koso.app>".
+    pub(crate) from: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Stripe {
@@ -89,6 +108,7 @@ fn load_settings(env: &str) -> Result<Settings> {
         database_url: raw.database_url,
         secrets_dir: raw.secrets_dir,
         plugins: raw.plugins,
+        notifiers: raw.notifiers,
         stripe: raw.stripe,
         debug_path,
     })
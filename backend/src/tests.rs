@@ -1,16 +1,27 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use crate::{
     api::{
         collab::{
             awareness::AwarenessState,
-            msg_sync::{self, MSG_SYNC, MSG_SYNC_REQUEST, MSG_SYNC_RESPONSE, MSG_SYNC_UPDATE},
+            msg_sync::{
+                self, MSG_KOSO_AWARENESS_UPDATE, MSG_SYNC, MSG_SYNC_REQUEST, MSG_SYNC_RESPONSE,
+                MSG_SYNC_UPDATE,
+            },
+            notifications::MENTION_SETTLE_DELAY,
             txn_origin::{self, YOrigin},
         },
         google::test_utils::{Claims, KID_1, PEM_1, encode_token, testonly_key_set},
-        model::{CreateProject, Project, ProjectExport, Task},
+        import::EXPORT_VERSION,
+        model::{CreateProject, Project, ProjectExport, ProjectSnapshot, Task},
         yproxy::YDocProxy,
     },
+    notifiers::webhook,
     plugins::PluginSettings,
     server::{self, Config},
     tests::{
@@ -19,7 +30,10 @@ use crate::{
     },
 };
 use anyhow::{Result, anyhow};
-use axum::http::HeaderValue;
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, HeaderValue},
+};
 use futures::{SinkExt, StreamExt, stream::FusedStream};
 use reqwest::{Client, Response, StatusCode};
 use serde_json::Value;
@@ -36,10 +50,10 @@ use tokio_tungstenite::{
 use tokio_util::sync::CancellationToken;
 use yrs::{
     Origin, ReadTxn, StateVector, Update,
-    encoding::read::Read as _,
+    encoding::{read::Read as _, write::Write as _},
     updates::{
         decoder::{Decode as _, DecoderV1},
-        encoder::Encode,
+        encoder::{Encode, Encoder as _, EncoderV1},
     },
 };
 
@@ -260,137 +274,3068 @@ async fn api_test(pool: PgPool) -> sqlx::Result<()> {
         assert!(user.get("premium").unwrap().as_bool().unwrap());
     }
 
-    server.shutdown_and_wait().await.unwrap();
+    server.shutdown_and_wait().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn not_invite_user(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+
+    // Log in a user
+    {
+        let res = client
+            .post(format!("http://{addr}/api/auth/login"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+    };
+
+    // Check that list users rejects the non-premium user.
+    {
+        let res = client
+            .get(format!("http://{addr}/api/users"))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_not_premium(res).await;
+    }
+
+    server.shutdown_and_wait().await.unwrap();
+    Ok(())
+}
+
+async fn assert_not_premium(res: Response) {
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let error: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let error = error.as_object().unwrap();
+    assert_eq!(error.get("status").unwrap().as_i64().unwrap(), 403);
+    assert_eq!(error.get("title").unwrap().as_str().unwrap(), "NOT_PREMIUM");
+    assert!(!error.get("detail").unwrap().as_str().unwrap().is_empty());
+}
+
+#[test_log::test(sqlx::test)]
+async fn create_and_delete_project(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let token = login(&client, &addr, pool).await.unwrap();
+
+    let project = create_project(&client, &addr, &token, "A Project to Delete")
+        .await
+        .unwrap();
+    assert_eq!(project.name, "A Project to Delete");
+    assert_eq!(project.deleted_on, None);
+
+    let project = delete_project(&client, &addr, &token, &project.project_id)
+        .await
+        .unwrap();
+    assert_eq!(project.name, "A Project to Delete");
+    assert_ne!(project.deleted_on, None);
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+async fn setup_project(
+    client: &Client,
+    addr: &SocketAddr,
+    token: &str,
+    claims: &Claims,
+    pool: &PgPool,
+) -> String {
+    // Log in and share the project
+    let res = client
+        .post(format!("http://{addr}/api/auth/login"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    set_user_premium(&claims.email, pool).await.unwrap();
+
+    let project_id = {
+        let res = client
+            .post(format!("http://{addr}/api/projects"))
+            .bearer_auth(token)
+            .header("Content-Type", "application/json")
+            .body("{\"name\":\"Test Project\"}")
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let project: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        let project = project.as_object().unwrap();
+        project
+            .get("projectId")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let res = client
+        .patch(format!("http://{addr}/api/projects/{project_id}/users"))
+        .bearer_auth(token)
+        .header("Content-Type", "application/json")
+        .body(format!(
+            "{{\"projectId\":\"{project_id}\", \"addEmails\":[\"{}\"], \"removeEmails\":[]}}",
+            claims.email
+        ))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    project_id
+}
+
+#[test_log::test(sqlx::test)]
+async fn ws_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+
+    // Log in and share the project
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    // Test that unauthenticated users are rejected.
+    {
+        let req = format!("ws://{addr}/api/ws/projects/{project_id}")
+            .into_client_request()
+            .unwrap();
+        let err = tokio_tungstenite::connect_async(req).await.unwrap_err();
+        let tokio_tungstenite::tungstenite::error::Error::Http(response) = err else {
+            panic!("Expected http error, got: {err:?}");
+        };
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // Test that authenticated but unauthorized users are rejected.
+    {
+        let mut req = format!("ws://{addr}/api/ws/projects/{project_id}")
+            .into_client_request()
+            .unwrap();
+        let claims = Claims {
+            email: "unauthorized-users@koso.app".to_string(),
+            ..Default::default()
+        };
+        let token = encode_token(&claims, KID_1, PEM_1).unwrap();
+        req.headers_mut().insert(
+            "Sec-Websocket-Protocol",
+            HeaderValue::from_str(format!("bearer, {token}").as_str()).unwrap(),
+        );
+        let (mut socket, response) = tokio_tungstenite::connect_async(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        let close = next_with_timeout(&mut socket).await.unwrap().unwrap();
+        let Message::Close(Some(close)) = close else {
+            panic!("Expected close frame, got: {close:?}");
+        };
+        assert_eq!(close.code, CloseCode::Iana(3000));
+        assert_eq!(close.reason, "Unauthorized.");
+        futures::SinkExt::close(&mut socket).await.unwrap();
+        assert!(next_with_timeout(&mut socket).await.unwrap().is_none());
+        assert!(socket.is_terminated());
+    }
+
+    // Test opening and closing sockets.
+    {
+        let mut req = format!("ws://{addr}/api/ws/projects/{project_id}")
+            .into_client_request()
+            .unwrap();
+        req.headers_mut().insert(
+            "Sec-Websocket-Protocol",
+            HeaderValue::from_str(
+                format!("bearer, {token}, koso-client-version, testversion").as_str(),
+            )
+            .unwrap(),
+        );
+
+        let (mut socket1, response) = tokio_tungstenite::connect_async(req.clone()).await.unwrap();
+        let socket1 = &mut socket1;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(read_sync_request(socket1).await, StateVector::default());
+        let (mut socket2, response) = tokio_tungstenite::connect_async(req.clone()).await.unwrap();
+        let socket2 = &mut socket2;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(read_sync_request(socket2).await, StateVector::default());
+
+        // Closing one socket should trigger awareness broadcasts to others.
+        close_socket(socket2).await;
+        read_awareness_state(socket1).await;
+        close_socket(socket1).await;
+
+        // Abruptly close the socket, this'll trigger the error handling in ClientMessageReceiver
+        let (_socket, response) = tokio_tungstenite::connect_async(req.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+    }
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn project_roles_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    // Log in a viewer and share the project with them.
+    const VIEWER_EMAIL: &str = "viewer@koso.app";
+    let viewer_token = {
+        let claims = Claims {
+            email: VIEWER_EMAIL.to_string(),
+            ..Claims::default()
+        };
+        let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+        let res = client
+            .post(format!("http://{addr}/api/auth/login"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        set_user_premium(&claims.email, pool).await.unwrap();
+        token
+    };
+    {
+        let res = client
+            .patch(format!("http://{addr}/api/projects/{project_id}/users"))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .body(format!(
+                "{{\"projectId\":\"{project_id}\", \"addEmails\":[\"{VIEWER_EMAIL}\"], \"removeEmails\":[], \"role\":\"viewer\"}}"
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    // Both users are listed with their roles.
+    {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}/users"))
+            .bearer_auth(&viewer_token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let users: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        let roles = users
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|u| {
+                (
+                    u.get("email").unwrap().as_str().unwrap(),
+                    u.get("role").unwrap().as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(roles.contains(&(claims.email.as_str(), "owner")));
+        assert!(roles.contains(&(VIEWER_EMAIL, "viewer")));
+    }
+
+    // Viewers can read the project, and are told they're viewers, but not change it.
+    {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}"))
+            .bearer_auth(&viewer_token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let project: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        assert_eq!(project["role"], "viewer");
+
+        let res = client
+            .get(format!("http://{addr}/api/projects"))
+            .bearer_auth(&viewer_token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let projects: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        assert_eq!(projects[0]["role"], "viewer");
+
+        let res = client
+            .patch(format!("http://{addr}/api/projects/{project_id}"))
+            .bearer_auth(&viewer_token)
+            .header("Content-Type", "application/json")
+            .body(format!(
+                "{{\"name\":\"Renamed\", \"projectId\":\"{project_id}\"}}"
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_insufficient_role(res).await;
+
+        let res = client
+            .patch(format!("http://{addr}/api/projects/{project_id}/users"))
+            .bearer_auth(&viewer_token)
+            .header("Content-Type", "application/json")
+            .body(format!(
+                "{{\"projectId\":\"{project_id}\", \"addEmails\":[\"{VIEWER_EMAIL}\"], \"removeEmails\":[], \"role\":\"owner\"}}"
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_insufficient_role(res).await;
+
+        let res = client
+            .delete(format!("http://{addr}/api/projects/{project_id}"))
+            .bearer_auth(&viewer_token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_insufficient_role(res).await;
+    }
+
+    // The last owner cannot be removed.
+    {
+        let res = client
+            .patch(format!("http://{addr}/api/projects/{project_id}/users"))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .body(format!(
+                "{{\"projectId\":\"{project_id}\", \"addEmails\":[], \"removeEmails\":[\"{}\"]}}",
+                claims.email
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Viewers receive syncs, but their updates are rejected.
+    {
+        let mut req = format!("ws://{addr}/api/ws/projects/{project_id}")
+            .into_client_request()
+            .unwrap();
+        req.headers_mut().insert(
+            "Sec-Websocket-Protocol",
+            HeaderValue::from_str(format!("bearer, {viewer_token}").as_str()).unwrap(),
+        );
+        let (mut socket, response) = tokio_tungstenite::connect_async(req).await.unwrap();
+        let socket = &mut socket;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(read_sync_request(socket).await, StateVector::default());
+
+        let ydoc = YDocProxy::new();
+        {
+            let mut txn = ydoc.transact_mut_with(origin());
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: "id1".to_string(),
+                    num: "1".to_string(),
+                    name: "Task 1".to_string(),
+                    ..Task::default()
+                },
+            );
+        }
+        socket
+            .send(Message::binary(msg_sync::sync_update(
+                &ydoc
+                    .transact()
+                    .encode_state_as_update_v2(&StateVector::default()),
+            )))
+            .await
+            .unwrap();
+
+        // Messages are processed in order, so the update was handled by now.
+        socket
+            .send(Message::binary(msg_sync::sync_request(
+                &StateVector::default(),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(read_sync_response(socket).await, Update::default());
+
+        close_socket(socket).await;
+    }
+
+    server.shutdown_and_wait().await.unwrap();
+    Ok(())
+}
+
+async fn assert_insufficient_role(res: Response) {
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let error: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let error = error.as_object().unwrap();
+    assert_eq!(
+        error.get("title").unwrap().as_str().unwrap(),
+        "INSUFFICIENT_ROLE"
+    );
+}
+
+#[test_log::test(sqlx::test)]
+async fn task_comments_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    let ydoc = YDocProxy::new();
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "id1".to_string(),
+                num: "1".to_string(),
+                name: "Task 1".to_string(),
+                ..Task::default()
+            },
+        );
+        txn.encode_update_v2()
+    };
+    sqlx::query("INSERT INTO yupdates (project_id, seq, update_v2) VALUES ($1, DEFAULT, $2)")
+        .bind(&project_id)
+        .bind(update)
+        .execute(pool)
+        .await?;
+
+    // Log in a viewer and a commenter and share the project with them.
+    const VIEWER_EMAIL: &str = "viewer@koso.app";
+    const COMMENTER_EMAIL: &str = "commenter@koso.app";
+    let mut tokens = Vec::new();
+    for (email, role) in [(VIEWER_EMAIL, "viewer"), (COMMENTER_EMAIL, "commenter")] {
+        let claims = Claims {
+            email: email.to_string(),
+            ..Claims::default()
+        };
+        let user_token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+        let res = client
+            .post(format!("http://{addr}/api/auth/login"))
+            .bearer_auth(&user_token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        set_user_premium(email, pool).await.unwrap();
+
+        let res = client
+            .patch(format!("http://{addr}/api/projects/{project_id}/users"))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .body(format!(
+                "{{\"projectId\":\"{project_id}\", \"addEmails\":[\"{email}\"], \"removeEmails\":[], \"role\":\"{role}\"}}"
+            ))
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        tokens.push(user_token);
+    }
+    let [viewer_token, commenter_token] = tokens.try_into().unwrap();
+
+    let comments_url = format!("http://{addr}/api/projects/{project_id}/tasks/id1/comments");
+    let post_comment = |token: &str, body: &str| {
+        client
+            .post(&comments_url)
+            .bearer_auth(token)
+            .header("Content-Type", "application/json")
+            .body(serde_json::json!({ "body": body }).to_string())
+            .send()
+    };
+    let delete_comment = |token: &str, comment_id: &str| {
+        client
+            .delete(format!("{comments_url}/{comment_id}"))
+            .bearer_auth(token)
+            .send()
+    };
+
+    // Commenters are told they're commenters.
+    {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}"))
+            .bearer_auth(&commenter_token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let project: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        assert_eq!(project["role"], "commenter");
+    }
+
+    // Viewers cannot comment.
+    let res = post_comment(&viewer_token, "From a viewer").await.unwrap();
+    assert_insufficient_role(res).await;
+
+    // Commenters and owners can.
+    let res = post_comment(&commenter_token, " From a commenter ")
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let commenter_comment: Value =
+        serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(commenter_comment["body"], "From a commenter");
+    assert_eq!(commenter_comment["email"], COMMENTER_EMAIL);
+    let commenter_comment_id = commenter_comment["commentId"].as_str().unwrap();
+
+    let res = post_comment(&token, "From an owner").await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let owner_comment: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let owner_comment_id = owner_comment["commentId"].as_str().unwrap();
+
+    // Empty comments and comments on unknown tasks are rejected.
+    let res = post_comment(&commenter_token, "  ").await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = client
+        .post(format!(
+            "http://{addr}/api/projects/{project_id}/tasks/missing/comments"
+        ))
+        .bearer_auth(&commenter_token)
+        .header("Content-Type", "application/json")
+        .body("{\"body\":\"Hello\"}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // Viewers can read the comments, oldest first.
+    {
+        let res = client
+            .get(&comments_url)
+            .bearer_auth(&viewer_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let comments: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        let bodies = comments
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["body"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["From a commenter", "From an owner"]);
+    }
+
+    // Commenters may only delete their own comments, while owners may delete any.
+    let res = delete_comment(&commenter_token, owner_comment_id)
+        .await
+        .unwrap();
+    assert_insufficient_role(res).await;
+    let res = delete_comment(&viewer_token, commenter_comment_id)
+        .await
+        .unwrap();
+    assert_insufficient_role(res).await;
+    let res = delete_comment(&commenter_token, commenter_comment_id)
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = delete_comment(&token, owner_comment_id).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = delete_comment(&token, owner_comment_id).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    server.shutdown_and_wait().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn snapshots_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
+
+    // Add a couple of tasks, which triggers the first snapshot of the project before them.
+    let expected_graph = ydoc.to_graph(&ydoc.transact()).unwrap();
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        for (id, num) in [("id1", "1"), ("id2", "2")] {
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: id.to_string(),
+                    num: num.to_string(),
+                    name: format!("Task {num}"),
+                    ..Task::default()
+                },
+            );
+        }
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+
+    let mut snapshots: Vec<Value> = vec![];
+    for _ in 0..50 {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}/snapshots"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        snapshots = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        if !snapshots.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(snapshots.len(), 1);
+    let snapshot_id = snapshots[0].get("snapshotId").unwrap().as_i64().unwrap();
+
+    // Delete a task. This is within the snapshot interval, so no new snapshot is taken.
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.remove(&mut txn, "id2");
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+
+    // The snapshot contains neither the added nor the deleted task.
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{project_id}/snapshots/{snapshot_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let snapshot: ProjectSnapshot =
+        serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(snapshot.graph, expected_graph);
+
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{project_id}/snapshots/99999"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // Restore the snapshot and verify the change is broadcast to the client.
+    let res = client
+        .post(format!(
+            "http://{addr}/api/projects/{project_id}/snapshots/{snapshot_id}/restore"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let sync_update = read_sync_update(socket).await;
+    ydoc.transact_mut_with(origin())
+        .apply_update(sync_update)
+        .unwrap();
+    assert_eq!(ydoc.to_graph(&ydoc.transact()).unwrap(), expected_graph);
+
+    // The pre-restore state was snapshotted too.
+    let res = client
+        .get(format!("http://{addr}/api/projects/{project_id}/snapshots"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let snapshots: Vec<Value> = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(snapshots.len(), 2);
+    // Snapshots are attributed to the last change they include, not the one that
+    // triggered them: the user's delete for the backup and, with no change
+    // persisted since the project was loaded, the server for the first one.
+    assert_eq!(
+        snapshots[0]["actor"]["user"]["email"].as_str(),
+        Some(claims.email.as_str())
+    );
+    assert_eq!(snapshots[1]["actor"].as_str(), Some("server"));
+
+    close_socket(socket).await;
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn bulk_task_changes_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
+
+    // Create, then delete, more tasks in a single transaction than there are slots
+    // in the collab channels.
+    const TASKS: i64 = 120;
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        for i in 1..=TASKS {
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: format!("id{i}"),
+                    num: i.to_string(),
+                    name: format!("Task {i}"),
+                    ..Task::default()
+                },
+            );
+        }
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        for i in 1..=TASKS {
+            ydoc.remove(&mut txn, &format!("id{i}"));
+        }
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+
+    let count_changes = || async {
+        let (created, deleted): (i64, i64) = sqlx::query_as(
+            "
+            SELECT
+              COUNT(*) FILTER (WHERE new_value IS NOT NULL),
+              COUNT(*) FILTER (WHERE new_value IS NULL)
+            FROM task_changes
+            WHERE project_id = $1 AND field = 'task'",
+        )
+        .bind(&project_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (created, deleted)
+    };
+    let mut changes = (0, 0);
+    for _ in 0..100 {
+        changes = count_changes().await;
+        if changes == (TASKS, TASKS) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(changes, (TASKS, TASKS));
+
+    close_socket(socket).await;
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn task_history_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
+
+    // Create a task and then move it to Done.
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "id1".to_string(),
+                num: "1".to_string(),
+                name: "Task 1".to_string(),
+                status: Some("In Progress".to_string()),
+                ..Task::default()
+            },
+        );
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.get(&txn, "id1")
+            .unwrap()
+            .set_status(&mut txn, Some("Done"));
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+
+    let mut history: Vec<Value> = vec![];
+    for _ in 0..50 {
+        let res = client
+            .get(format!(
+                "http://{addr}/api/projects/{project_id}/tasks/id1/history"
+            ))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        history = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        if history.len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(history.len(), 2, "Unexpected history: {history:?}");
+
+    // Newest first.
+    let done = &history[0];
+    assert_eq!(done.get("field").unwrap(), "status");
+    assert_eq!(done.get("oldValue").unwrap(), "In Progress");
+    assert_eq!(done.get("newValue").unwrap(), "Done");
+    assert_eq!(
+        done.pointer("/actor/user/email").unwrap().as_str().unwrap(),
+        claims.email
+    );
+    assert!(done.get("changedAt").unwrap().is_string());
+    let created = &history[1];
+    assert_eq!(created.get("field").unwrap(), "task");
+    assert_eq!(created.pointer("/newValue/name").unwrap(), "Task 1");
+
+    // The project feed includes the same changes and paginates.
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{project_id}/activity?limit=1"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let activity: Vec<Value> = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(activity.len(), 1);
+    assert_eq!(activity[0], history[0]);
+    let before = activity[0].get("changeId").unwrap().as_i64().unwrap();
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{project_id}/activity?before={before}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let activity: Vec<Value> = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert!(activity.contains(&history[1]));
+    assert!(!activity.contains(&history[0]));
+
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{project_id}/activity?limit=0"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    close_socket(socket).await;
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn search_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    let search = async |params: &str| -> Vec<(String, String)> {
+        let res = client
+            .get(format!("http://{addr}/api/search/tasks?{params}"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let results: Vec<Value> = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        let mut results: Vec<(String, String)> = results
+            .iter()
+            .map(|r| {
+                (
+                    r.get("projectId").unwrap().as_str().unwrap().to_string(),
+                    r.pointer("/task/id").unwrap().as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        results.sort();
+        results
+    };
+
+    // Import a project, which is indexed when created.
+    let imported_project_id = {
+        let create_req = CreateProject {
+            name: "Imported project".to_string(),
+            project_export: Some(ProjectExport {
+                version: EXPORT_VERSION,
+                project_id: "unused".to_string(),
+                graph: HashMap::from([
+                    (
+                        "root".to_string(),
+                        Task {
+                            id: "root".to_string(),
+                            num: "0".to_string(),
+                            name: "Root".to_string(),
+                            children: vec!["imported1".to_string()],
+                            ..Task::default()
+                        },
+                    ),
+                    (
+                        "imported1".to_string(),
+                        Task {
+                            id: "imported1".to_string(),
+                            num: "1".to_string(),
+                            name: "Deploy the importer".to_string(),
+                            assignee: Some(claims.email.clone()),
+                            ..Task::default()
+                        },
+                    ),
+                ]),
+            }),
+        };
+        let res = client
+            .post(format!("http://{addr}/api/projects"))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&create_req).unwrap())
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        let project: Project = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        project.project_id
+    };
+    assert_eq!(
+        search("q=importer").await,
+        vec![(imported_project_id.clone(), "imported1".to_string())]
+    );
+
+    // Edited tasks are indexed by the doc observers.
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "id1".to_string(),
+                num: "1".to_string(),
+                name: "Deploy the backend".to_string(),
+                desc: Some("Roll out to production".to_string()),
+                assignee: Some(claims.email.clone()),
+                status: Some("In Progress".to_string()),
+                deadline: Some(2000),
+                ..Task::default()
+            },
+        );
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "id2".to_string(),
+                num: "2".to_string(),
+                name: "Write docs".to_string(),
+                status: Some("Done".to_string()),
+                deadline: Some(1000),
+                archived: Some(true),
+                ..Task::default()
+            },
+        );
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+
+    let task = (project_id.clone(), "id1".to_string());
+    let imported_task = (imported_project_id.clone(), "imported1".to_string());
+    let mut expected = vec![task.clone(), imported_task.clone()];
+    expected.sort();
+
+    let mut results = vec![];
+    for _ in 0..50 {
+        results = search("q=deploy").await;
+        if results.len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(results, expected);
+
+    assert_eq!(search("q=production").await, vec![task]);
+    assert_eq!(
+        search(&format!(
+            "assignee={}&status=Not Started,In Progress",
+            claims.email
+        ))
+        .await,
+        expected
+    );
+    assert_eq!(
+        search(&format!("projectId={project_id}&archived=true")).await,
+        vec![(project_id.clone(), "id2".to_string())]
+    );
+    assert_eq!(
+        search(&format!("projectId={project_id}&deadlineAfter=1500")).await,
+        vec![(project_id.clone(), "id1".to_string())]
+    );
+    assert_eq!(
+        search(&format!("projectId={project_id}&deadlineBefore=1500")).await,
+        vec![(project_id.clone(), "id2".to_string())]
+    );
+    assert_eq!(search("q=nothing matches this").await, vec![]);
+
+    // Removed tasks are dropped from the index.
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.remove(&mut txn, "id1");
+        txn.encode_update_v2()
+    };
+    socket
+        .send(Message::binary(msg_sync::sync_update(&update)))
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        results = search("q=deploy").await;
+        if results.len() < 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(results, vec![imported_task]);
+
+    // Other users don't see the tasks.
+    let other_claims = Claims {
+        email: "other-user@koso.app".to_string(),
+        ..Claims::default()
+    };
+    let other_token: String = encode_token(&other_claims, KID_1, PEM_1).unwrap();
+    let res = client
+        .post(format!("http://{addr}/api/auth/login"))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get(format!("http://{addr}/api/search/tasks?q=deploy"))
+        .bearer_auth(&other_token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.text().await.unwrap(), "[]");
+
+    let res = client
+        .get(format!("http://{addr}/api/search/tasks?limit=0"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    close_socket(socket).await;
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn my_work_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    setup_project(&client, &addr, &token, &claims, pool).await;
+
+    let me = Some(claims.email.clone());
+    let projects = [
+        vec![
+            Task {
+                id: "a1".to_string(),
+                num: "1".to_string(),
+                name: "No deadline".to_string(),
+                assignee: me.clone(),
+                status: Some("In Progress".to_string()),
+                ..Task::default()
+            },
+            Task {
+                id: "a2".to_string(),
+                num: "2".to_string(),
+                name: "Not started".to_string(),
+                assignee: me.clone(),
+                ..Task::default()
+            },
+            Task {
+                id: "a3".to_string(),
+                num: "3".to_string(),
+                name: "Someone else's".to_string(),
+                assignee: Some("someone-else@koso.app".to_string()),
+                ..Task::default()
+            },
+            Task {
+                id: "a4".to_string(),
+                num: "4".to_string(),
+                name: "Archived".to_string(),
+                assignee: me.clone(),
+                archived: Some(true),
+                ..Task::default()
+            },
+        ],
+        vec![
+            Task {
+                id: "b1".to_string(),
+                num: "1".to_string(),
+                name: "Later deadline".to_string(),
+                assignee: me.clone(),
+                status: Some("In Progress".to_string()),
+                deadline: Some(2000),
+                ..Task::default()
+            },
+            Task {
+                id: "b2".to_string(),
+                num: "2".to_string(),
+                name: "Earlier deadline".to_string(),
+                assignee: me.clone(),
+                status: Some("In Progress".to_string()),
+                deadline: Some(1000),
+                ..Task::default()
+            },
+            Task {
+                id: "b3".to_string(),
+                num: "3".to_string(),
+                name: "Done".to_string(),
+                assignee: me.clone(),
+                status: Some("Done".to_string()),
+                ..Task::default()
+            },
+        ],
+    ];
+    for (i, mut tasks) in projects.into_iter().enumerate() {
+        tasks.push(Task {
+            id: "root".to_string(),
+            num: "0".to_string(),
+            name: "Root".to_string(),
+            children: tasks.iter().map(|t| t.id.clone()).collect(),
+            ..Task::default()
+        });
+        let create_req = CreateProject {
+            name: format!("Project {i}"),
+            project_export: Some(ProjectExport {
+                version: EXPORT_VERSION,
+                project_id: "unused".to_string(),
+                graph: tasks.into_iter().map(|t| (t.id.clone(), t)).collect(),
+            }),
+        };
+        let res = client
+            .post(format!("http://{addr}/api/projects"))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&create_req).unwrap())
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let res = client
+        .get(format!("http://{addr}/api/work"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let work: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let groups = work
+        .get("groups")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| {
+            (
+                group.get("status").unwrap().as_str().unwrap().to_string(),
+                group
+                    .get("tasks")
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.pointer("/task/id").unwrap().as_str().unwrap().to_string())
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        vec![
+            (
+                "In Progress".to_string(),
+                vec!["b2".to_string(), "b1".to_string(), "a1".to_string()]
+            ),
+            ("Not Started".to_string(), vec!["a2".to_string()]),
+            ("Done".to_string(), vec!["b3".to_string()]),
+        ]
+    );
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn webhook_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+    let (hook_url, requests) = serve_webhook().await;
+
+    // Subscribe to everything but assignments.
+    let res = client
+        .post(format!("http://{addr}/api/projects/{project_id}/webhooks"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "url": hook_url,
+            "events": ["task.created", "task.status_changed", "task.deleted"],
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let created: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let secret = created.get("secret").unwrap().as_str().unwrap().to_string();
+    let webhook_id = created
+        .pointer("/webhook/webhookId")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = client
+        .post(format!("http://{addr}/api/projects/{project_id}/webhooks"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"url": hook_url, "events": ["task.bogus"]}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
+    for update in [
+        {
+            let mut txn = ydoc.transact_mut_with(origin());
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: "id1".to_string(),
+                    num: "1".to_string(),
+                    name: "Task 1".to_string(),
+                    status: Some("In Progress".to_string()),
+                    ..Task::default()
+                },
+            );
+            txn.encode_update_v2()
+        },
+        {
+            let mut txn = ydoc.transact_mut_with(origin());
+            let task = ydoc.get(&txn, "id1").unwrap();
+            task.set_status(&mut txn, Some("Done"));
+            task.set_assignee(&mut txn, Some("someone@koso.app"));
+            txn.encode_update_v2()
+        },
+        {
+            let mut txn = ydoc.transact_mut_with(origin());
+            ydoc.remove(&mut txn, "id1");
+            txn.encode_update_v2()
+        },
+    ] {
+        socket
+            .send(Message::binary(msg_sync::sync_update(&update)))
+            .await
+            .unwrap();
+    }
+
+    for _ in 0..50 {
+        if requests.lock().unwrap().len() >= 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut received: Vec<(String, Value)> = {
+        let requests = requests.lock().unwrap();
+        requests
+            .iter()
+            .map(|(headers, body)| {
+                assert_eq!(
+                    headers["X-Koso-Signature-256"],
+                    webhook::sign(body, &secret).unwrap().as_str()
+                );
+                (
+                    headers["X-Koso-Event"].to_str().unwrap().to_string(),
+                    serde_json::from_slice(body).unwrap(),
+                )
+            })
+            .collect()
+    };
+    // Each event is delivered on its own, so they may arrive in any order.
+    received.sort_by(|(a, _), (b, _)| a.cmp(b));
+    assert_eq!(
+        received.iter().map(|(e, _)| e.as_str()).collect::<Vec<_>>(),
+        vec!["task.created", "task.deleted", "task.status_changed"]
+    );
+    assert_eq!(received[0].1["task"]["name"], "Task 1");
+    assert_eq!(received[0].1["actor"], claims.email.as_str());
+    assert_eq!(received[1].1["task"]["id"], "id1");
+    assert_eq!(received[2].1["task"]["status"], "Done");
+    assert_eq!(received[2].1["previous"], "In Progress");
+
+    // Deliveries are recorded after the receiver responds.
+    let mut deliveries: Vec<Value> = vec![];
+    for _ in 0..50 {
+        let res = client
+            .get(format!(
+                "http://{addr}/api/projects/{project_id}/webhooks/{webhook_id}/deliveries"
+            ))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        deliveries = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        if deliveries.len() >= 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(deliveries.len(), 3);
+    assert!(deliveries.iter().all(|d| d["succeeded"] == true));
+
+    // A user's webhook notifier receives test notifications as pings.
+    let res = client
+        .post(format!("http://{addr}/api/notifiers/webhook"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"url": hook_url, "secret": "user-secret"}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .post(format!("http://{addr}/api/notifiers"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"message": "Hello", "notifiers": ["webhook"]}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut deliveries: Vec<Value> = vec![];
+    for _ in 0..50 {
+        let res = client
+            .get(format!("http://{addr}/api/notifiers/webhook/deliveries"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        deliveries = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+        if !deliveries.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["event"], "ping");
+    assert_eq!(deliveries[0]["payload"]["message"], "Hello");
+    let (headers, body) = requests.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(
+        headers["X-Koso-Signature-256"],
+        webhook::sign(&body, "user-secret").unwrap().as_str()
+    );
+
+    let res = client
+        .delete(format!(
+            "http://{addr}/api/projects/{project_id}/webhooks/{webhook_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get(format!("http://{addr}/api/projects/{project_id}/webhooks"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.text().await.unwrap(), "[]");
+
+    close_socket(socket).await;
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn task_notifications_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    // Share the project with a teammate who receives notifications by webhook.
+    let teammate = Claims {
+        email: "teammate@koso.app".to_string(),
+        name: "Team Mate".to_string(),
+        ..Claims::default()
+    };
+    let teammate_token: String = encode_token(&teammate, KID_1, PEM_1).unwrap();
+    let res = client
+        .post(format!("http://{addr}/api/auth/login"))
+        .bearer_auth(&teammate_token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .patch(format!("http://{addr}/api/projects/{project_id}/users"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "projectId": project_id,
+            "addEmails": [teammate.email],
+            "removeEmails": [],
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let (hook_url, requests) = serve_webhook().await;
+    let res = client
+        .post(format!("http://{addr}/api/notifiers/webhook"))
+        .bearer_auth(&teammate_token)
+        .json(&serde_json::json!({"url": hook_url}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let received = |count: usize| {
+        let requests = requests.clone();
+        async move {
+            for _ in 0..50 {
+                if requests.lock().unwrap().len() >= count {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            // Give unexpected deliveries a chance to arrive.
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let mut events: Vec<String> = requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(headers, _)| headers["X-Koso-Event"].to_str().unwrap().to_string())
+                .collect();
+            events.sort();
+            events
+        }
+    };
+
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
+    let send = |update: Vec<u8>| Message::binary(msg_sync::sync_update(&update));
+
+    // Mentioning the teammate, in a task they reported, notifies them.
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "id1".to_string(),
+                num: "1".to_string(),
+                name: "Task 1".to_string(),
+                desc: Some("Could @Teammate@koso.app and @stranger@koso.app look?".to_string()),
+                reporter: Some(teammate.email.clone()),
+                ..Task::default()
+            },
+        );
+        txn.encode_update_v2()
+    };
+    socket.send(send(update)).await.unwrap();
+    assert_eq!(received(1).await, vec!["task.mentioned"]);
+    let (_, body) = requests.lock().unwrap()[0].clone();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["task"]["id"], "id1");
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("mentioned you in")
+    );
+
+    // Editing the description keeps existing mentions quiet.
+    // Status changes by others are sent to the reporter.
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        let task = ydoc.get(&txn, "id1").unwrap();
+        task.set_desc(&mut txn, Some("Could @teammate@koso.app look soon?"));
+        task.set_status(&mut txn, Some("In Progress"));
+        txn.encode_update_v2()
+    };
+    socket.send(send(update)).await.unwrap();
+    assert_eq!(
+        received(2).await,
+        vec!["task.mentioned", "task.status_changed"]
+    );
+
+    // Turned off notifications aren't sent.
+    let res = client
+        .patch(format!("http://{addr}/api/notifiers/configs/webhook"))
+        .bearer_auth(&teammate_token)
+        .json(&serde_json::json!({"disabledNotifications": ["reportedStatusChanged"]}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        let task = ydoc.get(&txn, "id1").unwrap();
+        task.set_desc(&mut txn, Some("Nevermind"));
+        task.set_status(&mut txn, Some("Done"));
+        txn.encode_update_v2()
+    };
+    socket.send(send(update)).await.unwrap();
+    // Mentions are checked once edits to the description settle.
+    tokio::time::sleep(MENTION_SETTLE_DELAY + Duration::from_millis(500)).await;
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        let task = ydoc.get(&txn, "id1").unwrap();
+        task.set_desc(&mut txn, Some("@teammate@koso.app, actually, please look"));
+        txn.encode_update_v2()
+    };
+    socket.send(send(update)).await.unwrap();
+    assert_eq!(
+        received(3).await,
+        vec!["task.mentioned", "task.mentioned", "task.status_changed"]
+    );
+
+    // Mentions removed before the edits settle aren't notified.
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        ydoc.set(
+            &mut txn,
+            &Task {
+                id: "id2".to_string(),
+                num: "2".to_string(),
+                name: "Task 2".to_string(),
+                desc: Some("Draft".to_string()),
+                ..Task::default()
+            },
+        );
+        txn.encode_update_v2()
+    };
+    socket.send(send(update)).await.unwrap();
+    for desc in ["Draft @teammate@koso.app", "Draft"] {
+        let update = {
+            let mut txn = ydoc.transact_mut_with(origin());
+            ydoc.get(&txn, "id2")
+                .unwrap()
+                .set_desc(&mut txn, Some(desc));
+            txn.encode_update_v2()
+        };
+        socket.send(send(update)).await.unwrap();
+    }
+    tokio::time::sleep(MENTION_SETTLE_DELAY).await;
+    assert_eq!(
+        received(4).await,
+        vec!["task.mentioned", "task.mentioned", "task.status_changed"]
+    );
+
+    close_socket(socket).await;
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn personal_access_tokens_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+    let other_project = create_project(&client, &addr, &token, "Other Project")
+        .await
+        .unwrap();
+
+    // Create a read-only token scoped to the first project.
+    let res = client
+        .post(format!("http://{addr}/api/tokens"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "name": "CI",
+            "projectIds": [project_id],
+            "readOnly": true,
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let created: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let read_only = created.get("secret").unwrap().as_str().unwrap().to_string();
+    assert!(read_only.starts_with("koso_pat_"));
+    let token_id = created
+        .pointer("/token/tokenId")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Only the scoped project is visible.
+    let res = client
+        .get(format!("http://{addr}/api/projects"))
+        .bearer_auth(&read_only)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let projects: Vec<Project> = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(
+        projects.iter().map(|p| &p.project_id).collect::<Vec<_>>(),
+        vec![&project_id]
+    );
+    let res = client
+        .get(format!("http://{addr}/api/projects/{project_id}/users"))
+        .bearer_auth(&read_only)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{}/users",
+            other_project.project_id
+        ))
+        .bearer_auth(&read_only)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // Read-only tokens can't make changes or manage tokens.
+    let res = client
+        .patch(format!("http://{addr}/api/projects/{project_id}"))
+        .bearer_auth(&read_only)
+        .json(&serde_json::json!({"projectId": project_id, "name": "Renamed"}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = client
+        .get(format!("http://{addr}/api/tokens"))
+        .bearer_auth(&read_only)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // Project scoped tokens can't be used outside of their projects.
+    let res = client
+        .post(format!("http://{addr}/api/projects"))
+        .bearer_auth(&read_only)
+        .json(&serde_json::json!({"name": "Escaped"}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    for path in [
+        "profile",
+        "notifiers/preferences",
+        "notifiers/webhook/deliveries",
+    ] {
+        let res = client
+            .get(format!("http://{addr}/api/{path}"))
+            .bearer_auth(&read_only)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{path}");
+    }
+
+    // Read-write tokens may edit any of the user's projects, but still can't manage tokens.
+    let res = client
+        .post(format!("http://{addr}/api/tokens"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "Scripts", "readOnly": false}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let created: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let read_write = created.get("secret").unwrap().as_str().unwrap().to_string();
+    let res = client
+        .patch(format!(
+            "http://{addr}/api/projects/{}",
+            other_project.project_id
+        ))
+        .bearer_auth(&read_write)
+        .json(&serde_json::json!({"projectId": other_project.project_id, "name": "Renamed"}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .post(format!("http://{addr}/api/tokens"))
+        .bearer_auth(&read_write)
+        .json(&serde_json::json!({"name": "Escalated", "readOnly": false}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = client
+        .get(format!("http://{addr}/api/profile"))
+        .bearer_auth(&read_write)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Expired tokens can't be created.
+    let res = client
+        .post(format!("http://{addr}/api/tokens"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "name": "Expired",
+            "readOnly": true,
+            "expiresAt": "2020-01-01T00:00:00Z",
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Rename and list the tokens.
+    let res = client
+        .patch(format!("http://{addr}/api/tokens/{token_id}"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "Nightly CI"}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get(format!("http://{addr}/api/tokens"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let tokens: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let tokens = tokens.as_array().unwrap();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].get("name").unwrap(), "Nightly CI");
+    assert!(tokens[0].get("lastUsedAt").unwrap().is_string());
+    assert!(tokens[0].get("secret").is_none());
+
+    // Recent uses don't rewrite last_used_at.
+    let last_used_at = |pool: &'static PgPool, token_id: String| async move {
+        sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+            "SELECT last_used_at FROM personal_access_tokens WHERE token_id = $1",
+        )
+        .bind(token_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    };
+    let before = last_used_at(pool, token_id.clone()).await;
+    let res = client
+        .get(format!("http://{addr}/api/projects"))
+        .bearer_auth(&read_only)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(last_used_at(pool, token_id.clone()).await, before);
+
+    // Revoked tokens are rejected.
+    let res = client
+        .delete(format!("http://{addr}/api/tokens/{token_id}"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get(format!("http://{addr}/api/projects"))
+        .bearer_auth(&read_only)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn github_settings_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+    sqlx::query(
+        "
+        INSERT INTO plugin_configs (project_id, plugin_id, external_id, settings)
+        VALUES ($1, 'github', '123', '{\"type\":\"github\"}'::jsonb)",
+    )
+    .bind(&project_id)
+    .execute(pool)
+    .await?;
+
+    // Settings default when never set.
+    let res = client
+        .get(format!(
+            "http://{addr}/plugins/github/settings?projectId={project_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let settings: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(settings.pointer("/0/installationId").unwrap(), "123");
+    assert_eq!(settings.pointer("/0/settings/importDrafts").unwrap(), true);
+    assert_eq!(
+        settings.pointer("/0/settings/prStatuses/open").unwrap(),
+        "In Progress"
+    );
+
+    // Invalid settings are rejected.
+    for invalid in [
+        serde_json::json!({"includeRepos": ["koso"]}),
+        serde_json::json!({"repoParents": {"kosolabs/koso": "root"}}),
+        serde_json::json!({"prStatuses": {"approved": "Shipped"}}),
+        serde_json::json!({"twoWaySync": true, "issueParentId": "p1"}),
+    ] {
+        let res = client
+            .put(format!("http://{addr}/plugins/github/settings"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "projectId": project_id,
+                "installationId": "123",
+                "settings": invalid,
+            }))
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{invalid}");
+    }
+
+    // Only connected installations have settings.
+    let res = client
+        .put(format!("http://{addr}/plugins/github/settings"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "projectId": project_id,
+            "installationId": "456",
+            "settings": {},
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let res = client
+        .put(format!("http://{addr}/plugins/github/settings"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "projectId": project_id,
+            "installationId": "123",
+            "settings": {
+                "excludeRepos": ["kosolabs/secret"],
+                "repoParents": {"kosolabs/koso": "p1"},
+                "importDrafts": false,
+                "prStatuses": {"draft": "Not Started", "approved": "Ready"},
+            },
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .get(format!(
+            "http://{addr}/plugins/github/settings?projectId={project_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let settings: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(
+        settings.pointer("/0/settings/excludeRepos/0").unwrap(),
+        "kosolabs/secret"
+    );
+    assert_eq!(
+        settings
+            .pointer("/0/settings/repoParents/kosolabs~1koso")
+            .unwrap(),
+        "p1"
+    );
+    assert_eq!(settings.pointer("/0/settings/importDrafts").unwrap(), false);
+    assert_eq!(
+        settings.pointer("/0/settings/prStatuses/approved").unwrap(),
+        "Ready"
+    );
+    assert_eq!(
+        settings.pointer("/0/settings/prStatuses/open").unwrap(),
+        "In Progress"
+    );
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+/// Serve a webhook endpoint recording the headers and body of every request.
+async fn serve_webhook() -> (String, Arc<Mutex<Vec<(HeaderMap, Bytes)>>>) {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let app = {
+        let requests = requests.clone();
+        axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |headers: HeaderMap, body: Bytes| async move {
+                requests.lock().unwrap().push((headers, body));
+            }),
+        )
+    };
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, requests)
+}
+
+#[test_log::test(sqlx::test)]
+async fn gitlab_plugin_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+    let (gitlab_url, merge_requests) = serve_gitlab().await;
+    let mr_url = |iid: u32| format!("{gitlab_url}/kosolabs/koso/-/merge_requests/{iid}");
+    merge_requests.lock().unwrap().push(serde_json::json!({
+        "title": "Polled MR",
+        "description": null,
+        "state": "opened",
+        "web_url": mr_url(1),
+    }));
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    setup_project(&client, &addr, &token, &claims, pool).await;
+    let project_id = {
+        let create_req = CreateProject {
+            name: "GitLab project".to_string(),
+            project_export: Some(ProjectExport {
+                version: EXPORT_VERSION,
+                project_id: "unused".to_string(),
+                graph: HashMap::from([(
+                    "root".to_string(),
+                    Task {
+                        id: "root".to_string(),
+                        num: "0".to_string(),
+                        name: "root".to_string(),
+                        ..Task::default()
+                    },
+                )]),
+            }),
+        };
+        let res = client
+            .post(format!("http://{addr}/api/projects"))
+            .bearer_auth(&token)
+            .json(&create_req)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        res.json::<Project>().await.unwrap().project_id
+    };
+
+    // Invalid connections are rejected.
+    for (invalid, code) in [
+        (
+            serde_json::json!({"baseUrl": "gitlab", "accessToken": "glpat-test", "projects": ["kosolabs/koso"]}),
+            "INVALID_URL",
+        ),
+        (
+            serde_json::json!({"baseUrl": gitlab_url, "accessToken": "glpat-test", "projects": ["koso"]}),
+            "INVALID_PROJECTS",
+        ),
+        (
+            serde_json::json!({"baseUrl": gitlab_url, "accessToken": "glpat-wrong", "projects": ["kosolabs/koso"]}),
+            "INVALID_TOKEN",
+        ),
+        (
+            serde_json::json!({"baseUrl": gitlab_url, "accessToken": "glpat-test", "projects": ["kosolabs/other"]}),
+            "INVALID_PROJECT",
+        ),
+    ] {
+        let mut request = invalid.clone();
+        request["projectId"] = project_id.clone().into();
+        let res = client
+            .post(format!("http://{addr}/plugins/gitlab/connect"))
+            .bearer_auth(&token)
+            .json(&request)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{invalid}");
+        assert!(res.text().await.unwrap().contains(code), "{invalid}");
+    }
+
+    let res = client
+        .post(format!("http://{addr}/plugins/gitlab/connect"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "projectId": project_id,
+            "baseUrl": format!("{gitlab_url}/"),
+            "accessToken": "glpat-test",
+            "projects": ["KosoLabs/Koso"],
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let connection: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    let connection_id = connection
+        .pointer("/connection/connectionId")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string();
+    let webhook_token = connection
+        .pointer("/webhookToken")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(
+        connection
+            .pointer("/webhookUrl")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .ends_with(&format!("/plugins/gitlab/webhook/{connection_id}"))
+    );
+
+    // Connections don't reveal their tokens.
+    let res = client
+        .get(format!(
+            "http://{addr}/plugins/gitlab/connections?projectId={project_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.text().await.unwrap();
+    assert!(!body.contains("glpat-test"));
+    assert!(!body.contains(&webhook_token));
+    let connections: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(connections.pointer("/0/baseUrl").unwrap(), &gitlab_url);
+    assert_eq!(
+        connections.pointer("/0/projects/0").unwrap(),
+        "kosolabs/koso"
+    );
+
+    // Polling imports open merge requests.
+    let res = client
+        .post(format!("http://{addr}/plugins/gitlab/poll"))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let polled = wait_for_task_status(
+        &client,
+        &addr,
+        &token,
+        &project_id,
+        &mr_url(1),
+        "In Progress",
+    )
+    .await;
+    assert_eq!(polled.kind.as_deref(), Some("gitlab_mr"));
+
+    // Webhook events must carry the connection's token.
+    let event = serde_json::json!({
+        "object_kind": "merge_request",
+        "project": {"path_with_namespace": "kosolabs/koso"},
+        "object_attributes": {
+            "title": "Pushed MR",
+            "description": "Implements a feature",
+            "state": "opened",
+            "url": mr_url(2),
+        },
+    });
+    let res = client
+        .post(format!(
+            "http://{addr}/plugins/gitlab/webhook/{connection_id}"
+        ))
+        .header("X-Gitlab-Event", "Merge Request Hook")
+        .header("X-Gitlab-Token", "wrong")
+        .json(&event)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = client
+        .post(format!(
+            "http://{addr}/plugins/gitlab/webhook/{connection_id}"
+        ))
+        .header("X-Gitlab-Event", "Merge Request Hook")
+        .header("X-Gitlab-Token", &webhook_token)
+        .json(&event)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let pushed = wait_for_task_status(
+        &client,
+        &addr,
+        &token,
+        &project_id,
+        &mr_url(2),
+        "In Progress",
+    )
+    .await;
+    assert_eq!(pushed.name, "Pushed MR");
+
+    // Merge requests no longer open are resolved on the next poll.
+    merge_requests.lock().unwrap().clear();
+    let res = client
+        .post(format!("http://{addr}/plugins/gitlab/poll"))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    for iid in [1, 2] {
+        wait_for_task_status(&client, &addr, &token, &project_id, &mr_url(iid), "Done").await;
+    }
+
+    let res = client
+        .delete(format!(
+            "http://{addr}/plugins/gitlab/connections/{connection_id}?projectId={project_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .delete(format!(
+            "http://{addr}/plugins/gitlab/connections/{connection_id}?projectId={project_id}"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+/// Serve the parts of the GitLab API the plugin uses, for the kosolabs/koso project only.
+/// Returns the instance's URL and the merge requests to list.
+async fn serve_gitlab() -> (String, Arc<Mutex<Vec<Value>>>) {
+    use axum::{extract::Path, response::IntoResponse, routing::get};
+
+    const TOKEN: &str = "glpat-test";
+    let merge_requests = Arc::new(Mutex::new(vec![]));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let authorized = |headers: &HeaderMap| {
+        headers
+            .get("PRIVATE-TOKEN")
+            .is_some_and(|token| token == TOKEN)
+    };
+    let app = {
+        let merge_requests = merge_requests.clone();
+        axum::Router::new()
+            .route(
+                "/api/v4/user",
+                get(move |headers: HeaderMap| async move {
+                    if !authorized(&headers) {
+                        return StatusCode::UNAUTHORIZED.into_response();
+                    }
+                    axum::Json(serde_json::json!({"username": "koso"})).into_response()
+                }),
+            )
+            .route(
+                "/api/v4/projects/{path}",
+                get(
+                    move |headers: HeaderMap, Path(path): Path<String>| async move {
+                        if !authorized(&headers) || !path.eq_ignore_ascii_case("kosolabs/koso") {
+                            return StatusCode::NOT_FOUND.into_response();
+                        }
+                        axum::Json(serde_json::json!({"path_with_namespace": "kosolabs/koso"}))
+                            .into_response()
+                    },
+                ),
+            )
+            .route(
+                "/api/v4/projects/{path}/merge_requests",
+                get(
+                    move |headers: HeaderMap, Path(path): Path<String>| async move {
+                        if !authorized(&headers) || path != "kosolabs/koso" {
+                            return StatusCode::NOT_FOUND.into_response();
+                        }
+                        let merge_requests = merge_requests.lock().unwrap().clone();
+                        ([("x-next-page", "")], axum::Json(merge_requests)).into_response()
+                    },
+                ),
+            )
+    };
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, merge_requests)
+}
+
+/// Wait for the exported project to contain the task with the given URL and status.
+async fn wait_for_task_status(
+    client: &Client,
+    addr: &SocketAddr,
+    token: &str,
+    project_id: &str,
+    url: &str,
+    status: &str,
+) -> Task {
+    let mut task = None;
+    for _ in 0..50 {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}/export"))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        task = res
+            .json::<ProjectExport>()
+            .await
+            .unwrap()
+            .graph
+            .into_values()
+            .find(|task| task.url.as_deref() == Some(url));
+        if task
+            .as_ref()
+            .is_some_and(|task| task.status.as_deref() == Some(status))
+        {
+            return task.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Task {url} never reached status {status}: {task:?}");
+}
+
+#[test_log::test(sqlx::test)]
+async fn jira_import_export_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    setup_project(&client, &addr, &token, &claims, pool).await;
+
+    let data = serde_json::json!({
+        "issues": [{
+            "key": "KOSO-1",
+            "fields": {
+                "summary": "Migrate to Koso",
+                "status": {"name": "In Progress"},
+                "assignee": {"emailAddress": claims.email, "displayName": "Valid User"},
+                "customfield_10016": 8,
+            },
+        }, {
+            "key": "KOSO-2",
+            "fields": {
+                "summary": "Export from Jira",
+                "status": {"name": "Waiting"},
+                "assignee": {"emailAddress": "stranger@example.com", "displayName": "Stranger"},
+                "parent": {"key": "KOSO-1"},
+            },
+        }],
+    })
+    .to_string();
+
+    // Invalid imports are rejected.
+    for (invalid, code) in [
+        (
+            serde_json::json!({"name": "Jira", "format": "json", "data": "{}"}),
+            "INVALID_EXPORT",
+        ),
+        (
+            serde_json::json!({"name": "Jira", "format": "csv", "data": "Key\nKOSO-1"}),
+            "INVALID_EXPORT",
+        ),
+        (
+            serde_json::json!({"name": "Jira", "format": "json", "data": data, "statuses": {"Waiting": "Waiting"}}),
+            "INVALID_STATUS_MAPPING",
+        ),
+    ] {
+        let res = client
+            .post(format!("http://{addr}/api/projects/import/jira"))
+            .bearer_auth(&token)
+            .json(&invalid)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{invalid}");
+        assert!(res.text().await.unwrap().contains(code), "{invalid}");
+    }
+
+    // Dry runs report without creating a project.
+    let res = client
+        .post(format!("http://{addr}/api/projects/import/jira"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "Jira", "format": "json", "data": data, "dryRun": true}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let response: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(response.pointer("/project").unwrap(), &Value::Null);
+    assert_eq!(response.pointer("/report/tasks").unwrap(), 2);
+    assert_eq!(
+        response.pointer("/report/unknownUsers/0").unwrap(),
+        "Stranger"
+    );
+    assert_eq!(
+        response.pointer("/report/unknownStatuses/0").unwrap(),
+        "Waiting"
+    );
+
+    let res = client
+        .post(format!("http://{addr}/api/projects/import/jira"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "name": "Jira",
+            "format": "json",
+            "data": data,
+            "statuses": {"Waiting": "Blocked"},
+            "baseUrl": "https://example.atlassian.net",
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let response: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
+    assert_eq!(
+        response.pointer("/report/unknownStatuses").unwrap(),
+        &serde_json::json!([])
+    );
+    let project_id = response
+        .pointer("/project/projectId")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = client
+        .get(format!("http://{addr}/api/projects/{project_id}/export"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let graph = res.json::<ProjectExport>().await.unwrap().graph;
+    assert_eq!(graph.len(), 3);
+    let epic = graph
+        .values()
+        .find(|task| task.name == "Migrate to Koso")
+        .unwrap();
+    assert_eq!(graph["root"].children, vec![epic.id.clone()]);
+    assert_eq!(epic.assignee.as_deref(), Some(claims.email.as_str()));
+    assert_eq!(epic.status.as_deref(), Some("In Progress"));
+    assert_eq!(epic.estimate, Some(8));
+    assert_eq!(
+        epic.url.as_deref(),
+        Some("https://example.atlassian.net/browse/KOSO-1")
+    );
+    let story = &graph[&epic.children[0]];
+    assert_eq!(story.name, "Export from Jira");
+    assert_eq!(story.assignee, None);
+    assert_eq!(story.status.as_deref(), Some("Blocked"));
+
+    let res = client
+        .get(format!(
+            "http://{addr}/api/projects/{project_id}/export/jira"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    assert_eq!(
+        res.text().await.unwrap(),
+        format!(
+            "\
+Issue id,Parent id,Summary,Issue Type,Status,Assignee,Reporter,Description,Custom field (Story Points),Due Date
+{},,Migrate to Koso,Epic,In Progress,{},,,8,
+{},{},Export from Jira,Task,Blocked,,,,,
+",
+            epic.num, claims.email, story.num, epic.num
+        )
+    );
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn export_formats_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    setup_project(&client, &addr, &token, &claims, pool).await;
+    let task = |id: &str, name: &str, children: &[&str], status: Option<&str>| {
+        (
+            id.to_string(),
+            Task {
+                id: id.to_string(),
+                num: if id == "root" {
+                    "0"
+                } else {
+                    id.trim_start_matches('t')
+                }
+                .to_string(),
+                name: name.to_string(),
+                children: children.iter().map(|c| c.to_string()).collect(),
+                status: status.map(|s| s.to_string()),
+                assignee: Some(claims.email.clone()),
+                ..Task::default()
+            },
+        )
+    };
+    let create_req = CreateProject {
+        name: "Export project".to_string(),
+        project_export: Some(ProjectExport {
+            version: EXPORT_VERSION,
+            project_id: "unused".to_string(),
+            graph: HashMap::from([
+                task("root", "root", &["t1"], None),
+                task("t1", "Launch", &["t2", "t3"], None),
+                task("t2", "Design", &[], Some("Done")),
+                task("t3", "Build", &[], Some("In Progress")),
+            ]),
+        }),
+    };
+    let res = client
+        .post(format!("http://{addr}/api/projects"))
+        .bearer_auth(&token)
+        .json(&create_req)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let project_id = res.json::<Project>().await.unwrap().project_id;
+
+    let export = |query: &str| {
+        client
+            .get(format!(
+                "http://{addr}/api/projects/{project_id}/export?{query}"
+            ))
+            .bearer_auth(&token)
+            .send()
+    };
+
+    let res = export("format=csv").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    assert_eq!(
+        res.text().await.unwrap(),
+        format!(
+            "\
+path,num,name,status,assignee,estimate,deadline
+,1,Launch,In Progress,{0},,
+Launch,2,Design,Done,{0},,
+Launch,3,Build,In Progress,{0},,
+",
+            claims.email
+        )
+    );
+
+    let res = export("format=markdown&taskId=t1")
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/markdown; charset=utf-8"
+    );
+    assert_eq!(
+        res.text().await.unwrap(),
+        format!(
+            "\
+- [ ] #1 Launch (In Progress, {0})
+  - [x] #2 Design (Done, {0})
+  - [ ] #3 Build (In Progress, {0})
+",
+            claims.email
+        )
+    );
+
+    let res = export("taskId=t3").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let graph = res.json::<ProjectExport>().await.unwrap().graph;
+    assert_eq!(graph.len(), 2);
+    assert_eq!(graph["root"].children, vec!["t3"]);
+
+    let res = export("taskId=missing")
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = export("format=pdf").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn import_validation_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    setup_project(&client, &addr, &token, &claims, pool).await;
+    let task = |id: &str, children: &[&str]| {
+        serde_json::json!({
+            "id": id,
+            "num": if id == "root" { "0" } else { id.trim_start_matches('t') },
+            "name": format!("Task {id}"),
+            "children": children,
+        })
+    };
+
+    // Invalid graphs are rejected, and a dry run explains why.
+    let invalid = serde_json::json!({
+        "projectId": "unused",
+        "graph": {
+            "root": task("root", &["t1"]),
+            "t1": task("t1", &["t2", "t9"]),
+            "t2": task("t2", &["t1"]),
+        },
+    });
+    let res = client
+        .post(format!("http://{addr}/api/projects"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "Invalid", "projectExport": invalid}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert!(res.text().await.unwrap().contains("INVALID_IMPORT"));
+
+    let res = client
+        .post(format!("http://{addr}/api/projects/import/validate"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"projectExport": invalid}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let report = res.json::<Value>().await.unwrap();
+    assert_eq!(report["valid"], false);
+    let codes: Vec<&str> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, vec!["DANGLING_CHILD", "CYCLE"]);
+
+    // Exports written before versioning, without a version, still import.
+    let legacy = serde_json::json!({
+        "projectId": "unused",
+        "graph": {
+            "root": task("root", &["t1"]),
+            "t1": task("t1", &["t2"]),
+            "t2": task("t2", &[]),
+        },
+    });
+    let res = client
+        .post(format!("http://{addr}/api/projects"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "Legacy", "projectExport": legacy}))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let project_id = res.json::<Project>().await.unwrap().project_id;
+
+    let res = client
+        .post(format!("http://{addr}/api/projects"))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "name": "Future",
+            "projectExport": {"version": EXPORT_VERSION + 1, "projectId": "unused", "graph": {}},
+        }))
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert!(res.status().is_client_error());
+
+    // Import the project's own tasks as a new subtree, with fresh ids and nums.
+    let import = |dry_run: bool| {
+        client
+            .post(format!("http://{addr}/api/projects/{project_id}/import"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({
+                "projectExport": legacy,
+                "parentId": "t1",
+                "dryRun": dry_run,
+            }))
+            .send()
+    };
+    let res = import(true).await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.json::<Value>().await.unwrap();
+    assert_eq!(body["report"]["valid"], true);
+    assert_eq!(body["report"]["tasks"], 2);
+    assert_eq!(body["taskIds"], serde_json::json!([]));
+
+    let res = import(false).await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.json::<Value>().await.unwrap();
+    let new_id = body["taskIds"][0].as_str().unwrap().to_string();
+
+    let mut graph = HashMap::new();
+    for _ in 0..50 {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}/export"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        graph = res.json::<ProjectExport>().await.unwrap().graph;
+        if graph.len() == 5 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(graph.len(), 5);
+    assert_eq!(graph["t1"].children, vec!["t2".to_string(), new_id.clone()]);
+    let copy = &graph[&new_id];
+    assert_eq!(copy.name, "Task t1");
+    assert_eq!(copy.num, "3");
+    assert_eq!(copy.children.len(), 1);
+    let copy_child = &graph[&copy.children[0]];
+    assert_eq!(copy_child.name, "Task t2");
+    assert_eq!(copy_child.num, "4");
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
+    Ok(())
+}
+
+#[test_log::test(sqlx::test)]
+async fn integrity_test(pool: PgPool) -> sqlx::Result<()> {
+    let pool_wrapper = UnsafePoolWrapper::wrap(pool);
+    let pool = pool_wrapper.pool;
+    let (mut server, addr) = start_server(pool).await;
+    let client = Client::default();
+
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
+
+    // Persist a malformed graph, which imports would reject.
+    let ydoc = YDocProxy::new();
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        for (id, num, children) in [
+            ("root", "0", vec!["a", "missing"]),
+            ("a", "1", vec![]),
+            ("b", "1", vec![]),
+        ] {
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: id.to_string(),
+                    num: num.to_string(),
+                    name: format!("Task {id}"),
+                    children: children.into_iter().map(|c| c.to_string()).collect(),
+                    ..Task::default()
+                },
+            );
+        }
+        txn.encode_update_v2()
+    };
+    sqlx::query("INSERT INTO yupdates (project_id, seq, update_v2) VALUES ($1, DEFAULT, $2)")
+        .bind(&project_id)
+        .bind(update)
+        .execute(pool)
+        .await?;
+
+    let check = || async {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}/integrity"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        assert_eq!(res.status(), StatusCode::OK);
+        res.json::<Value>().await.unwrap()
+    };
+    let codes = |report: &Value| -> Vec<String> {
+        report["violations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["code"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let report = check().await;
+    assert_eq!(
+        codes(&report),
+        vec!["DANGLING_CHILD", "ORPHANED_TASK", "DUPLICATE_NUM"]
+    );
+    assert_eq!(report["repaired"], false);
+
+    let res = client
+        .post(format!(
+            "http://{addr}/api/projects/{project_id}/integrity/repair"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let report = res.json::<Value>().await.unwrap();
+    assert_eq!(codes(&report).len(), 3);
+    assert_eq!(report["repaired"], true);
+
+    assert!(codes(&check().await).is_empty());
+    let mut graph = HashMap::new();
+    for _ in 0..50 {
+        let res = client
+            .get(format!("http://{addr}/api/projects/{project_id}/export"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("Failed to send request.");
+        graph = res.json::<ProjectExport>().await.unwrap().graph;
+        if graph["b"].num == "2" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(graph["root"].children, vec!["a", "b"]);
+    assert_eq!(graph["b"].num, "2");
+
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
     Ok(())
 }
 
 #[test_log::test(sqlx::test)]
-async fn not_invite_user(pool: PgPool) -> sqlx::Result<()> {
+async fn dependencies_test(pool: PgPool) -> sqlx::Result<()> {
     let pool_wrapper = UnsafePoolWrapper::wrap(pool);
     let pool = pool_wrapper.pool;
-    let (server, addr) = start_server(pool).await;
+    let (mut server, addr) = start_server(pool).await;
     let client = Client::default();
 
     let claims = Claims::default();
     let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
 
-    // Log in a user
-    {
-        let res = client
-            .post(format!("http://{addr}/api/auth/login"))
+    let ydoc = YDocProxy::new();
+    let update = {
+        let mut txn = ydoc.transact_mut_with(origin());
+        for (id, num, children, status) in [
+            ("root", "0", vec!["a", "b", "c"], None),
+            ("a", "1", vec![], Some("In Progress")),
+            ("b", "2", vec![], Some("Blocked")),
+            ("c", "3", vec!["a"], None),
+        ] {
+            ydoc.set(
+                &mut txn,
+                &Task {
+                    id: id.to_string(),
+                    num: num.to_string(),
+                    name: format!("Task {id}"),
+                    children: children.into_iter().map(|c| c.to_string()).collect(),
+                    status: status.map(str::to_string),
+                    assignee: Some(claims.email.clone()),
+                    ..Task::default()
+                },
+            );
+        }
+        txn.encode_update_v2()
+    };
+    sqlx::query("INSERT INTO yupdates (project_id, seq, update_v2) VALUES ($1, DEFAULT, $2)")
+        .bind(&project_id)
+        .bind(update)
+        .execute(pool)
+        .await?;
+
+    let add = |task_id: &'static str, blocker_id: &'static str| {
+        client
+            .post(format!(
+                "http://{addr}/api/projects/{project_id}/tasks/{task_id}/dependencies"
+            ))
             .bearer_auth(&token)
+            .json(&serde_json::json!({ "blockerId": blocker_id }))
             .send()
-            .await
-            .expect("Failed to send request.");
-        assert_eq!(res.status(), StatusCode::OK);
     };
 
-    // Check that list users rejects the non-premium user.
-    {
+    let res = add("b", "a").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let task = res.json::<Task>().await.unwrap();
+    assert_eq!(task.blocked_by, vec!["a"]);
+
+    // Duplicates and tasks waiting on each other are rejected.
+    let res = add("b", "a").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = add("a", "c").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = add("b", "missing").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = add("root", "a").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let remove = |task_id: &'static str, blocker_id: &'static str| {
+        client
+            .delete(format!(
+                "http://{addr}/api/projects/{project_id}/tasks/{task_id}/dependencies/{blocker_id}"
+            ))
+            .bearer_auth(&token)
+            .send()
+    };
+    let res = remove("b", "c").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = remove("b", "a").await.expect("Failed to send request.");
+    assert_eq!(res.status(), StatusCode::OK);
+    let task = res.json::<Task>().await.unwrap();
+    assert!(task.blocked_by.is_empty());
+
+    // Removing the last incomplete blocker unblocks the task.
+    let mut graph = HashMap::new();
+    for _ in 0..50 {
         let res = client
-            .get(format!("http://{addr}/api/users"))
+            .get(format!("http://{addr}/api/projects/{project_id}/export"))
             .bearer_auth(&token)
-            .header("Content-Type", "application/json")
             .send()
             .await
             .expect("Failed to send request.");
-        assert_not_premium(res).await;
+        graph = res.json::<ProjectExport>().await.unwrap().graph;
+        if graph["b"].status.as_deref() == Some("Not Started") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
+    assert_eq!(graph["b"].status.as_deref(), Some("Not Started"));
+    assert!(graph["b"].blocked_by.is_empty());
 
-    server.shutdown_and_wait().await.unwrap();
+    server.start_shutdown().await;
+    server.wait_for_shutdown().await.unwrap();
     Ok(())
 }
 
-async fn assert_not_premium(res: Response) {
-    assert_eq!(res.status(), StatusCode::FORBIDDEN);
-    let error: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
-    let error = error.as_object().unwrap();
-    assert_eq!(error.get("status").unwrap().as_i64().unwrap(), 403);
-    assert_eq!(error.get("title").unwrap().as_str().unwrap(), "NOT_PREMIUM");
-    assert!(!error.get("detail").unwrap().as_str().unwrap().is_empty());
-}
-
 #[test_log::test(sqlx::test)]
-async fn create_and_delete_project(pool: PgPool) -> sqlx::Result<()> {
+async fn cluster_test(pool: PgPool) -> sqlx::Result<()> {
     let pool_wrapper = UnsafePoolWrapper::wrap(pool);
     let pool = pool_wrapper.pool;
-    let (mut server, addr) = start_server(pool).await;
+    // Run two servers against the same database.
+    let (mut server_a, addr_a) = start_server(pool).await;
+    let (mut server_b, addr_b) = start_server(pool).await;
     let client = Client::default();
 
-    let token = login(&client, &addr, pool).await.unwrap();
+    let claims = Claims::default();
+    let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
+    let project_id = setup_project(&client, &addr_a, &token, &claims, pool).await;
+
+    let (mut socket_a, ydoc_a) = connect_and_sync(&addr_a, &project_id, &token).await;
+    let socket_a = &mut socket_a;
+    let (mut socket_b, ydoc_b) = connect_and_sync(&addr_b, &project_id, &token).await;
+    let socket_b = &mut socket_b;
+
+    // Updates applied on one server are broadcast to clients of the other.
+    for i in 0..2 {
+        let (from_socket, from_doc, to_socket, to_doc) = if i == 0 {
+            (&mut *socket_a, &ydoc_a, &mut *socket_b, &ydoc_b)
+        } else {
+            (&mut *socket_b, &ydoc_b, &mut *socket_a, &ydoc_a)
+        };
+        let update = {
+            let mut txn = from_doc.transact_mut_with(origin());
+            from_doc.set(
+                &mut txn,
+                &Task {
+                    id: format!("id{i}"),
+                    num: format!("{i}"),
+                    name: format!("Task {i}"),
+                    ..Task::default()
+                },
+            );
+            txn.encode_update_v2()
+        };
+        from_socket
+            .send(Message::binary(msg_sync::sync_update(&update)))
+            .await
+            .unwrap();
 
-    let project = create_project(&client, &addr, &token, "A Project to Delete")
-        .await
-        .unwrap();
-    assert_eq!(project.name, "A Project to Delete");
-    assert_eq!(project.deleted_on, None);
+        let sync_update = read_sync_update(to_socket).await;
+        to_doc
+            .transact_mut_with(origin())
+            .apply_update(sync_update)
+            .unwrap();
+        assert_eq!(
+            from_doc.to_graph(&from_doc.transact()).unwrap(),
+            to_doc.to_graph(&to_doc.transact()).unwrap()
+        );
+    }
 
-    let project = delete_project(&client, &addr, &token, &project.project_id)
+    // Awareness is forwarded too.
+    let mut encoder = EncoderV1::new();
+    encoder.write_var(MSG_KOSO_AWARENESS);
+    encoder.write_var(MSG_KOSO_AWARENESS_UPDATE);
+    encoder.write_string(r#"{"clientId":1,"sequence":1,"selected":["id0"]}"#);
+    socket_a
+        .send(Message::binary(encoder.to_vec()))
         .await
         .unwrap();
-    assert_eq!(project.name, "A Project to Delete");
-    assert_ne!(project.deleted_on, None);
-
-    server.start_shutdown().await;
-    server.wait_for_shutdown().await.unwrap();
-    Ok(())
-}
-
-async fn setup_project(
-    client: &Client,
-    addr: &SocketAddr,
-    token: &str,
-    claims: &Claims,
-    pool: &PgPool,
-) -> String {
-    // Log in and share the project
-    let res = client
-        .post(format!("http://{addr}/api/auth/login"))
-        .bearer_auth(token)
-        .send()
-        .await
-        .expect("Failed to send request.");
-    assert_eq!(res.status(), StatusCode::OK);
-    set_user_premium(&claims.email, pool).await.unwrap();
-
-    let project_id = {
-        let res = client
-            .post(format!("http://{addr}/api/projects"))
-            .bearer_auth(token)
-            .header("Content-Type", "application/json")
-            .body("{\"name\":\"Test Project\"}")
-            .send()
-            .await
-            .expect("Failed to send request.");
-        assert_eq!(res.status(), StatusCode::OK);
-        let project: Value = serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
-        let project = project.as_object().unwrap();
-        project
-            .get("projectId")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string()
-    };
+    assert_eq!(read_awareness_state(socket_a).await.len(), 1);
+    let awareness = read_awareness_state(socket_b).await;
+    assert_eq!(awareness.len(), 1);
+    let awareness = serde_json::to_value(&awareness[0]).unwrap();
+    assert_eq!(
+        awareness.get("selected").unwrap(),
+        &serde_json::json!(["id0"])
+    );
+    assert_eq!(
+        awareness.pointer("/user/email").unwrap().as_str().unwrap(),
+        claims.email
+    );
 
-    let res = client
-        .patch(format!("http://{addr}/api/projects/{project_id}/users"))
-        .bearer_auth(token)
-        .header("Content-Type", "application/json")
-        .body(format!(
-            "{{\"projectId\":\"{project_id}\", \"addEmails\":[\"{}\"], \"removeEmails\":[]}}",
-            claims.email
-        ))
-        .send()
-        .await
-        .expect("Failed to send request.");
-    assert_eq!(res.status(), StatusCode::OK);
+    // Leaving clears the awareness on the other server.
+    close_socket(socket_a).await;
+    assert!(read_awareness_state(socket_b).await.is_empty());
+    close_socket(socket_b).await;
 
-    project_id
+    server_a.start_shutdown().await;
+    server_b.start_shutdown().await;
+    server_a.wait_for_shutdown().await.unwrap();
+    server_b.wait_for_shutdown().await.unwrap();
+    Ok(())
 }
 
 #[test_log::test(sqlx::test)]
-async fn ws_test(pool: PgPool) -> sqlx::Result<()> {
+async fn cluster_reconnect_test(pool: PgPool) -> sqlx::Result<()> {
     let pool_wrapper = UnsafePoolWrapper::wrap(pool);
     let pool = pool_wrapper.pool;
     let (mut server, addr) = start_server(pool).await;
@@ -398,84 +3343,66 @@ async fn ws_test(pool: PgPool) -> sqlx::Result<()> {
 
     let claims = Claims::default();
     let token: String = encode_token(&claims, KID_1, PEM_1).unwrap();
-
-    // Log in and share the project
     let project_id = setup_project(&client, &addr, &token, &claims, pool).await;
 
-    // Test that unauthenticated users are rejected.
-    {
-        let req = format!("ws://{addr}/api/ws/projects/{project_id}")
-            .into_client_request()
-            .unwrap();
-        let err = tokio_tungstenite::connect_async(req).await.unwrap_err();
-        let tokio_tungstenite::tungstenite::error::Error::Http(response) = err else {
-            panic!("Expected http error, got: {err:?}");
-        };
-        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
-    }
-
-    // Test that authenticated but unauthorized users are rejected.
-    {
-        let mut req = format!("ws://{addr}/api/ws/projects/{project_id}")
-            .into_client_request()
-            .unwrap();
-        let claims = Claims {
-            email: "unauthorized-users@koso.app".to_string(),
-            ..Default::default()
-        };
-        let token = encode_token(&claims, KID_1, PEM_1).unwrap();
-        req.headers_mut().insert(
-            "Sec-Websocket-Protocol",
-            HeaderValue::from_str(format!("bearer, {token}").as_str()).unwrap(),
-        );
-        let (mut socket, response) = tokio_tungstenite::connect_async(req).await.unwrap();
-        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
-        let close = next_with_timeout(&mut socket).await.unwrap().unwrap();
-        let Message::Close(Some(close)) = close else {
-            panic!("Expected close frame, got: {close:?}");
-        };
-        assert_eq!(close.code, CloseCode::Iana(3000));
-        assert_eq!(close.reason, "Unauthorized.");
-        futures::SinkExt::close(&mut socket).await.unwrap();
-        assert!(next_with_timeout(&mut socket).await.unwrap().is_none());
-        assert!(socket.is_terminated());
-    }
+    let (mut socket, ydoc) = connect_and_sync(&addr, &project_id, &token).await;
+    let socket = &mut socket;
 
-    // Test opening and closing sockets.
-    {
-        let mut req = format!("ws://{addr}/api/ws/projects/{project_id}")
-            .into_client_request()
-            .unwrap();
-        req.headers_mut().insert(
-            "Sec-Websocket-Protocol",
-            HeaderValue::from_str(
-                format!("bearer, {token}, koso-client-version, testversion").as_str(),
+    // Persist an update as another node would, without notifying the cluster,
+    // as if the notification was published while the listener was disconnected.
+    let other_doc = YDocProxy::new();
+    let update = {
+        let mut txn = other_doc.transact_mut_with(origin());
+        txn.apply_update(
+            Update::decode_v2(
+                &ydoc
+                    .transact()
+                    .encode_state_as_update_v2(&StateVector::default()),
             )
             .unwrap(),
+        )
+        .unwrap();
+        other_doc.set(
+            &mut txn,
+            &Task {
+                id: "id1".to_string(),
+                num: "1".to_string(),
+                name: "Task 1".to_string(),
+                ..Task::default()
+            },
         );
+        txn.encode_update_v2()
+    };
+    sqlx::query("INSERT INTO yupdates (project_id, seq, update_v2) VALUES ($1, DEFAULT, $2)")
+        .bind(&project_id)
+        .bind(&update)
+        .execute(pool)
+        .await?;
+
+    // Drop the listener connection. Once reconnected, the node catches up on the update.
+    let (terminated,): (i64,) = sqlx::query_as(
+        "
+        SELECT COUNT(pg_terminate_backend(pid))
+        FROM pg_stat_activity
+        WHERE datname = current_database()
+        AND query LIKE 'LISTEN%'",
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(terminated, 1);
 
-        let (mut socket1, response) = tokio_tungstenite::connect_async(req.clone()).await.unwrap();
-        let socket1 = &mut socket1;
-        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
-        assert_eq!(read_sync_request(socket1).await, StateVector::default());
-        let (mut socket2, response) = tokio_tungstenite::connect_async(req.clone()).await.unwrap();
-        let socket2 = &mut socket2;
-        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
-        assert_eq!(read_sync_request(socket2).await, StateVector::default());
-
-        // Closing one socket should trigger awareness broadcasts to others.
-        close_socket(socket2).await;
-        read_awareness_state(socket1).await;
-        close_socket(socket1).await;
-
-        // Abruptly close the socket, this'll trigger the error handling in ClientMessageReceiver
-        let (_socket, response) = tokio_tungstenite::connect_async(req.clone()).await.unwrap();
-        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
-    }
+    let sync_update = read_sync_update(socket).await;
+    ydoc.transact_mut_with(origin())
+        .apply_update(sync_update)
+        .unwrap();
+    assert_eq!(
+        ydoc.to_graph(&ydoc.transact()).unwrap(),
+        other_doc.to_graph(&other_doc.transact()).unwrap()
+    );
 
+    close_socket(socket).await;
     server.start_shutdown().await;
     server.wait_for_shutdown().await.unwrap();
-
     Ok(())
 }
 
@@ -630,10 +3557,22 @@ async fn ws_test_full(pool: PgPool) -> sqlx::Result<()> {
             .await
             .expect("Failed to send request.");
         assert_eq!(res.status(), StatusCode::OK);
-        let export: ProjectExport =
+        let mut export: ProjectExport =
             serde_json::from_str(res.text().await.unwrap().as_str()).unwrap();
         assert_eq!(export.project_id, project_id);
 
+        // Imports require a root.
+        export.graph.insert(
+            "root".to_string(),
+            Task {
+                id: "root".to_string(),
+                num: "0".to_string(),
+                name: "Root".to_string(),
+                children: vec!["id1".to_string()],
+                ..Task::default()
+            },
+        );
+
         let create_req = CreateProject {
             name: "Imported project".to_string(),
             project_export: Some(export),
@@ -967,6 +3906,44 @@ async fn plugin_test(pool: PgPool) -> Result<()> {
     Ok(())
 }
 
+/// Connect to the project and sync its current state into a new doc.
+async fn connect_and_sync(addr: &SocketAddr, project_id: &str, token: &str) -> (Socket, YDocProxy) {
+    let mut req = format!("ws://{addr}/api/ws/projects/{project_id}")
+        .into_client_request()
+        .unwrap();
+    req.headers_mut().insert(
+        "Sec-Websocket-Protocol",
+        HeaderValue::from_str(
+            format!("bearer, {token}, koso-client-version, testversion").as_str(),
+        )
+        .unwrap(),
+    );
+    let (mut socket, response) = tokio_tungstenite::connect_async(req).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+    // Sync the initial state of the project.
+    let ydoc = YDocProxy::new();
+    read_sync_request(&mut socket).await;
+    socket
+        .send(Message::binary(msg_sync::sync_request(
+            &ydoc.transact().state_vector(),
+        )))
+        .await
+        .unwrap();
+    let sync_response = read_sync_response(&mut socket).await;
+    ydoc.transact_mut_with(origin())
+        .apply_update(sync_response)
+        .unwrap();
+    socket
+        .send(Message::binary(msg_sync::sync_response(
+            &Update::default().encode_v2(),
+        )))
+        .await
+        .unwrap();
+
+    (socket, ydoc)
+}
+
 async fn read_sync_request(socket: &mut Socket) -> StateVector {
     let sync_request = next_with_timeout(socket).await.unwrap().unwrap();
 
@@ -1499,7 +4476,7 @@ async fn dupes_api_test(pool: PgPool) -> sqlx::Result<()> {
         assert_eq!(res.status(), StatusCode::FORBIDDEN);
     }
 
-    drop(server);
+    server.shutdown_and_wait().await.unwrap();
     Ok(())
 }
 
@@ -1746,6 +4723,6 @@ async fn individual_dupe_api_test(pool: PgPool) -> sqlx::Result<()> {
         assert_eq!(res.status(), StatusCode::FORBIDDEN);
     }
 
-    drop(server);
+    server.shutdown_and_wait().await.unwrap();
     Ok(())
 }
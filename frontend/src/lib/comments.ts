@@ -0,0 +1,54 @@
+import { headers, parseResponse } from "$lib/api";
+import type { AuthContext } from "./auth.svelte";
+
+export type TaskComment = {
+  commentId: string;
+  projectId: string;
+  taskId: string;
+  email: string;
+  body: string;
+  createdAt: string;
+};
+
+export async function fetchComments(
+  auth: AuthContext,
+  projectId: string,
+  taskId: string,
+): Promise<TaskComment[]> {
+  const response = await fetch(
+    `/api/projects/${projectId}/tasks/${taskId}/comments`,
+    { headers: headers(auth) },
+  );
+  return parseResponse(auth, response);
+}
+
+export async function createComment(
+  auth: AuthContext,
+  projectId: string,
+  taskId: string,
+  body: string,
+): Promise<TaskComment> {
+  const response = await fetch(
+    `/api/projects/${projectId}/tasks/${taskId}/comments`,
+    {
+      method: "POST",
+      headers: {
+        ...headers(auth),
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify({ body }),
+    },
+  );
+  return parseResponse(auth, response);
+}
+
+export async function deleteComment(
+  auth: AuthContext,
+  comment: TaskComment,
+): Promise<void> {
+  const response = await fetch(
+    `/api/projects/${comment.projectId}/tasks/${comment.taskId}/comments/${comment.commentId}`,
+    { method: "DELETE", headers: headers(auth) },
+  );
+  await parseResponse(auth, response);
+}
@@ -837,6 +837,24 @@ describe("Koso tests", () => {
       expect(koso.toJSON()).not.toHaveProperty("2");
     });
 
+    it("delete node 2 removes deleted tasks from the blockers of others", () => {
+      init([
+        { id: "root", name: "Root", children: ["1", "2"] },
+        { id: "1", name: "Task 1", blockedBy: ["3", "2", "4"] },
+        { id: "2", name: "Task 2", children: ["3"] },
+        { id: "3", name: "Task 3" },
+        { id: "4", name: "Task 4" },
+      ]);
+
+      koso.delete(Node.parse("2").linkage);
+
+      expect(koso.toJSON()).toMatchObject({
+        ["1"]: { id: "1", blockedBy: ["4"] },
+      });
+      expect(koso.toJSON()).not.toHaveProperty("2");
+      expect(koso.toJSON()).not.toHaveProperty("3");
+    });
+
     it("delete canonical plugin task/container throws", () => {
       init([
         { id: "root", name: "Root", children: ["1", "github"] },
@@ -962,6 +980,23 @@ describe("Koso tests", () => {
       expect(koso.toJSON()).not.toHaveProperty("7");
     });
 
+    it("delete task 2 removes deleted tasks from the blockers of others", () => {
+      init([
+        { id: "root", name: "Root", children: ["1", "2", "4"] },
+        { id: "1", name: "Task 1", blockedBy: ["2", "3"] },
+        { id: "2", name: "Task 2", children: ["3"] },
+        { id: "3", name: "Task 3" },
+        { id: "4", name: "Task 4", blockedBy: ["1", "3"] },
+      ]);
+
+      koso.deleteTask("2");
+
+      expect(koso.toJSON()).toMatchObject({
+        ["1"]: { id: "1", blockedBy: [] },
+        ["4"]: { id: "4", blockedBy: ["1"] },
+      });
+    });
+
     it("delete canonical plugin task/container throws", () => {
       init([
         { id: "root", name: "Root", children: ["1", "github"] },
@@ -986,6 +1021,27 @@ describe("Koso tests", () => {
     });
   });
 
+  describe("readOnly", () => {
+    it("read only projects are not editable", () => {
+      init([
+        { id: "root", name: "Root", children: ["1", "2"] },
+        { id: "1", name: "Task 1", children: ["2"] },
+        { id: "2", name: "Task 2" },
+      ]);
+      koso.readOnly = true;
+
+      expect(koso.isEditable("1")).toBe(false);
+      expect(koso.canInsert("1")).toBe(false);
+      expect(koso.canUnlink("2", "1")).toBe(false);
+      expect(koso.canMove("2", "1", "1")).toBe(false);
+      expect(koso.canDeleteTask("2")).toBe(false);
+
+      koso.readOnly = false;
+      expect(koso.isEditable("1")).toBe(true);
+      expect(koso.canDeleteTask("2")).toBe(true);
+    });
+  });
+
   describe("getTask", () => {
     it("retrieves task 1", () => {
       const task: Task = fullyPopulatedTask();
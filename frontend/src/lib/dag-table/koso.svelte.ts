@@ -105,6 +105,12 @@ export class Koso {
     console.debug("Client message handler was invoked but was not set");
   };
 
+  /**
+   * Whether the user may only view the project. The server rejects edits by
+   * viewers, so the UI must not offer them.
+   */
+  readOnly: boolean = $state(false);
+
   #awareness: Awareness[] = $state([]);
   #awarenessSequence: number = 0;
 
@@ -187,7 +193,7 @@ export class Koso {
       } else if (syncType === MSG_SYNC_RESPONSE) {
         const message = decoding.readVarUint8Array(decoder);
         Y.applyUpdateV2(this.doc, message, "koso.SYNC_RESPONSE");
-        if (this.graph.size === 0) {
+        if (this.graph.size === 0 && !this.readOnly) {
           this.upsertRoot();
         }
 
@@ -589,7 +595,7 @@ export class Koso {
 
   /** Determines if a task can be unlinked from a parent task. */
   canUnlink(task: string, parent: string): boolean {
-    return !this.isCanonicalManagedLink(task, parent);
+    return !this.readOnly && !this.isCanonicalManagedLink(task, parent);
   }
 
   /**
@@ -617,7 +623,7 @@ export class Koso {
 
   canMove(task: string, src: string, dest: string): boolean {
     return (
-      src === dest ||
+      (src === dest && !this.readOnly) ||
       (!this.isCanonicalManagedLink(task, src) &&
         this.canLink(new TaskLinkage({ parentId: dest, id: task })))
     );
@@ -651,7 +657,9 @@ export class Koso {
   }
 
   canDelete(link: TaskLinkage): boolean {
-    return !this.isCanonicalManagedLink(link.id, link.parentId);
+    return (
+      !this.readOnly && !this.isCanonicalManagedLink(link.id, link.parentId)
+    );
   }
 
   canDeleteTask(id: string): boolean {
@@ -709,6 +717,7 @@ export class Koso {
         console.debug(`Deleting task: ${taskId}`);
         this.graph.delete(taskId);
       }
+      this.graph.deleteBlockers(orphanTaskIds);
     });
   }
 
@@ -778,6 +787,7 @@ export class Koso {
         console.debug(`Deleting task: ${taskId}`);
         this.graph.delete(taskId);
       }
+      this.graph.deleteBlockers(orphanTaskIds);
     });
   }
 
@@ -790,11 +800,11 @@ export class Koso {
   }
 
   /**
-   * Determines if a task is editable by users. Today, only tasks managed by a
-   * plugin are not editable.
+   * Determines if a task is editable by users. Tasks managed by a plugin are
+   * not editable, and nothing is in read only projects.
    */
   isEditable(taskId: string): boolean {
-    return !this.isManagedTask(taskId);
+    return !this.readOnly && !this.isManagedTask(taskId);
   }
 
   /**
@@ -1,5 +1,6 @@
 import { page } from "$app/state";
 import type { AuthContext } from "$lib/auth.svelte";
+import type { ProjectRole } from "$lib/projects";
 import type { User } from "$lib/users";
 import { getContext, setContext } from "svelte";
 import * as Y from "yjs";
@@ -12,6 +13,8 @@ export class ProjectContext {
   socket: KosoSocket;
   name: string = "";
   users: User[] = $state([]);
+  // The signed in user's role, once the project is loaded.
+  role: ProjectRole | undefined = $state();
 
   constructor(id: string, koso: Koso, socket: KosoSocket) {
     this.id = id;
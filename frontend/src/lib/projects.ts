@@ -7,16 +7,35 @@ export type Project = {
   projectId: string;
   name: string;
   deletedOn?: string;
+  // The signed in user's role in the project.
+  role?: ProjectRole;
 };
 
+export type ProjectRole = "viewer" | "commenter" | "editor" | "owner";
+
 export type UpdateProjectUsers = {
   projectId: string;
   addEmails: string[];
   removeEmails: string[];
+  role?: ProjectRole;
 };
 
 export type ProjectExport = {
+  // Absent in exports written before the schema was versioned.
+  version?: number;
+  projectId: string;
+  graph: Graph;
+};
+
+export type Snapshot = {
   projectId: string;
+  snapshotId: number;
+  createdAt: string;
+  actor: unknown;
+};
+
+export type ProjectSnapshot = {
+  snapshot: Snapshot;
   graph: Graph;
 };
 
@@ -126,3 +145,37 @@ export async function exportProject(
   });
   return await parseResponse(auth, response);
 }
+
+export async function fetchSnapshots(
+  auth: AuthContext,
+  projectId: string,
+): Promise<Snapshot[]> {
+  const response = await fetch(`/api/projects/${projectId}/snapshots`, {
+    headers: headers(auth),
+  });
+  return await parseResponse(auth, response);
+}
+
+export async function fetchSnapshot(
+  auth: AuthContext,
+  projectId: string,
+  snapshotId: number,
+): Promise<ProjectSnapshot> {
+  const response = await fetch(
+    `/api/projects/${projectId}/snapshots/${snapshotId}`,
+    { headers: headers(auth) },
+  );
+  return await parseResponse(auth, response);
+}
+
+export async function restoreSnapshot(
+  auth: AuthContext,
+  projectId: string,
+  snapshotId: number,
+): Promise<Snapshot> {
+  const response = await fetch(
+    `/api/projects/${projectId}/snapshots/${snapshotId}/restore`,
+    { method: "POST", headers: headers(auth) },
+  );
+  return await parseResponse(auth, response);
+}
@@ -15,6 +15,9 @@ export type Task = {
   name: string;
   desc: string | null;
   children: string[];
+  // IDs of the tasks that must be done before this one can start.
+  // Managed by the server for now.
+  blockedBy?: string[];
   assignee: string | null;
   reporter: string | null;
   status: Status | null;
@@ -40,12 +43,22 @@ export type Status =
   | "In Progress"
   | "Done"
   | "Blocked";
-export type Kind = "Rollup" | "Task" | "github" | "github_pr";
+export type Kind =
+  | "Rollup"
+  | "Task"
+  | "github"
+  | "github_pr"
+  | "github_issue"
+  | "gitlab"
+  | "gitlab_mr";
 // Keep this in sync with the corresponding list in
 // backend/yproxy.rs
 export const MANAGED_KINDS: ImmutableSet<Kind> = ImmutableSet.of(
   "github",
   "github_pr",
+  "github_issue",
+  "gitlab",
+  "gitlab_mr",
 );
 export const ESTIMATES = <const>[1, 2, 3, 5, 8, 13, 20];
 export type Estimate = (typeof ESTIMATES)[number];
@@ -104,6 +117,23 @@ export class YGraphProxy {
     this.#yGraph.delete(taskId);
   }
 
+  /**
+   * Removes the given tasks from the blockers of every task, since deleted
+   * tasks no longer block anything. Mirrors `YDocProxy::delete_task` in
+   * backend/yproxy.rs.
+   */
+  deleteBlockers(taskIds: ImmutableSet<string>) {
+    for (const task of this.values()) {
+      const blockedBy = task.blockedBy;
+      if (!blockedBy) continue;
+      for (let i = blockedBy.length - 1; i >= 0; i--) {
+        if (taskIds.has(blockedBy.get(i))) {
+          blockedBy.delete(i);
+        }
+      }
+    }
+  }
+
   set(task: Task): YTaskProxy {
     const value = new Y.Map<YTaskProps>([
       ["id", task.id],
@@ -121,6 +151,9 @@ export class YGraphProxy {
       ["deadline", task.deadline],
       ["archived", task.archived],
     ]);
+    if (task.blockedBy) {
+      value.set("blockedBy", Y.Array.from(task.blockedBy));
+    }
     this.#yGraph.set(task.id, value);
     return new YTaskProxy(value);
   }
@@ -200,6 +233,11 @@ export class YTaskProxy {
     return new YChildrenProxy(yChildren);
   }
 
+  get blockedBy(): YChildrenProxy | null {
+    const yBlockedBy = this.#yTask.get("blockedBy") as YChildren | undefined;
+    return yBlockedBy ? new YChildrenProxy(yBlockedBy) : null;
+  }
+
   get assignee(): string | null {
     return (this.#yTask.get("assignee") as string) || null;
   }
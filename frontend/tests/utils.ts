@@ -22,6 +22,7 @@ export function buildTask(task: TaskBuilder): Task {
     estimate: task.estimate ?? null,
     deadline: task.deadline ?? null,
     archived: task.archived ?? null,
+    ...(task.blockedBy ? { blockedBy: task.blockedBy } : {}),
   };
 }
 